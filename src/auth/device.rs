@@ -0,0 +1,234 @@
+// RFC 8628 device authorization grant for headless clients (cli on build boxes)
+// cli gets device_code + user_code, user approves the user_code in the browser, cli polls /oauth/token
+use chrono::Utc;
+use rand::Rng;
+use serde::Serialize;
+use std::sync::Arc;
+
+use crate::config::DeviceFlowConfig;
+use crate::error::AppError;
+use crate::models::{DeviceAuthorization, DeviceAuthorizationStatus, OAuthClient};
+use crate::repository::DeviceAuthorizationRepository;
+use super::tokens::{generate_opaque_token, hash_opaque_token};
+
+// no vowels so we dont accidentally spell words, no lookalikes like 0/O or 1/I
+const USER_CODE_ALPHABET: &[u8] = b"BCDFGHJKLMNPQRSTVWXZ";
+const USER_CODE_LENGTH: usize = 8;
+const SLOW_DOWN_INCREMENT_SECS: i64 = 5;
+const MAX_USER_CODE_ATTEMPTS: usize = 3;
+
+#[derive(Debug, Serialize)]
+pub struct DeviceAuthorizationResponse {
+    pub device_code: String,
+    pub user_code: String,
+    pub verification_uri: String,
+    pub verification_uri_complete: String,
+    pub expires_in: i64,
+    pub interval: i64,
+}
+
+#[derive(Debug)]
+pub enum DevicePollOutcome {
+    Approved(DeviceAuthorization),
+    Pending,
+    SlowDown,
+    Denied,
+    Expired,
+    // unknown, consumed or belongs to another client
+    Invalid,
+}
+
+pub struct DeviceFlowService {
+    config: DeviceFlowConfig,
+    repository: Arc<dyn DeviceAuthorizationRepository>,
+}
+
+impl DeviceFlowService {
+    pub fn new(config: DeviceFlowConfig, repository: Arc<dyn DeviceAuthorizationRepository>) -> Self {
+        Self { config, repository }
+    }
+
+    pub async fn start(
+        &self,
+        client: &OAuthClient,
+        scopes: Vec<String>,
+    ) -> Result<DeviceAuthorizationResponse, AppError> {
+        if let Err(e) = self.repository.delete_expired().await {
+            tracing::warn!(error = %e, "cleanup of expired device codes failed");
+        }
+
+        let device_code = generate_opaque_token();
+
+        // user codes only have ~34 bits so collisions with pending codes are possible
+        let mut attempts = 0;
+        let user_code = loop {
+            let user_code = generate_user_code();
+            let authorization = DeviceAuthorization::new(
+                hash_opaque_token(&device_code),
+                user_code.clone(),
+                client.client_id.clone(),
+                scopes.clone(),
+                self.config.interval_secs,
+                self.config.expiration_secs,
+            );
+
+            match self.repository.create(&authorization).await {
+                Ok(()) => break user_code,
+                Err(AppError::Conflict(_)) if attempts + 1 < MAX_USER_CODE_ATTEMPTS => attempts += 1,
+                Err(e) => return Err(e),
+            }
+        };
+
+        tracing::info!(client_id = %client.client_id, "device authorization started");
+
+        let display_code = format_user_code(&user_code);
+
+        Ok(DeviceAuthorizationResponse {
+            device_code,
+            verification_uri_complete: format!(
+                "{}?user_code={}",
+                self.config.verification_uri, display_code
+            ),
+            verification_uri: self.config.verification_uri.clone(),
+            user_code: display_code,
+            expires_in: self.config.expiration_secs,
+            interval: self.config.interval_secs,
+        })
+    }
+
+    // pending and not expired authorization for the code the user typed in
+    pub async fn find_pending(&self, user_code: &str) -> Result<DeviceAuthorization, AppError> {
+        let authorization = self
+            .repository
+            .find_by_user_code(&normalize_user_code(user_code))
+            .await?
+            .filter(|a| a.status == DeviceAuthorizationStatus::Pending && !a.is_expired())
+            .ok_or_else(|| AppError::NotFound("Unknown or expired code".to_string()))?;
+
+        Ok(authorization)
+    }
+
+    pub async fn approve(&self, user_code: &str, user_id: &str) -> Result<DeviceAuthorization, AppError> {
+        let mut authorization = self.find_pending(user_code).await?;
+
+        authorization.status = DeviceAuthorizationStatus::Approved;
+        authorization.user_id = Some(user_id.to_string());
+        self.repository.update(&authorization).await?;
+
+        tracing::info!(
+            user_id = %user_id,
+            client_id = %authorization.client_id,
+            "device authorization approved"
+        );
+
+        Ok(authorization)
+    }
+
+    pub async fn deny(&self, user_code: &str, user_id: &str) -> Result<(), AppError> {
+        let mut authorization = self.find_pending(user_code).await?;
+
+        authorization.status = DeviceAuthorizationStatus::Denied;
+        self.repository.update(&authorization).await?;
+
+        tracing::info!(
+            user_id = %user_id,
+            client_id = %authorization.client_id,
+            "device authorization denied"
+        );
+
+        Ok(())
+    }
+
+    // one poll from the device, approved codes are consumed so tokens are only issued once
+    pub async fn poll(&self, device_code: &str, client_id: &str) -> Result<DevicePollOutcome, AppError> {
+        let Some(mut authorization) = self
+            .repository
+            .find_by_device_code_hash(&hash_opaque_token(device_code))
+            .await?
+        else {
+            return Ok(DevicePollOutcome::Invalid);
+        };
+
+        if authorization.client_id != client_id {
+            tracing::warn!(client_id = %client_id, "device code used by wrong client");
+            return Ok(DevicePollOutcome::Invalid);
+        }
+
+        if authorization.is_expired() {
+            return Ok(DevicePollOutcome::Expired);
+        }
+
+        let now = Utc::now();
+
+        let outcome = match authorization.status {
+            DeviceAuthorizationStatus::Consumed => return Ok(DevicePollOutcome::Invalid),
+            DeviceAuthorizationStatus::Denied => DevicePollOutcome::Denied,
+            _ if authorization.polled_too_fast(now) => {
+                authorization.interval_secs += SLOW_DOWN_INCREMENT_SECS;
+                DevicePollOutcome::SlowDown
+            }
+            DeviceAuthorizationStatus::Pending => DevicePollOutcome::Pending,
+            DeviceAuthorizationStatus::Approved => {
+                // two polls can both read approved, only the one that flips the row gets tokens
+                if !self.repository.consume(&authorization.device_code_hash).await? {
+                    return Ok(DevicePollOutcome::Invalid);
+                }
+                authorization.status = DeviceAuthorizationStatus::Consumed;
+                authorization.last_polled_at = Some(now);
+                return Ok(DevicePollOutcome::Approved(authorization));
+            }
+        };
+
+        authorization.last_polled_at = Some(now);
+        self.repository.update(&authorization).await?;
+
+        Ok(outcome)
+    }
+}
+
+fn generate_user_code() -> String {
+    let mut rng = rand::thread_rng();
+    (0..USER_CODE_LENGTH)
+        .map(|_| USER_CODE_ALPHABET[rng.gen_range(0..USER_CODE_ALPHABET.len())] as char)
+        .collect()
+}
+
+// users type codes in any case, with or without the dash
+pub fn normalize_user_code(input: &str) -> String {
+    input
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_uppercase())
+        .collect()
+}
+
+fn format_user_code(code: &str) -> String {
+    let (left, right) = code.split_at(code.len() / 2);
+    format!("{}-{}", left, right)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_user_code_format() {
+        let code = generate_user_code();
+
+        assert_eq!(code.len(), USER_CODE_LENGTH);
+        assert!(code.bytes().all(|b| USER_CODE_ALPHABET.contains(&b)));
+    }
+
+    #[test]
+    fn test_normalize_user_code() {
+        assert_eq!(normalize_user_code("bcdf-ghjk"), "BCDFGHJK");
+        assert_eq!(normalize_user_code(" BCDF GHJK "), "BCDFGHJK");
+        assert_eq!(normalize_user_code("BCDFGHJK"), "BCDFGHJK");
+    }
+
+    #[test]
+    fn test_format_user_code() {
+        assert_eq!(format_user_code("BCDFGHJK"), "BCDF-GHJK");
+        assert_eq!(normalize_user_code(&format_user_code("BCDFGHJK")), "BCDFGHJK");
+    }
+}
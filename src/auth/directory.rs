@@ -0,0 +1,261 @@
+// what LdapAuthProvider needs from a directory server
+// ldap3 in production, InMemoryDirectory for tests and local development
+use async_trait::async_trait;
+use ldap3::adapters::{Adapter, EntriesOnly, PagedResults};
+use ldap3::exop::PasswordModify;
+use ldap3::{Ldap, LdapConnSettings, LdapResult, Mod, Scope, SearchEntry};
+use rustls::ClientConfig;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::config::LdapConfig;
+use crate::error::AppError;
+
+use super::ldap_pool::LdapServers;
+use super::ldap_tls;
+
+// result of a bind, modify or extended operation
+#[derive(Debug, Clone)]
+pub struct OperationResult {
+    // ldap result code, 0 = success, 49 = invalid credentials
+    pub rc: u32,
+    // diagnostic message, AD puts the sub-code in here
+    pub text: String,
+}
+
+#[async_trait]
+pub trait DirectoryClient: Send + Sync {
+    // new connection to one of the configured servers
+    async fn connect(&self) -> Result<Box<dyn DirectoryConnection>, AppError>;
+
+    // idle pooled connections to a server marked down are not reused
+    fn is_available(&self, _url: &str) -> bool {
+        true
+    }
+
+    // false while every server is marked down, the offline login fallback depends on it
+    fn is_reachable(&self) -> bool {
+        true
+    }
+}
+
+#[async_trait]
+pub trait DirectoryConnection: Send {
+    fn url(&self) -> &str;
+
+    // ldaps:// or starttls, AD only changes passwords over encrypted connections
+    fn is_encrypted(&self) -> bool;
+
+    // transport problems are Err, a rejected bind is Ok with rc != 0
+    async fn simple_bind(&mut self, dn: &str, password: &str) -> Result<OperationResult, AppError>;
+
+    async fn search(
+        &mut self,
+        base: &str,
+        scope: Scope,
+        filter: &str,
+        attributes: &[&str],
+    ) -> Result<Vec<SearchEntry>, AppError>;
+
+    // subtree search in pages, for result sets above the server size limit
+    async fn search_paged(
+        &mut self,
+        base: &str,
+        filter: &str,
+        attributes: &[&str],
+        page_size: i32,
+    ) -> Result<Vec<SearchEntry>, AppError>;
+
+    // rejected changes (policy, access) are Ok with rc != 0 like binds
+    async fn modify(&mut self, dn: &str, mods: Vec<Mod<Vec<u8>>>) -> Result<OperationResult, AppError>;
+
+    // RFC 3062 password modify extended operation (OpenLDAP, FreeIPA)
+    async fn password_modify(
+        &mut self,
+        user_dn: &str,
+        old_password: &str,
+        new_password: &str,
+    ) -> Result<OperationResult, AppError>;
+
+    async fn unbind(&mut self);
+}
+
+pub struct Ldap3Client {
+    servers: LdapServers,
+    timeout: Duration,
+    use_starttls: bool,
+    // built once, None = ldap3 default with the system roots
+    tls: Option<Arc<ClientConfig>>,
+}
+
+impl Ldap3Client {
+    // fails when the CA bundle or client certificate cannot be loaded
+    pub fn new(config: &LdapConfig) -> Result<Self, AppError> {
+        let tls = ldap_tls::client_config(config).map_err(AppError::LdapError)?;
+
+        Ok(Self {
+            servers: LdapServers::new(
+                config.urls.clone(),
+                Duration::from_secs(config.server_retry_secs),
+            ),
+            timeout: Duration::from_secs(config.timeout_secs),
+            use_starttls: config.use_starttls,
+            tls,
+        })
+    }
+
+    fn conn_settings(&self) -> LdapConnSettings {
+        let settings = LdapConnSettings::new()
+            .set_conn_timeout(self.timeout)
+            .set_starttls(self.use_starttls);
+
+        match &self.tls {
+            Some(tls) => settings.set_config(Arc::clone(tls)),
+            None => settings,
+        }
+    }
+}
+
+#[async_trait]
+impl DirectoryClient for Ldap3Client {
+    //tries to connect to the first reachable ldap server
+    async fn connect(&self) -> Result<Box<dyn DirectoryConnection>, AppError> {
+        let (url, ldap) = self.servers.connect(&self.conn_settings()).await?;
+        let encrypted = self.use_starttls || url.starts_with("ldaps://") || url.starts_with("ldapi://");
+        Ok(Box::new(Ldap3Connection { ldap, url, encrypted }))
+    }
+
+    fn is_available(&self, url: &str) -> bool {
+        !self.servers.is_down(url)
+    }
+
+    fn is_reachable(&self) -> bool {
+        !self.servers.all_down()
+    }
+}
+
+struct Ldap3Connection {
+    ldap: Ldap,
+    url: String,
+    encrypted: bool,
+}
+
+impl From<LdapResult> for OperationResult {
+    fn from(result: LdapResult) -> Self {
+        Self {
+            rc: result.rc,
+            text: result.text,
+        }
+    }
+}
+
+#[async_trait]
+impl DirectoryConnection for Ldap3Connection {
+    fn url(&self) -> &str {
+        &self.url
+    }
+
+    fn is_encrypted(&self) -> bool {
+        self.encrypted
+    }
+
+    async fn simple_bind(&mut self, dn: &str, password: &str) -> Result<OperationResult, AppError> {
+        let result = self
+            .ldap
+            .simple_bind(dn, password)
+            .await
+            .map_err(|e| AppError::LdapError(format!("Bind failed: {}", e)))?;
+
+        Ok(result.into())
+    }
+
+    async fn search(
+        &mut self,
+        base: &str,
+        scope: Scope,
+        filter: &str,
+        attributes: &[&str],
+    ) -> Result<Vec<SearchEntry>, AppError> {
+        let (entries, _result) = self
+            .ldap
+            .search(base, scope, filter, attributes.to_vec())
+            .await
+            .map_err(|e| AppError::LdapError(format!("Search failed: {}", e)))?
+            .success()
+            .map_err(|e| AppError::LdapError(format!("Search result error: {}", e)))?;
+
+        Ok(entries.into_iter().map(SearchEntry::construct).collect())
+    }
+
+    // AD returns at most MaxPageSize (1000) entries per request
+    async fn search_paged(
+        &mut self,
+        base: &str,
+        filter: &str,
+        attributes: &[&str],
+        page_size: i32,
+    ) -> Result<Vec<SearchEntry>, AppError> {
+        let adapters: Vec<Box<dyn Adapter<_, _>>> = vec![
+            Box::new(EntriesOnly::new()),
+            Box::new(PagedResults::new(page_size)),
+        ];
+
+        let mut search = self
+            .ldap
+            .streaming_search_with(adapters, base, Scope::Subtree, filter, attributes.to_vec())
+            .await
+            .map_err(|e| AppError::LdapError(format!("Search failed: {}", e)))?;
+
+        let mut entries = Vec::new();
+        while let Some(entry) = search
+            .next()
+            .await
+            .map_err(|e| AppError::LdapError(format!("Search failed: {}", e)))?
+        {
+            entries.push(SearchEntry::construct(entry));
+        }
+
+        search
+            .finish()
+            .await
+            .success()
+            .map_err(|e| AppError::LdapError(format!("Search result error: {}", e)))?;
+
+        Ok(entries)
+    }
+
+    async fn modify(&mut self, dn: &str, mods: Vec<Mod<Vec<u8>>>) -> Result<OperationResult, AppError> {
+        let result = self
+            .ldap
+            .modify(dn, mods)
+            .await
+            .map_err(|e| AppError::LdapError(format!("Modify failed: {}", e)))?;
+
+        Ok(result.into())
+    }
+
+    async fn password_modify(
+        &mut self,
+        user_dn: &str,
+        old_password: &str,
+        new_password: &str,
+    ) -> Result<OperationResult, AppError> {
+        let exop = PasswordModify {
+            user_id: Some(user_dn),
+            old_pass: Some(old_password),
+            new_pass: Some(new_password),
+        };
+
+        let result = self
+            .ldap
+            .extended(exop)
+            .await
+            .map_err(|e| AppError::LdapError(format!("Password modify failed: {}", e)))?;
+
+        Ok(result.1.into())
+    }
+
+    async fn unbind(&mut self) {
+        let _ = self.ldap.unbind().await;
+    }
+}
@@ -0,0 +1,933 @@
+// in-memory directory for tests and local development
+// speaks just enough ldap for LdapAuthProvider: simple bind with AD style 525/52e answers,
+// searches with &, |, !, =, presence, substrings and the AD in-chain matching rule
+use async_trait::async_trait;
+use ldap3::{Mod, Scope, SearchEntry};
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, RwLock};
+
+use crate::error::AppError;
+
+use super::directory::{OperationResult, DirectoryClient, DirectoryConnection};
+use super::ldap::MATCHING_RULE_IN_CHAIN;
+use super::ldap_groups::normalize_dn;
+
+const URL: &str = "memory://directory";
+const INVALID_CREDENTIALS: u32 = 49;
+const CONSTRAINT_VIOLATION: u32 = 19;
+const UNWILLING_TO_PERFORM: u32 = 53;
+// AD default "enforce password history"
+const PASSWORD_HISTORY: usize = 24;
+
+#[derive(Debug, Clone)]
+struct Entry {
+    dn: String,
+    attrs: HashMap<String, Vec<String>>,
+    password: Option<String>,
+    // previous passwords, newest last
+    password_history: Vec<String>,
+}
+
+impl Entry {
+    // attribute names are case insensitive
+    fn values(&self, name: &str) -> &[String] {
+        self.attrs
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, values)| values.as_slice())
+            .unwrap_or_default()
+    }
+
+    fn values_mut(&mut self, name: &str) -> &mut Vec<String> {
+        let key = self
+            .attrs
+            .keys()
+            .find(|key| key.eq_ignore_ascii_case(name))
+            .cloned()
+            .unwrap_or_else(|| name.to_string());
+        self.attrs.entry(key).or_default()
+    }
+
+    fn to_search_entry(&self, attributes: &[&str]) -> SearchEntry {
+        let all = attributes.is_empty() || attributes.contains(&"*");
+        let attrs = self
+            .attrs
+            .iter()
+            .filter(|(key, _)| all || attributes.iter().any(|a| a.eq_ignore_ascii_case(key)))
+            .map(|(key, values)| (key.clone(), values.clone()))
+            .collect();
+
+        SearchEntry {
+            dn: self.dn.clone(),
+            attrs,
+            bin_attrs: HashMap::new(),
+        }
+    }
+}
+
+// upn style bind names have no rdns, compare those lowercased
+fn dn_key(dn: &str) -> String {
+    if dn.contains('=') {
+        normalize_dn(dn)
+    } else {
+        dn.trim().to_lowercase()
+    }
+}
+
+fn values_equal(a: &str, b: &str) -> bool {
+    if a.contains('=') && b.contains('=') {
+        normalize_dn(a) == normalize_dn(b)
+    } else {
+        a.to_lowercase() == b.to_lowercase()
+    }
+}
+
+fn in_scope(dn: &str, base: &str, scope: Scope) -> bool {
+    let dn = normalize_dn(dn);
+    let base = normalize_dn(base);
+    match scope {
+        Scope::Base => dn == base,
+        Scope::OneLevel => dn.split_once(',').is_some_and(|(_, parent)| parent == base),
+        Scope::Subtree => dn == base || dn.ends_with(&format!(",{}", base)),
+    }
+}
+
+#[derive(Default)]
+struct DirectoryState {
+    entries: Vec<Entry>,
+    // dn -> forced bind answer, for locked/expired accounts
+    bind_failures: HashMap<String, OperationResult>,
+    unavailable: bool,
+    search_error: Option<String>,
+    // bumped by disconnect_all, connections of an older generation are dead
+    generation: u64,
+    connections: usize,
+    // password policy for changes: minimum length, no reuse of the history
+    min_password_length: usize,
+    // connections behave like plain ldap:// without starttls
+    plaintext: bool,
+}
+
+impl DirectoryState {
+    fn find(&self, dn: &str) -> Option<&Entry> {
+        let key = dn_key(dn);
+        self.entries.iter().find(|e| dn_key(&e.dn) == key)
+    }
+
+    fn find_mut(&mut self, dn: &str) -> Option<&mut Entry> {
+        let key = dn_key(dn);
+        self.entries.iter_mut().find(|e| dn_key(&e.dn) == key)
+    }
+
+    // bind name is a dn or a userPrincipalName
+    fn find_bind_entry(&self, name: &str) -> Option<&Entry> {
+        self.find(name).or_else(|| {
+            self.entries
+                .iter()
+                .find(|e| e.values("userPrincipalName").iter().any(|upn| upn.eq_ignore_ascii_case(name)))
+        })
+    }
+
+    fn matches(&self, entry: &Entry, filter: &Filter) -> bool {
+        match filter {
+            Filter::And(filters) => filters.iter().all(|f| self.matches(entry, f)),
+            Filter::Or(filters) => filters.iter().any(|f| self.matches(entry, f)),
+            Filter::Not(filter) => !self.matches(entry, filter),
+            Filter::Present(attr) => !entry.values(attr).is_empty(),
+            Filter::Equal(attr, value) => entry.values(attr).iter().any(|v| values_equal(v, value)),
+            Filter::Substring(attr, parts) => entry
+                .values(attr)
+                .iter()
+                .any(|v| substring_match(&v.to_lowercase(), parts)),
+            Filter::InChain(attr, target) => self.in_chain(entry, attr, target, &mut Vec::new()),
+        }
+    }
+
+    // target is a member of the group directly or through nested groups
+    fn in_chain(&self, group: &Entry, attr: &str, target: &str, visited: &mut Vec<String>) -> bool {
+        let key = normalize_dn(&group.dn);
+        if visited.contains(&key) {
+            return false;
+        }
+        visited.push(key);
+
+        group.values(attr).iter().any(|member| {
+            values_equal(member, target)
+                || self
+                    .find(member)
+                    .is_some_and(|nested| self.in_chain(nested, attr, target, visited))
+        })
+    }
+}
+
+#[derive(Debug)]
+enum Filter {
+    And(Vec<Filter>),
+    Or(Vec<Filter>),
+    Not(Box<Filter>),
+    Present(String),
+    Equal(String, String),
+    Substring(String, Vec<String>),
+    InChain(String, String),
+}
+
+fn parse_filter(input: &str) -> Result<Filter, String> {
+    let input = input.trim();
+    let inner = input
+        .strip_prefix('(')
+        .and_then(|s| s.strip_suffix(')'))
+        .ok_or_else(|| format!("Bad filter: {}", input))?;
+
+    match inner.chars().next() {
+        Some('&') => Ok(Filter::And(parse_filter_list(&inner[1..])?)),
+        Some('|') => Ok(Filter::Or(parse_filter_list(&inner[1..])?)),
+        Some('!') => Ok(Filter::Not(Box::new(parse_filter(&inner[1..])?))),
+        _ => parse_filter_item(inner),
+    }
+}
+
+// values are escaped (\28, \29), so parens in the string are always structure
+fn parse_filter_list(input: &str) -> Result<Vec<Filter>, String> {
+    let mut filters = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0;
+
+    for (i, c) in input.char_indices() {
+        match c {
+            '(' => {
+                if depth == 0 {
+                    start = i;
+                }
+                depth += 1;
+            }
+            ')' => {
+                depth = depth
+                    .checked_sub(1)
+                    .ok_or_else(|| format!("Unbalanced filter: {}", input))?;
+                if depth == 0 {
+                    filters.push(parse_filter(&input[start..=i])?);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if depth != 0 {
+        return Err(format!("Unbalanced filter: {}", input));
+    }
+    Ok(filters)
+}
+
+fn parse_filter_item(item: &str) -> Result<Filter, String> {
+    let (left, value) = item
+        .split_once('=')
+        .ok_or_else(|| format!("Bad filter item: {}", item))?;
+
+    // extensible match, only the AD in-chain rule: member:1.2.840.113556.1.4.1941:
+    if let Some(left) = left.strip_suffix(':') {
+        return match left.split_once(':') {
+            Some((attr, MATCHING_RULE_IN_CHAIN)) => Ok(Filter::InChain(attr.to_string(), unescape(value))),
+            _ => Err(format!("Unsupported matching rule: {}", item)),
+        };
+    }
+
+    if left.ends_with(['~', '<', '>']) {
+        return Err(format!("Unsupported filter item: {}", item));
+    }
+
+    let attr = left.trim().to_string();
+    if value == "*" {
+        Ok(Filter::Present(attr))
+    } else if value.contains('*') {
+        Ok(Filter::Substring(attr, value.split('*').map(unescape).collect()))
+    } else {
+        Ok(Filter::Equal(attr, unescape(value)))
+    }
+}
+
+// RFC 4515 \xx escapes back to the raw value
+fn unescape(value: &str) -> String {
+    let raw = value.as_bytes();
+    let mut bytes = Vec::with_capacity(raw.len());
+    let mut i = 0;
+
+    while i < raw.len() {
+        if raw[i] == b'\\'
+            && i + 2 < raw.len()
+            && let Some(byte) = value.get(i + 1..i + 3).and_then(|hex| u8::from_str_radix(hex, 16).ok())
+        {
+            bytes.push(byte);
+            i += 3;
+        } else {
+            bytes.push(raw[i]);
+            i += 1;
+        }
+    }
+
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+// parts are the pieces between the *, first one anchored at the start, last one at the end
+fn substring_match(value: &str, parts: &[String]) -> bool {
+    let last = parts.len().saturating_sub(1);
+    let mut rest = value;
+
+    for (i, part) in parts.iter().enumerate() {
+        let part = part.to_lowercase();
+        if part.is_empty() {
+            continue;
+        }
+        if i == 0 {
+            match rest.strip_prefix(&part) {
+                Some(r) => rest = r,
+                None => return false,
+            }
+        } else if i == last {
+            return rest.ends_with(&part);
+        } else {
+            match rest.find(&part) {
+                Some(pos) => rest = &rest[pos + part.len()..],
+                None => return false,
+            }
+        }
+    }
+    true
+}
+
+fn ad_invalid_credentials(sub_code: &str) -> OperationResult {
+    OperationResult {
+        rc: INVALID_CREDENTIALS,
+        text: format!(
+            "80090308: LdapErr: DSID-0C09044E, comment: AcceptSecurityContext error, data {}, v4563",
+            sub_code
+        ),
+    }
+}
+
+fn result(rc: u32, text: &str) -> OperationResult {
+    OperationResult {
+        rc,
+        text: text.to_string(),
+    }
+}
+
+fn success() -> OperationResult {
+    result(0, "")
+}
+
+// AD wants "\"password\"" as UTF-16LE
+fn decode_unicode_pwd(value: &[u8]) -> Option<String> {
+    let units: Vec<u16> = value
+        .chunks_exact(2)
+        .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+        .collect();
+    let quoted = String::from_utf16(&units).ok()?;
+    quoted
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .map(str::to_string)
+}
+
+fn single_value(values: &HashSet<Vec<u8>>) -> Option<&[u8]> {
+    match values.len() {
+        1 => values.iter().next().map(Vec::as_slice),
+        _ => None,
+    }
+}
+
+impl Entry {
+    // false when the policy rejects the new password
+    fn change_password(&mut self, new_password: &str, min_length: usize) -> bool {
+        let reused = self.password.as_deref() == Some(new_password)
+            || self.password_history.iter().any(|old| old == new_password);
+        if reused || new_password.chars().count() < min_length {
+            return false;
+        }
+
+        if let Some(old) = self.password.replace(new_password.to_string()) {
+            self.password_history.push(old);
+            if self.password_history.len() > PASSWORD_HISTORY {
+                self.password_history.remove(0);
+            }
+        }
+        true
+    }
+
+    // ldap3 Mod on a copy, the caller only keeps it when every mod worked
+    fn apply(&mut self, modification: Mod<Vec<u8>>) -> Result<(), OperationResult> {
+        let text = |v: &Vec<u8>| String::from_utf8_lossy(v).into_owned();
+        match modification {
+            Mod::Add(attr, values) => {
+                let values: Vec<String> = values.iter().map(text).collect();
+                self.values_mut(&text(&attr)).extend(values);
+            }
+            Mod::Replace(attr, values) => {
+                *self.values_mut(&text(&attr)) = values.iter().map(text).collect();
+            }
+            Mod::Delete(attr, values) if values.is_empty() => {
+                self.values_mut(&text(&attr)).clear();
+            }
+            Mod::Delete(attr, values) => {
+                let values: Vec<String> = values.iter().map(text).collect();
+                self.values_mut(&text(&attr)).retain(|v| !values.contains(v));
+            }
+            Mod::Increment(..) => return Err(result(UNWILLING_TO_PERFORM, "increment not supported")),
+        }
+
+        let attr = self.attrs.iter().find(|(_, v)| v.is_empty()).map(|(k, _)| k.clone());
+        if let Some(attr) = attr {
+            self.attrs.remove(&attr);
+        }
+        Ok(())
+    }
+
+    // AD password change: delete the old unicodePwd value, add the new one
+    fn change_unicode_pwd(&mut self, mods: &[Mod<Vec<u8>>], min_length: usize) -> OperationResult {
+        const WRONG_PASSWORD: &str = "00000056: AtrErr: DSID-03190F80, #1:\n\t0: 00000056: DSID-03190F80, problem 1005 (CONSTRAINT_ATT_TYPE), data 0, Att 9005a (unicodePwd)";
+        const POLICY: &str = "0000052D: AtrErr: DSID-03191083, #1:\n\t0: 0000052D: DSID-03191083, problem 1005 (CONSTRAINT_ATT_TYPE), data 0, Att 9005a (unicodePwd)";
+
+        let (old, new) = match mods {
+            [Mod::Delete(a, old), Mod::Add(b, new)] if a == b => (single_value(old), single_value(new)),
+            _ => (None, None),
+        };
+        let (Some(old), Some(new)) = (old.and_then(decode_unicode_pwd), new.and_then(decode_unicode_pwd)) else {
+            return result(UNWILLING_TO_PERFORM, "0000001F: SvcErr: DSID-031A12D2, problem 5003 (WILL_NOT_PERFORM), data 0");
+        };
+
+        if self.password.as_deref() != Some(old.as_str()) {
+            return result(CONSTRAINT_VIOLATION, WRONG_PASSWORD);
+        }
+        if !self.change_password(&new, min_length) {
+            return result(CONSTRAINT_VIOLATION, POLICY);
+        }
+        success()
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum OneOrMany {
+    One(String),
+    Many(Vec<String>),
+}
+
+#[derive(Deserialize)]
+struct SeedUser {
+    dn: String,
+    password: String,
+    #[serde(default)]
+    attributes: HashMap<String, OneOrMany>,
+}
+
+#[derive(Deserialize)]
+struct SeedGroup {
+    dn: String,
+    #[serde(default)]
+    members: Vec<String>,
+}
+
+// json file for LDAP_URL=memory:<path>
+#[derive(Deserialize)]
+struct Seed {
+    #[serde(default)]
+    users: Vec<SeedUser>,
+    #[serde(default)]
+    groups: Vec<SeedGroup>,
+}
+
+// cheap to clone, all clones share the same directory
+#[derive(Clone, Default)]
+pub struct InMemoryDirectory {
+    state: Arc<RwLock<DirectoryState>>,
+}
+
+impl InMemoryDirectory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let seed: Seed = serde_json::from_str(json).map_err(|e| format!("Invalid directory seed: {}", e))?;
+        let directory = Self::new();
+
+        for user in seed.users {
+            let attrs = user
+                .attributes
+                .into_iter()
+                .map(|(key, value)| match value {
+                    OneOrMany::One(v) => (key, vec![v]),
+                    OneOrMany::Many(v) => (key, v),
+                })
+                .collect();
+            directory.insert(&user.dn, attrs, Some(user.password), &["top", "person", "user"]);
+        }
+
+        for group in seed.groups {
+            let members: Vec<&str> = group.members.iter().map(String::as_str).collect();
+            directory.add_group(&group.dn, &members);
+        }
+
+        Ok(directory)
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let json = std::fs::read_to_string(path).map_err(|e| format!("Cannot read {}: {}", path, e))?;
+        Self::from_json(&json)
+    }
+
+    fn insert(&self, dn: &str, mut attrs: HashMap<String, Vec<String>>, password: Option<String>, object_classes: &[&str]) {
+        let mut entry = Entry {
+            dn: dn.to_string(),
+            attrs: HashMap::new(),
+            password,
+            password_history: Vec::new(),
+        };
+        for (key, values) in attrs.drain() {
+            entry.values_mut(&key).extend(values);
+        }
+
+        if entry.values("objectClass").is_empty() {
+            entry.values_mut("objectClass").extend(object_classes.iter().map(|c| c.to_string()));
+        }
+        if entry.values("cn").is_empty()
+            && let Some(cn) = super::ldap_groups::group_cn(dn)
+        {
+            // keep the original spelling of the first rdn
+            let original = dn.split(',').next().and_then(|rdn| rdn.split_once('=')).map(|(_, v)| v.trim());
+            entry.values_mut("cn").push(original.map(str::to_string).unwrap_or(cn));
+        }
+
+        let mut state = self.state.write().unwrap();
+        state.entries.retain(|e| dn_key(&e.dn) != dn_key(dn));
+        state.entries.push(entry);
+    }
+
+    fn pairs(attrs: &[(&str, &str)]) -> HashMap<String, Vec<String>> {
+        let mut map: HashMap<String, Vec<String>> = HashMap::new();
+        for (key, value) in attrs {
+            map.entry(key.to_string()).or_default().push(value.to_string());
+        }
+        map
+    }
+
+    // repeat a key for multi valued attributes
+    pub fn add_user(&self, dn: &str, password: &str, attrs: &[(&str, &str)]) {
+        self.insert(dn, Self::pairs(attrs), Some(password.to_string()), &["top", "person", "user"]);
+    }
+
+    // any entry without password, posixGroup etc
+    pub fn add_entry(&self, dn: &str, attrs: &[(&str, &str)]) {
+        self.insert(dn, Self::pairs(attrs), None, &["top"]);
+    }
+
+    // group with member, members that already exist get memberOf like in AD
+    pub fn add_group(&self, dn: &str, members: &[&str]) {
+        let attrs = members.iter().map(|m| ("member", *m)).collect::<Vec<_>>();
+        self.insert(dn, Self::pairs(&attrs), None, &["top", "group", "groupOfNames"]);
+
+        let mut state = self.state.write().unwrap();
+        for member in members {
+            if let Some(entry) = state.find_mut(member) {
+                entry.values_mut("memberOf").push(dn.to_string());
+            }
+        }
+    }
+
+    pub fn set_attribute(&self, dn: &str, name: &str, values: &[&str]) {
+        let mut state = self.state.write().unwrap();
+        if let Some(entry) = state.find_mut(dn) {
+            *entry.values_mut(name) = values.iter().map(|v| v.to_string()).collect();
+        }
+    }
+
+    pub fn set_password(&self, dn: &str, password: &str) {
+        let mut state = self.state.write().unwrap();
+        if let Some(entry) = state.find_mut(dn) {
+            entry.password = Some(password.to_string());
+        }
+    }
+
+    pub fn remove(&self, dn: &str) {
+        let key = dn_key(dn);
+        self.state.write().unwrap().entries.retain(|e| dn_key(&e.dn) != key);
+    }
+
+    // every bind as this dn gets this answer, e.g. AD "data 775" for a locked account
+    pub fn fail_bind(&self, dn: &str, rc: u32, text: &str) {
+        self.state.write().unwrap().bind_failures.insert(
+            dn_key(dn),
+            OperationResult {
+                rc,
+                text: text.to_string(),
+            },
+        );
+    }
+
+    pub fn clear_bind_failures(&self) {
+        self.state.write().unwrap().bind_failures.clear();
+    }
+
+    // connect fails like an unreachable server
+    pub fn set_unavailable(&self, unavailable: bool) {
+        self.state.write().unwrap().unavailable = unavailable;
+    }
+
+    pub fn fail_searches(&self, error: Option<&str>) {
+        self.state.write().unwrap().search_error = error.map(str::to_string);
+    }
+
+    // open connections die, like a server restart
+    pub fn disconnect_all(&self) {
+        self.state.write().unwrap().generation += 1;
+    }
+
+    // password changes shorter than this are rejected like an AD/ppolicy quality check
+    pub fn set_min_password_length(&self, length: usize) {
+        self.state.write().unwrap().min_password_length = length;
+    }
+
+    // ldap:// without starttls, AD refuses password changes then
+    pub fn set_plaintext(&self, plaintext: bool) {
+        self.state.write().unwrap().plaintext = plaintext;
+    }
+
+    pub fn password_of(&self, dn: &str) -> Option<String> {
+        self.state.read().unwrap().find(dn).and_then(|e| e.password.clone())
+    }
+
+    pub fn connection_count(&self) -> usize {
+        self.state.read().unwrap().connections
+    }
+}
+
+#[async_trait]
+impl DirectoryClient for InMemoryDirectory {
+    async fn connect(&self) -> Result<Box<dyn DirectoryConnection>, AppError> {
+        let mut state = self.state.write().unwrap();
+        if state.unavailable {
+            return Err(AppError::LdapError("Connection failed: directory unavailable".to_string()));
+        }
+        state.connections += 1;
+
+        Ok(Box::new(InMemoryConnection {
+            state: Arc::clone(&self.state),
+            generation: state.generation,
+            bound: None,
+        }))
+    }
+
+    fn is_available(&self, _url: &str) -> bool {
+        !self.state.read().unwrap().unavailable
+    }
+
+    fn is_reachable(&self) -> bool {
+        !self.state.read().unwrap().unavailable
+    }
+}
+
+struct InMemoryConnection {
+    state: Arc<RwLock<DirectoryState>>,
+    generation: u64,
+    // dn we are bound as, None = anonymous
+    bound: Option<String>,
+}
+
+impl InMemoryConnection {
+    fn check_alive(&self) -> Result<(), AppError> {
+        if self.state.read().unwrap().generation != self.generation {
+            return Err(AppError::LdapError("Connection closed".to_string()));
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl DirectoryConnection for InMemoryConnection {
+    fn url(&self) -> &str {
+        URL
+    }
+
+    fn is_encrypted(&self) -> bool {
+        !self.state.read().unwrap().plaintext
+    }
+
+    async fn simple_bind(&mut self, dn: &str, password: &str) -> Result<OperationResult, AppError> {
+        self.check_alive()?;
+        self.bound = None;
+
+        // unauthenticated bind, real servers accept it as anonymous
+        if password.is_empty() {
+            return Ok(OperationResult { rc: 0, text: String::new() });
+        }
+
+        let state = self.state.read().unwrap();
+        let entry = state.find_bind_entry(dn);
+
+        let forced = state
+            .bind_failures
+            .get(&dn_key(dn))
+            .or_else(|| entry.and_then(|e| state.bind_failures.get(&dn_key(&e.dn))));
+        if let Some(result) = forced {
+            return Ok(result.clone());
+        }
+
+        Ok(match entry {
+            Some(entry) if entry.password.as_deref() == Some(password) => {
+                self.bound = Some(entry.dn.clone());
+                OperationResult { rc: 0, text: String::new() }
+            }
+            Some(_) => ad_invalid_credentials("52e"),
+            None => ad_invalid_credentials("525"),
+        })
+    }
+
+    async fn search(
+        &mut self,
+        base: &str,
+        scope: Scope,
+        filter: &str,
+        attributes: &[&str],
+    ) -> Result<Vec<SearchEntry>, AppError> {
+        self.check_alive()?;
+
+        let state = self.state.read().unwrap();
+        if let Some(error) = &state.search_error {
+            return Err(AppError::LdapError(format!("Search failed: {}", error)));
+        }
+        // AD wants a bind before searching
+        if self.bound.is_none() {
+            return Err(AppError::LdapError(
+                "Search result error: 000004DC: LdapErr: In order to perform this operation a successful bind must be completed on the connection".to_string(),
+            ));
+        }
+
+        let filter = parse_filter(filter).map_err(|e| AppError::LdapError(format!("Search failed: {}", e)))?;
+
+        if scope == Scope::Base && state.find(base).is_none() {
+            return Err(AppError::LdapError(format!("Search result error: no such object {}", base)));
+        }
+
+        Ok(state
+            .entries
+            .iter()
+            .filter(|e| in_scope(&e.dn, base, scope) && state.matches(e, &filter))
+            .map(|e| e.to_search_entry(attributes))
+            .collect())
+    }
+
+    async fn search_paged(
+        &mut self,
+        base: &str,
+        filter: &str,
+        attributes: &[&str],
+        _page_size: i32,
+    ) -> Result<Vec<SearchEntry>, AppError> {
+        self.search(base, Scope::Subtree, filter, attributes).await
+    }
+
+    async fn modify(&mut self, dn: &str, mods: Vec<Mod<Vec<u8>>>) -> Result<OperationResult, AppError> {
+        self.check_alive()?;
+        if self.bound.is_none() {
+            return Ok(result(1, "000004DC: LdapErr: a successful bind must be completed on the connection"));
+        }
+
+        let mut state = self.state.write().unwrap();
+        let min_length = state.min_password_length;
+        let plaintext = state.plaintext;
+        let Some(entry) = state.find_mut(dn) else {
+            return Ok(result(32, "0000208D: NameErr: DSID-0310020A, problem 2001 (NO_OBJECT)"));
+        };
+
+        let password_change = mods.iter().any(|m| match m {
+            Mod::Add(attr, _) | Mod::Delete(attr, _) | Mod::Replace(attr, _) | Mod::Increment(attr, _) => {
+                attr.eq_ignore_ascii_case(b"unicodePwd")
+            }
+        });
+        if password_change {
+            if plaintext {
+                return Ok(result(UNWILLING_TO_PERFORM, "0000001F: SvcErr: DSID-031A12D2, problem 5003 (WILL_NOT_PERFORM), data 0"));
+            }
+            return Ok(entry.change_unicode_pwd(&mods, min_length));
+        }
+
+        let mut changed = entry.clone();
+        for modification in mods {
+            if let Err(failure) = changed.apply(modification) {
+                return Ok(failure);
+            }
+        }
+        *entry = changed;
+        Ok(success())
+    }
+
+    // answers like slapd with ppolicy
+    async fn password_modify(
+        &mut self,
+        user_dn: &str,
+        old_password: &str,
+        new_password: &str,
+    ) -> Result<OperationResult, AppError> {
+        self.check_alive()?;
+        if self.bound.is_none() {
+            return Ok(result(50, "anonymous password modify not allowed"));
+        }
+
+        let mut state = self.state.write().unwrap();
+        let min_length = state.min_password_length;
+        if state.plaintext {
+            return Ok(result(13, "confidentiality required"));
+        }
+        let Some(entry) = state.find_mut(user_dn) else {
+            return Ok(result(32, "no such object"));
+        };
+
+        if entry.password.as_deref() != Some(old_password) {
+            return Ok(result(UNWILLING_TO_PERFORM, "unwilling to verify old password"));
+        }
+        if !entry.change_password(new_password, min_length) {
+            return Ok(result(CONSTRAINT_VIOLATION, "Password fails quality checking policy"));
+        }
+        Ok(success())
+    }
+
+    async fn unbind(&mut self) {
+        self.bound = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE: &str = "DC=example,DC=com";
+
+    fn directory() -> InMemoryDirectory {
+        let directory = InMemoryDirectory::new();
+        directory.add_user(
+            "CN=Max Mustermann,OU=Users,DC=example,DC=com",
+            "secret",
+            &[("sAMAccountName", "max"), ("userPrincipalName", "max@example.com")],
+        );
+        directory.add_user("CN=Anna,OU=Users,DC=example,DC=com", "secret", &[("sAMAccountName", "anna")]);
+        directory.add_group("CN=Staff,OU=Groups,DC=example,DC=com", &["CN=Max Mustermann,OU=Users,DC=example,DC=com"]);
+        directory.add_group("CN=All,OU=Groups,DC=example,DC=com", &["CN=Staff,OU=Groups,DC=example,DC=com"]);
+        directory
+    }
+
+    async fn bound(directory: &InMemoryDirectory) -> Box<dyn DirectoryConnection> {
+        let mut conn = directory.connect().await.unwrap();
+        let result = conn.simple_bind("max@example.com", "secret").await.unwrap();
+        assert_eq!(result.rc, 0);
+        conn
+    }
+
+    async fn search_dns(conn: &mut Box<dyn DirectoryConnection>, filter: &str) -> Vec<String> {
+        let mut dns: Vec<String> = conn
+            .search(BASE, Scope::Subtree, filter, &["1.1"])
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|e| e.dn)
+            .collect();
+        dns.sort();
+        dns
+    }
+
+    #[tokio::test]
+    async fn test_bind_answers_like_ad() {
+        let directory = directory();
+        let mut conn = directory.connect().await.unwrap();
+
+        let wrong = conn.simple_bind("CN=Anna,OU=Users,DC=example,DC=com", "nope").await.unwrap();
+        assert_eq!(wrong.rc, 49);
+        assert!(wrong.text.contains("data 52e"));
+
+        let unknown = conn.simple_bind("CN=Nobody,OU=Users,DC=example,DC=com", "nope").await.unwrap();
+        assert!(unknown.text.contains("data 525"));
+
+        directory.fail_bind("CN=Anna,OU=Users,DC=example,DC=com", 49, "data 775");
+        let locked = conn.simple_bind("cn=anna,ou=users,dc=example,dc=com", "secret").await.unwrap();
+        assert_eq!(locked.text, "data 775");
+    }
+
+    #[tokio::test]
+    async fn test_search_needs_bind() {
+        let directory = directory();
+        let mut conn = directory.connect().await.unwrap();
+
+        // empty password is an anonymous bind
+        assert_eq!(conn.simple_bind("max@example.com", "").await.unwrap().rc, 0);
+        assert!(conn.search(BASE, Scope::Subtree, "(cn=*)", &[]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_filters() {
+        let directory = directory();
+        let mut conn = bound(&directory).await;
+
+        assert_eq!(
+            search_dns(&mut conn, "(&(objectClass=user)(sAMAccountName=MAX))").await,
+            vec!["CN=Max Mustermann,OU=Users,DC=example,DC=com"]
+        );
+        assert_eq!(search_dns(&mut conn, "(|(sAMAccountName=max)(sAMAccountName=anna))").await.len(), 2);
+        assert_eq!(search_dns(&mut conn, "(&(objectClass=user)(!(sAMAccountName=max)))").await.len(), 1);
+        assert_eq!(search_dns(&mut conn, "(cn=max*mann)").await.len(), 1);
+        // escaped injection attempt is a literal value
+        assert!(search_dns(&mut conn, "(sAMAccountName=\\2a\\29\\28cn=\\2a)").await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_in_chain_and_member_of() {
+        let directory = directory();
+        let mut conn = bound(&directory).await;
+
+        let filter = format!(
+            "(member:{}:=CN=Max Mustermann,OU=Users,DC=example,DC=com)",
+            MATCHING_RULE_IN_CHAIN
+        );
+        assert_eq!(
+            search_dns(&mut conn, &filter).await,
+            vec!["CN=All,OU=Groups,DC=example,DC=com", "CN=Staff,OU=Groups,DC=example,DC=com"]
+        );
+
+        let entry = conn
+            .search("CN=Max Mustermann,OU=Users,DC=example,DC=com", Scope::Base, "(objectClass=*)", &["memberOf"])
+            .await
+            .unwrap();
+        assert_eq!(entry[0].attrs["memberOf"], vec!["CN=Staff,OU=Groups,DC=example,DC=com"]);
+    }
+
+    #[tokio::test]
+    async fn test_error_injection() {
+        let directory = directory();
+        let mut conn = bound(&directory).await;
+
+        directory.disconnect_all();
+        assert!(conn.search(BASE, Scope::Subtree, "(cn=*)", &[]).await.is_err());
+
+        directory.set_unavailable(true);
+        assert!(directory.connect().await.is_err());
+        directory.set_unavailable(false);
+
+        let mut conn = bound(&directory).await;
+        directory.fail_searches(Some("boom"));
+        assert!(conn.search(BASE, Scope::Subtree, "(cn=*)", &[]).await.is_err());
+    }
+
+    #[test]
+    fn test_from_json() {
+        let directory = InMemoryDirectory::from_json(
+            r#"{
+                "users": [{"dn": "uid=max,ou=people,dc=example,dc=com", "password": "pw", "attributes": {"uid": "max", "mail": ["max@example.com"]}}],
+                "groups": [{"dn": "cn=admins,ou=groups,dc=example,dc=com", "members": ["uid=max,ou=people,dc=example,dc=com"]}]
+            }"#,
+        )
+        .unwrap();
+
+        let state = directory.state.read().unwrap();
+        let max = state.find("UID=max,ou=people,dc=example,dc=com").unwrap();
+        assert_eq!(max.values("memberOf"), ["cn=admins,ou=groups,dc=example,dc=com"]);
+        assert!(InMemoryDirectory::from_json("{\"users\": 1}").is_err());
+    }
+}
@@ -125,7 +125,7 @@ impl GoogleAuthProvider {
                 google_sub = %google_user.sub,
                 "existing user"
             );
-            return Ok((AuthResult { user }, false));
+            return Ok((AuthResult { user, offline: false }, false));
         }
 
         if let Some(existing) = self.repository.find_by_email(&google_user.email).await? {
@@ -160,7 +160,7 @@ impl GoogleAuthProvider {
             "google user created"
         );
 
-        Ok((AuthResult { user }, true))
+        Ok((AuthResult { user, offline: false }, true))
     }
 }
 
@@ -0,0 +1,218 @@
+// argon2 takes memory_kib and a few hundred ms per call, directly in a handler it blocks the actix worker
+// and a login burst would allocate 64 MiB per request. the work runs on tokio's blocking threads,
+// at most `limit` at once, everything else waits up to queue_timeout and then gets a 503
+use serde::Serialize;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::Semaphore;
+
+use crate::error::AppError;
+
+// upper bounds in ms, the last bucket takes the rest
+const BUCKETS_MS: [u64; 6] = [10, 50, 100, 250, 500, 1000];
+
+#[derive(Debug, Clone, Copy)]
+pub enum HashOperation {
+    Hash,
+    Verify,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LatencyBucket {
+    // None = slower than every bound
+    pub le_ms: Option<u64>,
+    pub count: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct LatencySummary {
+    pub count: u64,
+    pub avg_ms: f64,
+    pub max_ms: f64,
+    pub buckets: Vec<LatencyBucket>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HashingMetrics {
+    pub limit: usize,
+    pub in_flight: usize,
+    pub waiting: usize,
+    // queue timeout hit, answered with 503
+    pub rejected: u64,
+    pub hash: LatencySummary,
+    pub verify: LatencySummary,
+    pub queue_wait: LatencySummary,
+}
+
+#[derive(Default)]
+struct Latencies {
+    count: u64,
+    total: Duration,
+    max: Duration,
+    buckets: [u64; BUCKETS_MS.len() + 1],
+}
+
+impl Latencies {
+    fn record(&mut self, elapsed: Duration) {
+        self.count += 1;
+        self.total += elapsed;
+        self.max = self.max.max(elapsed);
+
+        let ms = elapsed.as_millis() as u64;
+        let bucket = BUCKETS_MS.iter().position(|&le| ms <= le).unwrap_or(BUCKETS_MS.len());
+        self.buckets[bucket] += 1;
+    }
+
+    fn summary(&self) -> LatencySummary {
+        let avg = if self.count == 0 { Duration::ZERO } else { self.total / self.count as u32 };
+
+        LatencySummary {
+            count: self.count,
+            avg_ms: avg.as_secs_f64() * 1000.0,
+            max_ms: self.max.as_secs_f64() * 1000.0,
+            buckets: self
+                .buckets
+                .iter()
+                .enumerate()
+                .map(|(i, &count)| LatencyBucket { le_ms: BUCKETS_MS.get(i).copied(), count })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Stats {
+    rejected: u64,
+    hash: Latencies,
+    verify: Latencies,
+    queue_wait: Latencies,
+}
+
+pub struct HashingPool {
+    permits: Arc<Semaphore>,
+    limit: usize,
+    queue_timeout: Duration,
+    waiting: AtomicUsize,
+    stats: Arc<Mutex<Stats>>,
+}
+
+impl HashingPool {
+    pub fn new(limit: usize, queue_timeout: Duration) -> Self {
+        Self {
+            permits: Arc::new(Semaphore::new(limit)),
+            limit,
+            queue_timeout,
+            waiting: AtomicUsize::new(0),
+            stats: Arc::new(Mutex::new(Stats::default())),
+        }
+    }
+
+    pub async fn run<T, F>(&self, operation: HashOperation, work: F) -> Result<T, AppError>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> T + Send + 'static,
+    {
+        let queued = Instant::now();
+        self.waiting.fetch_add(1, Ordering::Relaxed);
+        let permit = tokio::time::timeout(self.queue_timeout, Arc::clone(&self.permits).acquire_owned()).await;
+        self.waiting.fetch_sub(1, Ordering::Relaxed);
+
+        let permit = match permit {
+            Ok(Ok(permit)) => permit,
+            Ok(Err(_)) => return Err(AppError::InternalError("Hashing pool closed".to_string())),
+            Err(_) => {
+                self.stats.lock().unwrap().rejected += 1;
+                tracing::warn!(
+                    limit = self.limit,
+                    waited_ms = self.queue_timeout.as_millis() as u64,
+                    ?operation,
+                    "Password hashing overloaded"
+                );
+                return Err(AppError::Overloaded("Password hashing queue full".to_string()));
+            }
+        };
+        self.stats.lock().unwrap().queue_wait.record(queued.elapsed());
+
+        // the permit moves into the blocking task, a dropped request still counts until argon2 is done
+        let stats = Arc::clone(&self.stats);
+        tokio::task::spawn_blocking(move || {
+            let started = Instant::now();
+            let result = work();
+            let elapsed = started.elapsed();
+            drop(permit);
+
+            let mut stats = stats.lock().unwrap();
+            match operation {
+                HashOperation::Hash => stats.hash.record(elapsed),
+                HashOperation::Verify => stats.verify.record(elapsed),
+            }
+            result
+        })
+        .await
+        .map_err(|e| AppError::InternalError(format!("Hashing task failed: {}", e)))
+    }
+
+    pub fn metrics(&self) -> HashingMetrics {
+        let stats = self.stats.lock().unwrap();
+
+        HashingMetrics {
+            limit: self.limit,
+            in_flight: self.limit - self.permits.available_permits(),
+            waiting: self.waiting.load(Ordering::Relaxed),
+            rejected: stats.rejected,
+            hash: stats.hash.summary(),
+            verify: stats.verify.summary(),
+            queue_wait: stats.queue_wait.summary(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_run_records_latency() {
+        let pool = HashingPool::new(2, Duration::from_secs(1));
+
+        let result = pool.run(HashOperation::Verify, || 40 + 2).await.unwrap();
+        assert_eq!(result, 42);
+
+        let metrics = pool.metrics();
+        assert_eq!(metrics.verify.count, 1);
+        assert_eq!(metrics.hash.count, 0);
+        assert_eq!(metrics.queue_wait.count, 1);
+        assert_eq!(metrics.verify.buckets.len(), BUCKETS_MS.len() + 1);
+        assert_eq!(metrics.verify.buckets[0].count, 1);
+        assert_eq!(metrics.in_flight, 0);
+    }
+
+    #[tokio::test]
+    async fn test_full_pool_rejects_after_queue_timeout() {
+        let pool = Arc::new(HashingPool::new(1, Duration::from_millis(50)));
+
+        let busy = {
+            let pool = Arc::clone(&pool);
+            tokio::spawn(async move {
+                pool.run(HashOperation::Hash, || std::thread::sleep(Duration::from_millis(300))).await
+            })
+        };
+        // let the first one take the only permit
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(pool.metrics().in_flight, 1);
+
+        let rejected = pool.run(HashOperation::Verify, || ()).await;
+        assert!(matches!(rejected, Err(AppError::Overloaded(_))));
+
+        busy.await.unwrap().unwrap();
+        let metrics = pool.metrics();
+        assert_eq!(metrics.rejected, 1);
+        assert_eq!(metrics.hash.count, 1);
+        assert_eq!(metrics.hash.buckets[4].count, 1);
+        assert_eq!(metrics.in_flight, 0);
+
+        // free again
+        assert!(pool.run(HashOperation::Verify, || ()).await.is_ok());
+    }
+}
@@ -4,7 +4,7 @@ use serde::{Deserialize, Serialize};
 
 use crate::config::JwtConfig;
 use crate::error::AppError;
-use crate::models::UserRole;
+use crate::models::{format_scope, UserRole};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Claims {
@@ -15,6 +15,12 @@ pub struct Claims {
     pub exp: i64,
     pub iat: i64,
     pub iss: String,
+    // space separated like the oauth scope param, tokens from before scopes had none
+    #[serde(default)]
+    pub scope: String,
+    // logged in with the cached LDAP credential while the directory was unreachable
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub offline: bool,
 }
 
 impl Claims {
@@ -29,6 +35,13 @@ impl Claims {
             exp: exp.timestamp(),
             iat: now.timestamp(),
             iss: config.issuer.clone(),
+            scope: role
+                .permissions()
+                .iter()
+                .map(|p| p.as_str())
+                .collect::<Vec<_>>()
+                .join(" "),
+            offline: false,
         }
     }
 
@@ -41,6 +54,14 @@ impl Claims {
             .parse()
             .map_err(|_| AppError::InternalError("Invalid role in token".to_string()))
     }
+
+    pub fn scopes(&self) -> impl Iterator<Item = &str> {
+        self.scope.split_whitespace()
+    }
+
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scopes().any(|s| s == scope)
+    }
 }
 
 pub struct JwtService {
@@ -76,11 +97,46 @@ impl JwtService {
         role: UserRole,
     ) -> Result<String, AppError> {
         let claims = Claims::new(user_id, email, role, &self.config);
+        self.sign(&claims)
+    }
+
+    // same token, marked so services can refuse offline logins for sensitive actions
+    pub fn generate_offline_token(
+        &self,
+        user_id: &str,
+        email: &str,
+        role: UserRole,
+    ) -> Result<String, AppError> {
+        let mut claims = Claims::new(user_id, email, role, &self.config);
+        claims.offline = true;
+        self.sign(&claims)
+    }
+
+    // tokens for oauth clients: own lifetime and only the granted scopes
+    pub fn generate_scoped_token(
+        &self,
+        user_id: &str,
+        email: &str,
+        role: UserRole,
+        scopes: &[String],
+        expiration_secs: i64,
+    ) -> Result<String, AppError> {
+        let mut claims = Claims::new(user_id, email, role, &self.config);
+        claims.exp = claims.iat + expiration_secs;
+        claims.scope = format_scope(scopes);
+
+        self.sign(&claims)
+    }
 
-        encode(&Header::default(), &claims, &self.encoding_key)
+    fn sign(&self, claims: &Claims) -> Result<String, AppError> {
+        encode(&Header::default(), claims, &self.encoding_key)
             .map_err(|e| AppError::InternalError(format!("Token generation failed: {}", e)))
     }
 
+    pub fn expiration_secs(&self) -> i64 {
+        self.config.expiration_secs
+    }
+
     pub fn validate_token(&self, token: &str) -> Result<Claims, AppError> {
         let token_data = decode::<Claims>(token, &self.decoding_key, &self.validation)?;
         Ok(token_data.claims)
@@ -171,6 +227,56 @@ mod tests {
         assert_eq!(claims.exp, claims.iat + 3600);
     }
 
+    #[test]
+    fn test_generate_scoped_token() {
+        let service = JwtService::new(test_config());
+        let scopes = vec!["openid".to_string(), "profile".to_string()];
+        let token = service
+            .generate_scoped_token("user-123", "test@example.com", UserRole::User, &scopes, 300)
+            .unwrap();
+
+        let claims = service.validate_token(&token).unwrap();
+        assert_eq!(claims.exp, claims.iat + 300);
+        assert_eq!(claims.scope, "openid profile");
+        assert!(claims.has_scope("profile"));
+        assert!(!claims.has_scope("email"));
+    }
+
+    #[test]
+    fn test_generate_offline_token() {
+        let service = JwtService::new(test_config());
+
+        let offline = service
+            .generate_offline_token("user-123", "test@example.com", UserRole::User)
+            .unwrap();
+        assert!(service.validate_token(&offline).unwrap().offline);
+
+        // normal tokens dont carry the claim at all
+        let online = service.generate_token("user-123", "test@example.com", UserRole::User).unwrap();
+        let claims = service.validate_token(&online).unwrap();
+        assert!(!claims.offline);
+        assert!(!serde_json::to_string(&claims).unwrap().contains("offline"));
+    }
+
+    #[test]
+    fn test_token_carries_role_permissions() {
+        let service = JwtService::new(test_config());
+
+        let token = service
+            .generate_token("user-123", "test@example.com", UserRole::User)
+            .unwrap();
+        let claims = service.validate_token(&token).unwrap();
+        assert!(claims.has_scope("profile"));
+        assert!(!claims.has_scope("users:write"));
+
+        let token = service
+            .generate_token("admin-456", "admin@example.com", UserRole::Admin)
+            .unwrap();
+        let claims = service.validate_token(&token).unwrap();
+        assert!(claims.has_scope("users:write"));
+        assert!(claims.has_scope("clients:read"));
+    }
+
     // ==================== Token Validation Tests ====================
 
     #[test]
@@ -267,6 +373,8 @@ mod tests {
             exp: Utc::now().timestamp() - 100,
             iat: Utc::now().timestamp() - 200,
             iss: "test".to_string(),
+            scope: String::new(),
+            offline: false,
         };
         assert!(claims.is_expired());
     }
@@ -296,6 +404,8 @@ mod tests {
             exp: Utc::now().timestamp() + 3600,
             iat: Utc::now().timestamp(),
             iss: "test".to_string(),
+            scope: String::new(),
+            offline: false,
         };
         assert!(claims.get_role().is_err());
     }
@@ -1,165 +1,623 @@
 // ldap authentication blind -> die credentials die eingegeben werden verwendet um ldap server zu binden
+// mit service account: search-then-bind -> service account sucht dn vom user, dann bind als dieser dn
 use async_trait::async_trait;
-use ldap3::{LdapConnAsync, LdapConnSettings, Scope, SearchEntry};
+use chrono::Utc;
+use ldap3::{dn_escape, ldap_escape, Mod, Scope, SearchEntry};
+use std::collections::HashSet;
 use std::sync::Arc;
 use std::time::Duration;
 
-use crate::config::LdapConfig;
-use crate::error::AppError;
+use crate::config::{GroupMembership, LdapConfig, NestedGroupMode};
+use crate::error::{AccountRestriction, AppError};
 use crate::models::{AuthProviderType, User, UserRole};
-use crate::repository::UserRepository;
+use crate::models::CachedCredential;
+use crate::repository::{CredentialCacheRepository, UserRepository};
 
-use super::{AuthProvider, AuthResult};
+use super::{AuthProvider, AuthResult, PasswordHasher};
+use super::directory::{DirectoryClient, DirectoryConnection, Ldap3Client, OperationResult};
+use super::ldap_groups::{normalize_dn, role_for_groups};
+use super::ldap_pool::{PooledConnection, ServiceConnectionPool};
+
+// LDAP_MATCHING_RULE_IN_CHAIN
+pub(super) const MATCHING_RULE_IN_CHAIN: &str = "1.2.840.113556.1.4.1941";
+const MAX_NESTED_GROUPS: usize = 500;
+const LDAP_INVALID_CREDENTIALS: u32 = 49;
+const LDAP_CONFIDENTIALITY_REQUIRED: u32 = 13;
+const LDAP_CONSTRAINT_VIOLATION: u32 = 19;
+const LDAP_INSUFFICIENT_ACCESS: u32 = 50;
+const LDAP_UNWILLING_TO_PERFORM: u32 = 53;
+// userAccountControl ACCOUNTDISABLE flag
+const UAC_ACCOUNT_DISABLED: u32 = 0x2;
 
 pub struct LdapAuthProvider {
     config: LdapConfig,
     repository: Arc<dyn UserRepository>,
+    client: Arc<dyn DirectoryClient>,
+    pool: ServiceConnectionPool,
+    // only with LDAP_OFFLINE_GRACE_SECS
+    credential_cache: Option<Arc<dyn CredentialCacheRepository>>,
+    password_hasher: PasswordHasher,
 }
 
 impl LdapAuthProvider {
-    pub fn new(config: LdapConfig, repository: Arc<dyn UserRepository>) -> Self {
-        Self { config, repository }
+    pub fn new(config: LdapConfig, repository: Arc<dyn UserRepository>) -> Result<Self, AppError> {
+        let client = Arc::new(Ldap3Client::new(&config)?);
+        Ok(Self::with_client(config, repository, client))
     }
 
-    //construct Domain name -> bsp CN=username,OU=Users,DC=domain,DC=com or using userPrincipalName: username@domain.com
-    fn build_bind_dn(&self, username: &str) -> String {
-        if self.config.use_upn {
-            format!("{}@{}", username, self.config.domain)
-        } else {
-            format!(
-                "CN={},{}",
-                username, self.config.user_base_dn
-            )
+    // any directory, InMemoryDirectory in tests
+    pub fn with_client(
+        config: LdapConfig,
+        repository: Arc<dyn UserRepository>,
+        client: Arc<dyn DirectoryClient>,
+    ) -> Self {
+        let pool = ServiceConnectionPool::new(
+            config.pool_size,
+            Duration::from_secs(config.pool_idle_secs),
+            Duration::from_secs(config.timeout_secs),
+        );
+
+        Self {
+            config,
+            repository,
+            client,
+            pool,
+            credential_cache: None,
+            password_hasher: PasswordHasher::new(),
         }
     }
 
-    //tries to connect to ldap server
-    async fn bind_user(&self, username: &str, password: &str) -> Result<ldap3::Ldap, AppError> {
-        let settings = LdapConnSettings::new()
-            .set_conn_timeout(Duration::from_secs(self.config.timeout_secs))
-            .set_starttls(self.config.use_starttls);
-
-        let (conn, mut ldap) = LdapConnAsync::with_settings(settings, &self.config.url)
-            .await
-            .map_err(|e| {
-                tracing::error!(error = %e, "cannt connect ldap server");
-                AppError::LdapError(format!("Connection failed: {}", e))
-            })?;
+    // remember a verifier of every working password, used when all servers are down
+    pub fn with_credential_cache(mut self, cache: Arc<dyn CredentialCacheRepository>) -> Self {
+        self.credential_cache = Some(cache);
+        self
+    }
 
-        // Spawn con handler
-        ldap3::drive!(conn);
+    // cache verifiers use the configured Argon2 cost too
+    pub fn with_password_hasher(mut self, hasher: PasswordHasher) -> Self {
+        self.password_hasher = hasher;
+        self
+    }
 
-        let bind_dn = self.build_bind_dn(username);
+    async fn bind_as(&self, ldap: &mut dyn DirectoryConnection, bind_dn: &str, password: &str, username: &str) -> Result<(), AppError> {
         tracing::debug!(bind_dn = %bind_dn, "tried ldap bind");
 
-        let result = ldap.simple_bind(&bind_dn, password).await.map_err(|e| {
+        let result = ldap.simple_bind(bind_dn, password).await.map_err(|e| {
             tracing::warn!(error = %e, username = %username, "bind faild");
             AppError::Unauthorized("invalid imputs".to_string())
         })?;
 
         if result.rc != 0 {
+            let sub_code = ad_sub_code(&result.text);
             tracing::warn!(
                 username = %username,
                 result_code = result.rc,
+                sub_code = sub_code.unwrap_or("-"),
                 "ldap rejected"
             );
+
+            if self.config.provider == AuthProviderType::ActiveDirectory && result.rc == LDAP_INVALID_CREDENTIALS {
+                return Err(ad_bind_failure(sub_code));
+            }
             return Err(AppError::Unauthorized("Invalid credentials".to_string()));
         }
 
         tracing::debug!(username = %username, "ldap bind successful");
+        Ok(())
+    }
+
+    async fn bind_service(&self, ldap: &mut dyn DirectoryConnection) -> Result<(), AppError> {
+        let service_dn = self.config.bind_dn.as_deref().unwrap_or_default();
+        let service_password = self.config.bind_password.as_deref().unwrap_or_default();
+
+        let result = ldap.simple_bind(service_dn, service_password).await.map_err(|e| {
+            tracing::error!(error = %e, "service account bind failed");
+            AppError::LdapError(format!("Service account bind failed: {}", e))
+        })?;
+
+        if result.rc != 0 {
+            tracing::error!(result_code = result.rc, bind_dn = %service_dn, "ldap rejected service account");
+            return Err(AppError::LdapError("Service account bind failed".to_string()));
+        }
+
+        Ok(())
+    }
+
+    // direct bind with the dn built from the username
+    async fn bind_user(&self, username: &str, password: &str) -> Result<Box<dyn DirectoryConnection>, AppError> {
+        let mut ldap = self.client.connect().await?;
+        tracing::debug!(url = %ldap.url(), "direct bind");
+
+        let bind_dn = build_bind_dn(&self.config, username);
+        self.bind_as(ldap.as_mut(), &bind_dn, password, username).await?;
+
         Ok(ldap)
     }
 
-    //search attributes
-    async fn fetch_user_info(&self, ldap: &mut ldap3::Ldap, username: &str) -> Result<LdapUserInfo, AppError> {
-        let search_filter = format!("({}={})", self.config.username_attribute, username);
+    // idle pooled connection if there is one, otherwise a new one bound as the service account
+    async fn service_connection(&self, reuse: bool) -> Result<PooledConnection<'_>, AppError> {
+        let permit = self.pool.acquire().await?;
+
+        if reuse && let Some(ldap) = self.pool.take_idle(self.client.as_ref()) {
+            return Ok(PooledConnection::new(ldap, true, &self.pool, permit));
+        }
+
+        let mut ldap = self.client.connect().await?;
+        self.bind_service(ldap.as_mut()).await?;
+
+        Ok(PooledConnection::new(ldap, false, &self.pool, permit))
+    }
+
+    // user lookup on a pooled connection, a stale idle connection is replaced once
+    async fn find_user_pooled(&self, username: &str) -> Result<(PooledConnection<'_>, Option<SearchEntry>), AppError> {
+        let mut conn = self.service_connection(true).await?;
+
+        let mut conn = match self.search_user(&mut *conn, username).await {
+            Ok(entry) => return Ok((conn, entry)),
+            Err(AppError::LdapError(e)) if conn.reused => {
+                // server closed the idle connection (dc restart, server side idle timeout)
+                tracing::debug!(error = %e, url = %conn.url(), "pooled ldap connection stale, reconnecting");
+                conn.discard();
+                drop(conn);
+                self.service_connection(false).await?
+            }
+            Err(e) => {
+                conn.discard();
+                return Err(e);
+            }
+        };
+
+        match self.search_user(&mut *conn, username).await {
+            Ok(entry) => Ok((conn, entry)),
+            Err(e) => {
+                conn.discard();
+                Err(e)
+            }
+        }
+    }
+
+    // service account looks up the real dn of the user, then we bind as that dn on the same pooled connection
+    async fn search_then_bind(&self, username: &str, password: &str) -> Result<(SearchEntry, Vec<String>), AppError> {
+        let (mut conn, entry) = self.find_user_pooled(username).await?;
+
+        // same answer as a wrong password, dont tell if the user exists
+        let entry = entry.ok_or_else(|| {
+            tracing::warn!(username = %username, "User not found");
+            AppError::Unauthorized("Invalid credentials".to_string())
+        })?;
+
+        let bind_result = self.bind_as(&mut *conn, &entry.dn, password, username).await;
+
+        // connection is now bound as the user (or anonymous after a failed bind), back to the service account before it is reused
+        if let Err(e) = self.bind_service(&mut *conn).await {
+            conn.discard();
+            bind_result?;
+            return Err(e);
+        }
+        bind_result?;
+
+        let groups = self.resolve_groups(&mut *conn, &entry).await;
+
+        tracing::debug!(url = %conn.url(), idle = self.pool.idle_count(), "search-then-bind done on pooled connection");
+        Ok((entry, groups))
+    }
+
+    // password change with the old password, AD over unicodePwd delete/add, other servers over RFC 3062
+    pub async fn change_password(&self, username: &str, old_password: &str, new_password: &str) -> Result<(), AppError> {
+        tracing::info!(username = %username, "LDAP password change attempt");
+
+        if old_password.is_empty() {
+            return Err(AppError::Unauthorized("Invalid credentials".to_string()));
+        }
+        if new_password.is_empty() || new_password == old_password {
+            return Err(AppError::ValidationError("New password must differ from the old one".to_string()));
+        }
+
+        if !self.config.search_then_bind() {
+            // expired passwords cannot bind, without service account there is no way to find the dn then
+            let mut ldap = self.bind_user(username, old_password).await?;
+            let result = match self.fetch_user_entry(ldap.as_mut(), username).await {
+                Ok(entry) => self.modify_password(ldap.as_mut(), &entry.dn, username, old_password, new_password).await,
+                Err(e) => Err(e),
+            };
+            ldap.unbind().await;
+            return result;
+        }
+
+        let (mut conn, entry) = self.find_user_pooled(username).await?;
+        let entry = entry.ok_or_else(|| {
+            tracing::warn!(username = %username, "User not found");
+            AppError::Unauthorized("Invalid credentials".to_string())
+        })?;
+
+        let result = self
+            .change_password_on(&mut *conn, &entry.dn, username, old_password, new_password)
+            .await;
+
+        // back to the service account before the connection is reused
+        if let Err(e) = self.bind_service(&mut *conn).await {
+            conn.discard();
+            result?;
+            return Err(e);
+        }
+        result
+    }
+
+    async fn change_password_on(
+        &self,
+        ldap: &mut dyn DirectoryConnection,
+        user_dn: &str,
+        username: &str,
+        old_password: &str,
+        new_password: &str,
+    ) -> Result<(), AppError> {
+        match self.bind_as(ldap, user_dn, old_password, username).await {
+            Ok(()) => {}
+            // AD answers 532/773 only for the right old password, the user cant bind but the change is allowed
+            // (Everyone has "Change Password" on user objects), the delete below checks the old password again
+            Err(AppError::AccountRestricted(AccountRestriction::PasswordExpired | AccountRestriction::PasswordMustChange))
+                if self.config.provider == AuthProviderType::ActiveDirectory =>
+            {
+                tracing::info!(username = %username, "password expired, changing it as service account");
+                self.bind_service(ldap).await?;
+            }
+            Err(e) => return Err(e),
+        }
+
+        self.modify_password(ldap, user_dn, username, old_password, new_password).await
+    }
+
+    async fn modify_password(
+        &self,
+        ldap: &mut dyn DirectoryConnection,
+        user_dn: &str,
+        username: &str,
+        old_password: &str,
+        new_password: &str,
+    ) -> Result<(), AppError> {
+        // the password goes over the wire, AD refuses it anyway (rc 53)
+        if !ldap.is_encrypted() {
+            tracing::error!(url = %ldap.url(), "password change over unencrypted ldap connection refused");
+            return Err(AppError::LdapError(
+                "Password change needs an encrypted connection (ldaps:// or LDAP_USE_STARTTLS)".to_string(),
+            ));
+        }
+
+        let result = if self.config.provider == AuthProviderType::ActiveDirectory {
+            let mods = vec![
+                Mod::Delete(b"unicodePwd".to_vec(), HashSet::from([ad_password_value(old_password)])),
+                Mod::Add(b"unicodePwd".to_vec(), HashSet::from([ad_password_value(new_password)])),
+            ];
+            ldap.modify(user_dn, mods).await?
+        } else {
+            ldap.password_modify(user_dn, old_password, new_password).await?
+        };
+
+        if result.rc != 0 {
+            tracing::warn!(
+                username = %username,
+                result_code = result.rc,
+                text = %result.text,
+                "ldap rejected password change"
+            );
+            return Err(password_change_failure(&self.config.provider, &result));
+        }
+
+        tracing::info!(username = %username, "LDAP password changed");
+
+        // old password must not keep working offline
+        if let Some(cache) = &self.credential_cache
+            && let Ok(Some(cached)) = cache.find_by_username(username).await
+        {
+            let _ = cache.delete(&cached.user_id).await;
+        }
+        Ok(())
+    }
+
+    // failures dont break the login, the user just has no offline fallback
+    async fn cache_credential(&self, user: &User, username: &str, password: &str) {
+        let Some(cache) = &self.credential_cache else {
+            return;
+        };
+
+        let stored = match self.password_hasher.hash(password).await {
+            Ok(verifier) => cache.store(&CachedCredential::new(user.id.clone(), username, verifier)).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = stored {
+            tracing::warn!(error = %e, user_id = %user.id, "could not cache LDAP credential");
+        }
+    }
+
+    // restricted accounts (disabled, locked, expired) lose their offline login right away
+    async fn forget_credential(&self, username: &str) {
+        let Some(cache) = &self.credential_cache else {
+            return;
+        };
+        if let Ok(Some(cached)) = cache.find_by_username(username).await {
+            let _ = cache.delete(&cached.user_id).await;
+            tracing::info!(user_id = %cached.user_id, "cached LDAP credential removed");
+        }
+    }
+
+    // accepted only for a fresh cached credential, a matching password and an active account
+    async fn authenticate_offline(&self, username: &str, password: &str) -> Result<OfflineLogin, AppError> {
+        let (Some(cache), Some(grace)) = (&self.credential_cache, self.config.offline_grace_secs) else {
+            return Ok(OfflineLogin::Unavailable);
+        };
+
+        let Some(cached) = cache.find_by_username(username).await? else {
+            return Ok(OfflineLogin::Unavailable);
+        };
+        if !cached.is_within_grace(chrono::Duration::seconds(grace as i64)) {
+            tracing::info!(user_id = %cached.user_id, "cached LDAP credential older than the grace period");
+            return Ok(OfflineLogin::Unavailable);
+        }
+        if !self.password_hasher.verify(password, &cached.verifier).await? {
+            tracing::warn!(user_id = %cached.user_id, "wrong password against cached LDAP credential");
+            return Ok(OfflineLogin::WrongPassword);
+        }
+
+        let user = self
+            .repository
+            .find_by_id(&cached.user_id)
+            .await?
+            .filter(|user| user.is_active && user.auth_provider == self.config.provider);
+        Ok(user.map_or(OfflineLogin::Unavailable, OfflineLogin::Accepted))
+    }
+
+    async fn search_user(&self, ldap: &mut dyn DirectoryConnection, username: &str) -> Result<Option<SearchEntry>, AppError> {
+        let search_filter = render_filter(&self.config.user_filter, username);
 
-        let (entries, _result) = ldap
+        let entries = ldap
             .search(
                 &self.config.user_base_dn,
                 Scope::Subtree,
                 &search_filter,
-                vec!["cn", "mail", "displayName", "sAMAccountName", "userPrincipalName", "memberOf"],
+                &user_attributes(&self.config),
             )
             .await
-            .map_err(|e| {
-                tracing::error!(error = %e, "search failed");
-                AppError::LdapError(format!("Search failed: {}", e))
-            })?
-            .success()
-            .map_err(|e| {
-                tracing::error!(error = %e, "search result error");
-                AppError::LdapError(format!("Search result error: {}", e))
-            })?;
-
-        let entry = entries.into_iter().next().ok_or_else(|| {
+            .inspect_err(|e| tracing::error!(error = %e, "search failed"))?;
+
+        // filter too broad, never pick one of several users
+        if entries.len() > 1 {
+            tracing::error!(username = %username, matches = entries.len(), "user filter matched more than one entry");
+            return Err(AppError::LdapError("Ambiguous user".to_string()));
+        }
+
+        Ok(entries.into_iter().next())
+    }
+
+    async fn fetch_user_entry(&self, ldap: &mut dyn DirectoryConnection, username: &str) -> Result<SearchEntry, AppError> {
+        self.search_user(ldap, username).await?.ok_or_else(|| {
             tracing::warn!(username = %username, "User not found");
             AppError::LdapError("User not found".to_string())
-        })?;
-
-        let entry = SearchEntry::construct(entry);
-
-        let display_name = entry
-            .attrs
-            .get("displayName")
-            .and_then(|v| v.first())
-            .or_else(|| entry.attrs.get("cn").and_then(|v| v.first()))
-            .cloned()
-            .unwrap_or_else(|| username.to_string());
-
-        let email = entry
-            .attrs
-            .get("mail")
-            .and_then(|v| v.first())
-            .cloned()
-            .or_else(|| entry.attrs.get("userPrincipalName").and_then(|v| v.first()).cloned())
-            .unwrap_or_else(|| format!("{}@{}", username, self.config.domain));
-
-        let sam_account = entry
-            .attrs
-            .get("sAMAccountName")
-            .and_then(|v| v.first())
-            .cloned()
-            .unwrap_or_else(|| username.to_string());
-
-        // Check group membership for admin role
-        let groups: Vec<String> = entry
-            .attrs
-            .get("memberOf")
-            .cloned()
-            .unwrap_or_default();
-
-        let is_admin = self.config.admin_group.as_ref().map_or(false, |admin_group| {
-            groups.iter().any(|g| g.to_lowercase().contains(&admin_group.to_lowercase()))
-        });
-
-        Ok(LdapUserInfo {
-            username: sam_account,
-            display_name,
-            email,
-            is_admin,
         })
     }
 
+    // direct groups plus nested groups depending on config, lookup problems only cost the nested groups
+    async fn resolve_groups(&self, ldap: &mut dyn DirectoryConnection, entry: &SearchEntry) -> Vec<String> {
+        let direct = match self.direct_groups(ldap, entry).await {
+            Ok(direct) => direct,
+            Err(e) => {
+                tracing::warn!(error = %e, user_dn = %entry.dn, "group lookup failed, user gets no groups");
+                return Vec::new();
+            }
+        };
+
+        let nested = match self.config.nested_groups {
+            NestedGroupMode::None => return direct,
+            NestedGroupMode::InChain => self.groups_in_chain(ldap, &entry.dn).await,
+            NestedGroupMode::Recursive => self.groups_recursive(ldap, &direct).await,
+        };
+
+        match nested {
+            Ok(nested) => {
+                let mut groups = direct;
+                for group in nested {
+                    if !groups.iter().any(|g| normalize_dn(g) == normalize_dn(&group)) {
+                        groups.push(group);
+                    }
+                }
+                groups
+            }
+            Err(e) => {
+                tracing::warn!(error = %e, user_dn = %entry.dn, "nested group lookup failed, using direct groups");
+                direct
+            }
+        }
+    }
+
+    // memberOf on the user, or a search for groups listing the user as member
+    async fn direct_groups(&self, ldap: &mut dyn DirectoryConnection, entry: &SearchEntry) -> Result<Vec<String>, AppError> {
+        match self.config.group_membership {
+            GroupMembership::MemberOf => Ok(attr_values(entry, &self.config.group_attribute)),
+            GroupMembership::GroupOfNames => self.search_groups(ldap, &group_of_names_filter(&entry.dn)).await,
+            GroupMembership::PosixGroup => {
+                let Some(uid) = first_attr(entry, &self.config.username_attribute) else {
+                    return Ok(Vec::new());
+                };
+                let filter = format!("(&(objectClass=posixGroup)(memberUid={}))", ldap_escape(uid));
+                self.search_groups(ldap, &filter).await
+            }
+        }
+    }
+
+    // dns of all groups below group_base_dn matching the filter
+    async fn search_groups(&self, ldap: &mut dyn DirectoryConnection, filter: &str) -> Result<Vec<String>, AppError> {
+        let entries = ldap
+            .search(&self.config.group_base_dn, Scope::Subtree, filter, &["1.1"])
+            .await?;
+
+        Ok(entries.into_iter().map(|e| e.dn).collect())
+    }
+
+    // AD resolves the whole chain server side with LDAP_MATCHING_RULE_IN_CHAIN
+    async fn groups_in_chain(&self, ldap: &mut dyn DirectoryConnection, user_dn: &str) -> Result<Vec<String>, AppError> {
+        let filter = format!("(member:{}:={})", MATCHING_RULE_IN_CHAIN, ldap_escape(user_dn));
+        self.search_groups(ldap, &filter).await
+    }
+
+    // parent groups of one group, same membership model as for users
+    async fn parent_groups(&self, ldap: &mut dyn DirectoryConnection, group_dn: &str) -> Result<Vec<String>, AppError> {
+        match self.config.group_membership {
+            GroupMembership::MemberOf => {
+                let entries = ldap
+                    .search(group_dn, Scope::Base, "(objectClass=*)", &[self.config.group_attribute.as_str()])
+                    .await?;
+
+                Ok(entries
+                    .iter()
+                    .flat_map(|e| attr_values(e, &self.config.group_attribute))
+                    .collect())
+            }
+            GroupMembership::GroupOfNames => self.search_groups(ldap, &group_of_names_filter(group_dn)).await,
+            // memberUid holds usernames, posix groups cant be nested
+            GroupMembership::PosixGroup => Ok(Vec::new()),
+        }
+    }
+
+    // walk up the parents of each group, for servers without the matching rule
+    async fn groups_recursive(&self, ldap: &mut dyn DirectoryConnection, direct: &[String]) -> Result<Vec<String>, AppError> {
+        let mut seen: Vec<String> = direct.iter().map(|g| normalize_dn(g)).collect();
+        let mut queue: Vec<String> = direct.to_vec();
+        let mut found = Vec::new();
+
+        while let Some(group_dn) = queue.pop() {
+            if seen.len() > MAX_NESTED_GROUPS {
+                tracing::warn!(limit = MAX_NESTED_GROUPS, "nested group limit reached");
+                break;
+            }
+
+            for parent in self.parent_groups(ldap, &group_dn).await? {
+                let normalized = normalize_dn(&parent);
+                // cycles between groups are allowed in ldap
+                if !seen.contains(&normalized) {
+                    seen.push(normalized);
+                    queue.push(parent.clone());
+                    found.push(parent);
+                }
+            }
+        }
+
+        Ok(found)
+    }
+
+    pub(super) fn provider_type(&self) -> &AuthProviderType {
+        &self.config.provider
+    }
+
+    // every user below user_base_dn with groups and disabled flag, for the background sync
+    pub(super) async fn directory_users(&self) -> Result<Vec<DirectoryUser>, AppError> {
+        if !self.config.search_then_bind() {
+            return Err(AppError::LdapError("Directory sync needs a service account (LDAP_BIND_DN)".to_string()));
+        }
+
+        let mut conn = self.service_connection(false).await?;
+        let entries = match self.search_all_users(&mut *conn).await {
+            Ok(entries) => entries,
+            Err(e) => {
+                conn.discard();
+                return Err(e);
+            }
+        };
+
+        let mut users = Vec::with_capacity(entries.len());
+        for entry in entries {
+            let Some(username) = first_attr(&entry, &self.config.username_attribute).cloned() else {
+                tracing::debug!(dn = %entry.dn, "directory entry without username attribute, skipped");
+                continue;
+            };
+
+            let groups = self.resolve_groups(&mut *conn, &entry).await;
+            users.push(DirectoryUser {
+                disabled: is_account_disabled(&entry),
+                info: user_info(&self.config, &entry, &username, &groups),
+            });
+        }
+
+        Ok(users)
+    }
+
+    // all users, paged because of the server size limit
+    async fn search_all_users(&self, ldap: &mut dyn DirectoryConnection) -> Result<Vec<SearchEntry>, AppError> {
+        let filter = self.config.user_filter.replace("{username}", "*");
+
+        let mut attributes = user_attributes(&self.config);
+        attributes.push("userAccountControl");
+        attributes.push("nsAccountLock");
+
+        ldap.search_paged(&self.config.user_base_dn, &filter, &attributes, self.config.sync_page_size)
+            .await
+    }
+
+    // directory is the source of truth, name/email/role changes in ad land here on the next login
+    // and a deactivated account that is back in the directory is active again
+    // returns the user and whether anything changed
+    pub(super) async fn refresh_user(&self, mut user: User, info: &LdapUserInfo) -> Result<(User, bool), AppError> {
+        let new_email = info.email.to_lowercase();
+
+        // unique email, another account could already use the new address
+        let email_taken = user.email != new_email
+            && self
+                .repository
+                .find_by_email_including_inactive(&new_email)
+                .await?
+                .is_some_and(|other| other.id != user.id);
+
+        if email_taken {
+            tracing::warn!(
+                user_id = %user.id,
+                email = %new_email,
+                "directory email already used by another account, keeping old email"
+            );
+        }
+
+        let mut changes = apply_directory_attributes(&mut user, info, !email_taken);
+        if !user.is_active {
+            changes.push("reactivated".to_string());
+            user.is_active = true;
+            user.updated_at = Utc::now();
+        }
+        if changes.is_empty() {
+            return Ok((user, false));
+        }
+
+        self.repository.update(&user).await?;
+
+        tracing::info!(
+            user_id = %user.id,
+            username = %info.username,
+            changes = %changes.join(", "),
+            "Updated LDAP user from directory"
+        );
+
+        Ok((user, true))
+    }
+
     //create user record
     async fn sync_user(&self, info: &LdapUserInfo) -> Result<User, AppError> {
-        let provider = "activedirectory";
+        let provider = self.config.provider.to_string();
         let external_id = &info.username;
 
-        // user alredy exist
-        if let Some(existing) = self.repository.find_by_external_id(provider, external_id).await? {
+        // user alredy exist, deactivated ones too, otherwise the insert below runs into the unique index
+        if let Some(existing) = self
+            .repository
+            .find_by_external_id_including_inactive(&provider, external_id)
+            .await?
+        {
             tracing::debug!(
                 user_id = %existing.id,
                 username = %info.username,
                 "Found existing LDAP user"
             );
-            return Ok(existing);
+            return self.refresh_user(existing, info).await.map(|(user, _)| user);
         }
 
         // check existing by mail
-        if let Some(existing) = self.repository.find_by_email(&info.email).await? {
-            if existing.auth_provider == AuthProviderType::ActiveDirectory {
-                return Ok(existing);
+        if let Some(existing) = self.repository.find_by_email_including_inactive(&info.email).await? {
+            if existing.auth_provider == self.config.provider {
+                return self.refresh_user(existing, info).await.map(|(user, _)| user);
             }
             tracing::warn!(
                 email = %info.email,
@@ -172,16 +630,12 @@ impl LdapAuthProvider {
         }
 
         // Create new user
-        let role = if info.is_admin {
-            UserRole::Admin
-        } else {
-            UserRole::User
-        };
+        let role = info.role;
 
         let user = User::new_external(
             info.display_name.clone(),
             info.email.clone(),
-            AuthProviderType::ActiveDirectory,
+            self.config.provider.clone(),
             info.username.clone(),
             role,
         );
@@ -200,11 +654,202 @@ impl LdapAuthProvider {
     }
 }
 
-struct LdapUserInfo {
-    username: String,
-    display_name: String,
-    email: String,
-    is_admin: bool,
+//construct Domain name -> bsp CN=username,OU=Users,DC=domain,DC=com or using userPrincipalName: username@domain.com
+// generic ldap uses the username attribute as rdn: uid=username,ou=people,dc=domain,dc=com
+fn build_bind_dn(config: &LdapConfig, username: &str) -> String {
+    if config.use_upn {
+        format!("{}@{}", username, config.domain)
+    } else if config.provider == AuthProviderType::ActiveDirectory {
+        format!("CN={},{}", dn_escape(username), config.user_base_dn)
+    } else {
+        format!("{}={},{}", config.username_attribute, dn_escape(username), config.user_base_dn)
+    }
+}
+
+// AD puts the reason into the diagnostic message:
+// "80090308: LdapErr: DSID-0C09044E, comment: AcceptSecurityContext error, data 775, v4563"
+fn ad_sub_code(text: &str) -> Option<&str> {
+    let (_, rest) = text.split_once(" data ")?;
+    let code = rest.split([',', ' ']).next()?;
+    (!code.is_empty()).then_some(code)
+}
+
+// 525 (no such user) and 52e (wrong password) give the same answer, otherwise usernames could be probed.
+// AD only sends the other codes after the password was checked, except 775 (locked)
+fn ad_bind_failure(sub_code: Option<&str>) -> AppError {
+    let restriction = match sub_code.map(str::to_lowercase).as_deref() {
+        Some("530") | Some("531") => AccountRestriction::LogonRestricted,
+        Some("532") => AccountRestriction::PasswordExpired,
+        Some("533") => AccountRestriction::AccountDisabled,
+        Some("701") => AccountRestriction::AccountExpired,
+        Some("773") => AccountRestriction::PasswordMustChange,
+        Some("775") => AccountRestriction::AccountLocked,
+        _ => return AppError::Unauthorized("Invalid credentials".to_string()),
+    };
+
+    AppError::AccountRestricted(restriction)
+}
+
+// unicodePwd value: the password in quotes as UTF-16LE
+fn ad_password_value(password: &str) -> Vec<u8> {
+    format!("\"{}\"", password)
+        .encode_utf16()
+        .flat_map(u16::to_le_bytes)
+        .collect()
+}
+
+// AD puts the win32 error first in the text: 0000052D = policy (length, complexity, history, min age),
+// 00000056 = wrong old password. slapd ppolicy sends the reason as text
+fn password_change_failure(provider: &AuthProviderType, result: &OperationResult) -> AppError {
+    let is_ad = *provider == AuthProviderType::ActiveDirectory;
+
+    match result.rc {
+        LDAP_CONSTRAINT_VIOLATION if is_ad && result.text.starts_with("00000056") => {
+            AppError::Unauthorized("Invalid credentials".to_string())
+        }
+        // some servers check oldPasswd of the extended operation themselves
+        LDAP_INVALID_CREDENTIALS => AppError::Unauthorized("Invalid credentials".to_string()),
+        LDAP_CONSTRAINT_VIOLATION if is_ad => AppError::ValidationError(
+            "New password does not meet the password policy (length, complexity, history or minimum age)".to_string(),
+        ),
+        LDAP_CONSTRAINT_VIOLATION => AppError::ValidationError(format!(
+            "New password does not meet the password policy: {}",
+            result.text
+        )),
+        // slapd: "unwilling to verify old password"
+        LDAP_UNWILLING_TO_PERFORM if !is_ad && result.text.contains("old password") => {
+            AppError::Unauthorized("Invalid credentials".to_string())
+        }
+        LDAP_UNWILLING_TO_PERFORM | LDAP_CONFIDENTIALITY_REQUIRED => AppError::LdapError(
+            "Directory refused the password change, it needs an encrypted connection".to_string(),
+        ),
+        LDAP_INSUFFICIENT_ACCESS => AppError::Forbidden("Not allowed to change the password".to_string()),
+        rc => AppError::LdapError(format!("Password change failed (rc={})", rc)),
+    }
+}
+
+fn group_of_names_filter(member_dn: &str) -> String {
+    format!("(&(objectClass=groupOfNames)(member={}))", ldap_escape(member_dn))
+}
+
+// attribute names are case insensitive, servers answer with their own spelling
+fn attr_values(entry: &SearchEntry, name: &str) -> Vec<String> {
+    entry
+        .attrs
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .map(|(_, values)| values.clone())
+        .unwrap_or_default()
+}
+
+fn first_attr<'a>(entry: &'a SearchEntry, name: &str) -> Option<&'a String> {
+    entry
+        .attrs
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .and_then(|(_, values)| values.first())
+}
+
+// everything we read from a user entry, names come from the config
+fn user_attributes(config: &LdapConfig) -> Vec<&str> {
+    let mut attributes = vec![
+        "cn",
+        "userPrincipalName",
+        config.username_attribute.as_str(),
+        config.email_attribute.as_str(),
+        config.display_name_attribute.as_str(),
+    ];
+    if config.group_membership == GroupMembership::MemberOf {
+        attributes.push(config.group_attribute.as_str());
+    }
+    attributes
+}
+
+fn user_info(config: &LdapConfig, entry: &SearchEntry, username: &str, groups: &[String]) -> LdapUserInfo {
+    let display_name = first_attr(entry, &config.display_name_attribute)
+        .or_else(|| first_attr(entry, "cn"))
+        .cloned()
+        .unwrap_or_else(|| username.to_string());
+
+    let email = first_attr(entry, &config.email_attribute)
+        .or_else(|| first_attr(entry, "userPrincipalName"))
+        .cloned()
+        .unwrap_or_else(|| format!("{}@{}", username, config.domain));
+
+    // directory spelling of the username, login could differ in case
+    let username = first_attr(entry, &config.username_attribute)
+        .cloned()
+        .unwrap_or_else(|| username.to_string());
+
+    LdapUserInfo {
+        username,
+        display_name,
+        email,
+        role: role_for_groups(&config.group_roles, groups),
+    }
+}
+
+// copies name/email/role from the directory, returns a description of every change for the log
+fn apply_directory_attributes(user: &mut User, info: &LdapUserInfo, update_email: bool) -> Vec<String> {
+    let mut changes = Vec::new();
+
+    if user.name != info.display_name {
+        changes.push(format!("name '{}' -> '{}'", user.name, info.display_name));
+        user.name = info.display_name.clone();
+    }
+
+    let email = info.email.to_lowercase();
+    if update_email && user.email != email {
+        changes.push(format!("email '{}' -> '{}'", user.email, email));
+        user.email = email;
+    }
+
+    if user.role != info.role {
+        changes.push(format!("role {} -> {}", user.role, info.role));
+        user.role = info.role;
+    }
+
+    if !changes.is_empty() {
+        user.updated_at = Utc::now();
+    }
+
+    changes
+}
+
+// RFC 4515 escaping, otherwise "*)(cn=*" would match whatever the attacker wants
+fn render_filter(template: &str, username: &str) -> String {
+    template.replace("{username}", &ldap_escape(username))
+}
+
+// AD userAccountControl flag or FreeIPA nsAccountLock
+fn is_account_disabled(entry: &SearchEntry) -> bool {
+    let ad_disabled = first_attr(entry, "userAccountControl")
+        .and_then(|v| v.parse::<u32>().ok())
+        .is_some_and(|flags| flags & UAC_ACCOUNT_DISABLED != 0);
+
+    let locked = first_attr(entry, "nsAccountLock").is_some_and(|v| v.eq_ignore_ascii_case("true"));
+
+    ad_disabled || locked
+}
+
+enum OfflineLogin {
+    // no fresh cached credential or the account is gone
+    Unavailable,
+    WrongPassword,
+    Accepted(User),
+}
+
+pub(super) struct LdapUserInfo {
+    pub(super) username: String,
+    pub(super) display_name: String,
+    pub(super) email: String,
+    pub(super) role: UserRole,
+}
+
+// one entry of the full directory listing
+pub(super) struct DirectoryUser {
+    pub(super) info: LdapUserInfo,
+    pub(super) disabled: bool,
 }
 
 #[async_trait]
@@ -214,16 +859,58 @@ impl AuthProvider for LdapAuthProvider {
     }
 
     async fn authenticate(&self, username: &str, password: &str) -> Result<AuthResult, AppError> {
-        tracing::info!(username = %username, server = %self.config.url, "LDAP authentication attempt");
+        let error = match self.authenticate_online(username, password).await {
+            Ok(result) => {
+                self.cache_credential(&result.user, username, password).await;
+                return Ok(result);
+            }
+            Err(e) => e,
+        };
+
+        match &error {
+            AppError::AccountRestricted(_) => self.forget_credential(username).await,
+            // only when no server answers at all, not for search or config problems
+            AppError::LdapError(msg) if self.credential_cache.is_some() && !self.client.is_reachable() => {
+                // every failure keeps the original 503, dont tell who has a cached credential
+                match self.authenticate_offline(username, password).await? {
+                    OfflineLogin::Accepted(user) => {
+                        tracing::warn!(user_id = %user.id, username = %username, "directory unreachable, offline login with cached credential");
+                        return Ok(AuthResult { user, offline: true });
+                    }
+                    OfflineLogin::WrongPassword => return Err(AppError::OfflineLoginRejected(msg.clone())),
+                    OfflineLogin::Unavailable => {}
+                }
+            }
+            _ => {}
+        }
 
-        // bind
-        let mut ldap = self.bind_user(username, password).await?;
+        Err(error)
+    }
+}
 
-        // fetch info
-        let user_info = self.fetch_user_info(&mut ldap, username).await?;
+impl LdapAuthProvider {
+    async fn authenticate_online(&self, username: &str, password: &str) -> Result<AuthResult, AppError> {
+        tracing::info!(username = %username, servers = %self.config.urls.join(","), "LDAP authentication attempt");
 
-        // unbind
-        let _ = ldap.unbind().await;
+        // empty password would be an anonymous bind which most servers accept
+        if password.is_empty() {
+            return Err(AppError::Unauthorized("Invalid credentials".to_string()));
+        }
+
+        // bind + fetch entry + groups
+        let (entry, groups) = if self.config.search_then_bind() {
+            self.search_then_bind(username, password).await?
+        } else {
+            let mut ldap = self.bind_user(username, password).await?;
+            let entry = self.fetch_user_entry(ldap.as_mut(), username).await?;
+            let groups = self.resolve_groups(ldap.as_mut(), &entry).await;
+
+            // unbind, direct bind connections are not pooled
+            ldap.unbind().await;
+            (entry, groups)
+        };
+
+        let user_info = user_info(&self.config, &entry, username, &groups);
 
         // sync to db
         let user = self.sync_user(&user_info).await?;
@@ -234,6 +921,248 @@ impl AuthProvider for LdapAuthProvider {
             "auth successful"
         );
 
-        Ok(AuthResult { user })
+        Ok(AuthResult { user, offline: false })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_config(use_upn: bool) -> LdapConfig {
+        LdapConfig {
+            use_upn,
+            nested_groups: NestedGroupMode::None,
+            group_base_dn: "OU=Groups,DC=example,DC=com".to_string(),
+            user_filter: "(&(objectClass=user)(sAMAccountName={username}))".to_string(),
+            ..LdapConfig::new(vec!["ldap://localhost:389".to_string()], "OU=Users,DC=example,DC=com", "example.com")
+        }
+    }
+
+    fn openldap_config() -> LdapConfig {
+        LdapConfig {
+            provider: AuthProviderType::Ldap,
+            user_base_dn: "ou=people,dc=example,dc=com".to_string(),
+            username_attribute: "uid".to_string(),
+            display_name_attribute: "cn".to_string(),
+            group_membership: GroupMembership::GroupOfNames,
+            ..test_config(false)
+        }
+    }
+
+    fn entry(attrs: &[(&str, &str)]) -> SearchEntry {
+        SearchEntry {
+            dn: "uid=max,ou=people,dc=example,dc=com".to_string(),
+            attrs: attrs
+                .iter()
+                .map(|(k, v)| (k.to_string(), vec![v.to_string()]))
+                .collect(),
+            bin_attrs: Default::default(),
+        }
+    }
+
+    fn ldap_user(role: UserRole) -> User {
+        User::new_external(
+            "Max Mustermann".to_string(),
+            "max@example.com".to_string(),
+            AuthProviderType::ActiveDirectory,
+            "max".to_string(),
+            role,
+        )
+    }
+
+    fn info(name: &str, email: &str, role: UserRole) -> LdapUserInfo {
+        LdapUserInfo {
+            username: "max".to_string(),
+            display_name: name.to_string(),
+            email: email.to_string(),
+            role,
+        }
+    }
+
+    #[test]
+    fn test_apply_directory_attributes_no_changes() {
+        let mut user = ldap_user(UserRole::User);
+        let changes = apply_directory_attributes(
+            &mut user,
+            &info("Max Mustermann", "MAX@example.com", UserRole::User),
+            true,
+        );
+
+        assert!(changes.is_empty());
+    }
+
+    #[test]
+    fn test_apply_directory_attributes_demotes_admin() {
+        let mut user = ldap_user(UserRole::Admin);
+        let changes = apply_directory_attributes(
+            &mut user,
+            &info("Max Muster", "max.muster@example.com", UserRole::User),
+            true,
+        );
+
+        assert_eq!(changes.len(), 3);
+        assert_eq!(user.role, UserRole::User);
+        assert_eq!(user.name, "Max Muster");
+        assert_eq!(user.email, "max.muster@example.com");
+    }
+
+    #[test]
+    fn test_apply_directory_attributes_keeps_email_on_conflict() {
+        let mut user = ldap_user(UserRole::User);
+        let changes = apply_directory_attributes(
+            &mut user,
+            &info("Max Mustermann", "taken@example.com", UserRole::User),
+            false,
+        );
+
+        assert!(changes.is_empty());
+        assert_eq!(user.email, "max@example.com");
+    }
+
+    #[test]
+    fn test_render_filter_plain_username() {
+        let config = test_config(true);
+        assert_eq!(
+            render_filter(&config.user_filter, "mustermann"),
+            "(&(objectClass=user)(sAMAccountName=mustermann))"
+        );
+    }
+
+    #[test]
+    fn test_render_filter_escapes_injection() {
+        assert_eq!(render_filter("(uid={username})", "*)(uid=*"), "(uid=\\2a\\29\\28uid=\\2a)");
+        assert_eq!(render_filter("(uid={username})", "a\\b"), "(uid=a\\5cb)");
+        assert_eq!(render_filter("(uid={username})", "nul\0"), "(uid=nul\\00)");
+    }
+
+    #[test]
+    fn test_account_disabled_flag() {
+        let uac = |value: &str| entry(&[("userAccountControl", value)]);
+
+        // 512 = NORMAL_ACCOUNT, 514 = NORMAL_ACCOUNT | ACCOUNTDISABLE
+        assert!(!is_account_disabled(&uac("512")));
+        assert!(is_account_disabled(&uac("514")));
+        assert!(is_account_disabled(&uac("66050")));
+        assert!(!is_account_disabled(&uac("garbage")));
+
+        // FreeIPA
+        assert!(is_account_disabled(&entry(&[("nsAccountLock", "TRUE")])));
+        assert!(!is_account_disabled(&entry(&[("nsAccountLock", "false")])));
+    }
+
+    #[test]
+    fn test_ad_sub_code() {
+        let text = "80090308: LdapErr: DSID-0C09044E, comment: AcceptSecurityContext error, data 52e, v4563\0";
+        assert_eq!(ad_sub_code(text), Some("52e"));
+        assert_eq!(ad_sub_code("Invalid credentials"), None);
+    }
+
+    #[test]
+    fn test_ad_bind_failure_mapping() {
+        let restriction = |code: &str| match ad_bind_failure(Some(code)) {
+            AppError::AccountRestricted(r) => Some(r),
+            _ => None,
+        };
+
+        assert_eq!(restriction("532"), Some(AccountRestriction::PasswordExpired));
+        assert_eq!(restriction("533"), Some(AccountRestriction::AccountDisabled));
+        assert_eq!(restriction("701"), Some(AccountRestriction::AccountExpired));
+        assert_eq!(restriction("773"), Some(AccountRestriction::PasswordMustChange));
+        assert_eq!(restriction("775"), Some(AccountRestriction::AccountLocked));
+        assert_eq!(restriction("530"), Some(AccountRestriction::LogonRestricted));
+        assert_eq!(restriction("531"), Some(AccountRestriction::LogonRestricted));
+
+        // unknown user and wrong password must look the same
+        for code in [Some("525"), Some("52e"), Some("52E"), None] {
+            assert!(matches!(
+                ad_bind_failure(code),
+                AppError::Unauthorized(msg) if msg == "Invalid credentials"
+            ));
+        }
+    }
+
+    #[test]
+    fn test_ad_password_value() {
+        assert_eq!(ad_password_value("ab"), vec![b'"', 0, b'a', 0, b'b', 0, b'"', 0]);
+        // outside ascii still two bytes per utf-16 unit
+        assert_eq!(ad_password_value("\u{e4}").len(), 6);
+    }
+
+    #[test]
+    fn test_password_change_failure_mapping() {
+        let ad = AuthProviderType::ActiveDirectory;
+        let ldap = AuthProviderType::Ldap;
+        let result = |rc: u32, text: &str| OperationResult { rc, text: text.to_string() };
+
+        let policy = password_change_failure(&ad, &result(19, "0000052D: AtrErr: DSID-03191083, problem 1005"));
+        assert!(matches!(policy, AppError::ValidationError(msg) if msg.contains("password policy")));
+
+        let wrong_old = password_change_failure(&ad, &result(19, "00000056: AtrErr: DSID-03190F80"));
+        assert!(matches!(wrong_old, AppError::Unauthorized(_)));
+
+        let ppolicy = password_change_failure(&ldap, &result(19, "Password is in history of old passwords"));
+        assert!(matches!(ppolicy, AppError::ValidationError(msg) if msg.ends_with("history of old passwords")));
+
+        let slapd_old = password_change_failure(&ldap, &result(53, "unwilling to verify old password"));
+        assert!(matches!(slapd_old, AppError::Unauthorized(_)));
+
+        let exop_old = password_change_failure(&ldap, &result(49, ""));
+        assert!(matches!(exop_old, AppError::Unauthorized(_)));
+
+        assert!(matches!(password_change_failure(&ad, &result(53, "0000001F: SvcErr")), AppError::LdapError(_)));
+        assert!(matches!(password_change_failure(&ad, &result(50, "")), AppError::Forbidden(_)));
+    }
+
+    #[test]
+    fn test_user_info_uses_configured_attributes() {
+        let info = user_info(
+            &openldap_config(),
+            &entry(&[("UID", "max"), ("cn", "Max Mustermann"), ("mail", "max@example.com")]),
+            "MAX",
+            &[],
+        );
+
+        assert_eq!(info.username, "max");
+        assert_eq!(info.display_name, "Max Mustermann");
+        assert_eq!(info.email, "max@example.com");
+    }
+
+    #[test]
+    fn test_user_info_fallbacks() {
+        let info = user_info(&test_config(true), &entry(&[]), "max", &[]);
+
+        assert_eq!(info.username, "max");
+        assert_eq!(info.display_name, "max");
+        assert_eq!(info.email, "max@example.com");
+    }
+
+    #[test]
+    fn test_user_attributes_follow_config() {
+        assert!(user_attributes(&test_config(true)).contains(&"memberOf"));
+
+        let config = openldap_config();
+        let attributes = user_attributes(&config);
+        assert!(attributes.contains(&"uid"));
+        // groupOfNames lookup is a search, nothing to read on the user
+        assert!(!attributes.contains(&"memberOf"));
+    }
+
+    #[test]
+    fn test_build_bind_dn() {
+        assert_eq!(build_bind_dn(&test_config(true), "max"), "max@example.com");
+        assert_eq!(
+            build_bind_dn(&test_config(false), "max"),
+            "CN=max,OU=Users,DC=example,DC=com"
+        );
+        // a comma would otherwise start a new rdn
+        assert_eq!(
+            build_bind_dn(&test_config(false), "max,OU=Admins"),
+            "CN=max\\2cOU\\3dAdmins,OU=Users,DC=example,DC=com"
+        );
+        assert_eq!(
+            build_bind_dn(&openldap_config(), "max"),
+            "uid=max,ou=people,dc=example,dc=com"
+        );
     }
 }
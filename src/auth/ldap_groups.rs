@@ -0,0 +1,140 @@
+// group -> role mapping for ldap users
+// groups are compared exactly (full dn or cn), substring matches would make "NotAdmins" an admin
+use crate::config::GroupRoleMapping;
+use crate::models::UserRole;
+
+// splits a dn into (attr, value) pairs, commas escaped with a backslash stay in the value
+fn rdns(dn: &str) -> Vec<(String, String)> {
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    let mut escaped = false;
+
+    for c in dn.chars() {
+        match c {
+            _ if escaped => {
+                current.push(c);
+                escaped = false;
+            }
+            '\\' => {
+                current.push(c);
+                escaped = true;
+            }
+            ',' => parts.push(std::mem::take(&mut current)),
+            _ => current.push(c),
+        }
+    }
+    parts.push(current);
+
+    parts
+        .iter()
+        .filter_map(|rdn| {
+            let (attr, value) = rdn.split_once('=')?;
+            Some((attr.trim().to_lowercase(), value.trim().to_lowercase()))
+        })
+        .collect()
+}
+
+// dns are case insensitive and servers differ in spaces after commas
+pub fn normalize_dn(dn: &str) -> String {
+    rdns(dn)
+        .iter()
+        .map(|(attr, value)| format!("{}={}", attr, value))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+// cn of the first rdn, CN=Domain Admins,CN=Users,... -> domain admins
+pub fn group_cn(dn: &str) -> Option<String> {
+    rdns(dn)
+        .into_iter()
+        .next()
+        .filter(|(attr, _)| attr == "cn")
+        .map(|(_, value)| value)
+}
+
+pub fn matches_group(spec: &str, group_dn: &str) -> bool {
+    if spec.contains('=') {
+        normalize_dn(spec) == normalize_dn(group_dn)
+    } else {
+        group_cn(group_dn).is_some_and(|cn| cn == spec.trim().to_lowercase())
+    }
+}
+
+fn rank(role: UserRole) -> u8 {
+    match role {
+        UserRole::User => 0,
+        UserRole::Admin => 1,
+    }
+}
+
+// highest role of all matching mappings, plain user if nothing matches
+pub fn role_for_groups(mappings: &[GroupRoleMapping], groups: &[String]) -> UserRole {
+    mappings
+        .iter()
+        .filter(|m| groups.iter().any(|g| matches_group(&m.group, g)))
+        .map(|m| m.role)
+        .max_by_key(|role| rank(*role))
+        .unwrap_or(UserRole::User)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mapping(group: &str, role: UserRole) -> GroupRoleMapping {
+        GroupRoleMapping {
+            group: group.to_string(),
+            role,
+        }
+    }
+
+    #[test]
+    fn test_normalize_dn() {
+        assert_eq!(
+            normalize_dn("CN=Domain Admins, CN=Users,DC=tgm , DC=ac,DC=at"),
+            "cn=domain admins,cn=users,dc=tgm,dc=ac,dc=at"
+        );
+    }
+
+    #[test]
+    fn test_group_cn_with_escaped_comma() {
+        assert_eq!(group_cn("CN=Admins\\, Old,OU=Groups,DC=x").unwrap(), "admins\\, old");
+        assert_eq!(group_cn("OU=Groups,DC=x"), None);
+    }
+
+    #[test]
+    fn test_cn_match_is_exact() {
+        assert!(matches_group("Admins", "CN=Admins,OU=Groups,DC=tgm,DC=ac,DC=at"));
+        assert!(matches_group("admins", "cn=ADMINS,OU=Groups,DC=tgm,DC=ac,DC=at"));
+        assert!(!matches_group("Admins", "CN=NotAdmins,OU=Groups,DC=tgm,DC=ac,DC=at"));
+        assert!(!matches_group("Admins", "CN=Admins-Old,OU=Groups,DC=tgm,DC=ac,DC=at"));
+        // cn only matches the first rdn, not an ou somewhere in the path
+        assert!(!matches_group("Admins", "CN=Staff,OU=Admins,DC=tgm,DC=ac,DC=at"));
+    }
+
+    #[test]
+    fn test_dn_match_is_exact() {
+        let spec = "CN=Admins,OU=Groups,DC=tgm,DC=ac,DC=at";
+        assert!(matches_group(spec, "cn=admins, ou=groups, dc=tgm, dc=ac, dc=at"));
+        assert!(!matches_group(spec, "CN=Admins,OU=Old,DC=tgm,DC=ac,DC=at"));
+    }
+
+    #[test]
+    fn test_role_for_groups_picks_highest() {
+        let mappings = vec![
+            mapping("Staff", UserRole::User),
+            mapping("CN=IT,OU=Groups,DC=x", UserRole::Admin),
+        ];
+
+        let staff = vec!["CN=Staff,OU=Groups,DC=x".to_string()];
+        assert_eq!(role_for_groups(&mappings, &staff), UserRole::User);
+
+        let both = vec![
+            "CN=Staff,OU=Groups,DC=x".to_string(),
+            "CN=IT,OU=Groups,DC=x".to_string(),
+        ];
+        assert_eq!(role_for_groups(&mappings, &both), UserRole::Admin);
+
+        assert_eq!(role_for_groups(&mappings, &[]), UserRole::User);
+    }
+}
@@ -0,0 +1,305 @@
+// several ldap servers with failover + pool of connections bound as the service account
+use ldap3::{Ldap, LdapConnAsync, LdapConnSettings};
+use std::ops::{Deref, DerefMut};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+use crate::error::AppError;
+
+use super::directory::{DirectoryClient, DirectoryConnection};
+
+// a dead dc is retried after retry_after, doubling per failure up to this
+const MAX_BACKOFF: Duration = Duration::from_secs(300);
+
+#[derive(Debug, Default, Clone)]
+struct ServerHealth {
+    failures: u32,
+    down_until: Option<Instant>,
+}
+
+pub struct LdapServers {
+    urls: Vec<String>,
+    health: Mutex<Vec<ServerHealth>>,
+    retry_after: Duration,
+}
+
+impl LdapServers {
+    pub fn new(urls: Vec<String>, retry_after: Duration) -> Self {
+        let health = Mutex::new(vec![ServerHealth::default(); urls.len()]);
+        Self {
+            urls,
+            health,
+            retry_after,
+        }
+    }
+
+    // healthy servers in config order, servers marked down come last as a last resort
+    pub fn candidates(&self) -> Vec<String> {
+        let now = Instant::now();
+        let health = self.health.lock().unwrap();
+
+        let (up, down): (Vec<_>, Vec<_>) = self
+            .urls
+            .iter()
+            .zip(health.iter())
+            .partition(|(_, h)| h.down_until.is_none_or(|until| until <= now));
+
+        up.into_iter()
+            .chain(down)
+            .map(|(url, _)| url.clone())
+            .collect()
+    }
+
+    pub fn mark_up(&self, url: &str) {
+        if let Some(index) = self.urls.iter().position(|u| u == url) {
+            let mut health = self.health.lock().unwrap();
+            if health[index].failures > 0 {
+                tracing::info!(url = %url, "ldap server back up");
+            }
+            health[index] = ServerHealth::default();
+        }
+    }
+
+    pub fn mark_down(&self, url: &str) {
+        if let Some(index) = self.urls.iter().position(|u| u == url) {
+            let mut health = self.health.lock().unwrap();
+            let entry = &mut health[index];
+            entry.failures += 1;
+
+            let backoff = self
+                .retry_after
+                .saturating_mul(2u32.saturating_pow(entry.failures - 1))
+                .min(MAX_BACKOFF);
+            entry.down_until = Some(Instant::now() + backoff);
+
+            tracing::warn!(
+                url = %url,
+                failures = entry.failures,
+                retry_in_secs = backoff.as_secs(),
+                "ldap server marked down"
+            );
+        }
+    }
+
+    pub fn all_down(&self) -> bool {
+        self.urls.iter().all(|url| self.is_down(url))
+    }
+
+    pub fn is_down(&self, url: &str) -> bool {
+        let now = Instant::now();
+        self.urls
+            .iter()
+            .position(|u| u == url)
+            .and_then(|index| self.health.lock().unwrap()[index].down_until)
+            .is_some_and(|until| until > now)
+    }
+
+    // first server that accepts a connection, failed ones are marked down
+    pub async fn connect(&self, settings: &LdapConnSettings) -> Result<(String, Ldap), AppError> {
+        let mut last_error = None;
+
+        for url in self.candidates() {
+            match LdapConnAsync::with_settings(settings.clone(), &url).await {
+                Ok((conn, ldap)) => {
+                    // Spawn con handler
+                    ldap3::drive!(conn);
+                    self.mark_up(&url);
+                    return Ok((url, ldap));
+                }
+                Err(e) => {
+                    tracing::error!(error = %e, url = %url, "cannt connect ldap server");
+                    self.mark_down(&url);
+                    last_error = Some(e);
+                }
+            }
+        }
+
+        Err(AppError::LdapError(match last_error {
+            Some(e) => format!("Connection failed: {}", e),
+            None => "No ldap server configured".to_string(),
+        }))
+    }
+}
+
+struct IdleConnection {
+    conn: Box<dyn DirectoryConnection>,
+    idle_since: Instant,
+}
+
+// connections stay bound as the service account while idle
+pub struct ServiceConnectionPool {
+    idle: Mutex<Vec<IdleConnection>>,
+    permits: Semaphore,
+    max_idle: Duration,
+    acquire_timeout: Duration,
+}
+
+impl ServiceConnectionPool {
+    pub fn new(size: usize, max_idle: Duration, acquire_timeout: Duration) -> Self {
+        Self {
+            idle: Mutex::new(Vec::new()),
+            permits: Semaphore::new(size),
+            max_idle,
+            acquire_timeout,
+        }
+    }
+
+    // waits for a free slot, at most `size` connections are in use at once
+    pub async fn acquire(&self) -> Result<SemaphorePermit<'_>, AppError> {
+        tokio::time::timeout(self.acquire_timeout, self.permits.acquire())
+            .await
+            .map_err(|_| {
+                tracing::warn!("ldap connection pool exhausted");
+                AppError::LdapError("Directory busy".to_string())
+            })?
+            .map_err(|_| AppError::InternalError("ldap pool closed".to_string()))
+    }
+
+    // most recently used connection, stale ones are dropped
+    pub fn take_idle(&self, client: &dyn DirectoryClient) -> Option<Box<dyn DirectoryConnection>> {
+        let mut idle = self.idle.lock().unwrap();
+        while let Some(entry) = idle.pop() {
+            if entry.idle_since.elapsed() < self.max_idle && client.is_available(entry.conn.url()) {
+                return Some(entry.conn);
+            }
+        }
+        None
+    }
+
+    pub fn put_back(&self, conn: Box<dyn DirectoryConnection>) {
+        self.idle.lock().unwrap().push(IdleConnection {
+            conn,
+            idle_since: Instant::now(),
+        });
+    }
+
+    pub fn idle_count(&self) -> usize {
+        self.idle.lock().unwrap().len()
+    }
+}
+
+// checked out connection, goes back into the pool on drop unless discarded
+pub struct PooledConnection<'a> {
+    conn: Option<Box<dyn DirectoryConnection>>,
+    // came out of the idle list, could be stale
+    pub reused: bool,
+    pool: &'a ServiceConnectionPool,
+    discarded: bool,
+    _permit: SemaphorePermit<'a>,
+}
+
+impl<'a> PooledConnection<'a> {
+    pub fn new(
+        conn: Box<dyn DirectoryConnection>,
+        reused: bool,
+        pool: &'a ServiceConnectionPool,
+        permit: SemaphorePermit<'a>,
+    ) -> Self {
+        Self {
+            conn: Some(conn),
+            reused,
+            pool,
+            discarded: false,
+            _permit: permit,
+        }
+    }
+
+    // broken or bound as someone else, must not be reused
+    pub fn discard(&mut self) {
+        self.discarded = true;
+    }
+}
+
+impl Deref for PooledConnection<'_> {
+    type Target = dyn DirectoryConnection;
+
+    fn deref(&self) -> &Self::Target {
+        self.conn.as_deref().expect("pooled connection already returned")
+    }
+}
+
+impl DerefMut for PooledConnection<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.conn.as_deref_mut().expect("pooled connection already returned")
+    }
+}
+
+impl Drop for PooledConnection<'_> {
+    fn drop(&mut self) {
+        if let Some(conn) = self.conn.take()
+            && !self.discarded
+        {
+            self.pool.put_back(conn);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn servers() -> LdapServers {
+        LdapServers::new(
+            vec!["ldap://dc-01".to_string(), "ldap://dc-02".to_string()],
+            Duration::from_secs(30),
+        )
+    }
+
+    #[test]
+    fn test_candidates_in_config_order() {
+        assert_eq!(servers().candidates(), vec!["ldap://dc-01", "ldap://dc-02"]);
+    }
+
+    #[test]
+    fn test_down_server_moves_to_the_end() {
+        let servers = servers();
+        servers.mark_down("ldap://dc-01");
+
+        assert!(servers.is_down("ldap://dc-01"));
+        assert_eq!(servers.candidates(), vec!["ldap://dc-02", "ldap://dc-01"]);
+
+        servers.mark_up("ldap://dc-01");
+        assert!(!servers.is_down("ldap://dc-01"));
+        assert_eq!(servers.candidates(), vec!["ldap://dc-01", "ldap://dc-02"]);
+    }
+
+    #[test]
+    fn test_backoff_grows_and_is_capped() {
+        let servers = servers();
+        for _ in 0..20 {
+            servers.mark_down("ldap://dc-02");
+        }
+
+        let until = servers.health.lock().unwrap()[1].down_until.unwrap();
+        assert!(until <= Instant::now() + MAX_BACKOFF);
+        assert!(until > Instant::now() + Duration::from_secs(200));
+    }
+
+    #[tokio::test]
+    async fn test_pool_limits_concurrent_connections() {
+        let pool = ServiceConnectionPool::new(1, Duration::from_secs(60), Duration::from_millis(50));
+
+        let permit = pool.acquire().await.unwrap();
+        assert!(matches!(pool.acquire().await, Err(AppError::LdapError(_))));
+
+        drop(permit);
+        assert!(pool.acquire().await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_connect_fails_over_and_marks_down() {
+        // nothing listens on these ports
+        let servers = LdapServers::new(
+            vec!["ldap://127.0.0.1:1".to_string(), "ldap://127.0.0.1:2".to_string()],
+            Duration::from_secs(30),
+        );
+        let settings = LdapConnSettings::new().set_conn_timeout(Duration::from_secs(1));
+
+        let result = servers.connect(&settings).await;
+
+        assert!(matches!(result, Err(AppError::LdapError(_))));
+        assert!(servers.is_down("ldap://127.0.0.1:1"));
+        assert!(servers.is_down("ldap://127.0.0.1:2"));
+    }
+}
@@ -0,0 +1,298 @@
+// periodic sync of all directory users, accounts removed or disabled there get deactivated here too
+// without it they keep working until somebody tries to log in. back in the directory -> active again
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use crate::error::AppError;
+use crate::models::User;
+use crate::repository::{RefreshTokenRepository, UserRepository};
+
+use super::ldap::{DirectoryUser, LdapAuthProvider, LdapUserInfo};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DeactivatedUser {
+    pub user_id: String,
+    pub username: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReactivatedUser {
+    pub user_id: String,
+    pub username: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DirectorySyncReport {
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    pub directory_users: usize,
+    pub local_users: usize,
+    pub updated: usize,
+    pub deactivated: Vec<DeactivatedUser>,
+    pub reactivated: Vec<ReactivatedUser>,
+    // per user problems, the rest of the run continues
+    pub errors: Vec<String>,
+    // whole run failed, nothing was changed
+    pub failure: Option<String>,
+}
+
+enum SyncAction<'a> {
+    Refresh(User, &'a LdapUserInfo),
+    Deactivate(User, &'static str),
+    Reactivate(User, &'a LdapUserInfo),
+}
+
+// local user -> directory entry by username, case insensitive like AD
+// deactivated users that are still gone or disabled are left alone
+fn reconcile(local: Vec<User>, directory: &[DirectoryUser]) -> Vec<SyncAction<'_>> {
+    let by_username: HashMap<String, &DirectoryUser> = directory
+        .iter()
+        .map(|d| (d.info.username.to_lowercase(), d))
+        .collect();
+
+    local
+        .into_iter()
+        .filter_map(|user| {
+            let key = user.external_id.as_deref().unwrap_or_default().to_lowercase();
+            match by_username.get(&key) {
+                Some(entry) if !entry.disabled && user.is_active => Some(SyncAction::Refresh(user, &entry.info)),
+                Some(entry) if !entry.disabled => Some(SyncAction::Reactivate(user, &entry.info)),
+                _ if !user.is_active => None,
+                None => Some(SyncAction::Deactivate(user, "not found in directory")),
+                Some(_) => Some(SyncAction::Deactivate(user, "disabled in directory")),
+            }
+        })
+        .collect()
+}
+
+pub struct DirectorySyncService {
+    provider: Arc<LdapAuthProvider>,
+    repository: Arc<dyn UserRepository>,
+    refresh_token_repository: Arc<dyn RefreshTokenRepository>,
+    last_report: RwLock<Option<DirectorySyncReport>>,
+    running: tokio::sync::Mutex<()>,
+}
+
+impl DirectorySyncService {
+    pub fn new(
+        provider: Arc<LdapAuthProvider>,
+        repository: Arc<dyn UserRepository>,
+        refresh_token_repository: Arc<dyn RefreshTokenRepository>,
+    ) -> Self {
+        Self {
+            provider,
+            repository,
+            refresh_token_repository,
+            last_report: RwLock::new(None),
+            running: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    pub fn last_report(&self) -> Option<DirectorySyncReport> {
+        self.last_report.read().unwrap().clone()
+    }
+
+    pub async fn run(&self) -> Result<DirectorySyncReport, AppError> {
+        let _running = self
+            .running
+            .try_lock()
+            .map_err(|_| AppError::Conflict("Directory sync already running".to_string()))?;
+
+        let started_at = Utc::now();
+        let result = self.sync(started_at).await;
+
+        let report = match &result {
+            Ok(report) => {
+                tracing::info!(
+                    directory_users = report.directory_users,
+                    local_users = report.local_users,
+                    updated = report.updated,
+                    deactivated = report.deactivated.len(),
+                    reactivated = report.reactivated.len(),
+                    errors = report.errors.len(),
+                    "Directory sync finished"
+                );
+                report.clone()
+            }
+            Err(e) => {
+                tracing::error!(error = %e, "Directory sync failed");
+                DirectorySyncReport {
+                    started_at,
+                    finished_at: Utc::now(),
+                    directory_users: 0,
+                    local_users: 0,
+                    updated: 0,
+                    deactivated: Vec::new(),
+                    reactivated: Vec::new(),
+                    errors: Vec::new(),
+                    failure: Some(e.to_string()),
+                }
+            }
+        };
+
+        *self.last_report.write().unwrap() = Some(report);
+        result
+    }
+
+    async fn sync(&self, started_at: DateTime<Utc>) -> Result<DirectorySyncReport, AppError> {
+        let directory = self.provider.directory_users().await?;
+        let provider = self.provider.provider_type().to_string();
+        let local = self.repository.list_by_provider(&provider).await?;
+
+        // wrong base dn or filter would otherwise deactivate every directory account
+        if directory.is_empty() && local.iter().any(|user| user.is_active) {
+            return Err(AppError::LdapError(
+                "Directory returned no users, refusing to deactivate all accounts".to_string(),
+            ));
+        }
+
+        let mut report = DirectorySyncReport {
+            started_at,
+            finished_at: started_at,
+            directory_users: directory.len(),
+            local_users: local.len(),
+            updated: 0,
+            deactivated: Vec::new(),
+            reactivated: Vec::new(),
+            errors: Vec::new(),
+            failure: None,
+        };
+
+        for action in reconcile(local, &directory) {
+            match action {
+                SyncAction::Refresh(user, info) => {
+                    let user_id = user.id.clone();
+                    match self.provider.refresh_user(user, info).await {
+                        Ok((_, true)) => report.updated += 1,
+                        Ok((_, false)) => {}
+                        Err(e) => report.errors.push(format!("{}: {}", user_id, e)),
+                    }
+                }
+                SyncAction::Deactivate(user, reason) => match self.deactivate(user, reason).await {
+                    Ok(deactivated) => report.deactivated.push(deactivated),
+                    Err(e) => report.errors.push(e.to_string()),
+                },
+                SyncAction::Reactivate(user, info) => {
+                    let user_id = user.id.clone();
+                    match self.provider.refresh_user(user, info).await {
+                        Ok((user, _)) => report.reactivated.push(ReactivatedUser {
+                            user_id: user.id,
+                            username: info.username.clone(),
+                        }),
+                        Err(e) => report.errors.push(format!("{}: {}", user_id, e)),
+                    }
+                }
+            }
+        }
+
+        report.finished_at = Utc::now();
+        Ok(report)
+    }
+
+    async fn deactivate(&self, mut user: User, reason: &str) -> Result<DeactivatedUser, AppError> {
+        user.is_active = false;
+        user.updated_at = Utc::now();
+        self.repository.update(&user).await?;
+
+        // access tokens run out on their own, refresh tokens must not outlive the account
+        let revoked = self.refresh_token_repository.revoke_all_for_user(&user.id).await?;
+
+        let username = user.external_id.clone().unwrap_or_default();
+        tracing::warn!(
+            user_id = %user.id,
+            username = %username,
+            reason = %reason,
+            revoked_tokens = revoked,
+            "Deactivated LDAP user"
+        );
+
+        Ok(DeactivatedUser {
+            user_id: user.id,
+            username,
+            reason: reason.to_string(),
+        })
+    }
+
+    // first run after one interval, not at startup
+    pub fn spawn(self: Arc<Self>, interval: Duration) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval_at(tokio::time::Instant::now() + interval, interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+            loop {
+                ticker.tick().await;
+                // errors are logged and kept in the report, next tick tries again
+                let _ = self.run().await;
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{AuthProviderType, UserRole};
+
+    fn local(username: &str) -> User {
+        User::new_external(
+            username.to_string(),
+            format!("{}@example.com", username),
+            AuthProviderType::ActiveDirectory,
+            username.to_string(),
+            UserRole::User,
+        )
+    }
+
+    fn directory(username: &str, disabled: bool) -> DirectoryUser {
+        DirectoryUser {
+            info: LdapUserInfo {
+                username: username.to_string(),
+                display_name: username.to_string(),
+                email: format!("{}@example.com", username),
+                role: UserRole::User,
+            },
+            disabled,
+        }
+    }
+
+    #[test]
+    fn test_reconcile() {
+        let inactive = |username: &str| User { is_active: false, ..local(username) };
+        let local_users = vec![
+            local("max"),
+            local("anna"),
+            local("gone"),
+            inactive("back"),
+            inactive("still-gone"),
+            inactive("still-disabled"),
+        ];
+        let directory_users = vec![
+            directory("MAX", false),
+            directory("anna", true),
+            directory("new", false),
+            directory("back", false),
+            directory("still-disabled", true),
+        ];
+
+        let actions = reconcile(local_users, &directory_users);
+        assert_eq!(actions.len(), 4);
+
+        let outcome: Vec<(String, Option<&str>)> = actions
+            .iter()
+            .map(|a| match a {
+                SyncAction::Refresh(user, _) => (user.name.clone(), None),
+                SyncAction::Deactivate(user, reason) => (user.name.clone(), Some(*reason)),
+                SyncAction::Reactivate(user, _) => (user.name.clone(), Some("reactivate")),
+            })
+            .collect();
+
+        assert!(outcome.contains(&("max".to_string(), None)));
+        assert!(outcome.contains(&("anna".to_string(), Some("disabled in directory"))));
+        assert!(outcome.contains(&("gone".to_string(), Some("not found in directory"))));
+        assert!(outcome.contains(&("back".to_string(), Some("reactivate"))));
+    }
+}
@@ -0,0 +1,292 @@
+// hashes imported from the old php app (bcrypt, sha512-crypt) and from django (pbkdf2, scrypt)
+// only ever verified, needs_rehash is true for all of them so the next login upgrades to argon2id
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use scrypt::Scrypt;
+use scrypt::password_hash::{PasswordHash, PasswordVerifier};
+use sha1::Sha1;
+use sha2::Sha256;
+
+// an imported hash decides how much work every login on that account costs, these keep one row
+// from tying up a hashing thread for minutes. far above what php and django ever wrote by default
+const MAX_BCRYPT_COST: u32 = 14;
+const MAX_SHA512_CRYPT_ROUNDS: u32 = 1_000_000;
+const MAX_PBKDF2_ITERATIONS: u32 = 2_000_000;
+const MAX_PBKDF2_HASH_LEN: usize = 64;
+const MAX_SCRYPT_LOG_N: u8 = 20;
+const MAX_SCRYPT_R_TIMES_P: u64 = 64;
+// 128 * r * n bytes
+const MAX_SCRYPT_MEMORY: u64 = 1 << 30;
+
+pub(super) enum LegacyHash<'a> {
+    // php password_hash(), $2y$ / $2b$ / $2a$
+    Bcrypt(&'a str),
+    // $6$[rounds=N$]salt$hash, crypt(3) on linux and php crypt()
+    Sha512Crypt(&'a str),
+    // PHC string $scrypt$ln=..,r=..,p=..$salt$hash
+    Scrypt(PasswordHash<'a>),
+    // pbkdf2_sha256$iterations$salt$base64 (django default), pbkdf2_sha1 from old django versions
+    DjangoPbkdf2 {
+        sha1: bool,
+        iterations: u32,
+        salt: &'a str,
+        hash: Vec<u8>,
+    },
+    // scrypt$n$salt$r$p$base64 (django 4.2+)
+    DjangoScrypt {
+        params: scrypt::Params,
+        salt: &'a str,
+        hash: Vec<u8>,
+    },
+}
+
+impl<'a> LegacyHash<'a> {
+    // None: not one of the legacy formats, Some(Err): looks like one but is broken
+    pub(super) fn parse(hash: &'a str) -> Option<Result<Self, String>> {
+        let result = if ["$2a$", "$2b$", "$2y$"].iter().any(|prefix| hash.starts_with(prefix)) {
+            parse_bcrypt(hash)
+        } else if hash.starts_with("$6$") {
+            parse_sha512_crypt(hash)
+        } else if hash.starts_with("$scrypt$") {
+            parse_phc_scrypt(hash)
+        } else if let Some(rest) = hash.strip_prefix("pbkdf2_sha256$") {
+            parse_django_pbkdf2(rest, false)
+        } else if let Some(rest) = hash.strip_prefix("pbkdf2_sha1$") {
+            parse_django_pbkdf2(rest, true)
+        } else if let Some(rest) = hash.strip_prefix("scrypt$") {
+            parse_django_scrypt(rest)
+        } else {
+            return None;
+        };
+
+        Some(result.map_err(|e| format!("Invalid legacy hash: {}", e)))
+    }
+
+    pub(super) fn verify(&self, password: &str) -> bool {
+        match self {
+            LegacyHash::Bcrypt(hash) => pwhash::bcrypt::verify(password, hash),
+            LegacyHash::Sha512Crypt(hash) => pwhash::sha512_crypt::verify(password, hash),
+            LegacyHash::Scrypt(hash) => Scrypt.verify_password(password.as_bytes(), hash).is_ok(),
+            LegacyHash::DjangoPbkdf2 { sha1, iterations, salt, hash } => {
+                let mut derived = vec![0u8; hash.len()];
+                if *sha1 {
+                    pbkdf2::pbkdf2_hmac::<Sha1>(password.as_bytes(), salt.as_bytes(), *iterations, &mut derived);
+                } else {
+                    pbkdf2::pbkdf2_hmac::<Sha256>(password.as_bytes(), salt.as_bytes(), *iterations, &mut derived);
+                }
+                constant_time_eq(&derived, hash)
+            }
+            LegacyHash::DjangoScrypt { params, salt, hash } => {
+                let mut derived = vec![0u8; hash.len()];
+                scrypt::scrypt(password.as_bytes(), salt.as_bytes(), params, &mut derived).is_ok()
+                    && constant_time_eq(&derived, hash)
+            }
+        }
+    }
+}
+
+fn check_scrypt_cost(params: &scrypt::Params) -> Result<(), String> {
+    let (log_n, r, p) = (params.log_n(), params.r() as u64, params.p() as u64);
+    if log_n > MAX_SCRYPT_LOG_N || r * p > MAX_SCRYPT_R_TIMES_P || 128 * r * (1u64 << log_n) > MAX_SCRYPT_MEMORY {
+        return Err(format!(
+            "scrypt cost too high, at most ln={} with r*p <= {} and 1 GiB memory",
+            MAX_SCRYPT_LOG_N, MAX_SCRYPT_R_TIMES_P
+        ));
+    }
+    Ok(())
+}
+
+fn parse_phc_scrypt(hash: &str) -> Result<LegacyHash<'_>, String> {
+    let parsed = PasswordHash::new(hash).map_err(|e| e.to_string())?;
+    let params = scrypt::Params::try_from(&parsed).map_err(|e| format!("scrypt parameters: {}", e))?;
+    check_scrypt_cost(&params)?;
+    if parsed.salt.is_none() || parsed.hash.is_none() {
+        return Err("scrypt hash must have salt and hash".to_string());
+    }
+    Ok(LegacyHash::Scrypt(parsed))
+}
+
+// $2y$10$ + 22 chars salt + 31 chars hash
+fn parse_bcrypt(hash: &str) -> Result<LegacyHash<'_>, String> {
+    let cost: u32 = hash
+        .get(4..6)
+        .and_then(|cost| cost.parse().ok())
+        .ok_or("bcrypt cost missing")?;
+    if !(4..=MAX_BCRYPT_COST).contains(&cost) || hash.len() != 60 || hash.as_bytes()[6] != b'$' {
+        return Err(format!("bcrypt hash must be 60 chars with a cost between 04 and {}", MAX_BCRYPT_COST));
+    }
+    if !hash[7..].chars().all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '/') {
+        return Err("bcrypt salt and hash must be bcrypt base64".to_string());
+    }
+    Ok(LegacyHash::Bcrypt(hash))
+}
+
+fn parse_sha512_crypt(hash: &str) -> Result<LegacyHash<'_>, String> {
+    let mut parts: Vec<&str> = hash[3..].split('$').collect();
+    if let Some(rounds) = parts.first().and_then(|p| p.strip_prefix("rounds=")) {
+        // crypt(3) clamps anything below 1000 itself, only the upper end matters here
+        if rounds.parse::<u32>().map_or(true, |rounds| rounds > MAX_SHA512_CRYPT_ROUNDS) {
+            return Err(format!("sha512-crypt rounds must be a number up to {}", MAX_SHA512_CRYPT_ROUNDS));
+        }
+        parts.remove(0);
+    }
+    match parts.as_slice() {
+        [salt, checksum] if salt.len() <= 16 && checksum.len() == 86 => Ok(LegacyHash::Sha512Crypt(hash)),
+        _ => Err("sha512-crypt hash must be $6$[rounds=N$]salt$checksum".to_string()),
+    }
+}
+
+fn parse_django_pbkdf2(rest: &str, sha1: bool) -> Result<LegacyHash<'_>, String> {
+    let [iterations, salt, hash] = rest.splitn(3, '$').collect::<Vec<_>>()[..] else {
+        return Err("django pbkdf2 hash must be algorithm$iterations$salt$hash".to_string());
+    };
+    let iterations: u32 = iterations
+        .parse()
+        .ok()
+        .filter(|i| (1..=MAX_PBKDF2_ITERATIONS).contains(i))
+        .ok_or_else(|| format!("pbkdf2 iterations must be between 1 and {}", MAX_PBKDF2_ITERATIONS))?;
+    let hash = decode(hash)?;
+    if hash.len() > MAX_PBKDF2_HASH_LEN {
+        return Err(format!("pbkdf2 hash longer than {} bytes", MAX_PBKDF2_HASH_LEN));
+    }
+
+    Ok(LegacyHash::DjangoPbkdf2 { sha1, iterations, salt, hash })
+}
+
+fn parse_django_scrypt(rest: &str) -> Result<LegacyHash<'_>, String> {
+    let [n, salt, r, p, hash] = rest.splitn(5, '$').collect::<Vec<_>>()[..] else {
+        return Err("django scrypt hash must be scrypt$n$salt$r$p$hash".to_string());
+    };
+    let number = |value: &str, name: &str| -> Result<u32, String> {
+        value.parse().map_err(|_| format!("scrypt {} must be a number", name))
+    };
+    let n = number(n, "n")?;
+    if !n.is_power_of_two() || n < 2 {
+        return Err("scrypt n must be a power of two".to_string());
+    }
+    let hash = decode(hash)?;
+    let params = scrypt::Params::new(n.trailing_zeros() as u8, number(r, "r")?, number(p, "p")?, hash.len())
+        .map_err(|e| format!("scrypt parameters: {}", e))?;
+    check_scrypt_cost(&params)?;
+
+    Ok(LegacyHash::DjangoScrypt { params, salt, hash })
+}
+
+fn decode(hash: &str) -> Result<Vec<u8>, String> {
+    STANDARD
+        .decode(hash)
+        .ok()
+        .filter(|h| !h.is_empty())
+        .ok_or_else(|| "hash must be base64".to_string())
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use scrypt::password_hash::{PasswordHasher, SaltString, rand_core::OsRng};
+
+    // made with python hashlib like django does it
+    const DJANGO_PBKDF2_SHA256: &str = "pbkdf2_sha256$1000$seasalt$iQbGf4uimNHthLwDk+B/WUv+q9caE1SVKDrO8xeKykc=";
+    const DJANGO_PBKDF2_SHA1: &str = "pbkdf2_sha1$1000$seasalt$PFYCoCICnhKwdgytoDLWITNDs1o=";
+    const DJANGO_SCRYPT: &str = "scrypt$1024$seasalt$8$1$VGUafSEvPXU0U/gtu6rbdvkM4kTqrDnWMB0LVXuqZab24vgrO9mbqior1dMWNJ/du+u8kVZLL4M8lZ7aYLBP5g==";
+    // OpenBSD bcrypt test vector, password "U*U"
+    const BCRYPT: &str = "$2a$05$CCCCCCCCCCCCCCCCCCCCC.E5YPO9kmyuRGyh0XouQYb4YMJKvyOeW";
+    // test vectors from the SHA-crypt spec, password "Hello world!"
+    const SHA512_CRYPT: &str = "$6$saltstring$svn8UoSVapNtMuq1ukKS4tPQd8iKwSMHWjl/O817G3uBnIFNjnQJuesI68u4OTLiBFdcbYEdFCoEOfaS35inz1";
+    const SHA512_CRYPT_ROUNDS: &str = "$6$rounds=10000$saltstringsaltst$OW1/O6BYHV6BcXZu8QVeXbDWra3Oeqh0sbHbbMCVNSnCM/UrjmM0Dp8vOuZeHBy/YTBmSK6H9qs/y3RnOaw5v.";
+
+    fn verify(hash: &str, password: &str) -> bool {
+        LegacyHash::parse(hash).unwrap().unwrap().verify(password)
+    }
+
+    #[test]
+    fn test_django_pbkdf2() {
+        assert!(verify(DJANGO_PBKDF2_SHA256, "legacy_password_1"));
+        assert!(!verify(DJANGO_PBKDF2_SHA256, "legacy_password_2"));
+        assert!(verify(DJANGO_PBKDF2_SHA1, "legacy_password_1"));
+        assert!(!verify(DJANGO_PBKDF2_SHA1, "legacy_password_2"));
+    }
+
+    #[test]
+    fn test_django_scrypt() {
+        assert!(verify(DJANGO_SCRYPT, "legacy_password_1"));
+        assert!(!verify(DJANGO_SCRYPT, "legacy_password_2"));
+    }
+
+    #[test]
+    fn test_phc_scrypt() {
+        let salt = SaltString::generate(&mut OsRng);
+        let params = scrypt::Params::new(10, 8, 1, 32).unwrap();
+        let hash = Scrypt
+            .hash_password_customized(b"legacy_password_1", None, None, params, &salt)
+            .unwrap()
+            .to_string();
+
+        assert!(hash.starts_with("$scrypt$"));
+        assert!(verify(&hash, "legacy_password_1"));
+        assert!(!verify(&hash, "legacy_password_2"));
+    }
+
+    #[test]
+    fn test_bcrypt_variants() {
+        assert!(verify(BCRYPT, "U*U"));
+        assert!(!verify(BCRYPT, "U*V"));
+        // php writes $2y$, same algorithm
+        assert!(verify(&BCRYPT.replacen("$2a$", "$2y$", 1), "U*U"));
+        assert!(verify(&BCRYPT.replacen("$2a$", "$2b$", 1), "U*U"));
+    }
+
+    #[test]
+    fn test_sha512_crypt() {
+        assert!(verify(SHA512_CRYPT, "Hello world!"));
+        assert!(verify(SHA512_CRYPT_ROUNDS, "Hello world!"));
+        assert!(!verify(SHA512_CRYPT, "Hello world?"));
+    }
+
+    #[test]
+    fn test_parse_rejects_broken_and_ignores_unknown() {
+        for broken in [
+            "$2y$99$CCCCCCCCCCCCCCCCCCCCC.E5YPO9kmyuRGyh0XouQYb4YMJKvyOeW",
+            "$2y$05$short",
+            "$6$salt$tooshort",
+            "pbkdf2_sha256$abc$salt$aGFzaA==",
+            "pbkdf2_sha256$1000$salt",
+            "pbkdf2_sha256$1000$salt$not base64!",
+            "scrypt$1000$salt$8$1$aGFzaA==",
+            "$scrypt$garbage",
+        ] {
+            assert!(LegacyHash::parse(broken).unwrap().is_err(), "{} accepted", broken);
+        }
+
+        assert!(LegacyHash::parse("$argon2id$v=19$m=65536,t=3,p=4$c2FsdA$aGFzaA").is_none());
+        assert!(LegacyHash::parse("$1$md5$isnotsupported").is_none());
+        assert!(LegacyHash::parse("plaintext").is_none());
+    }
+
+    #[test]
+    fn test_parse_rejects_costs_over_the_ceiling() {
+        let hash = STANDARD.encode([0u8; 32]);
+        let long_hash = STANDARD.encode([0u8; 65]);
+        for expensive in [
+            BCRYPT.replacen("$05$", "$15$", 1),
+            SHA512_CRYPT_ROUNDS.replacen("rounds=10000", "rounds=1000001", 1),
+            "pbkdf2_sha256$2000001$salt$aGFzaA==".to_string(),
+            format!("pbkdf2_sha256$1000$salt${}", long_hash),
+            format!("scrypt$2097152$salt$8$1${}", hash),
+            format!("scrypt$1024$salt$8$16${}", hash),
+            format!("scrypt$1048576$salt$16$1${}", hash),
+            "$scrypt$ln=21,r=8,p=1$c2FsdHNhbHQ$aGFzaGhhc2hoYXNoaGFzaGhhc2hoYXNoaGFzaGhhc2g".to_string(),
+        ] {
+            assert!(LegacyHash::parse(&expensive).unwrap().is_err(), "{} accepted", expensive);
+        }
+
+        // right at the ceiling is fine
+        assert!(LegacyHash::parse(&BCRYPT.replacen("$05$", "$14$", 1)).unwrap().is_ok());
+        assert!(LegacyHash::parse("pbkdf2_sha256$2000000$salt$aGFzaA==").unwrap().is_ok());
+        assert!(LegacyHash::parse(&format!("scrypt$1048576$salt$8$1${}", hash)).unwrap().is_ok());
+    }
+}
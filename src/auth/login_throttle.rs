@@ -0,0 +1,283 @@
+// brute force protection for the password logins, counters live in the repository so restarts dont reset them
+// three counters per failed login:
+// - account from this ip: locked after max_failures, exponential backoff up to max_lockout_secs
+// - whole account: after account_max_failures only ips that already failed on it are blocked,
+//   so somebody guessing from elsewhere cannot lock the real user out
+// - ip over all accounts: credential stuffing
+// every lock runs out on its own and the counters reset after window_secs without a failure
+// wrong device user codes are counted the same way, with their own counters per user and per ip
+use chrono::{Duration, Utc};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+
+use crate::config::LockoutConfig;
+use crate::error::AppError;
+use crate::models::LoginAttempt;
+use crate::repository::LoginAttemptRepository;
+
+pub struct LoginThrottle {
+    config: LockoutConfig,
+    repository: Arc<dyn LoginAttemptRepository>,
+    // one login per account at a time in this process, otherwise a burst of guesses
+    // all pass check() before the first failure is counted
+    in_progress: Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>,
+}
+
+impl LoginThrottle {
+    pub fn new(config: LockoutConfig, repository: Arc<dyn LoginAttemptRepository>) -> Self {
+        Self { config, repository, in_progress: Mutex::new(HashMap::new()) }
+    }
+
+    pub fn local_subject(email: &str) -> String {
+        format!("local:{}", email.trim().to_lowercase())
+    }
+
+    pub fn ldap_subject(username: &str) -> String {
+        format!("ldap:{}", username.trim().to_lowercase())
+    }
+
+    fn account_ip_key(subject: &str, ip: &str) -> String {
+        format!("account_ip:{}|{}", subject, ip)
+    }
+
+    fn account_key(subject: &str) -> String {
+        format!("account:{}", subject)
+    }
+
+    fn ip_key(ip: &str) -> String {
+        format!("ip:{}", ip)
+    }
+
+    fn reset_email_key(email: &str) -> String {
+        format!("reset_email:{}", email.trim().to_lowercase())
+    }
+
+    fn reset_ip_key(ip: &str) -> String {
+        format!("reset_ip:{}", ip)
+    }
+
+    fn user_code_user_key(user_id: &str) -> String {
+        format!("user_code_user:{}", user_id)
+    }
+
+    fn user_code_ip_key(ip: &str) -> String {
+        format!("user_code_ip:{}", ip)
+    }
+
+    // lockout_secs, doubled per failure over the threshold, capped
+    fn lockout(&self, failures: i64, threshold: u32) -> Duration {
+        let over = (failures - threshold as i64).clamp(0, 30) as u32;
+        let secs = self.config.lockout_secs.saturating_mul(1i64 << over).min(self.config.max_lockout_secs);
+        Duration::seconds(secs)
+    }
+
+    fn too_many(attempt: &LoginAttempt) -> AppError {
+        AppError::TooManyAttempts(attempt.retry_after_secs())
+    }
+
+    // locked -> 429 without checking the password at all
+    pub async fn check(&self, subject: &str, ip: &str) -> Result<(), AppError> {
+        if let Some(attempt) = self.repository.find(&Self::ip_key(ip)).await?
+            && attempt.is_locked()
+        {
+            return Err(Self::too_many(&attempt));
+        }
+
+        let own = self.repository.find(&Self::account_ip_key(subject, ip)).await?;
+        if let Some(attempt) = &own
+            && attempt.is_locked()
+        {
+            return Err(Self::too_many(attempt));
+        }
+
+        let window_start = Utc::now() - Duration::seconds(self.config.window_secs);
+        let failed_here = own.is_some_and(|attempt| attempt.last_failure_at >= window_start);
+        if failed_here
+            && let Some(attempt) = self.repository.find(&Self::account_key(subject)).await?
+            && attempt.is_locked()
+        {
+            return Err(Self::too_many(&attempt));
+        }
+
+        Ok(())
+    }
+
+    // repository problems only get logged, the login already failed anyway
+    pub async fn record_failure(&self, subject: &str, ip: &str) {
+        let window_start = Utc::now() - Duration::seconds(self.config.window_secs);
+
+        if let Err(e) = self.repository.delete_stale(window_start).await {
+            tracing::warn!(error = %e, "cleanup of old login attempts failed");
+        }
+
+        let counters = [
+            (Self::account_ip_key(subject, ip), Some(subject), self.config.max_failures),
+            (Self::account_key(subject), Some(subject), self.config.account_max_failures),
+            (Self::ip_key(ip), None, self.config.ip_max_failures),
+        ];
+
+        for (key, key_subject, threshold) in counters {
+            self.count_failure(&key, key_subject, threshold).await;
+        }
+    }
+
+    // one counter up, locked with backoff once it reaches the threshold
+    async fn count_failure(&self, key: &str, subject: Option<&str>, threshold: u32) {
+        let now = Utc::now();
+        let window_start = now - Duration::seconds(self.config.window_secs);
+
+        let attempt = match self.repository.record_failure(key, subject, window_start).await {
+            Ok(attempt) => attempt,
+            Err(e) => {
+                tracing::warn!(error = %e, key = %key, "could not record failed login");
+                return;
+            }
+        };
+        if threshold == 0 || attempt.failures < threshold as i64 {
+            return;
+        }
+
+        let lockout = self.lockout(attempt.failures, threshold);
+        tracing::warn!(
+            key = %key,
+            failures = attempt.failures,
+            lockout_secs = lockout.num_seconds(),
+            "Login locked after too many failures"
+        );
+        if let Err(e) = self.repository.lock(key, now + lockout).await {
+            tracing::warn!(error = %e, key = %key, "could not lock login");
+        }
+    }
+
+    // only this ip starts from zero, the account and ip counters keep running
+    pub async fn record_success(&self, subject: &str, ip: &str) {
+        if let Err(e) = self.repository.delete(&Self::account_ip_key(subject, ip)).await {
+            tracing::warn!(error = %e, "could not reset failed logins");
+        }
+    }
+
+    // check, run the login, count it; only wrong credentials count as failure, also the ones against the offline cache
+    pub async fn guard<T>(
+        &self,
+        subject: &str,
+        ip: &str,
+        login: impl Future<Output = Result<T, AppError>>,
+    ) -> Result<T, AppError> {
+        let lock = self.subject_lock(subject);
+        let result = {
+            let _serialized = lock.lock().await;
+            self.guarded(subject, ip, login).await
+        };
+        drop(lock);
+        self.release_subject_lock(subject);
+        result
+    }
+
+    async fn guarded<T>(
+        &self,
+        subject: &str,
+        ip: &str,
+        login: impl Future<Output = Result<T, AppError>>,
+    ) -> Result<T, AppError> {
+        self.check(subject, ip).await?;
+
+        let result = login.await;
+        match &result {
+            Ok(_) => self.record_success(subject, ip).await,
+            Err(AppError::Unauthorized(_) | AppError::OfflineLoginRejected(_)) => self.record_failure(subject, ip).await,
+            Err(_) => {}
+        }
+        result
+    }
+
+    fn subject_lock(&self, subject: &str) -> Arc<tokio::sync::Mutex<()>> {
+        let mut in_progress = self.in_progress.lock().unwrap();
+        Arc::clone(in_progress.entry(subject.to_string()).or_default())
+    }
+
+    // last one out removes the entry so the map does not grow with every name ever tried
+    fn release_subject_lock(&self, subject: &str) {
+        let mut in_progress = self.in_progress.lock().unwrap();
+        if in_progress.get(subject).is_some_and(|lock| Arc::strong_count(lock) == 1) {
+            in_progress.remove(subject);
+        }
+    }
+
+    // device user code lookups (RFC 8628 section 5.1), a code has only ~34 bits. unknown codes count like
+    // wrong passwords: max_failures per user, ip_max_failures per ip. serialized per user like guard()
+    pub async fn guard_user_code<T>(
+        &self,
+        user_id: &str,
+        ip: &str,
+        lookup: impl Future<Output = Result<T, AppError>>,
+    ) -> Result<T, AppError> {
+        let counters = [
+            (Self::user_code_user_key(user_id), self.config.max_failures),
+            (Self::user_code_ip_key(ip), self.config.ip_max_failures),
+        ];
+
+        let lock = self.subject_lock(&counters[0].0);
+        let result = {
+            let _serialized = lock.lock().await;
+            self.guarded_user_code(&counters, lookup).await
+        };
+        drop(lock);
+        self.release_subject_lock(&counters[0].0);
+        result
+    }
+
+    async fn guarded_user_code<T>(
+        &self,
+        counters: &[(String, u32)],
+        lookup: impl Future<Output = Result<T, AppError>>,
+    ) -> Result<T, AppError> {
+        for (key, _) in counters {
+            if let Some(attempt) = self.repository.find(key).await?
+                && attempt.is_locked()
+            {
+                return Err(Self::too_many(&attempt));
+            }
+        }
+
+        let result = lookup.await;
+        if let Err(AppError::NotFound(_)) = &result {
+            for (key, threshold) in counters {
+                self.count_failure(key, None, *threshold).await;
+            }
+        }
+        result
+    }
+
+    // forgot password requests, counted in the same table and window as failed logins but never locked.
+    // both counters always go up, so switching addresses does not help against the ip limit
+    pub async fn allow_reset_request(&self, email: &str, ip: &str, per_email: u32, per_ip: u32) -> bool {
+        let window_start = Utc::now() - Duration::seconds(self.config.window_secs);
+        let counters = [(Self::reset_email_key(email), per_email), (Self::reset_ip_key(ip), per_ip)];
+
+        let mut allowed = true;
+        for (key, limit) in counters {
+            if limit == 0 {
+                continue;
+            }
+            match self.repository.record_failure(&key, None, window_start).await {
+                Ok(attempt) if attempt.failures > limit as i64 => {
+                    tracing::warn!(key = %key, requests = attempt.failures, "Too many password reset requests");
+                    allowed = false;
+                }
+                Ok(_) => {}
+                Err(e) => tracing::warn!(error = %e, key = %key, "could not count password reset request"),
+            }
+        }
+        allowed
+    }
+
+    // admin unlock, every counter of these accounts is gone
+    pub async fn unlock(&self, subjects: &[String]) -> Result<u64, AppError> {
+        let mut cleared = 0;
+        for subject in subjects {
+            cleared += self.repository.delete_for_subject(subject).await?;
+        }
+        Ok(cleared)
+    }
+}
@@ -2,19 +2,57 @@
 //!
 //! This module is designed for extensibility:
 //! - `password`: Secure password hashing using Argon2
+//! - `hashing_pool`: Bounded blocking pool for Argon2 with latency metrics
+//! - `legacy_hash`: Verifies imported bcrypt, sha512-crypt, PBKDF2 and scrypt hashes
 //! - `jwt`: JWT token generation and validation
 //! - `provider`: Trait-based authentication providers for future OAuth/AD support
 //! - `google`: Google OAuth 2.0 authentication
 //! - `ldap`: LDAP/Active Directory authentication
+//! - `ldap_groups`: Exact group matching and group to role mapping
+//! - `ldap_pool`: LDAP server failover and pooled service account connections
+//! - `ldap_tls`: Custom CA, client certificate and minimum TLS version for LDAP
+//! - `directory`: Directory client abstraction with the ldap3 implementation
+//! - `directory_memory`: In-memory directory for tests and local development
+//! - `ldap_sync`: Scheduled directory sync, deactivates removed or disabled AD users and reactivates returning ones
+//! - `device`: OAuth 2.0 device authorization grant (RFC 8628)
+//! - `password_reset`: Forgot password flow with one time links by mail
+//! - `password_policy`: Length, character class, common and breached password rules
+//! - `password_history`: Rejects the last N passwords of a local account
+//! - `login_throttle`: Failed login counters, backoff and lockout per account and client ip
+//! - `tokens`: Opaque token generation and hashing
 
 mod password;
+mod hashing_pool;
+mod legacy_hash;
 mod jwt;
 mod provider;
 mod google;
 mod ldap;
+mod directory;
+mod directory_memory;
+mod ldap_groups;
+mod ldap_pool;
+mod ldap_tls;
+mod ldap_sync;
+mod device;
+mod password_reset;
+mod password_policy;
+mod password_history;
+mod login_throttle;
+mod tokens;
 
 pub use password::PasswordHasher;
+pub use hashing_pool::{HashingMetrics, LatencySummary, LatencyBucket};
 pub use jwt::{JwtService, Claims};
-pub use provider::{AuthProvider, AuthResult, LocalAuthProvider};
+pub use provider::{AuthProvider, AuthResult, LocalAuthProvider, LocalCredentials};
 pub use google::GoogleAuthProvider;
 pub use ldap::LdapAuthProvider;
+pub use directory::{OperationResult, DirectoryClient, DirectoryConnection, Ldap3Client};
+pub use directory_memory::InMemoryDirectory;
+pub use ldap_sync::{DirectorySyncService, DirectorySyncReport, DeactivatedUser, ReactivatedUser};
+pub use device::{DeviceFlowService, DeviceAuthorizationResponse, DevicePollOutcome};
+pub use password_reset::PasswordResetService;
+pub use password_policy::PasswordPolicy;
+pub use password_history::PasswordHistory;
+pub use login_throttle::LoginThrottle;
+pub use tokens::{generate_opaque_token, hash_opaque_token};
@@ -1,12 +1,33 @@
 // Warum Argon2 -> Roschger kennt sich aus und gut erprobt
 use argon2::{
     password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher as _, PasswordVerifier, SaltString},
-    Argon2, Algorithm, Params, Version,
+    Argon2, Algorithm, KeyId, Params, ParamsBuilder, Version,
 };
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::config::Argon2Config;
 use crate::error::AppError;
+use super::hashing_pool::{HashOperation, HashingMetrics, HashingPool};
+use super::legacy_hash::LegacyHash;
+
+// imported argon2 hashes may not ask for more than 1 GiB per verify
+const MAX_IMPORT_MEMORY_KIB: u32 = 1024 * 1024;
 
+#[derive(Clone)]
 pub struct PasswordHasher {
     argon2: Argon2<'static>,
+    // keyid of the current pepper is part of these
+    params: Params,
+    // pepper id -> secret, old ones stay for verify
+    peppers: Arc<HashMap<Vec<u8>, Vec<u8>>>,
+    // shared by all clones, limits argon2 across the whole service
+    pool: Arc<HashingPool>,
+    // random password hashed with the current params, for logins without a real hash
+    dummy_hash: Arc<tokio::sync::OnceCell<String>>,
 }
 
 impl Default for PasswordHasher {
@@ -16,36 +37,173 @@ impl Default for PasswordHasher {
 }
 
 impl PasswordHasher {
+    // 64 MiB, 3 iterations, 4 parallel threads
     pub fn new() -> Self {
+        Self::with_config(&Argon2Config::default()).expect("Invalid parameters")
+    }
 
-        let params = Params::new(
-            64 * 1024, // 64 MiB
-            3,         // 3 iterations
-            4,         // 4 parallel threads
-            None,      // output 32 bit
-        )
-        .expect("Invalid parameters");
+    pub fn with_config(config: &Argon2Config) -> Result<Self, String> {
+        let invalid = |e: argon2::Error| format!("Invalid Argon2 parameters: {}", e);
+        if config.max_concurrent == 0 {
+            return Err("ARGON2_MAX_CONCURRENT must be at least 1".to_string());
+        }
 
-        let argon2 = Argon2::new(Algorithm::Argon2id, Version::V0x13, params);
+        // output stays the default 32 byte
+        let mut builder = ParamsBuilder::new();
+        builder
+            .m_cost(config.memory_kib)
+            .t_cost(config.iterations)
+            .p_cost(config.parallelism);
+
+        let mut peppers = HashMap::new();
+        if let Some(pepper) = &config.pepper {
+            builder.keyid(KeyId::new(pepper.current_id().as_bytes()).map_err(invalid)?);
+            for (id, secret) in &pepper.keys {
+                peppers.insert(id.as_bytes().to_vec(), secret.as_bytes().to_vec());
+            }
+        }
 
-        Self { argon2 }
+        let params = builder.build().map_err(invalid)?;
+        let argon2 = Argon2::new(Algorithm::Argon2id, Version::V0x13, params.clone());
+
+        Ok(Self {
+            argon2,
+            params,
+            peppers: Arc::new(peppers),
+            pool: Arc::new(HashingPool::new(
+                config.max_concurrent,
+                Duration::from_millis(config.queue_timeout_ms),
+            )),
+            dummy_hash: Arc::new(tokio::sync::OnceCell::new()),
+        })
     }
 
-    pub fn hash(&self, password: &str) -> Result<String, AppError> {
+    // HMAC-SHA256 with the pepper of the hash, argon2 then gets the mac instead of the password
+    fn peppered(&self, password: &str, keyid: &[u8]) -> Result<Vec<u8>, AppError> {
+        if keyid.is_empty() {
+            return Ok(password.as_bytes().to_vec());
+        }
+
+        let secret = self.peppers.get(keyid).ok_or_else(|| {
+            AppError::InternalError(format!(
+                "Password hash uses unknown pepper '{}'",
+                String::from_utf8_lossy(keyid)
+            ))
+        })?;
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret)
+            .map_err(|e| AppError::InternalError(format!("Pepper failed: {}", e)))?;
+        mac.update(password.as_bytes());
+
+        Ok(mac.finalize().into_bytes().to_vec())
+    }
+
+    // made with another algorithm, version, cost or pepper than configured -> rehash after the next successful verify
+    // weaker and stronger both count, after lowering the cost old hashes should get cheaper too
+    pub fn needs_rehash(&self, hash: &str) -> bool {
+        if LegacyHash::parse(hash).is_some() {
+            return true;
+        }
+        let Ok(parsed) = PasswordHash::new(hash) else {
+            return false;
+        };
+        if parsed.algorithm != Algorithm::Argon2id.ident() || parsed.version != Some(Version::V0x13.into()) {
+            return true;
+        }
+
+        match Params::try_from(&parsed) {
+            Ok(params) => {
+                params.m_cost() != self.params.m_cost()
+                    || params.t_cost() != self.params.t_cost()
+                    || params.p_cost() != self.params.p_cost()
+                    || params.keyid() != self.params.keyid()
+                    || parsed.hash.map(|h| h.len()) != Some(self.params.output_len().unwrap_or(Params::DEFAULT_OUTPUT_LEN))
+            }
+            Err(_) => true,
+        }
+    }
+
+    // async code goes through the pool, blocking_* only for the cli and tests
+    pub async fn hash(&self, password: &str) -> Result<String, AppError> {
+        let hasher = self.clone();
+        let password = password.to_string();
+        self.pool
+            .run(HashOperation::Hash, move || hasher.blocking_hash(&password))
+            .await?
+    }
+
+    pub async fn verify(&self, password: &str, hash: &str) -> Result<bool, AppError> {
+        let hasher = self.clone();
+        let (password, hash) = (password.to_string(), hash.to_string());
+        self.pool
+            .run(HashOperation::Verify, move || hasher.blocking_verify(&password, &hash))
+            .await?
+    }
+
+    // unknown or non-local user: same verify as a wrong password, so the response time doesnt tell whether the account exists
+    // the first call also creates the dummy hash, main does that at startup
+    pub async fn verify_dummy(&self, password: &str) -> Result<(), AppError> {
+        let dummy = self
+            .dummy_hash
+            .get_or_try_init(|| async { self.hash(&super::tokens::generate_opaque_token()).await })
+            .await?;
+
+        // errors (overloaded pool) go up like on the real path
+        self.verify(password, dummy).await.map(|_| ())
+    }
+
+    pub fn metrics(&self) -> HashingMetrics {
+        self.pool.metrics()
+    }
+
+    pub fn blocking_hash(&self, password: &str) -> Result<String, AppError> {
         let salt = SaltString::generate(&mut OsRng);
+        let input = self.peppered(password, self.params.keyid())?;
         let hash = self
             .argon2
-            .hash_password(password.as_bytes(), &salt)
+            .hash_password(&input, &salt)
             .map_err(|e| AppError::InternalError(format!("Password hashing failed: {}", e)))?;
 
         Ok(hash.to_string())
     }
 
-    pub fn verify(&self, password: &str, hash: &str) -> Result<bool, AppError> {
+    // argon2 (any variant) or one of the imported legacy formats
+    pub fn validate_hash(&self, hash: &str) -> Result<(), AppError> {
+        let invalid = |e: String| AppError::ValidationError(format!("Unsupported password hash: {}", e));
+
+        if let Some(legacy) = LegacyHash::parse(hash) {
+            return legacy.map(|_| ()).map_err(invalid);
+        }
+
+        let parsed = PasswordHash::new(hash).map_err(|e| invalid(e.to_string()))?;
+        Algorithm::try_from(parsed.algorithm).map_err(|e| invalid(e.to_string()))?;
+        let params = Params::try_from(&parsed).map_err(|e| invalid(e.to_string()))?;
+        if parsed.hash.is_none() {
+            return Err(invalid("hash part missing".to_string()));
+        }
+        // every verify would allocate this much
+        if params.m_cost() > MAX_IMPORT_MEMORY_KIB {
+            return Err(invalid(format!("argon2 memory above {} KiB", MAX_IMPORT_MEMORY_KIB)));
+        }
+        if !params.keyid().is_empty() && !self.peppers.contains_key(params.keyid()) {
+            return Err(invalid("unknown pepper".to_string()));
+        }
+        Ok(())
+    }
+
+    pub fn blocking_verify(&self, password: &str, hash: &str) -> Result<bool, AppError> {
+        if let Some(legacy) = LegacyHash::parse(hash) {
+            return legacy
+                .map(|legacy| legacy.verify(password))
+                .map_err(AppError::InternalError);
+        }
+
         let parsed_hash = PasswordHash::new(hash)
             .map_err(|e| AppError::InternalError(format!("Invalid hash format: {}", e)))?;
+        let params = Params::try_from(&parsed_hash)
+            .map_err(|e| AppError::InternalError(format!("Invalid hash parameters: {}", e)))?;
+        let input = self.peppered(password, params.keyid())?;
 
-        match self.argon2.verify_password(password.as_bytes(), &parsed_hash) {
+        match self.argon2.verify_password(&input, &parsed_hash) {
             Ok(()) => Ok(true),
             Err(argon2::password_hash::Error::Password) => Ok(false),
             Err(e) => Err(AppError::InternalError(format!(
@@ -67,8 +225,8 @@ mod tests {
         let hasher = PasswordHasher::new();
         let password = "test_password_123!";
 
-        let hash = hasher.hash(password).unwrap();
-        assert!(hasher.verify(password, &hash).unwrap());
+        let hash = hasher.blocking_hash(password).unwrap();
+        assert!(hasher.blocking_verify(password, &hash).unwrap());
     }
 
     #[test]
@@ -76,8 +234,8 @@ mod tests {
         let hasher = PasswordHasher::new();
         let password = "correct_password";
 
-        let hash = hasher.hash(password).unwrap();
-        assert!(!hasher.verify("wrong_password", &hash).unwrap());
+        let hash = hasher.blocking_hash(password).unwrap();
+        assert!(!hasher.blocking_verify("wrong_password", &hash).unwrap());
     }
 
     #[test]
@@ -85,15 +243,15 @@ mod tests {
         let hasher = PasswordHasher::new();
         let password = "same_password";
 
-        let hash1 = hasher.hash(password).unwrap();
-        let hash2 = hasher.hash(password).unwrap();
+        let hash1 = hasher.blocking_hash(password).unwrap();
+        let hash2 = hasher.blocking_hash(password).unwrap();
 
         // Different salts = different hashes
         assert_ne!(hash1, hash2);
 
         // But both should verify correctly
-        assert!(hasher.verify(password, &hash1).unwrap());
-        assert!(hasher.verify(password, &hash2).unwrap());
+        assert!(hasher.blocking_verify(password, &hash1).unwrap());
+        assert!(hasher.blocking_verify(password, &hash2).unwrap());
     }
 
     // ==================== Edge Cases ====================
@@ -101,10 +259,10 @@ mod tests {
     #[test]
     fn test_empty_password() {
         let hasher = PasswordHasher::new();
-        let hash = hasher.hash("").unwrap();
+        let hash = hasher.blocking_hash("").unwrap();
 
-        assert!(hasher.verify("", &hash).unwrap());
-        assert!(!hasher.verify("not_empty", &hash).unwrap());
+        assert!(hasher.blocking_verify("", &hash).unwrap());
+        assert!(!hasher.blocking_verify("not_empty", &hash).unwrap());
     }
 
     #[test]
@@ -112,8 +270,8 @@ mod tests {
         let hasher = PasswordHasher::new();
         let password = "a".repeat(1000);
 
-        let hash = hasher.hash(&password).unwrap();
-        assert!(hasher.verify(&password, &hash).unwrap());
+        let hash = hasher.blocking_hash(&password).unwrap();
+        assert!(hasher.blocking_verify(&password, &hash).unwrap());
     }
 
     #[test]
@@ -121,8 +279,8 @@ mod tests {
         let hasher = PasswordHasher::new();
         let password = "пароль密码🔐";
 
-        let hash = hasher.hash(password).unwrap();
-        assert!(hasher.verify(password, &hash).unwrap());
+        let hash = hasher.blocking_hash(password).unwrap();
+        assert!(hasher.blocking_verify(password, &hash).unwrap());
     }
 
     #[test]
@@ -130,8 +288,8 @@ mod tests {
         let hasher = PasswordHasher::new();
         let password = "P@$$w0rd!#%^&*()[]{}|;':\",./<>?";
 
-        let hash = hasher.hash(password).unwrap();
-        assert!(hasher.verify(password, &hash).unwrap());
+        let hash = hasher.blocking_hash(password).unwrap();
+        assert!(hasher.blocking_verify(password, &hash).unwrap());
     }
 
     #[test]
@@ -139,8 +297,8 @@ mod tests {
         let hasher = PasswordHasher::new();
         let password = "password with spaces and\ttabs\nnewlines";
 
-        let hash = hasher.hash(password).unwrap();
-        assert!(hasher.verify(password, &hash).unwrap());
+        let hash = hasher.blocking_hash(password).unwrap();
+        assert!(hasher.blocking_verify(password, &hash).unwrap());
     }
 
     // ==================== Hash Format Tests ====================
@@ -148,7 +306,7 @@ mod tests {
     #[test]
     fn test_hash_format_is_argon2id() {
         let hasher = PasswordHasher::new();
-        let hash = hasher.hash("test").unwrap();
+        let hash = hasher.blocking_hash("test").unwrap();
 
         assert!(hash.starts_with("$argon2id$"));
     }
@@ -156,7 +314,7 @@ mod tests {
     #[test]
     fn test_hash_contains_version() {
         let hasher = PasswordHasher::new();
-        let hash = hasher.hash("test").unwrap();
+        let hash = hasher.blocking_hash("test").unwrap();
 
         assert!(hash.contains("$v=19$")); // Version 0x13 = 19
     }
@@ -164,7 +322,7 @@ mod tests {
     #[test]
     fn test_hash_contains_params() {
         let hasher = PasswordHasher::new();
-        let hash = hasher.hash("test").unwrap();
+        let hash = hasher.blocking_hash("test").unwrap();
 
         // m=65536 (64*1024), t=3, p=4
         assert!(hash.contains("m=65536"));
@@ -177,7 +335,7 @@ mod tests {
     #[test]
     fn test_verify_invalid_hash_format() {
         let hasher = PasswordHasher::new();
-        let result = hasher.verify("password", "not_a_valid_hash");
+        let result = hasher.blocking_verify("password", "not_a_valid_hash");
 
         assert!(result.is_err());
     }
@@ -185,7 +343,7 @@ mod tests {
     #[test]
     fn test_verify_empty_hash() {
         let hasher = PasswordHasher::new();
-        let result = hasher.verify("password", "");
+        let result = hasher.blocking_verify("password", "");
 
         assert!(result.is_err());
     }
@@ -193,10 +351,10 @@ mod tests {
     #[test]
     fn test_verify_truncated_hash() {
         let hasher = PasswordHasher::new();
-        let hash = hasher.hash("password").unwrap();
+        let hash = hasher.blocking_hash("password").unwrap();
         let truncated = &hash[..hash.len() - 10];
 
-        let result = hasher.verify("password", truncated);
+        let result = hasher.blocking_verify("password", truncated);
         assert!(result.is_err());
     }
 
@@ -205,23 +363,23 @@ mod tests {
     #[test]
     fn test_similar_passwords_different_results() {
         let hasher = PasswordHasher::new();
-        let hash = hasher.hash("password123").unwrap();
+        let hash = hasher.blocking_hash("password123").unwrap();
 
         // Similar but different passwords should not verify
-        assert!(!hasher.verify("password124", &hash).unwrap());
-        assert!(!hasher.verify("password12", &hash).unwrap());
-        assert!(!hasher.verify("Password123", &hash).unwrap());
-        assert!(!hasher.verify(" password123", &hash).unwrap());
+        assert!(!hasher.blocking_verify("password124", &hash).unwrap());
+        assert!(!hasher.blocking_verify("password12", &hash).unwrap());
+        assert!(!hasher.blocking_verify("Password123", &hash).unwrap());
+        assert!(!hasher.blocking_verify(" password123", &hash).unwrap());
     }
 
     #[test]
     fn test_case_sensitive_passwords() {
         let hasher = PasswordHasher::new();
-        let hash = hasher.hash("Password").unwrap();
+        let hash = hasher.blocking_hash("Password").unwrap();
 
-        assert!(hasher.verify("Password", &hash).unwrap());
-        assert!(!hasher.verify("password", &hash).unwrap());
-        assert!(!hasher.verify("PASSWORD", &hash).unwrap());
+        assert!(hasher.blocking_verify("Password", &hash).unwrap());
+        assert!(!hasher.blocking_verify("password", &hash).unwrap());
+        assert!(!hasher.blocking_verify("PASSWORD", &hash).unwrap());
     }
 
     // ==================== Default Implementation ====================
@@ -231,12 +389,163 @@ mod tests {
         let hasher1 = PasswordHasher::new();
         let hasher2 = PasswordHasher::default();
 
-        let hash1 = hasher1.hash("test").unwrap();
-        let hash2 = hasher2.hash("test").unwrap();
+        let hash1 = hasher1.blocking_hash("test").unwrap();
+        let hash2 = hasher2.blocking_hash("test").unwrap();
 
         // Both should be able to verify each other's hashes
-        assert!(hasher1.verify("test", &hash2).unwrap());
-        assert!(hasher2.verify("test", &hash1).unwrap());
+        assert!(hasher1.blocking_verify("test", &hash2).unwrap());
+        assert!(hasher2.blocking_verify("test", &hash1).unwrap());
+    }
+
+    // ==================== Parameter Tests ====================
+
+    fn cheap_config() -> Argon2Config {
+        Argon2Config {
+            memory_kib: 8 * 1024,
+            iterations: 1,
+            parallelism: 1,
+            ..Argon2Config::default()
+        }
+    }
+
+    #[test]
+    fn test_configured_params_in_hash() {
+        let hasher = PasswordHasher::with_config(&cheap_config()).unwrap();
+        let hash = hasher.blocking_hash("configured").unwrap();
+
+        assert!(hash.starts_with("$argon2id$v=19$m=8192,t=1,p=1$"));
+        assert!(hasher.blocking_verify("configured", &hash).unwrap());
+        // verify reads the params from the hash, the default hasher can check it too
+        assert!(PasswordHasher::new().blocking_verify("configured", &hash).unwrap());
+    }
+
+    #[test]
+    fn test_invalid_params_rejected() {
+        let config = Argon2Config {
+            parallelism: 0,
+            ..cheap_config()
+        };
+
+        assert!(PasswordHasher::with_config(&config).is_err());
+    }
+
+    #[test]
+    fn test_needs_rehash_on_different_params() {
+        let cheap = PasswordHasher::with_config(&cheap_config()).unwrap();
+        let stronger = PasswordHasher::with_config(&Argon2Config {
+            iterations: 2,
+            ..cheap_config()
+        })
+        .unwrap();
+        let hash = cheap.blocking_hash("rehash me").unwrap();
+
+        assert!(!cheap.needs_rehash(&hash));
+        assert!(stronger.needs_rehash(&hash));
+        assert!(!stronger.needs_rehash(&stronger.blocking_hash("rehash me").unwrap()));
+    }
+
+    #[test]
+    fn test_needs_rehash_on_other_variant() {
+        let hasher = PasswordHasher::with_config(&cheap_config()).unwrap();
+        let params = Params::new(8 * 1024, 1, 1, None).unwrap();
+        let argon2i = Argon2::new(Algorithm::Argon2i, Version::V0x13, params);
+        let salt = SaltString::generate(&mut OsRng);
+        let hash = argon2i.hash_password(b"old variant", &salt).unwrap().to_string();
+
+        assert!(hasher.blocking_verify("old variant", &hash).unwrap());
+        assert!(hasher.needs_rehash(&hash));
+        assert!(!hasher.needs_rehash("not a phc string"));
+    }
+
+    // ==================== Pepper Tests ====================
+
+    fn peppered(peppers: &str) -> PasswordHasher {
+        PasswordHasher::with_config(&Argon2Config {
+            pepper: Some(crate::config::parse_peppers(peppers).unwrap()),
+            ..cheap_config()
+        })
+        .unwrap()
+    }
+
+    const OLD: &str = "old:0ld-pepper-secret-with-at-least-32-chars";
+    const NEW: &str = "new:n3w-pepper-secret-with-at-least-32-chars";
+
+    #[test]
+    fn test_pepper_id_in_hash() {
+        let hasher = peppered(OLD);
+        let hash = hasher.blocking_hash("peppered password").unwrap();
+
+        // base64 of "old"
+        assert!(hash.contains(",keyid=b2xk$"));
+        assert!(hasher.blocking_verify("peppered password", &hash).unwrap());
+        assert!(!hasher.blocking_verify("other password", &hash).unwrap());
+        assert!(!hasher.needs_rehash(&hash));
+    }
+
+    #[test]
+    fn test_pepper_needed_for_verify() {
+        let hash = peppered(OLD).blocking_hash("peppered password").unwrap();
+
+        // leaked db without the secret
+        let plain = PasswordHasher::with_config(&cheap_config()).unwrap();
+        assert!(plain.blocking_verify("peppered password", &hash).is_err());
+
+        // same id, other secret
+        let wrong = peppered("old:another-secret-that-is-also-32-chars-long");
+        assert!(!wrong.blocking_verify("peppered password", &hash).unwrap());
+    }
+
+    #[test]
+    fn test_pepper_rotation() {
+        let old_hash = peppered(OLD).blocking_hash("rotate me").unwrap();
+        let unpeppered = PasswordHasher::with_config(&cheap_config()).unwrap().blocking_hash("rotate me").unwrap();
+        let rotated = peppered(&format!("{}\n{}", NEW, OLD));
+
+        assert!(rotated.blocking_verify("rotate me", &old_hash).unwrap());
+        assert!(rotated.needs_rehash(&old_hash));
+        assert!(rotated.blocking_verify("rotate me", &unpeppered).unwrap());
+        assert!(rotated.needs_rehash(&unpeppered));
+
+        let new_hash = rotated.blocking_hash("rotate me").unwrap();
+        assert!(!rotated.needs_rehash(&new_hash));
+        assert!(rotated.validate_hash(&old_hash).is_ok());
+        assert!(peppered(NEW).validate_hash(&old_hash).is_err());
+    }
+
+    #[test]
+    fn test_validate_hash_memory_ceiling() {
+        let hasher = PasswordHasher::with_config(&cheap_config()).unwrap();
+        let hash = |m: u32| format!("$argon2id$v=19$m={},t=1,p=1$c2FsdHNhbHQ$aGFzaGhhc2hoYXNoaGFzaA", m);
+
+        assert!(hasher.validate_hash(&hash(1024 * 1024)).is_ok());
+        assert!(hasher.validate_hash(&hash(2 * 1024 * 1024)).is_err());
+    }
+
+    // ==================== Hashing Pool Tests ====================
+
+    #[tokio::test]
+    async fn test_async_hash_and_verify_use_pool() {
+        let hasher = PasswordHasher::with_config(&cheap_config()).unwrap();
+        let hash = hasher.hash("pooled").await.unwrap();
+
+        assert!(hasher.verify("pooled", &hash).await.unwrap());
+        assert!(!hasher.clone().verify("other", &hash).await.unwrap());
+
+        // clones share the pool
+        let metrics = hasher.metrics();
+        assert_eq!(metrics.hash.count, 1);
+        assert_eq!(metrics.verify.count, 2);
+        assert_eq!(metrics.limit, cheap_config().max_concurrent);
+    }
+
+    #[test]
+    fn test_zero_concurrency_rejected() {
+        let config = Argon2Config {
+            max_concurrent: 0,
+            ..cheap_config()
+        };
+
+        assert!(PasswordHasher::with_config(&config).is_err());
     }
 
     // ==================== Performance Sanity Check ====================
@@ -246,7 +555,7 @@ mod tests {
         let hasher = PasswordHasher::new();
         let start = std::time::Instant::now();
 
-        let _ = hasher.hash("performance_test").unwrap();
+        let _ = hasher.blocking_hash("performance_test").unwrap();
 
         let duration = start.elapsed();
         // Should complete within 5 seconds (generous for CI)
@@ -0,0 +1,69 @@
+// no switching back and forth between two passwords, every local password setting path goes through here
+use std::sync::Arc;
+
+use crate::error::AppError;
+use crate::models::User;
+use crate::repository::PasswordHistoryRepository;
+use super::password::PasswordHasher;
+
+pub struct PasswordHistory {
+    repository: Arc<dyn PasswordHistoryRepository>,
+    // current password included
+    size: usize,
+    password_hasher: PasswordHasher,
+}
+
+impl PasswordHistory {
+    pub fn new(repository: Arc<dyn PasswordHistoryRepository>, size: usize) -> Self {
+        Self {
+            repository,
+            size,
+            password_hasher: PasswordHasher::new(),
+        }
+    }
+
+    // needs the configured peppers to verify the old hashes
+    pub fn with_password_hasher(mut self, hasher: PasswordHasher) -> Self {
+        self.password_hasher = hasher;
+        self
+    }
+
+    // the current hash is checked too, accounts from before the history table have no entries yet
+    pub async fn check(&self, user: &User, password: &str) -> Result<(), AppError> {
+        if self.size == 0 {
+            return Ok(());
+        }
+
+        let mut hashes = self.repository.recent(&user.id, self.size).await?;
+        if let Some(current) = &user.password_hash
+            && !hashes.contains(current)
+        {
+            hashes.push(current.clone());
+        }
+
+        for hash in &hashes {
+            if self.password_hasher.verify(password, hash).await? {
+                tracing::info!(user_id = %user.id, "Password from history rejected");
+                return Err(AppError::ValidationError(format!(
+                    "Password policy: must not be one of your last {} passwords",
+                    self.size
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    // call after the new hash is stored at the user, older entries are pruned right away
+    pub async fn record(&self, user_id: &str, password_hash: &str) -> Result<(), AppError> {
+        if self.size == 0 {
+            return Ok(());
+        }
+
+        self.repository.add(user_id, password_hash).await?;
+        let pruned = self.repository.prune(user_id, self.size).await?;
+        tracing::debug!(user_id = %user_id, pruned = pruned, "Password history updated");
+
+        Ok(())
+    }
+}
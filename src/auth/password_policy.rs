@@ -0,0 +1,347 @@
+// rules for every password we set: register, change, reset, initial admin, hash_password bin
+// directory passwords (AD / LDAP) are checked by the directory itself
+use sha1::{Digest, Sha1};
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use crate::config::PasswordPolicyConfig;
+use crate::error::AppError;
+
+const COMMON_PASSWORDS: &str = include_str!("common_passwords.txt");
+
+// AD rule: name tokens with less than 3 chars are ignored
+const MIN_NAME_TOKEN_LENGTH: usize = 3;
+
+pub struct PasswordPolicy {
+    config: PasswordPolicyConfig,
+    common: HashSet<String>,
+    breached: Option<BreachedPasswords>,
+}
+
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        Self::new(PasswordPolicyConfig::default()).expect("default policy has no breach file")
+    }
+}
+
+impl PasswordPolicy {
+    // a configured breach file has to exist, better than silently not checking
+    pub fn new(config: PasswordPolicyConfig) -> Result<Self, String> {
+        let common = if config.block_common {
+            COMMON_PASSWORDS
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .map(str::to_lowercase)
+                .collect()
+        } else {
+            HashSet::new()
+        };
+
+        let breached = config
+            .breached_passwords
+            .as_deref()
+            .map(BreachedPasswords::open)
+            .transpose()?;
+
+        Ok(Self {
+            config,
+            common,
+            breached,
+        })
+    }
+
+    // all violations at once, so the user does not have to try several times
+    pub fn check(&self, password: &str, name: &str, email: &str) -> Result<(), AppError> {
+        let mut violations = Vec::new();
+        let length = password.chars().count();
+        let lower = password.to_lowercase();
+
+        if length < self.config.min_length {
+            violations.push(format!("at least {} characters", self.config.min_length));
+        }
+        if length > self.config.max_length {
+            violations.push(format!("at most {} characters", self.config.max_length));
+        }
+        if character_classes(password) < self.config.min_character_classes {
+            violations.push(format!(
+                "at least {} of lowercase, uppercase, digits and symbols",
+                self.config.min_character_classes
+            ));
+        }
+        if personal_tokens(name, email).any(|token| lower.contains(&token)) {
+            violations.push("must not contain your name or email".to_string());
+        }
+        if self.common.contains(&lower) {
+            violations.push("too common".to_string());
+        }
+
+        // only worth the file lookup if nothing else is wrong
+        if violations.is_empty()
+            && let Some(breached) = &self.breached
+        {
+            let found = breached
+                .contains(password)
+                .map_err(|e| AppError::InternalError(format!("Breached password check failed: {}", e)))?;
+            if found {
+                violations.push("found in a known data breach".to_string());
+            }
+        }
+
+        if violations.is_empty() {
+            return Ok(());
+        }
+        Err(AppError::ValidationError(format!(
+            "Password policy: {}",
+            violations.join(", ")
+        )))
+    }
+}
+
+fn character_classes(password: &str) -> usize {
+    let classes = [
+        password.chars().any(char::is_lowercase),
+        password.chars().any(char::is_uppercase),
+        password.chars().any(|c| c.is_ascii_digit()),
+        password.chars().any(|c| !c.is_alphanumeric()),
+    ];
+    classes.iter().filter(|present| **present).count()
+}
+
+// "Max Mustermann", "max.mustermann@example.com" -> max, mustermann
+fn personal_tokens<'a>(name: &'a str, email: &'a str) -> impl Iterator<Item = String> + 'a {
+    let local_part = email.split('@').next().unwrap_or_default();
+
+    name.split(|c: char| !c.is_alphanumeric())
+        .chain(local_part.split(|c: char| !c.is_alphanumeric()))
+        .filter(|token| token.chars().count() >= MIN_NAME_TOKEN_LENGTH)
+        .map(str::to_lowercase)
+}
+
+// offline copy of the Have I Been Pwned password list, nothing is sent anywhere
+enum BreachedPasswords {
+    // PwnedPasswordsDownloader single file: full hash, sorted, binary search with seeks
+    SortedFile(PathBuf),
+    // range files like the api returns them, one per 5 char prefix
+    RangeDirectory(PathBuf),
+}
+
+impl BreachedPasswords {
+    fn open(path: &str) -> Result<Self, String> {
+        let path = Path::new(path);
+        if path.is_dir() {
+            Ok(BreachedPasswords::RangeDirectory(path.to_path_buf()))
+        } else if path.is_file() {
+            Ok(BreachedPasswords::SortedFile(path.to_path_buf()))
+        } else {
+            Err(format!("Breached password file {} not found", path.display()))
+        }
+    }
+
+    fn contains(&self, password: &str) -> std::io::Result<bool> {
+        let hash = hex::encode_upper(Sha1::digest(password.as_bytes()));
+
+        match self {
+            BreachedPasswords::SortedFile(path) => search_sorted_file(path, &hash),
+            BreachedPasswords::RangeDirectory(dir) => {
+                let (prefix, suffix) = hash.split_at(5);
+                let path = dir.join(format!("{}.txt", prefix));
+                // partial download, treat missing ranges as not breached
+                if !path.exists() {
+                    tracing::debug!(prefix = %prefix, "no HIBP range file");
+                    return Ok(false);
+                }
+
+                for line in BufReader::new(File::open(path)?).lines() {
+                    if let Some(count) = match_line(&line?, suffix) {
+                        return Ok(count > 0);
+                    }
+                }
+                Ok(false)
+            }
+        }
+    }
+}
+
+// "HASH:COUNT" -> count if the hash part is the one we look for, padding entries have count 0
+fn match_line(line: &str, hash: &str) -> Option<u64> {
+    let (line_hash, count) = line.trim_end().split_once(':')?;
+    if !line_hash.eq_ignore_ascii_case(hash) {
+        return None;
+    }
+    Some(count.trim().parse().unwrap_or(1))
+}
+
+// the hash we look for starts a line somewhere in [lo, hi)
+fn search_sorted_file(path: &Path, hash: &str) -> std::io::Result<bool> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let (mut lo, mut hi) = (0u64, reader.get_ref().metadata()?.len());
+    let mut line = String::new();
+
+    while lo < hi {
+        let mid = lo + (hi - lo) / 2;
+
+        // first line starting at or after mid
+        let start = if mid == 0 {
+            reader.seek(SeekFrom::Start(0))?;
+            0
+        } else {
+            reader.seek(SeekFrom::Start(mid - 1))?;
+            let mut skipped = Vec::new();
+            mid - 1 + reader.read_until(b'\n', &mut skipped)? as u64
+        };
+
+        line.clear();
+        let read = reader.read_line(&mut line)? as u64;
+        if read == 0 || start >= hi {
+            hi = mid;
+            continue;
+        }
+
+        let line_hash = line.split(':').next().unwrap_or_default().trim();
+        match line_hash.to_ascii_uppercase().as_str().cmp(hash) {
+            Ordering::Equal => return Ok(match_line(&line, hash).is_some_and(|count| count > 0)),
+            Ordering::Less => lo = start + read,
+            Ordering::Greater => hi = mid,
+        }
+    }
+
+    Ok(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn sha1_upper(password: &str) -> String {
+        hex::encode_upper(Sha1::digest(password.as_bytes()))
+    }
+
+    fn policy(config: PasswordPolicyConfig) -> PasswordPolicy {
+        PasswordPolicy::new(config).unwrap()
+    }
+
+    fn violation(policy: &PasswordPolicy, password: &str) -> String {
+        policy
+            .check(password, "Max Mustermann", "max.mustermann@example.com")
+            .unwrap_err()
+            .to_string()
+    }
+
+    #[test]
+    fn test_default_policy_accepts_long_password() {
+        let policy = PasswordPolicy::default();
+
+        assert!(policy.check("correct horse battery", "Max Mustermann", "max@example.com").is_ok());
+    }
+
+    #[test]
+    fn test_length_counts_characters() {
+        let policy = PasswordPolicy::default();
+
+        assert!(violation(&policy, "short").contains("at least 12 characters"));
+        assert!(violation(&policy, &"a".repeat(129)).contains("at most 128 characters"));
+        // 12 umlauts are 24 bytes but only 12 characters
+        assert!(policy.check("äöüäöüäöüäöü", "Max", "max@example.com").is_ok());
+    }
+
+    #[test]
+    fn test_character_classes() {
+        let policy = policy(PasswordPolicyConfig {
+            min_character_classes: 3,
+            ..Default::default()
+        });
+
+        assert!(violation(&policy, "onlylowercaseletters").contains("lowercase, uppercase"));
+        assert!(policy.check("Lower-and-Upper", "Max", "max@example.com").is_ok());
+        assert_eq!(character_classes("aA1!"), 4);
+        assert_eq!(character_classes("ÄÖÜäöü"), 2);
+    }
+
+    #[test]
+    fn test_name_and_email_blocked() {
+        let policy = PasswordPolicy::default();
+
+        assert!(violation(&policy, "ilovemustermann!").contains("your name or email"));
+        assert!(violation(&policy, "MAX.secret.2026").contains("your name or email"));
+        // tokens shorter than 3 chars are ignored
+        assert!(policy.check("a long passphrase", "Al Li", "al@example.com").is_ok());
+    }
+
+    #[test]
+    fn test_common_passwords_blocked() {
+        let policy = PasswordPolicy::default();
+
+        assert!(violation(&policy, "QwertyUiopAsdfgh").contains("too common"));
+        assert!(violation(&policy, "passwordpassword").contains("too common"));
+
+        let off = policy_without_common();
+        assert!(off.check("passwordpassword", "Max", "max@example.com").is_ok());
+    }
+
+    fn policy_without_common() -> PasswordPolicy {
+        policy(PasswordPolicyConfig {
+            block_common: false,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_breached_sorted_file() {
+        let mut hashes: Vec<String> = ["first breached one", "second breached one", "padding entry here"]
+            .iter()
+            .map(|p| sha1_upper(p))
+            .chain((0..200).map(|i| sha1_upper(&format!("filler-{}", i))))
+            .collect();
+        hashes.sort();
+
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        for hash in &hashes {
+            let count = if *hash == sha1_upper("padding entry here") { 0 } else { 3 };
+            writeln!(file, "{}:{}", hash, count).unwrap();
+        }
+
+        let breached = BreachedPasswords::open(file.path().to_str().unwrap()).unwrap();
+        assert!(breached.contains("first breached one").unwrap());
+        assert!(breached.contains("second breached one").unwrap());
+        assert!(breached.contains("filler-0").unwrap());
+        assert!(breached.contains("filler-199").unwrap());
+        assert!(!breached.contains("padding entry here").unwrap());
+        assert!(!breached.contains("not in the file at all").unwrap());
+    }
+
+    #[test]
+    fn test_breached_range_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        let hash = sha1_upper("hunter2hunter2");
+        let (prefix, suffix) = hash.split_at(5);
+        std::fs::write(
+            dir.path().join(format!("{}.txt", prefix)),
+            format!("0000000000000000000000000000000000A:1\r\n{}:52\r\n", suffix.to_lowercase()),
+        )
+        .unwrap();
+
+        let policy = policy(PasswordPolicyConfig {
+            breached_passwords: Some(dir.path().to_str().unwrap().to_string()),
+            ..Default::default()
+        });
+
+        assert!(violation(&policy, "hunter2hunter2").contains("data breach"));
+        // other prefix, no range file downloaded
+        assert!(policy.check("something else entirely", "Max", "max@example.com").is_ok());
+    }
+
+    #[test]
+    fn test_missing_breach_file_rejected() {
+        let result = PasswordPolicy::new(PasswordPolicyConfig {
+            breached_passwords: Some("/nonexistent/pwned.txt".to_string()),
+            ..Default::default()
+        });
+
+        assert!(result.is_err());
+    }
+}
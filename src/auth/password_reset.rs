@@ -0,0 +1,171 @@
+// forgot password for local accounts: one time link by mail, the new password is set without the old one
+use std::sync::Arc;
+
+use crate::config::PasswordResetConfig;
+use crate::error::AppError;
+use crate::mail::{Mail, Mailer};
+use crate::models::{PasswordResetToken, User};
+use crate::repository::{PasswordResetRepository, RefreshTokenRepository, UserRepository};
+use super::password::PasswordHasher;
+use super::password_policy::PasswordPolicy;
+use super::password_history::PasswordHistory;
+use super::tokens::{generate_opaque_token, hash_opaque_token};
+
+pub struct PasswordResetService {
+    config: PasswordResetConfig,
+    users: Arc<dyn UserRepository>,
+    tokens: Arc<dyn PasswordResetRepository>,
+    refresh_tokens: Arc<dyn RefreshTokenRepository>,
+    mailer: Arc<dyn Mailer>,
+    password_hasher: PasswordHasher,
+    password_policy: Arc<PasswordPolicy>,
+    password_history: Option<Arc<PasswordHistory>>,
+}
+
+impl PasswordResetService {
+    pub fn new(
+        config: PasswordResetConfig,
+        users: Arc<dyn UserRepository>,
+        tokens: Arc<dyn PasswordResetRepository>,
+        refresh_tokens: Arc<dyn RefreshTokenRepository>,
+        mailer: Arc<dyn Mailer>,
+    ) -> Self {
+        Self {
+            config,
+            users,
+            tokens,
+            refresh_tokens,
+            mailer,
+            password_hasher: PasswordHasher::new(),
+            password_policy: Arc::new(PasswordPolicy::default()),
+            password_history: None,
+        }
+    }
+
+    pub fn with_password_policy(mut self, policy: Arc<PasswordPolicy>) -> Self {
+        self.password_policy = policy;
+        self
+    }
+
+    pub fn with_password_history(mut self, history: Arc<PasswordHistory>) -> Self {
+        self.password_history = Some(history);
+        self
+    }
+
+    pub fn with_password_hasher(mut self, hasher: PasswordHasher) -> Self {
+        self.password_hasher = hasher;
+        self
+    }
+
+    pub fn config(&self) -> &PasswordResetConfig {
+        &self.config
+    }
+
+    // Ok for unknown and non-local accounts too, the caller must not be able to tell the difference
+    pub async fn request_reset(&self, email: &str) -> Result<(), AppError> {
+        if let Err(e) = self.tokens.delete_expired().await {
+            tracing::warn!(error = %e, "cleanup of expired reset tokens failed");
+        }
+
+        let user = self
+            .users
+            .find_by_email(email)
+            .await?
+            .filter(|user| user.password_hash.is_some() && user.is_active);
+        let Some(user) = user else {
+            tracing::info!(email = %email, "Password reset for unknown, inactive or non-local account, no mail sent");
+            return Ok(());
+        };
+
+        // repeated requests would flood the inbox, the link from the last mail still works
+        if let Some(latest) = self.tokens.find_latest_for_user(&user.id).await?
+            && latest.created_at > chrono::Utc::now() - chrono::Duration::seconds(self.config.resend_interval_secs)
+        {
+            tracing::info!(user_id = %user.id, "Password reset requested again too soon, no mail sent");
+            return Ok(());
+        }
+
+        // only the newest link works
+        self.tokens.invalidate_for_user(&user.id).await?;
+
+        let token = generate_opaque_token();
+        self.tokens
+            .create(&PasswordResetToken::new(
+                hash_opaque_token(&token),
+                user.id.clone(),
+                self.config.expiration_secs,
+            ))
+            .await?;
+
+        self.mailer.send(&self.reset_mail(&user, &token)).await?;
+
+        tracing::info!(user_id = %user.id, "Password reset link sent");
+        Ok(())
+    }
+
+    // consumes the token, sets the password and revokes every refresh token of the user
+    pub async fn reset_password(&self, token: &str, new_password: &str) -> Result<User, AppError> {
+        let invalid = || AppError::ValidationError("Invalid or expired reset token".to_string());
+        let token_hash = hash_opaque_token(token);
+
+        let reset = self
+            .tokens
+            .find_by_hash(&token_hash)
+            .await?
+            .filter(|t| t.is_valid())
+            .ok_or_else(invalid)?;
+
+        // deactivated or no longer local in the meantime
+        let mut user = self
+            .users
+            .find_by_id(&reset.user_id)
+            .await?
+            .filter(|user| user.password_hash.is_some() && user.is_active)
+            .ok_or_else(invalid)?;
+
+        // token stays valid when the password is rejected, the user just picks another one
+        self.password_policy.check(new_password, &user.name, &user.email)?;
+        if let Some(history) = &self.password_history {
+            history.check(&user, new_password).await?;
+        }
+        let password_hash = self.password_hasher.hash(new_password).await?;
+
+        if !self.tokens.mark_used(&token_hash).await? {
+            tracing::warn!(user_id = %user.id, "Password reset token used twice");
+            return Err(invalid());
+        }
+
+        let now = chrono::Utc::now();
+        user.password_hash = Some(password_hash.clone());
+        user.updated_at = now;
+        user.password_changed_at = Some(now);
+        self.users.update(&user).await?;
+
+        if let Some(history) = &self.password_history {
+            history.record(&user.id, &password_hash).await?;
+        }
+
+        self.tokens.invalidate_for_user(&user.id).await?;
+        let revoked = self.refresh_tokens.revoke_all_for_user(&user.id).await?;
+
+        tracing::info!(user_id = %user.id, revoked_tokens = revoked, "Password reset");
+        Ok(user)
+    }
+
+    fn reset_mail(&self, user: &User, token: &str) -> Mail {
+        let link = format!("{}?token={}", self.config.reset_uri, token);
+        let minutes = self.config.expiration_secs / 60;
+
+        Mail {
+            to: user.email.clone(),
+            subject: "Reset your password".to_string(),
+            body: format!(
+                "Hello {},\n\n\
+                 someone requested a password reset for your account. Open the link below to choose a new password:\n\n\
+                 {}\n\n\
+                 The link works once and expires in {} minutes. If you did not request this, just ignore this mail.\n",
+                user.name, link, minutes
+            ),
+        }
+    }
+}
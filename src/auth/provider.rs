@@ -5,10 +5,14 @@ use crate::error::AppError;
 use crate::models::{User, UserRole};
 use crate::repository::UserRepository;
 use super::password::PasswordHasher;
+use super::password_policy::PasswordPolicy;
+use super::password_history::PasswordHistory;
 
 #[derive(Debug, Clone)]
 pub struct AuthResult {
     pub user: User,
+    // password checked against the cached credential, directory was down
+    pub offline: bool,
 }
 
 #[derive(Debug)]
@@ -27,6 +31,8 @@ pub trait AuthProvider: Send + Sync {
 pub struct LocalAuthProvider {
     repository: Arc<dyn UserRepository>,
     password_hasher: PasswordHasher,
+    password_policy: Arc<PasswordPolicy>,
+    password_history: Option<Arc<PasswordHistory>>,
 }
 
 impl LocalAuthProvider {
@@ -34,9 +40,28 @@ impl LocalAuthProvider {
         Self {
             repository,
             password_hasher: PasswordHasher::new(),
+            password_policy: Arc::new(PasswordPolicy::default()),
+            password_history: None,
         }
     }
 
+    // shared with the reset service, the breach file is only opened once
+    pub fn with_password_policy(mut self, policy: Arc<PasswordPolicy>) -> Self {
+        self.password_policy = policy;
+        self
+    }
+
+    pub fn with_password_history(mut self, history: Arc<PasswordHistory>) -> Self {
+        self.password_history = Some(history);
+        self
+    }
+
+    // configured Argon2 cost instead of the defaults
+    pub fn with_password_hasher(mut self, hasher: PasswordHasher) -> Self {
+        self.password_hasher = hasher;
+        self
+    }
+
     pub async fn register(
         &self,
         name: &str,
@@ -44,17 +69,22 @@ impl LocalAuthProvider {
         password: &str,
         role: UserRole,
     ) -> Result<User, AppError> {
-        let password_hash = self.password_hasher.hash(password)?;
+        self.password_policy.check(password, name, email)?;
+        let password_hash = self.password_hasher.hash(password).await?;
 
         let user = User::new_local(
             name.to_string(),
             email.to_lowercase(),
-            password_hash,
+            password_hash.clone(),
             role,
         );
 
         self.repository.create(&user).await?;
 
+        if let Some(history) = &self.password_history {
+            history.record(&user.id, &password_hash).await?;
+        }
+
         tracing::info!(
             user_id = %user.id,
             email = %email,
@@ -65,9 +95,109 @@ impl LocalAuthProvider {
         Ok(user)
     }
 
+    // account from another system with its existing hash (argon2 or a legacy format),
+    // no policy check because we dont know the password, the first login upgrades it to argon2id
+    pub async fn import(
+        &self,
+        name: &str,
+        email: &str,
+        password_hash: &str,
+        role: UserRole,
+    ) -> Result<User, AppError> {
+        self.password_hasher.validate_hash(password_hash)?;
+
+        let user = User::new_local(
+            name.to_string(),
+            email.to_lowercase(),
+            password_hash.to_string(),
+            role,
+        );
+
+        self.repository.create(&user).await?;
+
+        if let Some(history) = &self.password_history {
+            history.record(&user.id, password_hash).await?;
+        }
+
+        tracing::info!(user_id = %user.id, email = %email, role = %role, "User imported with existing password hash");
+
+        Ok(user)
+    }
+
+    // own password of a local account, the caller revokes the sessions
+    pub async fn change_password(
+        &self,
+        user_id: &str,
+        current_password: &str,
+        new_password: &str,
+    ) -> Result<User, AppError> {
+        let mut user = self
+            .repository
+            .find_by_id(user_id)
+            .await?
+            .ok_or_else(|| AppError::Unauthorized("User not found".to_string()))?;
+
+        let password_hash = user.password_hash.as_ref().ok_or_else(|| {
+            tracing::warn!(user_id = %user.id, provider = %user.auth_provider, "Password change for non-local user");
+            AppError::ValidationError("Password is managed by the identity provider".to_string())
+        })?;
+
+        if !self.password_hasher.verify(current_password, password_hash).await? {
+            tracing::warn!(user_id = %user.id, "Password change with wrong current password");
+            return Err(AppError::Unauthorized("Current password is wrong".to_string()));
+        }
+
+        if new_password == current_password {
+            return Err(AppError::ValidationError(
+                "New password must differ from the current one".to_string(),
+            ));
+        }
+
+        self.password_policy.check(new_password, &user.name, &user.email)?;
+        if let Some(history) = &self.password_history {
+            history.check(&user, new_password).await?;
+        }
+
+        let password_hash = self.password_hasher.hash(new_password).await?;
+        let now = chrono::Utc::now();
+        user.password_hash = Some(password_hash.clone());
+        user.updated_at = now;
+        user.password_changed_at = Some(now);
+        self.repository.update(&user).await?;
+
+        if let Some(history) = &self.password_history {
+            history.record(&user.id, &password_hash).await?;
+        }
+
+        tracing::info!(user_id = %user.id, "Password changed");
+
+        Ok(user)
+    }
+
     pub fn password_hasher(&self) -> &PasswordHasher {
         &self.password_hasher
     }
+
+    // only place where we still have the plain password, a failed upgrade must not fail the login
+    async fn upgrade_hash(&self, mut user: User, password: &str) -> User {
+        let password_hash = match self.password_hasher.hash(password).await {
+            Ok(hash) => hash,
+            Err(e) => {
+                tracing::warn!(user_id = %user.id, error = %e, "Rehash with current parameters failed");
+                return user;
+            }
+        };
+
+        let previous = user.password_hash.replace(password_hash);
+        if let Err(e) = self.repository.update(&user).await {
+            tracing::warn!(user_id = %user.id, error = %e, "Storing rehashed password failed");
+            user.password_hash = previous;
+            return user;
+        }
+
+        tracing::info!(user_id = %user.id, "Password rehashed with current Argon2 parameters");
+        user
+    }
 }
 
 #[async_trait]
@@ -77,33 +207,38 @@ impl AuthProvider for LocalAuthProvider {
     }
 
     async fn authenticate(&self, email: &str, password: &str) -> Result<AuthResult, AppError> {
-        let user = self
-            .repository
-            .find_by_email(email)
-            .await?
-            .ok_or_else(|| {
-                tracing::warn!(email = %email, "Login attempt for non-existent user");
-                AppError::Unauthorized("Invalid credentials".to_string())
-            })?;
+        // every failure path pays for one argon2 verify, otherwise the timing leaks which emails exist
+        let Some(user) = self.repository.find_by_email(email).await? else {
+            self.password_hasher.verify_dummy(password).await?;
+            tracing::warn!(email = %email, "Login attempt for non-existent user");
+            return Err(AppError::Unauthorized("Invalid credentials".to_string()));
+        };
 
-        let password_hash = user.password_hash.as_ref().ok_or_else(|| {
+        let Some(password_hash) = user.password_hash.as_ref() else {
+            self.password_hasher.verify_dummy(password).await?;
             tracing::warn!(
                 user_id = %user.id,
                 "Login attempt with password for non-local user"
             );
-            AppError::Unauthorized("Invalid credentials".to_string())
-        })?;
+            return Err(AppError::Unauthorized("Invalid credentials".to_string()));
+        };
 
-        let valid = self.password_hasher.verify(password, password_hash)?;
+        let valid = self.password_hasher.verify(password, password_hash).await?;
 
         if !valid {
             tracing::warn!(user_id = %user.id, "Failed login attempt - invalid password");
             return Err(AppError::Unauthorized("Invalid credentials".to_string()));
         }
 
+        let user = if self.password_hasher.needs_rehash(password_hash) {
+            self.upgrade_hash(user, password).await
+        } else {
+            user
+        };
+
         tracing::info!(user_id = %user.id, "User authenticated successfully");
 
-        Ok(AuthResult { user })
+        Ok(AuthResult { user, offline: false })
     }
 }
 
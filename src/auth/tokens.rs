@@ -0,0 +1,35 @@
+// opaque tokens (device codes, refresh tokens)
+// high entropy so a plain sha256 is enough for storage, argon2 would only cost time
+use sha2::{Digest, Sha256};
+
+pub fn generate_opaque_token() -> String {
+    let bytes: [u8; 32] = rand::random();
+    hex::encode(bytes)
+}
+
+pub fn hash_opaque_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generated_tokens_are_unique() {
+        let a = generate_opaque_token();
+        let b = generate_opaque_token();
+
+        assert_eq!(a.len(), 64);
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn test_hash_is_deterministic() {
+        let token = generate_opaque_token();
+
+        assert_eq!(hash_opaque_token(&token), hash_opaque_token(&token));
+        assert_ne!(hash_opaque_token(&token), token);
+        assert_ne!(hash_opaque_token("a"), hash_opaque_token("b"));
+    }
+}
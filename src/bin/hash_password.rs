@@ -1,11 +1,11 @@
 // Password hash for initial admin
-use argon2::{
-    password_hash::{rand_core::OsRng, PasswordHasher, SaltString},
-    Argon2, Algorithm, Params, Version,
-};
 use std::io::{self, Write};
+use syt_ek962_security_concepts::auth::{PasswordHasher, PasswordPolicy};
+use syt_ek962_security_concepts::config::{Argon2Config, PasswordPolicyConfig};
 
 fn main() {
+    dotenvy::dotenv().ok();
+
     print!("Enter password to hash: ");
     io::stdout().flush().unwrap();
 
@@ -13,25 +13,23 @@ fn main() {
     io::stdin().read_line(&mut password).unwrap();
     let password = password.trim();
 
-    if password.len() < 12 {
-        eprintln!("Error: Password must be at least 12 characters");
+    // same rules as the server, PASSWORD_* from the environment
+    let policy = match PasswordPolicy::new(PasswordPolicyConfig::from_env()) {
+        Ok(policy) => policy,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    };
+    if let Err(e) = policy.check(password, "", "") {
+        eprintln!("Error: {}", e);
         std::process::exit(1);
     }
 
-    let params = Params::new(
-        64 * 1024,  
-        3,        
-        4,        
-        None,
-    )
-    .expect("Invalid Argon2 parameters");
-
-    let argon2 = Argon2::new(Algorithm::Argon2id, Version::V0x13, params);
-    let salt = SaltString::generate(&mut OsRng);
+    // ARGON2_* like the server, otherwise the hash gets rehashed on the first login
+    let hasher = PasswordHasher::with_config(&Argon2Config::from_env()).expect("Invalid Argon2 parameters");
 
-    let hash = argon2
-        .hash_password(password.as_bytes(), &salt)
-        .expect("Failed to hash password");
+    let hash = hasher.blocking_hash(password).expect("Failed to hash password");
 
     println!("\nPassword hash (use this in your config):\n");
     println!("{}", hash);
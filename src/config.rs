@@ -1,5 +1,8 @@
 use serde::Deserialize;
 use std::env;
+use std::fmt;
+
+use crate::models::{AuthProviderType, UserRole};
 
 #[derive(Debug, Clone)]
 pub struct Config {
@@ -10,6 +13,301 @@ pub struct Config {
     pub initial_admin_config: String,
     pub google_oauth: Option<GoogleOAuthConfig>,
     pub ldap: Option<LdapConfig>,
+    pub device_flow: DeviceFlowConfig,
+    // private-use schemes (RFC 8252) native apps may register redirect uris for
+    pub redirect_uri_schemes: Vec<String>,
+    pub password_reset: Option<PasswordResetConfig>,
+    pub password_policy: PasswordPolicyConfig,
+    pub argon2: Argon2Config,
+    pub lockout: LockoutConfig,
+}
+
+// Argon2id cost, changing it rehashes every local password on the next login
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Argon2Config {
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+    pub pepper: Option<PepperConfig>,
+    // hashes running at once, peak memory is max_concurrent * memory_kib
+    pub max_concurrent: usize,
+    // longer in the queue -> 503
+    pub queue_timeout_ms: u64,
+}
+
+impl Default for Argon2Config {
+    // OWASP: 64 MiB, 3 iterations, 4 lanes
+    fn default() -> Self {
+        Self {
+            memory_kib: 64 * 1024,
+            iterations: 3,
+            parallelism: 4,
+            pepper: None,
+            max_concurrent: 4,
+            queue_timeout_ms: 2000,
+        }
+    }
+}
+
+// server side secret, HMAC-SHA256 over the password before argon2, never in the db
+// the id ends up in the hash (keyid), so old peppers can stay for verify while new hashes use the first one
+#[derive(Clone, PartialEq, Eq)]
+pub struct PepperConfig {
+    // (id, secret), first one is current
+    pub keys: Vec<(String, String)>,
+}
+
+impl fmt::Debug for PepperConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ids: Vec<&str> = self.keys.iter().map(|(id, _)| id.as_str()).collect();
+        f.debug_struct("PepperConfig").field("ids", &ids).finish_non_exhaustive()
+    }
+}
+
+impl PepperConfig {
+    pub fn current_id(&self) -> &str {
+        &self.keys[0].0
+    }
+
+    // PASSWORD_PEPPER_FILE (one id:secret per line, docker / k8s secret) wins over PASSWORD_PEPPERS (comma separated)
+    fn from_env() -> Option<Self> {
+        let entries = if let Ok(path) = env::var("PASSWORD_PEPPER_FILE") {
+            std::fs::read_to_string(&path)
+                .unwrap_or_else(|e| panic!("Cannot read PASSWORD_PEPPER_FILE {}: {}", path, e))
+        } else {
+            env::var("PASSWORD_PEPPERS").ok()?.replace(',', "\n")
+        };
+
+        Some(parse_peppers(&entries).unwrap_or_else(|e| panic!("Invalid pepper config: {}", e)))
+    }
+}
+
+// "2:newsecret\n1:oldsecret", blank lines and # comments are skipped
+pub fn parse_peppers(value: &str) -> Result<PepperConfig, String> {
+    let mut keys: Vec<(String, String)> = Vec::new();
+
+    for line in value.lines().map(str::trim).filter(|l| !l.is_empty() && !l.starts_with('#')) {
+        let (id, secret) = line
+            .split_once(':')
+            .ok_or_else(|| "entries must be id:secret".to_string())?;
+        let id = id.trim();
+        // argon2 keyid is at most 8 bytes
+        if id.is_empty() || id.len() > 8 || !id.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Err(format!("pepper id '{}' must be 1-8 letters or digits", id));
+        }
+        if secret.len() < 32 {
+            return Err(format!("pepper '{}' must be at least 32 chars", id));
+        }
+        if keys.iter().any(|(existing, _)| existing == id) {
+            return Err(format!("pepper id '{}' used twice", id));
+        }
+        keys.push((id.to_string(), secret.to_string()));
+    }
+
+    if keys.is_empty() {
+        return Err("no pepper given".to_string());
+    }
+    Ok(PepperConfig { keys })
+}
+
+impl Argon2Config {
+    // validated by PasswordHasher::with_config, argon2 knows the limits best
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        fn number<T: std::str::FromStr>(name: &str, default: T) -> T {
+            env::var(name)
+                .ok()
+                .map(|v| v.parse().unwrap_or_else(|_| panic!("{} must be a valid number", name)))
+                .unwrap_or(default)
+        }
+
+        Self {
+            memory_kib: number("ARGON2_MEMORY_KIB", defaults.memory_kib),
+            iterations: number("ARGON2_ITERATIONS", defaults.iterations),
+            parallelism: number("ARGON2_PARALLELISM", defaults.parallelism),
+            pepper: PepperConfig::from_env(),
+            max_concurrent: number("ARGON2_MAX_CONCURRENT", defaults.max_concurrent),
+            queue_timeout_ms: number("ARGON2_QUEUE_TIMEOUT_MS", defaults.queue_timeout_ms),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PasswordPolicyConfig {
+    // counted in characters, not bytes
+    pub min_length: usize,
+    // argon2 cost does not depend on the length, but nobody needs more
+    pub max_length: usize,
+    // of lowercase, uppercase, digits, symbols; 0 = off (NIST / OWASP advise against composition rules)
+    pub min_character_classes: usize,
+    pub block_common: bool,
+    // HIBP sha1 file sorted by hash (HASH:COUNT) or directory with range files (PREFIX.txt, SUFFIX:COUNT)
+    pub breached_passwords: Option<String>,
+    // last N passwords (current one included) that can not be set again; 0 = off
+    pub history_size: usize,
+}
+
+impl Default for PasswordPolicyConfig {
+    fn default() -> Self {
+        Self {
+            min_length: 12,
+            max_length: 128,
+            min_character_classes: 0,
+            block_common: true,
+            breached_passwords: None,
+            history_size: 5,
+        }
+    }
+}
+
+impl PasswordPolicyConfig {
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        let number = |name: &str, default: usize| -> usize {
+            env::var(name)
+                .ok()
+                .map(|v| v.parse().unwrap_or_else(|_| panic!("{} must be a valid number", name)))
+                .unwrap_or(default)
+        };
+
+        let config = Self {
+            min_length: number("PASSWORD_MIN_LENGTH", defaults.min_length),
+            max_length: number("PASSWORD_MAX_LENGTH", defaults.max_length),
+            min_character_classes: number("PASSWORD_MIN_CHARACTER_CLASSES", defaults.min_character_classes),
+            block_common: env::var("PASSWORD_BLOCK_COMMON")
+                .map(|v| v.to_lowercase() != "false")
+                .unwrap_or(defaults.block_common),
+            breached_passwords: env::var("PASSWORD_BREACHED_FILE").ok().filter(|v| !v.is_empty()),
+            history_size: number("PASSWORD_HISTORY_SIZE", defaults.history_size),
+        };
+
+        if config.min_length > config.max_length {
+            panic!("PASSWORD_MIN_LENGTH must not be larger than PASSWORD_MAX_LENGTH");
+        }
+        if config.min_character_classes > 4 {
+            panic!("PASSWORD_MIN_CHARACTER_CLASSES must be between 0 and 4");
+        }
+        config
+    }
+}
+
+// brute force protection for /auth/signin and /auth/ldap/signin
+#[derive(Debug, Clone)]
+pub struct LockoutConfig {
+    // failures of one account from one ip before it gets locked, 0 = off
+    pub max_failures: u32,
+    // failures of one account from all ips, the lock only hits ips that failed on it themselves
+    pub account_max_failures: u32,
+    // failures of one ip over all accounts (credential stuffing)
+    pub ip_max_failures: u32,
+    // first lock, doubled with every further failure
+    pub lockout_secs: i64,
+    pub max_lockout_secs: i64,
+    // counters start at 0 again after this long without a failure
+    pub window_secs: i64,
+}
+
+impl Default for LockoutConfig {
+    fn default() -> Self {
+        Self {
+            max_failures: 5,
+            account_max_failures: 20,
+            ip_max_failures: 50,
+            lockout_secs: 30,
+            max_lockout_secs: 900,
+            window_secs: 900,
+        }
+    }
+}
+
+impl LockoutConfig {
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        fn number<T: std::str::FromStr>(name: &str, default: T) -> T {
+            env::var(name)
+                .ok()
+                .map(|v| v.parse().unwrap_or_else(|_| panic!("{} must be a valid number", name)))
+                .unwrap_or(default)
+        }
+
+        let config = Self {
+            max_failures: number("LOGIN_MAX_FAILURES", defaults.max_failures),
+            account_max_failures: number("LOGIN_ACCOUNT_MAX_FAILURES", defaults.account_max_failures),
+            ip_max_failures: number("LOGIN_IP_MAX_FAILURES", defaults.ip_max_failures),
+            lockout_secs: number("LOGIN_LOCKOUT_SECS", defaults.lockout_secs),
+            max_lockout_secs: number("LOGIN_MAX_LOCKOUT_SECS", defaults.max_lockout_secs),
+            window_secs: number("LOGIN_FAILURE_WINDOW_SECS", defaults.window_secs),
+        };
+
+        if config.lockout_secs < 1 || config.max_lockout_secs < config.lockout_secs {
+            panic!("LOGIN_LOCKOUT_SECS must be at least 1 and not larger than LOGIN_MAX_LOCKOUT_SECS");
+        }
+        if config.window_secs < 1 {
+            panic!("LOGIN_FAILURE_WINDOW_SECS must be at least 1");
+        }
+        config
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PasswordResetConfig {
+    // page with the new password form, the token is appended as ?token=
+    pub reset_uri: String,
+    pub expiration_secs: i64,
+    // no new mail for the same account within this time
+    pub resend_interval_secs: i64,
+    // forgot password requests per address and per client ip in the login failure window, 0 = unlimited
+    pub max_requests_per_email: u32,
+    pub max_requests_per_ip: u32,
+    pub mail: MailConfig,
+}
+
+#[derive(Debug, Clone)]
+pub enum MailConfig {
+    Smtp(SmtpConfig),
+    // every mail ends up as json file in this directory, for tests and local development
+    Outbox { dir: String, from: String },
+}
+
+#[derive(Debug, Clone)]
+pub struct SmtpConfig {
+    pub host: String,
+    pub port: u16,
+    pub security: SmtpSecurity,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub from: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SmtpSecurity {
+    StartTls,
+    // implicit tls, usually port 465
+    Tls,
+    // plaintext, only for a local mail catcher
+    None,
+}
+
+impl std::str::FromStr for SmtpSecurity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "starttls" => Ok(SmtpSecurity::StartTls),
+            "tls" => Ok(SmtpSecurity::Tls),
+            "none" => Ok(SmtpSecurity::None),
+            _ => Err(format!("Unknown SMTP security: {}", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DeviceFlowConfig {
+    // page where the user types in the user code
+    pub verification_uri: String,
+    pub expiration_secs: i64,
+    // min secs between two polls of the device
+    pub interval_secs: i64,
 }
 
 #[derive(Debug, Clone)]
@@ -21,18 +319,201 @@ pub struct GoogleOAuthConfig {
 
 #[derive(Debug, Clone)]
 pub struct LdapConfig {
-    // ldap url zb ldap://dc-01.tgm.ac.at:389 or ldaps://dc-01.tgm.ac.at:636
-    pub url: String,
+    // ldap urls zb ldap://dc-01.tgm.ac.at:389 or ldaps://dc-01.tgm.ac.at:636, tried in order
+    pub urls: Vec<String>,
+    // ActiveDirectory or Ldap (OpenLDAP, FreeIPA), also the auth_provider of synced users
+    pub provider: AuthProviderType,
     pub user_base_dn: String,
     // domain name
     pub domain: String,
     // use user@domain instead of dn
     pub use_upn: bool,
     pub use_starttls: bool,
+    // pem bundle, replaces the system roots for ldaps:// and starttls (internal CA)
+    pub tls_ca_file: Option<String>,
+    // pem client certificate + key, for servers that want mutual tls
+    pub tls_client_cert: Option<String>,
+    pub tls_client_key: Option<String>,
+    pub tls_min_version: TlsVersion,
     pub username_attribute: String,
+    pub email_attribute: String,
+    pub display_name_attribute: String,
+    // attribute on the user entry with the group dns
+    pub group_attribute: String,
+    pub group_membership: GroupMembership,
     pub timeout_secs: u64,
-    // ad groupe name for admin role
-    pub admin_group: Option<String>,
+    // group dn or cn -> role, highest matching role wins
+    pub group_roles: Vec<GroupRoleMapping>,
+    pub nested_groups: NestedGroupMode,
+    // where groups are searched for nested membership
+    pub group_base_dn: String,
+    // service account for search-then-bind, without it the user dn is guessed from the username
+    pub bind_dn: Option<String>,
+    pub bind_password: Option<String>,
+    // {username} gets replaced with the escaped login name
+    pub user_filter: String,
+    // max service account connections, idle ones are dropped after pool_idle_secs
+    pub pool_size: usize,
+    pub pool_idle_secs: u64,
+    // a failed server is skipped for this long, doubles with every further failure
+    pub server_retry_secs: u64,
+    // background sync of all directory users, None = only lazy sync on login
+    pub sync_interval_secs: Option<u64>,
+    pub sync_page_size: i32,
+    // opt-in: logins with the cached password while every server is down, for this long after the last online login
+    pub offline_grace_secs: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GroupRoleMapping {
+    // full dn (exact match) or just the cn
+    pub group: String,
+    pub role: UserRole,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NestedGroupMode {
+    // only direct memberOf
+    None,
+    // AD LDAP_MATCHING_RULE_IN_CHAIN, one search resolves the whole chain
+    InChain,
+    // follow memberOf of every group, for servers without the AD matching rule
+    Recursive,
+}
+
+impl std::str::FromStr for NestedGroupMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "none" => Ok(NestedGroupMode::None),
+            "in_chain" => Ok(NestedGroupMode::InChain),
+            "recursive" => Ok(NestedGroupMode::Recursive),
+            _ => Err(format!("Unknown nested group mode: {}", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TlsVersion {
+    Tls12,
+    Tls13,
+}
+
+impl std::str::FromStr for TlsVersion {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().trim_start_matches("tls").trim_start_matches('v') {
+            "1.2" => Ok(TlsVersion::Tls12),
+            "1.3" => Ok(TlsVersion::Tls13),
+            _ => Err(format!("Unknown TLS version: {}", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GroupMembership {
+    // groups listed on the user (memberOf), AD and OpenLDAP with memberof overlay
+    MemberOf,
+    // search groupOfNames entries with member=<user dn>
+    GroupOfNames,
+    // search posixGroup entries with memberUid=<username>, no nesting
+    PosixGroup,
+}
+
+impl std::str::FromStr for GroupMembership {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "member_of" => Ok(GroupMembership::MemberOf),
+            "group_of_names" => Ok(GroupMembership::GroupOfNames),
+            "posix_group" => Ok(GroupMembership::PosixGroup),
+            _ => Err(format!("Unknown group membership: {}", s)),
+        }
+    }
+}
+
+// "admin:CN=Domain Admins,CN=Users,DC=tgm,DC=ac,DC=at;admin:IT-Admins;user:Staff"
+pub fn parse_group_roles(value: &str) -> Result<Vec<GroupRoleMapping>, String> {
+    value
+        .split(';')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let (role, group) = entry
+                .split_once(':')
+                .ok_or_else(|| format!("Expected role:group, got {}", entry))?;
+            let group = group.trim();
+            if group.is_empty() {
+                return Err(format!("Empty group for role {}", role));
+            }
+            Ok(GroupRoleMapping {
+                group: group.to_string(),
+                role: role.trim().parse()?,
+            })
+        })
+        .collect()
+}
+
+// "at.tgm.app, at.tgm.cli" -> lowercased schemes, http and https never count as private-use
+pub fn parse_redirect_schemes(value: &str) -> Vec<String> {
+    value
+        .split([',', ' ', ';'])
+        .map(|scheme| scheme.trim().trim_end_matches(':').to_ascii_lowercase())
+        .filter(|scheme| !scheme.is_empty() && scheme != "http" && scheme != "https")
+        .collect()
+}
+
+// "ldap://dc-01:389, ldap://dc-02:389" -> both urls
+pub fn parse_ldap_urls(value: &str) -> Vec<String> {
+    value
+        .split([',', ' ', ';'])
+        .map(str::trim)
+        .filter(|url| !url.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+impl LdapConfig {
+    // AD with the same defaults as from_env without any optional var, the rest via struct update
+    pub fn new(urls: Vec<String>, user_base_dn: &str, domain: &str) -> Self {
+        Self {
+            urls,
+            provider: AuthProviderType::ActiveDirectory,
+            user_base_dn: user_base_dn.to_string(),
+            domain: domain.to_string(),
+            use_upn: true,
+            use_starttls: false,
+            tls_ca_file: None,
+            tls_client_cert: None,
+            tls_client_key: None,
+            tls_min_version: TlsVersion::Tls12,
+            username_attribute: "sAMAccountName".to_string(),
+            email_attribute: "mail".to_string(),
+            display_name_attribute: "displayName".to_string(),
+            group_attribute: "memberOf".to_string(),
+            group_membership: GroupMembership::MemberOf,
+            timeout_secs: 10,
+            group_roles: Vec::new(),
+            nested_groups: NestedGroupMode::InChain,
+            group_base_dn: user_base_dn.to_string(),
+            bind_dn: None,
+            bind_password: None,
+            user_filter: "(sAMAccountName={username})".to_string(),
+            pool_size: 10,
+            pool_idle_secs: 300,
+            server_retry_secs: 30,
+            sync_interval_secs: None,
+            sync_page_size: 500,
+            offline_grace_secs: None,
+        }
+    }
+
+    pub fn search_then_bind(&self) -> bool {
+        self.bind_dn.is_some()
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -101,9 +582,84 @@ impl Config {
                 .unwrap_or_else(|_| "initial_admin.json".to_string()),
             google_oauth: Self::google_oauth_from_env(),
             ldap: Self::ldap_from_env(),
+            device_flow: DeviceFlowConfig {
+                verification_uri: env::var("DEVICE_VERIFICATION_URI")
+                    .unwrap_or_else(|_| "http://localhost:8080/device.html".to_string()),
+                expiration_secs: env::var("DEVICE_CODE_EXPIRATION_SECS")
+                    .unwrap_or_else(|_| "600".to_string())
+                    .parse()
+                    .expect("DEVICE_CODE_EXPIRATION_SECS must be a valid number"),
+                interval_secs: env::var("DEVICE_POLL_INTERVAL_SECS")
+                    .unwrap_or_else(|_| "5".to_string())
+                    .parse()
+                    .expect("DEVICE_POLL_INTERVAL_SECS must be a valid number"),
+            },
+            redirect_uri_schemes: parse_redirect_schemes(
+                &env::var("OAUTH_REDIRECT_SCHEMES").unwrap_or_default(),
+            ),
+            password_reset: Self::password_reset_from_env(),
+            password_policy: PasswordPolicyConfig::from_env(),
+            argon2: Argon2Config::from_env(),
+            lockout: LockoutConfig::from_env(),
         }
     }
 
+    // without a way to send mails there is no reset flow
+    fn password_reset_from_env() -> Option<PasswordResetConfig> {
+        let from = env::var("MAIL_FROM").unwrap_or_else(|_| "auth-service@localhost".to_string());
+
+        let mail = if let Ok(host) = env::var("SMTP_HOST") {
+            let security: SmtpSecurity = env::var("SMTP_SECURITY")
+                .unwrap_or_else(|_| "starttls".to_string())
+                .parse()
+                .expect("SMTP_SECURITY must be starttls, tls or none");
+            let default_port = match security {
+                SmtpSecurity::Tls => 465,
+                SmtpSecurity::StartTls => 587,
+                SmtpSecurity::None => 25,
+            };
+
+            MailConfig::Smtp(SmtpConfig {
+                host,
+                port: env::var("SMTP_PORT")
+                    .ok()
+                    .map(|v| v.parse().expect("SMTP_PORT must be a valid number"))
+                    .unwrap_or(default_port),
+                security,
+                username: env::var("SMTP_USERNAME").ok(),
+                password: env::var("SMTP_PASSWORD").ok(),
+                from,
+            })
+        } else {
+            MailConfig::Outbox {
+                dir: env::var("MAIL_OUTBOX_DIR").ok()?,
+                from,
+            }
+        };
+
+        Some(PasswordResetConfig {
+            reset_uri: env::var("PASSWORD_RESET_URI")
+                .unwrap_or_else(|_| "http://localhost:8080/reset-password.html".to_string()),
+            expiration_secs: env::var("PASSWORD_RESET_EXPIRATION_SECS")
+                .unwrap_or_else(|_| "1800".to_string())
+                .parse()
+                .expect("PASSWORD_RESET_EXPIRATION_SECS must be a valid number"),
+            resend_interval_secs: env::var("PASSWORD_RESET_RESEND_SECS")
+                .unwrap_or_else(|_| "300".to_string())
+                .parse()
+                .expect("PASSWORD_RESET_RESEND_SECS must be a valid number"),
+            max_requests_per_email: env::var("PASSWORD_RESET_MAX_PER_EMAIL")
+                .unwrap_or_else(|_| "3".to_string())
+                .parse()
+                .expect("PASSWORD_RESET_MAX_PER_EMAIL must be a valid number"),
+            max_requests_per_ip: env::var("PASSWORD_RESET_MAX_PER_IP")
+                .unwrap_or_else(|_| "20".to_string())
+                .parse()
+                .expect("PASSWORD_RESET_MAX_PER_IP must be a valid number"),
+            mail,
+        })
+    }
+
     fn google_oauth_from_env() -> Option<GoogleOAuthConfig> {
         let client_id = env::var("GOOGLE_CLIENT_ID").ok()?;
         let client_secret = env::var("GOOGLE_CLIENT_SECRET").ok()?;
@@ -118,37 +674,147 @@ impl Config {
     }
 
     fn ldap_from_env() -> Option<LdapConfig> {
-        let url = env::var("LDAP_URL").ok()?;
+        let urls = parse_ldap_urls(&env::var("LDAP_URL").ok()?);
+        if urls.is_empty() {
+            panic!("LDAP_URL must contain at least one url");
+        }
         let user_base_dn = env::var("LDAP_USER_BASE_DN").ok()?;
         let domain = env::var("LDAP_DOMAIN").ok()?;
 
+        let provider = match env::var("LDAP_PROVIDER").unwrap_or_default().to_lowercase().as_str() {
+            "" | "activedirectory" | "ad" => AuthProviderType::ActiveDirectory,
+            "ldap" | "openldap" | "freeipa" => AuthProviderType::Ldap,
+            other => panic!("LDAP_PROVIDER must be activedirectory or ldap, got {}", other),
+        };
+        let is_ad = provider == AuthProviderType::ActiveDirectory;
+
         let use_upn = env::var("LDAP_USE_UPN")
             .map(|v| v.to_lowercase() == "true" || v == "1")
-            .unwrap_or(true); // Default to UPN for AD
+            .unwrap_or(is_ad); // Default to UPN for AD
 
         let use_starttls = env::var("LDAP_USE_STARTTLS")
             .map(|v| v.to_lowercase() == "true" || v == "1")
             .unwrap_or(false);
 
-        let username_attribute = env::var("LDAP_USERNAME_ATTRIBUTE")
-            .unwrap_or_else(|_| "sAMAccountName".to_string());
+        let tls_ca_file = env::var("LDAP_TLS_CA_FILE").ok();
+        let tls_client_cert = env::var("LDAP_TLS_CLIENT_CERT").ok();
+        let tls_client_key = env::var("LDAP_TLS_CLIENT_KEY").ok();
+        if tls_client_cert.is_some() != tls_client_key.is_some() {
+            panic!("LDAP_TLS_CLIENT_CERT and LDAP_TLS_CLIENT_KEY must be set together");
+        }
+
+        let tls_min_version = env::var("LDAP_TLS_MIN_VERSION")
+            .map(|v| v.parse().expect("LDAP_TLS_MIN_VERSION must be 1.2 or 1.3"))
+            .unwrap_or(TlsVersion::Tls12);
+
+        // defaults follow the schema of the directory type
+        let attribute = |key: &str, ad: &str, ldap: &str| {
+            env::var(key).unwrap_or_else(|_| if is_ad { ad } else { ldap }.to_string())
+        };
+
+        let username_attribute = attribute("LDAP_USERNAME_ATTRIBUTE", "sAMAccountName", "uid");
+        let email_attribute = attribute("LDAP_EMAIL_ATTRIBUTE", "mail", "mail");
+        let display_name_attribute = attribute("LDAP_DISPLAY_NAME_ATTRIBUTE", "displayName", "cn");
+        let group_attribute = attribute("LDAP_GROUP_ATTRIBUTE", "memberOf", "memberOf");
+
+        let group_membership = attribute("LDAP_GROUP_MEMBERSHIP", "member_of", "group_of_names")
+            .parse()
+            .expect("LDAP_GROUP_MEMBERSHIP must be member_of, group_of_names or posix_group");
 
         let timeout_secs = env::var("LDAP_TIMEOUT_SECS")
             .unwrap_or_else(|_| "10".to_string())
             .parse()
             .unwrap_or(10);
 
-        let admin_group = env::var("LDAP_ADMIN_GROUP").ok();
+        let mut group_roles = env::var("LDAP_GROUP_ROLES")
+            .map(|v| parse_group_roles(&v).expect("LDAP_GROUP_ROLES must be role:group;role:group"))
+            .unwrap_or_default();
+
+        // old single admin group setting still works
+        if let Ok(admin_group) = env::var("LDAP_ADMIN_GROUP") {
+            group_roles.push(GroupRoleMapping {
+                group: admin_group,
+                role: UserRole::Admin,
+            });
+        }
+
+        // matching rule in chain only exists in AD
+        let nested_groups = attribute("LDAP_NESTED_GROUPS", "in_chain", "none")
+            .parse()
+            .expect("LDAP_NESTED_GROUPS must be none, in_chain or recursive");
+
+        let group_base_dn = env::var("LDAP_GROUP_BASE_DN").unwrap_or_else(|_| user_base_dn.clone());
+
+        let bind_dn = env::var("LDAP_BIND_DN").ok();
+        let bind_password = env::var("LDAP_BIND_PASSWORD").ok();
+
+        let user_filter = env::var("LDAP_USER_FILTER")
+            .unwrap_or_else(|_| format!("({}={{username}})", username_attribute));
+        if !user_filter.contains("{username}") {
+            panic!("LDAP_USER_FILTER must contain {{username}}");
+        }
+
+        let pool_size = env::var("LDAP_POOL_SIZE")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|size| *size > 0)
+            .unwrap_or(10);
+
+        let pool_idle_secs = env::var("LDAP_POOL_IDLE_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(300);
+
+        let server_retry_secs = env::var("LDAP_SERVER_RETRY_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(30);
+
+        let sync_interval_secs = env::var("LDAP_SYNC_INTERVAL_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|secs| *secs > 0);
+
+        let sync_page_size = env::var("LDAP_SYNC_PAGE_SIZE")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|size| *size > 0)
+            .unwrap_or(500);
+
+        let offline_grace_secs = env::var("LDAP_OFFLINE_GRACE_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|secs| *secs > 0);
 
         Some(LdapConfig {
-            url,
+            urls,
+            provider,
             user_base_dn,
             domain,
             use_upn,
             use_starttls,
+            tls_ca_file,
+            tls_client_cert,
+            tls_client_key,
+            tls_min_version,
             username_attribute,
+            email_attribute,
+            display_name_attribute,
+            group_attribute,
+            group_membership,
             timeout_secs,
-            admin_group,
+            group_roles,
+            nested_groups,
+            group_base_dn,
+            bind_dn,
+            bind_password,
+            user_filter,
+            pool_size,
+            pool_idle_secs,
+            server_retry_secs,
+            sync_interval_secs,
+            sync_page_size,
+            offline_grace_secs,
         })
     }
 }
@@ -159,3 +825,94 @@ pub fn validate_jwt_secret(secret: &str) -> Result<(), String> {
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_group_roles() {
+        let mappings = parse_group_roles(
+            "admin:CN=Domain Admins,CN=Users,DC=tgm,DC=ac,DC=at; user:Staff;",
+        )
+        .unwrap();
+
+        assert_eq!(mappings.len(), 2);
+        assert_eq!(mappings[0].group, "CN=Domain Admins,CN=Users,DC=tgm,DC=ac,DC=at");
+        assert_eq!(mappings[0].role, UserRole::Admin);
+        assert_eq!(mappings[1].group, "Staff");
+        assert_eq!(mappings[1].role, UserRole::User);
+    }
+
+    #[test]
+    fn test_parse_peppers() {
+        let secret = "s".repeat(32);
+        let peppers = parse_peppers(&format!("# rotated 2026-10\n2026b:{0}\n\n2026a:{0}x\n", secret)).unwrap();
+
+        assert_eq!(peppers.current_id(), "2026b");
+        assert_eq!(peppers.keys.len(), 2);
+        assert_eq!(peppers.keys[1], ("2026a".to_string(), format!("{}x", secret)));
+        // secrets never end up in logs
+        assert!(!format!("{:?}", peppers).contains(&secret));
+
+        assert!(parse_peppers("").is_err());
+        assert!(parse_peppers("1:short").is_err());
+        assert!(parse_peppers(&format!("toolongid:{}", secret)).is_err());
+        assert!(parse_peppers(&format!("1:{0}\n1:{0}", secret)).is_err());
+        assert!(parse_peppers(&secret).is_err());
+    }
+
+    #[test]
+    fn test_parse_group_roles_invalid() {
+        assert!(parse_group_roles("Domain Admins").is_err());
+        assert!(parse_group_roles("superuser:Admins").is_err());
+        assert!(parse_group_roles("admin:").is_err());
+    }
+
+    #[test]
+    fn test_parse_redirect_schemes() {
+        assert_eq!(
+            parse_redirect_schemes("at.tgm.app, AT.TGM.CLI:;https,,"),
+            vec!["at.tgm.app", "at.tgm.cli"]
+        );
+        assert!(parse_redirect_schemes("").is_empty());
+    }
+
+    #[test]
+    fn test_parse_ldap_urls() {
+        assert_eq!(
+            parse_ldap_urls("ldap://dc-01:389, ldaps://dc-02:636,,"),
+            vec!["ldap://dc-01:389", "ldaps://dc-02:636"]
+        );
+        assert_eq!(parse_ldap_urls("ldap://dc-01"), vec!["ldap://dc-01"]);
+        assert!(parse_ldap_urls(" , ").is_empty());
+    }
+
+    #[test]
+    fn test_group_membership() {
+        assert_eq!("group_of_names".parse::<GroupMembership>().unwrap(), GroupMembership::GroupOfNames);
+        assert_eq!("POSIX_GROUP".parse::<GroupMembership>().unwrap(), GroupMembership::PosixGroup);
+        assert!("memberof".parse::<GroupMembership>().is_err());
+    }
+
+    #[test]
+    fn test_nested_group_mode() {
+        assert_eq!("in_chain".parse::<NestedGroupMode>().unwrap(), NestedGroupMode::InChain);
+        assert_eq!("Recursive".parse::<NestedGroupMode>().unwrap(), NestedGroupMode::Recursive);
+        assert!("deep".parse::<NestedGroupMode>().is_err());
+    }
+
+    #[test]
+    fn test_tls_version() {
+        assert_eq!("1.2".parse::<TlsVersion>().unwrap(), TlsVersion::Tls12);
+        assert_eq!("TLSv1.3".parse::<TlsVersion>().unwrap(), TlsVersion::Tls13);
+        assert!("1.1".parse::<TlsVersion>().is_err());
+    }
+
+    #[test]
+    fn test_smtp_security() {
+        assert_eq!("STARTTLS".parse::<SmtpSecurity>().unwrap(), SmtpSecurity::StartTls);
+        assert_eq!("none".parse::<SmtpSecurity>().unwrap(), SmtpSecurity::None);
+        assert!("ssl".parse::<SmtpSecurity>().is_err());
+    }
+}
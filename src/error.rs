@@ -12,6 +12,51 @@ pub enum AppError {
     DatabaseError(String),
     OAuthError(String),
     LdapError(String),
+    // directory down and the cached credential did not match. answered exactly like LdapError
+    // so nobody learns who has a cached credential, but the lockout counts it as a wrong password
+    OfflineLoginRejected(String),
+    // too many argon2 hashes queued, client should retry a bit later
+    Overloaded(String),
+    // too many failed logins, secs until the lock runs out
+    TooManyAttempts(i64),
+    // credentials were fine but the directory refuses the login
+    AccountRestricted(AccountRestriction),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccountRestriction {
+    PasswordExpired,
+    PasswordMustChange,
+    AccountDisabled,
+    AccountExpired,
+    AccountLocked,
+    // logon hours or allowed workstations
+    LogonRestricted,
+}
+
+impl AccountRestriction {
+    // stable value for the frontend
+    pub fn code(&self) -> &'static str {
+        match self {
+            AccountRestriction::PasswordExpired => "password_expired",
+            AccountRestriction::PasswordMustChange => "password_must_change",
+            AccountRestriction::AccountDisabled => "account_disabled",
+            AccountRestriction::AccountExpired => "account_expired",
+            AccountRestriction::AccountLocked => "account_locked",
+            AccountRestriction::LogonRestricted => "logon_restricted",
+        }
+    }
+
+    pub fn message(&self) -> &'static str {
+        match self {
+            AccountRestriction::PasswordExpired => "Passwort abgelaufen, bitte aendern",
+            AccountRestriction::PasswordMustChange => "Passwort muss vor dem ersten login geaendert werden",
+            AccountRestriction::AccountDisabled => "Account ist deaktiviert",
+            AccountRestriction::AccountExpired => "Account ist abgelaufen",
+            AccountRestriction::AccountLocked => "Account ist gesperrt, spaeter nochmal probieren",
+            AccountRestriction::LogonRestricted => "Login zu dieser zeit oder von diesem geraet nicht erlaubt",
+        }
+    }
 }
 
 impl fmt::Display for AppError {
@@ -25,7 +70,10 @@ impl fmt::Display for AppError {
             AppError::InternalError(msg) => write!(f, "Internal error: {}", msg),
             AppError::DatabaseError(msg) => write!(f, "Database error: {}", msg),
             AppError::OAuthError(msg) => write!(f, "OAuth error: {}", msg),
-            AppError::LdapError(msg) => write!(f, "LDAP error: {}", msg),
+            AppError::LdapError(msg) | AppError::OfflineLoginRejected(msg) => write!(f, "LDAP error: {}", msg),
+            AppError::Overloaded(msg) => write!(f, "Overloaded: {}", msg),
+            AppError::TooManyAttempts(secs) => write!(f, "Too many failed logins, retry in {}s", secs),
+            AppError::AccountRestricted(restriction) => write!(f, "Account restricted: {}", restriction.code()),
         }
     }
 }
@@ -58,15 +106,40 @@ impl ResponseError for AppError {
                 tracing::error!("OAuth error: {}", self);
                 (StatusCode::BAD_REQUEST, msg.as_str())
             }
-            AppError::LdapError(msg) => {
+            AppError::LdapError(msg) | AppError::OfflineLoginRejected(msg) => {
                 tracing::error!("ldap error: {}", self);
                 (StatusCode::SERVICE_UNAVAILABLE, msg.as_str())
             }
+            AppError::Overloaded(_) => {
+                (StatusCode::SERVICE_UNAVAILABLE, "Zu viele logins gerade, bitte gleich nochmal probieren")
+            }
+            AppError::TooManyAttempts(_) => {
+                (StatusCode::TOO_MANY_REQUESTS, "Zu viele fehlgeschlagene logins, spaeter nochmal probieren")
+            }
+            AppError::AccountRestricted(restriction) => {
+                (StatusCode::FORBIDDEN, restriction.message())
+            }
         };
 
-        HttpResponse::build(status).json(serde_json::json!({
+        let mut body = serde_json::json!({
             "error": message
-        }))
+        });
+        if let AppError::AccountRestricted(restriction) = self {
+            body["code"] = restriction.code().into();
+        }
+
+        let mut response = HttpResponse::build(status);
+        match self {
+            AppError::Overloaded(_) => {
+                response.insert_header(("Retry-After", "1"));
+            }
+            AppError::TooManyAttempts(secs) => {
+                body["retry_after"] = (*secs).into();
+                response.insert_header(("Retry-After", secs.to_string()));
+            }
+            _ => {}
+        }
+        response.json(body)
     }
 }
 
@@ -90,3 +163,48 @@ impl From<argon2::password_hash::Error> for AppError {
         AppError::InternalError("Password processing failed".to_string())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_account_restricted_response_has_code() {
+        let response = AppError::AccountRestricted(AccountRestriction::PasswordExpired).error_response();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let body = actix_web::body::to_bytes(response.into_body()).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["code"], "password_expired");
+        assert!(json["error"].is_string());
+    }
+
+    #[tokio::test]
+    async fn test_offline_login_rejected_looks_like_ldap_error() {
+        let rejected = AppError::OfflineLoginRejected("Server down".to_string()).error_response();
+        let outage = AppError::LdapError("Server down".to_string()).error_response();
+        assert_eq!(rejected.status(), outage.status());
+
+        let rejected = actix_web::body::to_bytes(rejected.into_body()).await.unwrap();
+        let outage = actix_web::body::to_bytes(outage.into_body()).await.unwrap();
+        assert_eq!(rejected, outage);
+    }
+
+    #[test]
+    fn test_overloaded_response_is_retryable() {
+        let response = AppError::Overloaded("queue full".to_string()).error_response();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.headers().get("Retry-After").unwrap(), "1");
+    }
+
+    #[tokio::test]
+    async fn test_too_many_attempts_response_has_retry_after() {
+        let response = AppError::TooManyAttempts(42).error_response();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers().get("Retry-After").unwrap(), "42");
+
+        let body = actix_web::body::to_bytes(response.into_body()).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["retry_after"], 42);
+    }
+}
@@ -3,17 +3,31 @@ use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use validator::Validate;
 
-use crate::auth::{JwtService, LocalAuthProvider, GoogleAuthProvider, LdapAuthProvider, Claims, AuthProvider};
+use crate::auth::{
+    JwtService, LocalAuthProvider, GoogleAuthProvider, LdapAuthProvider, DeviceFlowService, DirectorySyncService,
+    PasswordResetService, AuthProvider, LoginThrottle,
+};
 use crate::error::AppError;
-use crate::models::{CreateUser, UserResponse, UserRole};
-use crate::repository::UserRepository;
+use crate::models::{UserResponse, UserRole};
+use crate::repository::{ClientRepository, ConsentRepository, RefreshTokenRepository, UserRepository};
+use super::extractors::{scope, AuthenticatedUser, RequireScope};
 
 pub struct AppState {
     pub jwt_service: JwtService,
     pub auth_provider: LocalAuthProvider,
     pub google_provider: Option<GoogleAuthProvider>,
-    pub ldap_provider: Option<LdapAuthProvider>,
+    pub ldap_provider: Option<Arc<LdapAuthProvider>>,
     pub repository: Arc<dyn UserRepository>,
+    pub client_repository: Arc<dyn ClientRepository>,
+    pub refresh_token_repository: Arc<dyn RefreshTokenRepository>,
+    pub consent_repository: Arc<dyn ConsentRepository>,
+    pub device_flow: DeviceFlowService,
+    // OAUTH_REDIRECT_SCHEMES, besides https and http on loopback
+    pub redirect_uri_schemes: Vec<String>,
+    pub directory_sync: Option<Arc<DirectorySyncService>>,
+    // only with SMTP_HOST or MAIL_OUTBOX_DIR
+    pub password_reset: Option<Arc<PasswordResetService>>,
+    pub login_throttle: LoginThrottle,
 }
 
 #[derive(Debug, Deserialize, Validate)]
@@ -24,12 +38,48 @@ pub struct RegisterRequest {
     #[validate(email(message = "Invalid email"))]
     pub email: String,
 
-    #[validate(length(min = 12, message = "pw at least 12 char"))]
+    #[validate(length(min = 1, message = "Password required"))]
     pub password: String,
 
     pub role: Option<String>,
 }
 
+#[derive(Debug, Deserialize, Validate)]
+pub struct ImportUserRequest {
+    #[validate(length(min = 1, max = 100, message = "name between 1 and 100 char"))]
+    pub name: String,
+
+    #[validate(email(message = "Invalid email"))]
+    pub email: String,
+
+    // argon2, bcrypt, $6$ sha512-crypt, scrypt or django pbkdf2 / scrypt
+    #[validate(length(min = 1, message = "Password hash required"))]
+    pub password_hash: String,
+
+    pub role: Option<String>,
+}
+
+const MAX_IMPORT_USERS: usize = 1000;
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct ImportUsersRequest {
+    // count is checked by hand, the length validator would put the hashes into the error
+    #[validate(nested)]
+    pub users: Vec<ImportUserRequest>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImportFailure {
+    pub email: String,
+    pub error: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImportUsersResponse {
+    pub imported: Vec<UserResponse>,
+    pub failed: Vec<ImportFailure>,
+}
+
 #[derive(Debug, Serialize)]
 pub struct RegisterResponse {
     pub message: String,
@@ -45,6 +95,28 @@ pub struct SignInRequest {
     pub password: String,
 }
 
+#[derive(Debug, Deserialize, Validate)]
+pub struct ChangePasswordRequest {
+    #[validate(length(min = 1, message = "Current password required"))]
+    pub current_password: String,
+
+    #[validate(length(min = 1, message = "Password required"))]
+    pub new_password: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct LdapChangePasswordRequest {
+    #[validate(length(min = 1, message = "Username required"))]
+    pub username: String,
+
+    #[validate(length(min = 1, message = "Old password required"))]
+    pub old_password: String,
+
+    // policy is the directory's, it answers with a clear error
+    #[validate(length(min = 1, message = "New password required"))]
+    pub new_password: String,
+}
+
 #[derive(Debug, Deserialize, Validate)]
 pub struct LdapSignInRequest {
     #[validate(length(min = 1, message = "Username required"))]
@@ -73,57 +145,32 @@ pub struct VerifyResponse {
     pub user_id: String,
     pub email: String,
     pub role: String,
+    pub scope: String,
     pub message: String,
 }
 
-fn extract_bearer_token(req: &HttpRequest) -> Option<String> {
-    req.headers()
-        .get("Authorization")
-        .and_then(|h| h.to_str().ok())
-        .and_then(|h| h.strip_prefix("Bearer "))
-        .map(|s| s.to_string())
-}
-
-async fn require_admin(req: &HttpRequest, state: &web::Data<AppState>) -> Result<Claims, AppError> {
-    let token = extract_bearer_token(req).ok_or_else(|| {
-        AppError::Unauthorized("Authorization header required".to_string())
-    })?;
-
-    let claims = state.jwt_service.validate_token(&token)?;
-
-    if claims.role != "admin" {
-        tracing::warn!(
-            user_id = %claims.sub,
-            "Non-admin user attempted admin operation"
-        );
-        return Err(AppError::Forbidden(
-            "Administrator privileges required".to_string(),
-        ));
+fn parse_role(role: Option<&str>) -> Result<UserRole, AppError> {
+    match role {
+        Some("admin") => Ok(UserRole::Admin),
+        Some("user") | None => Ok(UserRole::User),
+        Some(other) => Err(AppError::ValidationError(format!(
+            "Invalid role: {}. Must be 'admin' or 'user'",
+            other
+        ))),
     }
-
-    Ok(claims)
 }
 
 pub async fn register_user(
-    req: HttpRequest,
+    admin: RequireScope<scope::UsersWrite>,
     state: web::Data<AppState>,
     body: web::Json<RegisterRequest>,
 ) -> Result<HttpResponse, AppError> {
     body.validate()
         .map_err(|e| AppError::ValidationError(e.to_string()))?;
 
-    let admin_claims = require_admin(&req, &state).await?;
+    let admin_claims = admin.claims;
 
-    let role = match body.role.as_deref() {
-        Some("admin") => UserRole::Admin,
-        Some("user") | None => UserRole::User,
-        Some(other) => {
-            return Err(AppError::ValidationError(format!(
-                "Invalid role: {}. Must be 'admin' or 'user'",
-                other
-            )));
-        }
-    };
+    let role = parse_role(body.role.as_deref())?;
 
     tracing::info!(
         admin_id = %admin_claims.sub,
@@ -143,13 +190,76 @@ pub async fn register_user(
     }))
 }
 
-pub async fn signin(state: web::Data<AppState>, body: web::Json<SignInRequest>) -> Result<HttpResponse, AppError> {
+// migration from the old apps, one bad entry does not stop the rest
+pub async fn import_users(
+    admin: RequireScope<scope::UsersWrite>,
+    state: web::Data<AppState>,
+    body: web::Json<ImportUsersRequest>,
+) -> Result<HttpResponse, AppError> {
+    if body.users.is_empty() || body.users.len() > MAX_IMPORT_USERS {
+        return Err(AppError::ValidationError(format!(
+            "between 1 and {} users per request",
+            MAX_IMPORT_USERS
+        )));
+    }
+    body.validate()
+        .map_err(|e| AppError::ValidationError(e.to_string()))?;
+
+    let mut imported = Vec::new();
+    let mut failed = Vec::new();
+
+    for entry in &body.users {
+        let result = match parse_role(entry.role.as_deref()) {
+            Ok(role) => {
+                state
+                    .auth_provider
+                    .import(&entry.name, &entry.email, &entry.password_hash, role)
+                    .await
+            }
+            Err(e) => Err(e),
+        };
+
+        match result {
+            Ok(user) => imported.push(user.into()),
+            Err(e) => failed.push(ImportFailure {
+                email: entry.email.clone(),
+                error: e.to_string(),
+            }),
+        }
+    }
+
+    tracing::info!(
+        admin_id = %admin.claims.sub,
+        imported = imported.len(),
+        failed = failed.len(),
+        "Admin imported users"
+    );
+
+    Ok(HttpResponse::Ok().json(ImportUsersResponse { imported, failed }))
+}
+
+// tcp peer, not X-Forwarded-For, otherwise every request could pick its own ip
+pub(super) fn client_ip(req: &HttpRequest) -> String {
+    req.peer_addr()
+        .map(|addr| addr.ip().to_string())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+pub async fn signin(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    body: web::Json<SignInRequest>,
+) -> Result<HttpResponse, AppError> {
     body.validate()
         .map_err(|e| AppError::ValidationError(e.to_string()))?;
 
     let result = state
-        .auth_provider
-        .authenticate(&body.email, &body.password)
+        .login_throttle
+        .guard(
+            &LoginThrottle::local_subject(&body.email),
+            &client_ip(&req),
+            state.auth_provider.authenticate(&body.email, &body.password),
+        )
         .await?;
 
     let token = state.jwt_service.generate_token(
@@ -166,7 +276,11 @@ pub async fn signin(state: web::Data<AppState>, body: web::Json<SignInRequest>)
     }))
 }
 
-pub async fn ldap_signin(state: web::Data<AppState>, body: web::Json<LdapSignInRequest>) -> Result<HttpResponse, AppError> {
+pub async fn ldap_signin(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    body: web::Json<LdapSignInRequest>,
+) -> Result<HttpResponse, AppError> {
     body.validate()
         .map_err(|e| AppError::ValidationError(e.to_string()))?;
 
@@ -175,15 +289,21 @@ pub async fn ldap_signin(state: web::Data<AppState>, body: web::Json<LdapSignInR
         AppError::ValidationError("ldap not activated".to_string())
     })?;
 
-    let result = ldap_provider
-        .authenticate(&body.username, &body.password)
+    let result = state
+        .login_throttle
+        .guard(
+            &LoginThrottle::ldap_subject(&body.username),
+            &client_ip(&req),
+            ldap_provider.authenticate(&body.username, &body.password),
+        )
         .await?;
 
-    let token = state.jwt_service.generate_token(
-        &result.user.id,
-        &result.user.email,
-        result.user.role,
-    )?;
+    // checked against the cached credential only, marked so the rest of the system can tell
+    let token = if result.offline {
+        state.jwt_service.generate_offline_token(&result.user.id, &result.user.email, result.user.role)?
+    } else {
+        state.jwt_service.generate_token(&result.user.id, &result.user.email, result.user.role)?
+    };
 
     Ok(HttpResponse::Ok().json(SignInResponse {
         token,
@@ -193,6 +313,72 @@ pub async fn ldap_signin(state: web::Data<AppState>, body: web::Json<LdapSignInR
     }))
 }
 
+// local accounts only, every refresh token of the user is revoked and the caller gets a new access token
+// the current password is a guess like any sign in, so it shares the lockout with /auth/signin
+pub async fn change_password(
+    req: HttpRequest,
+    AuthenticatedUser(claims): AuthenticatedUser,
+    state: web::Data<AppState>,
+    body: web::Json<ChangePasswordRequest>,
+) -> Result<HttpResponse, AppError> {
+    body.validate()
+        .map_err(|e| AppError::ValidationError(e.to_string()))?;
+
+    let user = state
+        .login_throttle
+        .guard(
+            &LoginThrottle::local_subject(&claims.email),
+            &client_ip(&req),
+            state.auth_provider.change_password(&claims.sub, &body.current_password, &body.new_password),
+        )
+        .await?;
+
+    let revoked = state
+        .refresh_token_repository
+        .revoke_all_for_user(&user.id)
+        .await?;
+
+    tracing::info!(user_id = %user.id, revoked_tokens = revoked, "Sessions revoked after password change");
+
+    let token = state.jwt_service.generate_token(&user.id, &user.email, user.role)?;
+
+    Ok(HttpResponse::Ok().json(SignInResponse {
+        token,
+        token_type: "Bearer".to_string(),
+        expires_in: 3600,
+        user: user.into(),
+    }))
+}
+
+// no token needed, an expired AD password cannot sign in. without a token this is a password oracle,
+// so wrong old passwords count against the same lockout as /auth/ldap/signin
+pub async fn ldap_change_password(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    body: web::Json<LdapChangePasswordRequest>,
+) -> Result<HttpResponse, AppError> {
+    body.validate()
+        .map_err(|e| AppError::ValidationError(e.to_string()))?;
+
+    let ldap_provider = state.ldap_provider.as_ref().ok_or_else(|| {
+        tracing::warn!("ldap not proper configured");
+        AppError::ValidationError("ldap not activated".to_string())
+    })?;
+
+    state
+        .login_throttle
+        .guard(
+            &LoginThrottle::ldap_subject(&body.username),
+            &client_ip(&req),
+            ldap_provider.change_password(&body.username, &body.old_password, &body.new_password),
+        )
+        .await?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "message": "Password changed"
+    })))
+}
+
 pub async fn verify_token(
     state: web::Data<AppState>,
     body: web::Json<VerifyRequest>,
@@ -223,11 +409,19 @@ pub async fn verify_token(
         })));
     }
 
+    if user.token_predates_password_change(claims.iat) {
+        return Ok(HttpResponse::Forbidden().json(serde_json::json!({
+            "valid": false,
+            "error": "Invalid or expired token"
+        })));
+    }
+
     Ok(HttpResponse::Ok().json(VerifyResponse {
         valid: true,
         user_id: claims.sub,
         email: claims.email,
         role: claims.role,
+        scope: claims.scope,
         message: "User is registered and token is valid".to_string(),
     }))
 }
@@ -236,10 +430,38 @@ pub fn configure_routes(cfg: &mut web::ServiceConfig) {
     cfg.service(
         web::scope("/auth")
             .route("/admin/register", web::post().to(register_user))
+            .route("/admin/import", web::post().to(import_users))
             .route("/signin", web::post().to(signin))
+            .route("/password", web::post().to(change_password))
+            .route("/password/forgot", web::post().to(super::password_reset::forgot_password))
+            .route("/password/reset", web::post().to(super::password_reset::reset_password))
             .route("/ldap/signin", web::post().to(ldap_signin))
+            .route("/ldap/password", web::post().to(ldap_change_password))
             .route("/verify", web::post().to(verify_token))
             .route("/google/login", web::get().to(super::oauth::google_login))
-            .route("/google/callback", web::get().to(super::oauth::google_callback)),
+            .route("/google/callback", web::get().to(super::oauth::google_callback))
+            .route("/admin/clients", web::post().to(super::clients::create_client))
+            .route("/admin/clients", web::get().to(super::clients::list_clients))
+            .route("/admin/clients/{client_id}", web::get().to(super::clients::get_client))
+            .route("/admin/clients/{client_id}", web::patch().to(super::clients::update_client))
+            .route("/admin/clients/{client_id}", web::delete().to(super::clients::delete_client))
+            .route(
+                "/admin/clients/{client_id}/secret",
+                web::post().to(super::clients::regenerate_client_secret),
+            )
+            .route("/admin/directory-sync", web::get().to(super::directory_sync::sync_status))
+            .route("/admin/directory-sync", web::post().to(super::directory_sync::run_sync))
+            .route("/admin/metrics/hashing", web::get().to(super::metrics::hashing_metrics))
+            .route("/admin/users/{user_id}/lockout", web::delete().to(super::lockout::unlock_user))
+            .route("/grants", web::get().to(super::grants::list_grants))
+            .route("/grants/{client_id}", web::delete().to(super::grants::revoke_grant)),
+    );
+
+    cfg.service(
+        web::scope("/oauth")
+            .route("/device_authorization", web::post().to(super::device::device_authorization))
+            .route("/device", web::get().to(super::device::device_info))
+            .route("/device", web::post().to(super::device::device_verify))
+            .route("/token", web::post().to(super::token::token)),
     );
 }
@@ -0,0 +1,357 @@
+// admin api for registered oauth clients
+use actix_web::{web, HttpResponse};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+use crate::auth::generate_opaque_token;
+use crate::error::AppError;
+use crate::models::{ClientResponse, GrantType, OAuthClient, Permission};
+use super::auth::AppState;
+use super::extractors::{scope, RequireScope};
+
+const DEFAULT_REFRESH_TOKEN_TTL_SECS: i64 = 30 * 24 * 3600;
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct CreateClientRequest {
+    #[validate(length(min = 1, max = 100, message = "name between 1 and 100 char"))]
+    pub name: String,
+
+    #[serde(default)]
+    pub redirect_uris: Vec<String>,
+
+    #[validate(length(min = 1, message = "at least one grant type"))]
+    pub grant_types: Vec<GrantType>,
+
+    #[serde(default)]
+    pub scopes: Vec<String>,
+
+    // public clients (cli, spa) get no secret
+    #[serde(default = "default_confidential")]
+    pub confidential: bool,
+
+    #[validate(range(min = 60, message = "access token ttl at least 60 secs"))]
+    pub access_token_ttl_secs: Option<i64>,
+
+    #[validate(range(min = 60, message = "refresh token ttl at least 60 secs"))]
+    pub refresh_token_ttl_secs: Option<i64>,
+}
+
+fn default_confidential() -> bool {
+    true
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct UpdateClientRequest {
+    #[validate(length(min = 1, max = 100, message = "name between 1 and 100 char"))]
+    pub name: Option<String>,
+
+    pub redirect_uris: Option<Vec<String>>,
+
+    #[validate(length(min = 1, message = "at least one grant type"))]
+    pub grant_types: Option<Vec<GrantType>>,
+
+    pub scopes: Option<Vec<String>>,
+
+    #[validate(range(min = 60, message = "access token ttl at least 60 secs"))]
+    pub access_token_ttl_secs: Option<i64>,
+
+    #[validate(range(min = 60, message = "refresh token ttl at least 60 secs"))]
+    pub refresh_token_ttl_secs: Option<i64>,
+}
+
+// secret is only ever shown in this response
+#[derive(Debug, Serialize)]
+pub struct ClientSecretResponse {
+    pub client: ClientResponse,
+    pub client_secret: Option<String>,
+}
+
+// codes must not end up on plain http outside the own machine, or in a scheme any app can claim
+fn validate_redirect_uris(uris: &[String], private_schemes: &[String]) -> Result<(), AppError> {
+    for uri in uris {
+        let parsed = url::Url::parse(uri)
+            .map_err(|_| AppError::ValidationError(format!("Invalid redirect uri: {}", uri)))?;
+
+        let allowed = match parsed.scheme() {
+            "https" => true,
+            "http" => is_loopback(&parsed),
+            scheme => private_schemes.iter().any(|allowed| allowed == scheme),
+        };
+        if !allowed {
+            return Err(AppError::ValidationError(format!(
+                "Redirect uri must be https, http on localhost or an allowed app scheme: {}",
+                uri
+            )));
+        }
+
+        if parsed.fragment().is_some() {
+            return Err(AppError::ValidationError(format!(
+                "Redirect uri must not contain a fragment: {}",
+                uri
+            )));
+        }
+    }
+    Ok(())
+}
+
+// RFC 8252 loopback redirect for native apps
+fn is_loopback(uri: &url::Url) -> bool {
+    match uri.host() {
+        Some(url::Host::Domain(host)) => host == "localhost",
+        Some(url::Host::Ipv4(ip)) => ip == std::net::Ipv4Addr::LOCALHOST,
+        Some(url::Host::Ipv6(ip)) => ip == std::net::Ipv6Addr::LOCALHOST,
+        None => false,
+    }
+}
+
+// clients can only be registered for scopes we know a permission for
+fn validate_scopes(scopes: &[String]) -> Result<(), AppError> {
+    for scope in scopes {
+        scope.parse::<Permission>().map_err(AppError::ValidationError)?;
+    }
+    Ok(())
+}
+
+fn validate_client(client: &OAuthClient, private_schemes: &[String]) -> Result<(), AppError> {
+    validate_redirect_uris(&client.redirect_uris, private_schemes)?;
+    validate_scopes(&client.scopes)?;
+
+    if client.allows_grant(GrantType::AuthorizationCode) && client.redirect_uris.is_empty() {
+        return Err(AppError::ValidationError(
+            "authorization_code requires at least one redirect uri".to_string(),
+        ));
+    }
+
+    if client.allows_grant(GrantType::ClientCredentials) && !client.is_confidential() {
+        return Err(AppError::ValidationError(
+            "client_credentials requires a confidential client".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+async fn find_client(state: &web::Data<AppState>, client_id: &str) -> Result<OAuthClient, AppError> {
+    state
+        .client_repository
+        .find_by_client_id(client_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Client not found".to_string()))
+}
+
+pub async fn create_client(
+    admin: RequireScope<scope::ClientsWrite>,
+    state: web::Data<AppState>,
+    body: web::Json<CreateClientRequest>,
+) -> Result<HttpResponse, AppError> {
+    body.validate()
+        .map_err(|e| AppError::ValidationError(e.to_string()))?;
+
+    let admin_claims = admin.claims;
+    let body = body.into_inner();
+
+    let client_secret = body.confidential.then(generate_opaque_token);
+    let client_secret_hash = match &client_secret {
+        Some(secret) => Some(state.auth_provider.password_hasher().hash(secret).await?),
+        None => None,
+    };
+
+    let client = OAuthClient::new(
+        body.name,
+        client_secret_hash,
+        body.redirect_uris,
+        body.grant_types,
+        body.scopes,
+        body.access_token_ttl_secs
+            .unwrap_or(state.jwt_service.expiration_secs()),
+        body.refresh_token_ttl_secs
+            .unwrap_or(DEFAULT_REFRESH_TOKEN_TTL_SECS),
+    );
+
+    validate_client(&client, &state.redirect_uri_schemes)?;
+
+    state.client_repository.create(&client).await?;
+
+    tracing::info!(
+        admin_id = %admin_claims.sub,
+        client_id = %client.client_id,
+        name = %client.name,
+        "Admin registered oauth client"
+    );
+
+    Ok(HttpResponse::Created().json(ClientSecretResponse {
+        client: client.into(),
+        client_secret,
+    }))
+}
+
+pub async fn list_clients(
+    _admin: RequireScope<scope::ClientsRead>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let clients: Vec<ClientResponse> = state
+        .client_repository
+        .list()
+        .await?
+        .into_iter()
+        .map(Into::into)
+        .collect();
+
+    Ok(HttpResponse::Ok().json(clients))
+}
+
+pub async fn get_client(
+    _admin: RequireScope<scope::ClientsRead>,
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, AppError> {
+    let client = find_client(&state, &path).await?;
+
+    Ok(HttpResponse::Ok().json(ClientResponse::from(client)))
+}
+
+pub async fn update_client(
+    admin: RequireScope<scope::ClientsWrite>,
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+    body: web::Json<UpdateClientRequest>,
+) -> Result<HttpResponse, AppError> {
+    body.validate()
+        .map_err(|e| AppError::ValidationError(e.to_string()))?;
+
+    let admin_claims = admin.claims;
+    let body = body.into_inner();
+
+    let mut client = find_client(&state, &path).await?;
+
+    if let Some(name) = body.name {
+        client.name = name;
+    }
+    if let Some(redirect_uris) = body.redirect_uris {
+        client.redirect_uris = redirect_uris;
+    }
+    if let Some(grant_types) = body.grant_types {
+        client.grant_types = grant_types;
+    }
+    if let Some(scopes) = body.scopes {
+        client.scopes = scopes;
+    }
+    if let Some(ttl) = body.access_token_ttl_secs {
+        client.access_token_ttl_secs = ttl;
+    }
+    if let Some(ttl) = body.refresh_token_ttl_secs {
+        client.refresh_token_ttl_secs = ttl;
+    }
+    client.updated_at = Utc::now();
+
+    validate_client(&client, &state.redirect_uri_schemes)?;
+
+    state.client_repository.update(&client).await?;
+
+    tracing::info!(
+        admin_id = %admin_claims.sub,
+        client_id = %client.client_id,
+        "Admin updated oauth client"
+    );
+
+    Ok(HttpResponse::Ok().json(ClientResponse::from(client)))
+}
+
+pub async fn delete_client(
+    admin: RequireScope<scope::ClientsWrite>,
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, AppError> {
+    let admin_claims = admin.claims;
+
+    state.client_repository.delete(&path).await?;
+
+    tracing::info!(
+        admin_id = %admin_claims.sub,
+        client_id = %path,
+        "Admin deleted oauth client"
+    );
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+pub async fn regenerate_client_secret(
+    admin: RequireScope<scope::ClientsWrite>,
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, AppError> {
+    let admin_claims = admin.claims;
+
+    let mut client = find_client(&state, &path).await?;
+
+    if !client.is_confidential() {
+        return Err(AppError::ValidationError(
+            "Public clients have no secret".to_string(),
+        ));
+    }
+
+    let client_secret = generate_opaque_token();
+    client.client_secret_hash = Some(state.auth_provider.password_hasher().hash(&client_secret).await?);
+    client.updated_at = Utc::now();
+
+    state.client_repository.update(&client).await?;
+
+    tracing::info!(
+        admin_id = %admin_claims.sub,
+        client_id = %client.client_id,
+        "Admin regenerated oauth client secret"
+    );
+
+    Ok(HttpResponse::Ok().json(ClientSecretResponse {
+        client: client.into(),
+        client_secret: Some(client_secret),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_redirect_uris() {
+        let schemes = ["at.tgm.app".to_string()];
+        let valid = |uri: &str| validate_redirect_uris(&[uri.to_string()], &schemes).is_ok();
+
+        assert!(valid("https://app.example.com/cb"));
+        assert!(valid("http://localhost:8080/cb"));
+        assert!(valid("http://127.0.0.1:53682/cb"));
+        assert!(valid("http://[::1]/cb"));
+        assert!(valid("at.tgm.app:/oauth/cb"));
+        assert!(!valid("not a url"));
+        assert!(!valid("https://app.example.com/cb#frag"));
+    }
+
+    #[test]
+    fn test_validate_redirect_uris_rejects_unsafe_schemes() {
+        let schemes = ["at.tgm.app".to_string()];
+        let valid = |uri: &str| validate_redirect_uris(&[uri.to_string()], &schemes).is_ok();
+
+        assert!(!valid("http://app.example.com/cb"));
+        assert!(!valid("http://localhost.example.com/cb"));
+        assert!(!valid("http://127.0.0.2/cb"));
+        assert!(!valid("javascript:alert(1)"));
+        assert!(!valid("data:text/html,hi"));
+        assert!(!valid("file:///etc/passwd"));
+        assert!(!valid("com.other.app:/cb"));
+        // one bad uri fails the whole list
+        assert!(validate_redirect_uris(
+            &["https://app.example.com/cb".to_string(), "ftp://app.example.com/cb".to_string()],
+            &schemes,
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_validate_scopes() {
+        assert!(validate_scopes(&["openid".to_string(), "users:read".to_string()]).is_ok());
+        assert!(validate_scopes(&["".to_string()]).is_err());
+        assert!(validate_scopes(&["two words".to_string()]).is_err());
+        assert!(validate_scopes(&["reports:read".to_string()]).is_err());
+    }
+}
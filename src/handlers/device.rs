@@ -0,0 +1,168 @@
+// device authorization endpoints (RFC 8628), polling happens at /oauth/token
+use actix_web::{http::StatusCode, web, HttpRequest, HttpResponse};
+use serde::{Deserialize, Serialize};
+
+use crate::error::AppError;
+use crate::models::{grantable_scopes, parse_scope, Consent, GrantType};
+use super::auth::{client_ip, AppState};
+use super::extractors::AuthenticatedUser;
+use super::token::{authenticate_client, invalid_client, oauth_error};
+
+#[derive(Debug, Deserialize)]
+pub struct DeviceAuthorizationRequest {
+    pub client_id: Option<String>,
+    pub client_secret: Option<String>,
+    pub scope: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeviceInfoQuery {
+    pub user_code: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DeviceInfoResponse {
+    pub client_name: String,
+    pub scopes: Vec<String>,
+    // not granted to this client before, empty if the user already agreed to everything
+    pub new_scopes: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeviceVerifyRequest {
+    pub user_code: String,
+    pub approve: bool,
+}
+
+pub async fn device_authorization(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    body: web::Form<DeviceAuthorizationRequest>,
+) -> Result<HttpResponse, AppError> {
+    let Some(client) = authenticate_client(
+        &req,
+        &state,
+        body.client_id.as_deref(),
+        body.client_secret.as_deref(),
+    )
+    .await?
+    else {
+        return Ok(invalid_client());
+    };
+
+    if !client.allows_grant(GrantType::DeviceCode) {
+        return Ok(oauth_error(StatusCode::BAD_REQUEST, "unauthorized_client", None));
+    }
+
+    // no scope param means everything the client is registered for
+    let requested = match body.scope.as_deref() {
+        Some(scope) => parse_scope(scope),
+        None => client.scopes.clone(),
+    };
+
+    if let Some(unknown) = requested.iter().find(|s| !client.scopes.contains(s)) {
+        tracing::warn!(client_id = %client.client_id, scope = %unknown, "client requested unknown scope");
+        return Ok(oauth_error(StatusCode::BAD_REQUEST, "invalid_scope", None));
+    }
+
+    let response = state.device_flow.start(&client, requested).await?;
+
+    Ok(HttpResponse::Ok()
+        .insert_header(("Cache-Control", "no-store"))
+        .json(response))
+}
+
+// consent page: shown on the verification page before the user approves
+pub async fn device_info(
+    req: HttpRequest,
+    AuthenticatedUser(claims): AuthenticatedUser,
+    state: web::Data<AppState>,
+    query: web::Query<DeviceInfoQuery>,
+) -> Result<HttpResponse, AppError> {
+    let authorization = state
+        .login_throttle
+        .guard_user_code(&claims.sub, &client_ip(&req), state.device_flow.find_pending(&query.user_code))
+        .await?;
+
+    let client = state
+        .client_repository
+        .find_by_client_id(&authorization.client_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Unknown or expired code".to_string()))?;
+
+    let new_scopes = match state
+        .consent_repository
+        .find(&claims.sub, &client.client_id)
+        .await?
+    {
+        Some(consent) => consent.missing_scopes(&authorization.scopes),
+        None => authorization.scopes.clone(),
+    };
+
+    Ok(HttpResponse::Ok().json(DeviceInfoResponse {
+        client_name: client.name,
+        scopes: authorization.scopes,
+        new_scopes,
+    }))
+}
+
+pub async fn device_verify(
+    req: HttpRequest,
+    AuthenticatedUser(claims): AuthenticatedUser,
+    state: web::Data<AppState>,
+    body: web::Json<DeviceVerifyRequest>,
+) -> Result<HttpResponse, AppError> {
+    // token could outlive a deactivated account
+    let user = state
+        .repository
+        .find_by_id(&claims.sub)
+        .await?
+        .filter(|u| u.is_active)
+        .ok_or_else(|| AppError::Forbidden("User not found".to_string()))?;
+
+    // guessing a code and approving it for the own account would hand it to the victim's cli
+    let ip = client_ip(&req);
+    if body.approve {
+        let authorization = state
+            .login_throttle
+            .guard_user_code(&user.id, &ip, state.device_flow.approve(&body.user_code, &claims.sub))
+            .await?;
+        let granted = grantable_scopes(user.role, &authorization.scopes);
+        record_consent(&state, &user.id, &authorization.client_id, &granted).await?;
+    } else {
+        state
+            .login_throttle
+            .guard_user_code(&user.id, &ip, state.device_flow.deny(&body.user_code, &claims.sub))
+            .await?;
+    }
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "approved": body.approve
+    })))
+}
+
+async fn record_consent(
+    state: &web::Data<AppState>,
+    user_id: &str,
+    client_id: &str,
+    scopes: &[String],
+) -> Result<(), AppError> {
+    let consent = match state.consent_repository.find(user_id, client_id).await? {
+        Some(mut consent) => {
+            consent.grant(scopes);
+            consent
+        }
+        None => Consent::new(user_id.to_string(), client_id.to_string(), scopes.to_vec()),
+    };
+
+    state.consent_repository.save(&consent).await?;
+
+    tracing::info!(
+        user_id = %user_id,
+        client_id = %client_id,
+        scopes = %consent.scopes.join(" "),
+        "consent recorded"
+    );
+
+    Ok(())
+}
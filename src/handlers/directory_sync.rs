@@ -0,0 +1,34 @@
+// admin view of the background ad sync, can also be started by hand
+use actix_web::{web, HttpResponse};
+
+use crate::auth::DirectorySyncService;
+use crate::error::AppError;
+use super::auth::AppState;
+use super::extractors::{scope, RequireScope};
+
+fn directory_sync(state: &AppState) -> Result<&DirectorySyncService, AppError> {
+    state
+        .directory_sync
+        .as_deref()
+        .ok_or_else(|| AppError::NotFound("Directory sync not configured".to_string()))
+}
+
+pub async fn sync_status(
+    _admin: RequireScope<scope::UsersRead>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let report = directory_sync(&state)?.last_report();
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({ "last_run": report })))
+}
+
+pub async fn run_sync(
+    admin: RequireScope<scope::UsersWrite>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    tracing::info!(admin_id = %admin.claims.sub, "Directory sync started by admin");
+
+    let report = directory_sync(&state)?.run().await?;
+
+    Ok(HttpResponse::Ok().json(report))
+}
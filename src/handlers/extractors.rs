@@ -0,0 +1,110 @@
+// bearer token extractors for handlers, RequireScope<S> also checks the scope claim
+use actix_web::{dev::Payload, web, FromRequest, HttpRequest};
+use std::future::Future;
+use std::marker::PhantomData;
+use std::pin::Pin;
+
+use crate::auth::Claims;
+use crate::error::AppError;
+use crate::models::Permission;
+use super::auth::AppState;
+
+pub struct AuthenticatedUser(pub Claims);
+
+impl AuthenticatedUser {
+    pub fn require_scope(&self, permission: Permission) -> Result<(), AppError> {
+        if self.0.has_scope(permission.as_str()) {
+            return Ok(());
+        }
+
+        tracing::warn!(
+            user_id = %self.0.sub,
+            scope = %permission,
+            "Token without required scope"
+        );
+        Err(AppError::Forbidden(format!("Missing scope: {}", permission)))
+    }
+}
+
+type ExtractFuture<T> = Pin<Box<dyn Future<Output = Result<T, AppError>>>>;
+
+// one user lookup per request, tokens from before the last password change are refused
+async fn bearer_claims(req: HttpRequest) -> Result<Claims, AppError> {
+    let state = req
+        .app_data::<web::Data<AppState>>()
+        .ok_or_else(|| AppError::InternalError("App state not configured".to_string()))?;
+
+    let token = req
+        .headers()
+        .get("Authorization")
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "))
+        .ok_or_else(|| AppError::Unauthorized("Authorization header required".to_string()))?;
+
+    let claims = state.jwt_service.validate_token(token)?;
+
+    if let Some(user) = state.repository.find_by_id(&claims.sub).await?
+        && user.token_predates_password_change(claims.iat)
+    {
+        tracing::warn!(user_id = %claims.sub, "Token issued before the last password change");
+        return Err(AppError::Unauthorized("Token no longer valid".to_string()));
+    }
+
+    Ok(claims)
+}
+
+impl FromRequest for AuthenticatedUser {
+    type Error = AppError;
+    type Future = ExtractFuture<Self>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let req = req.clone();
+        Box::pin(async move { bearer_claims(req).await.map(AuthenticatedUser) })
+    }
+}
+
+pub trait ScopeRequirement {
+    const PERMISSION: Permission;
+}
+
+pub struct RequireScope<S: ScopeRequirement> {
+    pub claims: Claims,
+    _scope: PhantomData<S>,
+}
+
+impl<S: ScopeRequirement + 'static> FromRequest for RequireScope<S> {
+    type Error = AppError;
+    type Future = ExtractFuture<Self>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let req = req.clone();
+        Box::pin(async move {
+            let user = AuthenticatedUser(bearer_claims(req).await?);
+            user.require_scope(S::PERMISSION)?;
+            Ok(RequireScope {
+                claims: user.0,
+                _scope: PhantomData,
+            })
+        })
+    }
+}
+
+// marker types for RequireScope<scope::UsersWrite> etc
+pub mod scope {
+    use super::ScopeRequirement;
+    use crate::models::Permission;
+
+    macro_rules! scope_marker {
+        ($($name:ident),*) => {
+            $(
+                pub struct $name;
+
+                impl ScopeRequirement for $name {
+                    const PERMISSION: Permission = Permission::$name;
+                }
+            )*
+        };
+    }
+
+    scope_marker!(OpenId, Profile, Email, UsersRead, UsersWrite, ClientsRead, ClientsWrite);
+}
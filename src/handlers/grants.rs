@@ -0,0 +1,61 @@
+// apps the user has granted access to, revoking also kills their refresh tokens
+use actix_web::{web, HttpResponse};
+
+use crate::error::AppError;
+use crate::models::GrantResponse;
+use super::auth::AppState;
+use super::extractors::AuthenticatedUser;
+
+pub async fn list_grants(
+    AuthenticatedUser(claims): AuthenticatedUser,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let consents = state.consent_repository.list_for_user(&claims.sub).await?;
+
+    let mut grants = Vec::with_capacity(consents.len());
+    for consent in consents {
+        // client could be deleted by an admin in the meantime
+        let client_name = state
+            .client_repository
+            .find_by_client_id(&consent.client_id)
+            .await?
+            .map(|c| c.name)
+            .unwrap_or_else(|| consent.client_id.clone());
+
+        grants.push(GrantResponse {
+            client_id: consent.client_id,
+            client_name,
+            scopes: consent.scopes,
+            granted_at: consent.created_at,
+            updated_at: consent.updated_at,
+        });
+    }
+
+    Ok(HttpResponse::Ok().json(grants))
+}
+
+pub async fn revoke_grant(
+    AuthenticatedUser(claims): AuthenticatedUser,
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, AppError> {
+    let client_id = path.into_inner();
+
+    if !state.consent_repository.delete(&claims.sub, &client_id).await? {
+        return Err(AppError::NotFound("Grant not found".to_string()));
+    }
+
+    let revoked = state
+        .refresh_token_repository
+        .revoke_for_client(&claims.sub, &client_id)
+        .await?;
+
+    tracing::info!(
+        user_id = %claims.sub,
+        client_id = %client_id,
+        revoked_tokens = revoked,
+        "User revoked app grant"
+    );
+
+    Ok(HttpResponse::NoContent().finish())
+}
@@ -0,0 +1,34 @@
+// admin unlock after too many failed logins, the lock would also run out by itself
+use actix_web::{web, HttpResponse};
+
+use crate::auth::LoginThrottle;
+use crate::error::AppError;
+use crate::models::AuthProviderType;
+use super::auth::AppState;
+use super::extractors::{scope, RequireScope};
+
+pub async fn unlock_user(
+    admin: RequireScope<scope::UsersWrite>,
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, AppError> {
+    let user = state
+        .repository
+        .find_by_id(&path)
+        .await?
+        .ok_or_else(|| AppError::NotFound("User not found".to_string()))?;
+
+    // directory users log in with their username, local ones with the email
+    let mut subjects = vec![LoginThrottle::local_subject(&user.email)];
+    if matches!(user.auth_provider, AuthProviderType::ActiveDirectory | AuthProviderType::Ldap)
+        && let Some(username) = &user.external_id
+    {
+        subjects.push(LoginThrottle::ldap_subject(username));
+    }
+
+    let cleared = state.login_throttle.unlock(&subjects).await?;
+
+    tracing::info!(admin_id = %admin.claims.sub, user_id = %user.id, cleared = cleared, "Login lockout cleared by admin");
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({ "cleared": cleared })))
+}
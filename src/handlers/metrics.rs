@@ -0,0 +1,13 @@
+// argon2 latency and queue of the hashing pool, to tune ARGON2_MAX_CONCURRENT
+use actix_web::{web, HttpResponse};
+
+use crate::error::AppError;
+use super::auth::AppState;
+use super::extractors::{scope, RequireScope};
+
+pub async fn hashing_metrics(
+    _admin: RequireScope<scope::UsersRead>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    Ok(HttpResponse::Ok().json(state.auth_provider.password_hasher().metrics()))
+}
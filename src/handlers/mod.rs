@@ -1,6 +1,16 @@
 //! HTTP request handlers for the authentication service.
 
 mod auth;
+mod extractors;
 pub mod oauth;
+pub mod clients;
+pub mod device;
+pub mod token;
+pub mod grants;
+pub mod directory_sync;
+pub mod password_reset;
+pub mod metrics;
+pub mod lockout;
 
 pub use auth::{register_user, signin, verify_token, configure_routes, AppState};
+pub use extractors::{AuthenticatedUser, RequireScope, ScopeRequirement, scope};
@@ -51,7 +51,7 @@ pub async fn google_callback(state: web::Data<AppState>, query: web::Query<OAuth
 
     let google_user = google_provider.exchange_code(&query.code).await?;
 
-    let (auth_result, is_new_user) = google_provider
+    let (auth_result, _is_new_user) = google_provider
         .authenticate_or_create(google_user)
         .await?;
 
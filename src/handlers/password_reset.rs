@@ -0,0 +1,79 @@
+// forgot password, both endpoints without token
+use actix_web::{web, HttpRequest, HttpResponse};
+use serde::Deserialize;
+use std::sync::Arc;
+use validator::Validate;
+
+use crate::auth::PasswordResetService;
+use crate::error::AppError;
+use super::auth::{client_ip, AppState};
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct ForgotPasswordRequest {
+    #[validate(email(message = "Invalid email"))]
+    pub email: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct ResetPasswordRequest {
+    #[validate(length(min = 1, message = "Token required"))]
+    pub token: String,
+
+    #[validate(length(min = 1, message = "Password required"))]
+    pub new_password: String,
+}
+
+fn reset_service(state: &AppState) -> Result<Arc<PasswordResetService>, AppError> {
+    state.password_reset.clone().ok_or_else(|| {
+        tracing::warn!("password reset without mail config");
+        AppError::ValidationError("password reset not activated".to_string())
+    })
+}
+
+pub async fn forgot_password(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    body: web::Json<ForgotPasswordRequest>,
+) -> Result<HttpResponse, AppError> {
+    body.validate()
+        .map_err(|e| AppError::ValidationError(e.to_string()))?;
+
+    let service = reset_service(&state)?;
+    let email = body.into_inner().email;
+
+    // over the limit gets the same answer, the request is just dropped
+    let config = service.config();
+    let allowed = state
+        .login_throttle
+        .allow_reset_request(&email, &client_ip(&req), config.max_requests_per_email, config.max_requests_per_ip)
+        .await;
+
+    // lookup and mail delivery in the background, same answer and timing for every address
+    if allowed {
+        tokio::spawn(async move {
+            if let Err(e) = service.request_reset(&email).await {
+                tracing::error!(error = %e, "password reset request failed");
+            }
+        });
+    }
+
+    Ok(HttpResponse::Accepted().json(serde_json::json!({
+        "message": "If the account exists, a reset link has been sent"
+    })))
+}
+
+pub async fn reset_password(
+    state: web::Data<AppState>,
+    body: web::Json<ResetPasswordRequest>,
+) -> Result<HttpResponse, AppError> {
+    body.validate()
+        .map_err(|e| AppError::ValidationError(e.to_string()))?;
+
+    reset_service(&state)?
+        .reset_password(&body.token, &body.new_password)
+        .await?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "message": "Password reset"
+    })))
+}
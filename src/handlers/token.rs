@@ -0,0 +1,309 @@
+// oauth token endpoint (RFC 6749 section 3.2), errors use the oauth error format instead of AppError
+use actix_web::{http::StatusCode, web, HttpRequest, HttpResponse};
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+
+use crate::auth::{generate_opaque_token, hash_opaque_token, DevicePollOutcome};
+use crate::error::AppError;
+use crate::models::{format_scope, grantable_scopes, GrantType, OAuthClient, RefreshToken, User};
+use super::auth::AppState;
+
+#[derive(Debug, Deserialize)]
+pub struct TokenRequest {
+    pub grant_type: String,
+    pub device_code: Option<String>,
+    pub refresh_token: Option<String>,
+    pub client_id: Option<String>,
+    pub client_secret: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TokenResponse {
+    pub access_token: String,
+    pub token_type: String,
+    pub expires_in: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub refresh_token: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct OAuthErrorResponse {
+    error: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error_description: Option<String>,
+}
+
+pub(crate) fn oauth_error(status: StatusCode, error: &'static str, description: Option<&str>) -> HttpResponse {
+    HttpResponse::build(status)
+        .insert_header(("Cache-Control", "no-store"))
+        .json(OAuthErrorResponse {
+            error,
+            error_description: description.map(str::to_string),
+        })
+}
+
+pub(crate) fn invalid_client() -> HttpResponse {
+    HttpResponse::Unauthorized()
+        .insert_header(("WWW-Authenticate", "Basic"))
+        .insert_header(("Cache-Control", "no-store"))
+        .json(OAuthErrorResponse {
+            error: "invalid_client",
+            error_description: None,
+        })
+}
+
+fn basic_credentials(req: &HttpRequest) -> Option<(String, String)> {
+    let encoded = req
+        .headers()
+        .get("Authorization")?
+        .to_str()
+        .ok()?
+        .strip_prefix("Basic ")?;
+
+    let decoded = base64::engine::general_purpose::STANDARD.decode(encoded).ok()?;
+    let decoded = String::from_utf8(decoded).ok()?;
+    let (id, secret) = decoded.split_once(':')?;
+
+    Some((form_decode(id)?, form_decode(secret)?))
+}
+
+// a ':' in the id or secret arrives as %3A, a space as +
+fn form_decode(value: &str) -> Option<String> {
+    percent_encoding::percent_decode_str(&value.replace('+', " "))
+        .decode_utf8()
+        .ok()
+        .map(|value| value.into_owned())
+}
+
+// client_secret_basic or client_secret_post, public clients only send their client_id
+pub(crate) async fn authenticate_client(
+    req: &HttpRequest,
+    state: &web::Data<AppState>,
+    client_id: Option<&str>,
+    client_secret: Option<&str>,
+) -> Result<Option<OAuthClient>, AppError> {
+    let (client_id, client_secret) = match basic_credentials(req) {
+        Some((id, secret)) => (id, Some(secret)),
+        None => match client_id {
+            Some(id) => (id.to_string(), client_secret.map(str::to_string)),
+            None => return Ok(None),
+        },
+    };
+
+    let Some(client) = state.client_repository.find_by_client_id(&client_id).await? else {
+        tracing::warn!(client_id = %client_id, "token request from unknown client");
+        return Ok(None);
+    };
+
+    let authenticated = match (&client.client_secret_hash, client_secret) {
+        (Some(hash), Some(secret)) => state.auth_provider.password_hasher().verify(&secret, hash).await?,
+        (None, None) => true,
+        _ => false,
+    };
+
+    if !authenticated {
+        tracing::warn!(client_id = %client_id, "client authentication failed");
+        return Ok(None);
+    }
+
+    Ok(Some(client))
+}
+
+// access token + refresh token if the client may use the refresh_token grant
+// scopes are cut down to what the users role allows, role could have changed since the grant
+pub(crate) async fn issue_tokens(
+    state: &web::Data<AppState>,
+    user: &User,
+    client: &OAuthClient,
+    requested: &[String],
+) -> Result<TokenResponse, AppError> {
+    let scopes = grantable_scopes(user.role, requested);
+
+    let access_token = state.jwt_service.generate_scoped_token(
+        &user.id,
+        &user.email,
+        user.role,
+        &scopes,
+        client.access_token_ttl_secs,
+    )?;
+
+    let refresh_token = if client.allows_grant(GrantType::RefreshToken) {
+        let token = generate_opaque_token();
+        state
+            .refresh_token_repository
+            .create(&RefreshToken::new(
+                hash_opaque_token(&token),
+                user.id.clone(),
+                client.client_id.clone(),
+                scopes.clone(),
+                client.refresh_token_ttl_secs,
+            ))
+            .await?;
+        Some(token)
+    } else {
+        None
+    };
+
+    Ok(TokenResponse {
+        access_token,
+        token_type: "Bearer".to_string(),
+        expires_in: client.access_token_ttl_secs,
+        refresh_token,
+        scope: (!scopes.is_empty()).then(|| format_scope(&scopes)),
+    })
+}
+
+fn token_response(response: TokenResponse) -> HttpResponse {
+    HttpResponse::Ok()
+        .insert_header(("Cache-Control", "no-store"))
+        .json(response)
+}
+
+async fn active_user(state: &web::Data<AppState>, user_id: &str) -> Result<Option<User>, AppError> {
+    Ok(state
+        .repository
+        .find_by_id(user_id)
+        .await?
+        .filter(|u| u.is_active))
+}
+
+pub async fn token(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    body: web::Form<TokenRequest>,
+) -> Result<HttpResponse, AppError> {
+    let grant_type: GrantType = match body.grant_type.parse() {
+        Ok(grant_type) => grant_type,
+        Err(_) => {
+            return Ok(oauth_error(StatusCode::BAD_REQUEST, "unsupported_grant_type", None));
+        }
+    };
+
+    let Some(client) = authenticate_client(
+        &req,
+        &state,
+        body.client_id.as_deref(),
+        body.client_secret.as_deref(),
+    )
+    .await?
+    else {
+        return Ok(invalid_client());
+    };
+
+    if !client.allows_grant(grant_type) {
+        return Ok(oauth_error(StatusCode::BAD_REQUEST, "unauthorized_client", None));
+    }
+
+    match grant_type {
+        GrantType::DeviceCode => device_code_grant(&state, &client, body.device_code.as_deref()).await,
+        GrantType::RefreshToken => refresh_token_grant(&state, &client, body.refresh_token.as_deref()).await,
+        _ => Ok(oauth_error(StatusCode::BAD_REQUEST, "unsupported_grant_type", None)),
+    }
+}
+
+async fn device_code_grant(
+    state: &web::Data<AppState>,
+    client: &OAuthClient,
+    device_code: Option<&str>,
+) -> Result<HttpResponse, AppError> {
+    let Some(device_code) = device_code else {
+        return Ok(oauth_error(StatusCode::BAD_REQUEST, "invalid_request", Some("device_code required")));
+    };
+
+    let authorization = match state.device_flow.poll(device_code, &client.client_id).await? {
+        DevicePollOutcome::Approved(authorization) => authorization,
+        DevicePollOutcome::Pending => {
+            return Ok(oauth_error(StatusCode::BAD_REQUEST, "authorization_pending", None));
+        }
+        DevicePollOutcome::SlowDown => {
+            return Ok(oauth_error(StatusCode::BAD_REQUEST, "slow_down", None));
+        }
+        DevicePollOutcome::Denied => {
+            return Ok(oauth_error(StatusCode::BAD_REQUEST, "access_denied", None));
+        }
+        DevicePollOutcome::Expired => {
+            return Ok(oauth_error(StatusCode::BAD_REQUEST, "expired_token", None));
+        }
+        DevicePollOutcome::Invalid => {
+            return Ok(oauth_error(StatusCode::BAD_REQUEST, "invalid_grant", None));
+        }
+    };
+
+    let user_id = authorization.user_id.as_deref().unwrap_or_default();
+    let Some(user) = active_user(state, user_id).await? else {
+        tracing::warn!(user_id = %user_id, "device code approved by user that is gone");
+        return Ok(oauth_error(StatusCode::BAD_REQUEST, "invalid_grant", None));
+    };
+
+    let response = issue_tokens(state, &user, client, &authorization.scopes).await?;
+
+    tracing::info!(
+        user_id = %user.id,
+        client_id = %client.client_id,
+        "tokens issued for device code"
+    );
+
+    Ok(token_response(response))
+}
+
+// refresh tokens are rotated, the presented one is revoked. a rotated token coming back means
+// it leaked (or two requests raced with it), then every token of the user for this client goes
+async fn refresh_token_grant(
+    state: &web::Data<AppState>,
+    client: &OAuthClient,
+    refresh_token: Option<&str>,
+) -> Result<HttpResponse, AppError> {
+    let Some(refresh_token) = refresh_token else {
+        return Ok(oauth_error(StatusCode::BAD_REQUEST, "invalid_request", Some("refresh_token required")));
+    };
+
+    let token_hash = hash_opaque_token(refresh_token);
+
+    let Some(stored) = state
+        .refresh_token_repository
+        .find_by_hash(&token_hash)
+        .await?
+        .filter(|t| t.client_id == client.client_id)
+    else {
+        return Ok(oauth_error(StatusCode::BAD_REQUEST, "invalid_grant", None));
+    };
+
+    if stored.revoked_at.is_some() {
+        return refresh_token_reused(state, &stored.user_id, &client.client_id).await;
+    }
+    if !stored.is_valid() {
+        return Ok(oauth_error(StatusCode::BAD_REQUEST, "invalid_grant", None));
+    }
+
+    let Some(user) = active_user(state, &stored.user_id).await? else {
+        return Ok(oauth_error(StatusCode::BAD_REQUEST, "invalid_grant", None));
+    };
+
+    // only the request that actually revokes it gets new tokens
+    if !state.refresh_token_repository.revoke(&token_hash).await? {
+        return refresh_token_reused(state, &user.id, &client.client_id).await;
+    }
+
+    let response = issue_tokens(state, &user, client, &stored.scopes).await?;
+
+    Ok(token_response(response))
+}
+
+async fn refresh_token_reused(
+    state: &web::Data<AppState>,
+    user_id: &str,
+    client_id: &str,
+) -> Result<HttpResponse, AppError> {
+    let revoked = state.refresh_token_repository.revoke_for_client(user_id, client_id).await?;
+
+    tracing::warn!(
+        user_id = %user_id,
+        client_id = %client_id,
+        revoked,
+        "revoked refresh token reused, all refresh tokens of this client revoked"
+    );
+
+    Ok(oauth_error(StatusCode::BAD_REQUEST, "invalid_grant", None))
+}
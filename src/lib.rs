@@ -5,18 +5,21 @@
 //! - JWT token generation and validation
 //! - Google OAuth 2.0
 //! - LDAP/Active Directory authentication
+//! - OAuth 2.0 device authorization grant for registered clients
+//! - Password reset links by mail (SMTP or file outbox)
 
 pub mod auth;
 pub mod config;
 pub mod error;
 pub mod handlers;
+pub mod mail;
 pub mod models;
 pub mod repository;
 
 // Re-export commonly used types
 pub use auth::{JwtService, LocalAuthProvider, GoogleAuthProvider, LdapAuthProvider, Claims, AuthProvider, AuthResult, PasswordHasher};
-pub use config::{Config, JwtConfig, GoogleOAuthConfig, LdapConfig, InitialAdminConfig};
+pub use config::{Config, JwtConfig, GoogleOAuthConfig, LdapConfig, InitialAdminConfig, DeviceFlowConfig, PasswordResetConfig, MailConfig, PasswordPolicyConfig, Argon2Config, PepperConfig, LockoutConfig, GroupRoleMapping, NestedGroupMode, GroupMembership, TlsVersion};
 pub use error::AppError;
-pub use handlers::{AppState, configure_routes};
-pub use models::{User, UserRole, AuthProviderType, UserResponse, CreateUser};
-pub use repository::{UserRepository, SqliteUserRepository};
+pub use handlers::{AppState, configure_routes, AuthenticatedUser, RequireScope};
+pub use models::{User, UserRole, AuthProviderType, UserResponse, CreateUser, OAuthClient, GrantType, Permission};
+pub use repository::{UserRepository, SqliteUserRepository, ClientRepository, SqliteClientRepository};
@@ -0,0 +1,37 @@
+//! Outgoing mail (password reset links).
+//!
+//! - `smtp`: delivery over SMTP with lettre
+//! - `outbox`: every mail as json file in a directory, for tests and local development
+
+mod outbox;
+mod smtp;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use crate::config::MailConfig;
+use crate::error::AppError;
+
+pub use outbox::FileOutboxMailer;
+pub use smtp::SmtpMailer;
+
+// plain text only, the sender comes from the mailer config
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Mail {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+#[async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, mail: &Mail) -> Result<(), AppError>;
+}
+
+pub fn mailer_from_config(config: &MailConfig) -> Result<Arc<dyn Mailer>, AppError> {
+    Ok(match config {
+        MailConfig::Smtp(smtp) => Arc::new(SmtpMailer::new(smtp)?),
+        MailConfig::Outbox { dir, from } => Arc::new(FileOutboxMailer::new(dir, from)?),
+    })
+}
@@ -0,0 +1,118 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+use crate::error::AppError;
+use super::{Mail, Mailer};
+
+// one file per mail, name starts with the timestamp so ls shows them in order
+pub struct FileOutboxMailer {
+    dir: PathBuf,
+    from: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct OutboxEntry {
+    from: String,
+    sent_at: DateTime<Utc>,
+    #[serde(flatten)]
+    mail: Mail,
+}
+
+impl FileOutboxMailer {
+    pub fn new(dir: impl Into<PathBuf>, from: &str) -> Result<Self, AppError> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)
+            .map_err(|e| AppError::InternalError(format!("Cannot create outbox {}: {}", dir.display(), e)))?;
+
+        Ok(Self {
+            dir,
+            from: from.to_string(),
+        })
+    }
+
+    // oldest first
+    pub fn messages(&self) -> Result<Vec<Mail>, AppError> {
+        let read_error = |e: std::io::Error| AppError::InternalError(format!("Cannot read outbox: {}", e));
+
+        let mut paths: Vec<PathBuf> = std::fs::read_dir(&self.dir)
+            .map_err(read_error)?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+            .collect();
+        paths.sort();
+
+        paths
+            .iter()
+            .map(|path| {
+                let content = std::fs::read_to_string(path).map_err(read_error)?;
+                let entry: OutboxEntry = serde_json::from_str(&content)
+                    .map_err(|e| AppError::InternalError(format!("Invalid outbox file {}: {}", path.display(), e)))?;
+                Ok(entry.mail)
+            })
+            .collect()
+    }
+}
+
+#[async_trait]
+impl Mailer for FileOutboxMailer {
+    async fn send(&self, mail: &Mail) -> Result<(), AppError> {
+        let entry = OutboxEntry {
+            from: self.from.clone(),
+            sent_at: Utc::now(),
+            mail: mail.clone(),
+        };
+        let path = self.dir.join(format!(
+            "{}-{}.json",
+            entry.sent_at.format("%Y%m%dT%H%M%S%.6f"),
+            uuid::Uuid::new_v4()
+        ));
+
+        let content = serde_json::to_string_pretty(&entry)
+            .map_err(|e| AppError::InternalError(format!("Cannot serialize mail: {}", e)))?;
+        tokio::fs::write(&path, content)
+            .await
+            .map_err(|e| AppError::InternalError(format!("Cannot write {}: {}", path.display(), e)))?;
+
+        tracing::info!(to = %mail.to, path = %path.display(), "mail written to outbox");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mail(subject: &str) -> Mail {
+        Mail {
+            to: "max@example.com".to_string(),
+            subject: subject.to_string(),
+            body: "Hallo".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_outbox_roundtrip_in_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let outbox = FileOutboxMailer::new(dir.path().join("outbox"), "auth@example.com").unwrap();
+
+        outbox.send(&mail("first")).await.unwrap();
+        outbox.send(&mail("second")).await.unwrap();
+
+        assert_eq!(outbox.messages().unwrap(), vec![mail("first"), mail("second")]);
+    }
+
+    #[tokio::test]
+    async fn test_outbox_file_has_sender() {
+        let dir = tempfile::tempdir().unwrap();
+        let outbox = FileOutboxMailer::new(dir.path(), "auth@example.com").unwrap();
+
+        outbox.send(&mail("subject")).await.unwrap();
+
+        let file = std::fs::read_dir(dir.path()).unwrap().next().unwrap().unwrap();
+        let json: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(file.path()).unwrap()).unwrap();
+        assert_eq!(json["from"], "auth@example.com");
+        assert_eq!(json["to"], "max@example.com");
+    }
+}
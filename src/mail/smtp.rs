@@ -0,0 +1,84 @@
+use async_trait::async_trait;
+use lettre::message::header::ContentType;
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+
+use crate::config::{SmtpConfig, SmtpSecurity};
+use crate::error::AppError;
+use super::{Mail, Mailer};
+
+pub struct SmtpMailer {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl SmtpMailer {
+    pub fn new(config: &SmtpConfig) -> Result<Self, AppError> {
+        let builder = match config.security {
+            SmtpSecurity::StartTls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.host),
+            SmtpSecurity::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(&config.host),
+            SmtpSecurity::None => Ok(AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&config.host)),
+        }
+        .map_err(|e| AppError::InternalError(format!("Invalid SMTP host {}: {}", config.host, e)))?;
+
+        let mut builder = builder.port(config.port);
+        if let (Some(username), Some(password)) = (&config.username, &config.password) {
+            builder = builder.credentials(Credentials::new(username.clone(), password.clone()));
+        }
+
+        Ok(Self {
+            transport: builder.build(),
+            from: parse_mailbox(&config.from)?,
+        })
+    }
+}
+
+fn parse_mailbox(address: &str) -> Result<Mailbox, AppError> {
+    address
+        .parse()
+        .map_err(|e| AppError::InternalError(format!("Invalid mail address {}: {}", address, e)))
+}
+
+#[async_trait]
+impl Mailer for SmtpMailer {
+    async fn send(&self, mail: &Mail) -> Result<(), AppError> {
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(parse_mailbox(&mail.to)?)
+            .subject(&mail.subject)
+            .header(ContentType::TEXT_PLAIN)
+            .body(mail.body.clone())
+            .map_err(|e| AppError::InternalError(format!("Cannot build mail: {}", e)))?;
+
+        self.transport
+            .send(message)
+            .await
+            .map_err(|e| AppError::InternalError(format!("SMTP delivery failed: {}", e)))?;
+
+        tracing::debug!(to = %mail.to, "mail sent via SMTP");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(from: &str) -> SmtpConfig {
+        SmtpConfig {
+            host: "localhost".to_string(),
+            port: 1025,
+            security: SmtpSecurity::None,
+            username: None,
+            password: None,
+            from: from.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_sender_address_checked_at_startup() {
+        assert!(SmtpMailer::new(&config("Auth Service <auth@example.com>")).is_ok());
+        assert!(SmtpMailer::new(&config("not an address")).is_err());
+    }
+}
@@ -1,17 +1,24 @@
 use std::fs;
 use std::path::Path;
 use std::sync::Arc;
+use std::time::Duration;
 
 use actix_web::{web, App, HttpServer, middleware::Logger};
 use actix_files::Files;
 use sqlx::sqlite::SqlitePoolOptions;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
-use syt_ek962_security_concepts::auth::{JwtService, LocalAuthProvider, GoogleAuthProvider, LdapAuthProvider};
+use syt_ek962_security_concepts::auth::{JwtService, LocalAuthProvider, GoogleAuthProvider, LdapAuthProvider, DeviceFlowService, DirectorySyncService, InMemoryDirectory, LoginThrottle, PasswordHasher, PasswordHistory, PasswordPolicy, PasswordResetService};
 use syt_ek962_security_concepts::config::{Config, InitialAdminConfig};
 use syt_ek962_security_concepts::handlers::{configure_routes, AppState};
+use syt_ek962_security_concepts::mail::mailer_from_config;
 use syt_ek962_security_concepts::models::UserRole;
-use syt_ek962_security_concepts::repository::{SqliteUserRepository, UserRepository};
+use syt_ek962_security_concepts::repository::{
+    ClientRepository, ConsentRepository, CredentialCacheRepository, RefreshTokenRepository,
+    SqliteClientRepository, SqliteConsentRepository, SqliteCredentialCacheRepository, SqliteDeviceAuthorizationRepository, SqliteRefreshTokenRepository,
+    SqliteLoginAttemptRepository,
+    SqlitePasswordHistoryRepository, SqlitePasswordResetRepository, SqliteUserRepository, UserRepository,
+};
 
 async fn initialize_admin(
     config_path: &str,
@@ -44,19 +51,12 @@ async fn initialize_admin(
     if let Some(password_hash) = &admin_config.password_hash {
         tracing::info!("pre hashed pw");
 
-        let user = syt_ek962_security_concepts::models::User::new_local(
-            admin_config.name.clone(),
-            admin_config.email.to_lowercase(),
-            password_hash.clone(),
-            UserRole::Admin,
-        );
-
-        repository.create(&user).await?;
+        // argon2 or legacy hash, gets upgraded on the first login
+        auth_provider
+            .import(&admin_config.name, &admin_config.email, password_hash, UserRole::Admin)
+            .await?;
     } else if let Some(password) = &admin_config.password {
-        if password.len() < 12 {
-            return Err("pw more than 12 char".into());
-        }
-
+        // register checks the password policy
         auth_provider
             .register(
                 &admin_config.name,
@@ -116,14 +116,120 @@ async fn main() -> std::io::Result<()> {
         .await
         .expect("Failed db con");
 
-    let repository = SqliteUserRepository::new(pool);
+    let repository = SqliteUserRepository::new(pool.clone());
     repository
         .initialize()
         .await
         .expect("db schema problem");
 
+    let client_repository = SqliteClientRepository::new(pool.clone());
+    client_repository
+        .initialize()
+        .await
+        .expect("db schema problem");
+    let client_repository: Arc<dyn ClientRepository> = Arc::new(client_repository);
+
+    let device_repository = SqliteDeviceAuthorizationRepository::new(pool.clone());
+    device_repository
+        .initialize()
+        .await
+        .expect("db schema problem");
+
+    let refresh_token_repository = SqliteRefreshTokenRepository::new(pool.clone());
+    refresh_token_repository
+        .initialize()
+        .await
+        .expect("db schema problem");
+    let refresh_token_repository: Arc<dyn RefreshTokenRepository> = Arc::new(refresh_token_repository);
+
+    let consent_repository = SqliteConsentRepository::new(pool.clone());
+    consent_repository
+        .initialize()
+        .await
+        .expect("db schema problem");
+    let consent_repository: Arc<dyn ConsentRepository> = Arc::new(consent_repository);
+
+    // offline LDAP logins are opt-in, no password verifiers stored otherwise
+    let credential_cache: Option<Arc<dyn CredentialCacheRepository>> =
+        if config.ldap.as_ref().is_some_and(|l| l.offline_grace_secs.is_some()) {
+            let cache = SqliteCredentialCacheRepository::new(pool.clone());
+            cache.initialize().await.expect("db schema problem");
+            Some(Arc::new(cache))
+        } else {
+            None
+        };
+
+    let login_attempt_repository = SqliteLoginAttemptRepository::new(pool.clone());
+    login_attempt_repository
+        .initialize()
+        .await
+        .expect("db schema problem");
+    tracing::info!(
+        max_failures = config.lockout.max_failures,
+        account_max_failures = config.lockout.account_max_failures,
+        ip_max_failures = config.lockout.ip_max_failures,
+        max_lockout_secs = config.lockout.max_lockout_secs,
+        "login lockout"
+    );
+    let login_throttle = LoginThrottle::new(config.lockout.clone(), Arc::new(login_attempt_repository));
+
     let repository: Arc<dyn syt_ek962_security_concepts::repository::UserRepository> = Arc::new(repository);
 
+    let password_policy = Arc::new(
+        PasswordPolicy::new(config.password_policy.clone()).expect("password policy config problem"),
+    );
+
+    let password_hasher = PasswordHasher::with_config(&config.argon2).expect("argon2 config problem");
+    tracing::info!(
+        memory_kib = config.argon2.memory_kib,
+        iterations = config.argon2.iterations,
+        parallelism = config.argon2.parallelism,
+        pepper = config.argon2.pepper.as_ref().map(|p| p.current_id()),
+        max_concurrent = config.argon2.max_concurrent,
+        queue_timeout_ms = config.argon2.queue_timeout_ms,
+        "argon2 parameters"
+    );
+    // dummy hash for unknown users now, not on the first failed login
+    password_hasher.verify_dummy("").await.expect("argon2 config problem");
+
+    let password_history = if config.password_policy.history_size > 0 {
+        let history_repository = SqlitePasswordHistoryRepository::new(pool.clone());
+        history_repository.initialize().await.expect("db schema problem");
+        Some(Arc::new(
+            PasswordHistory::new(Arc::new(history_repository), config.password_policy.history_size)
+                .with_password_hasher(password_hasher.clone()),
+        ))
+    } else {
+        None
+    };
+
+    let password_reset = match &config.password_reset {
+        Some(reset_config) => {
+            let reset_repository = SqlitePasswordResetRepository::new(pool);
+            reset_repository.initialize().await.expect("db schema problem");
+            let mailer = mailer_from_config(&reset_config.mail).expect("mail config problem");
+            tracing::info!(reset_uri = %reset_config.reset_uri, "password reset by mail enabled");
+
+            let mut service = PasswordResetService::new(
+                reset_config.clone(),
+                Arc::clone(&repository),
+                Arc::new(reset_repository),
+                Arc::clone(&refresh_token_repository),
+                mailer,
+            )
+            .with_password_policy(Arc::clone(&password_policy))
+            .with_password_hasher(password_hasher.clone());
+            if let Some(history) = &password_history {
+                service = service.with_password_history(Arc::clone(history));
+            }
+            Some(Arc::new(service))
+        }
+        None => {
+            tracing::info!("no SMTP_HOST or MAIL_OUTBOX_DIR, password reset disabled");
+            None
+        }
+    };
+
     let sqlite_repo = {
         let pool = SqlitePoolOptions::new()
             .max_connections(1)
@@ -133,7 +239,12 @@ async fn main() -> std::io::Result<()> {
         SqliteUserRepository::new(pool)
     };
 
-    let auth_provider = LocalAuthProvider::new(Arc::clone(&repository));
+    let mut auth_provider = LocalAuthProvider::new(Arc::clone(&repository))
+        .with_password_policy(Arc::clone(&password_policy))
+        .with_password_hasher(password_hasher.clone());
+    if let Some(history) = &password_history {
+        auth_provider = auth_provider.with_password_history(Arc::clone(history));
+    }
 
     if let Err(e) = initialize_admin(
         &config.initial_admin_config,
@@ -158,23 +269,76 @@ async fn main() -> std::io::Result<()> {
 
     let ldap_provider = config.ldap.as_ref().map(|ldap_config| {
         tracing::info!(
-            url = %ldap_config.url,
+            urls = %ldap_config.urls.join(","),
+            pool_size = ldap_config.pool_size,
             domain = %ldap_config.domain,
             "ldap activated"
         );
-        LdapAuthProvider::new(ldap_config.clone(), Arc::clone(&repository))
+
+        // LDAP_URL=memory:<seed.json> for local development without a directory server
+        let provider = if let Some(seed) = ldap_config.urls[0].strip_prefix("memory:") {
+            let directory = InMemoryDirectory::load(seed).expect("LDAP_URL memory seed file problem");
+            tracing::warn!(seed = %seed, "ldap uses the in-memory directory, not for production");
+            LdapAuthProvider::with_client(ldap_config.clone(), Arc::clone(&repository), Arc::new(directory))
+        } else {
+            LdapAuthProvider::new(ldap_config.clone(), Arc::clone(&repository)).expect("LDAP TLS config problem")
+        };
+        let provider = provider.with_password_hasher(password_hasher.clone());
+
+        match &credential_cache {
+            Some(cache) => {
+                tracing::info!(grace_secs = ?ldap_config.offline_grace_secs, "ldap offline login with cached credentials enabled");
+                Arc::new(provider.with_credential_cache(Arc::clone(cache)))
+            }
+            None => Arc::new(provider),
+        }
     });
 
     if ldap_provider.is_none() {
         tracing::info!("ldap conf not found");
     }
 
+    // directory sync needs the service account, without it only the lazy sync on login happens
+    let directory_sync = config
+        .ldap
+        .as_ref()
+        .zip(ldap_provider.as_ref())
+        .filter(|(ldap_config, _)| ldap_config.search_then_bind())
+        .map(|(ldap_config, provider)| {
+            let service = Arc::new(DirectorySyncService::new(
+                Arc::clone(provider),
+                Arc::clone(&repository),
+                Arc::clone(&refresh_token_repository),
+            ));
+
+            if let Some(secs) = ldap_config.sync_interval_secs {
+                tracing::info!(interval_secs = secs, "ldap directory sync scheduled");
+                Arc::clone(&service).spawn(Duration::from_secs(secs));
+            }
+
+            service
+        });
+
+    if directory_sync.is_none() && config.ldap.as_ref().is_some_and(|l| l.sync_interval_secs.is_some()) {
+        tracing::warn!("LDAP_SYNC_INTERVAL_SECS set but no LDAP_BIND_DN, directory sync disabled");
+    }
+
+    let device_flow = DeviceFlowService::new(config.device_flow.clone(), Arc::new(device_repository));
+
     let app_state = web::Data::new(AppState {
         jwt_service,
         auth_provider,
         google_provider,
         ldap_provider,
         repository,
+        client_repository,
+        refresh_token_repository,
+        consent_repository,
+        device_flow,
+        redirect_uri_schemes: config.redirect_uri_schemes.clone(),
+        directory_sync,
+        password_reset,
+        login_throttle,
     });
 
     let host = config.host.clone();
@@ -0,0 +1,198 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GrantType {
+    #[serde(rename = "authorization_code")]
+    AuthorizationCode,
+    #[serde(rename = "client_credentials")]
+    ClientCredentials,
+    #[serde(rename = "refresh_token")]
+    RefreshToken,
+    #[serde(rename = "urn:ietf:params:oauth:grant-type:device_code")]
+    DeviceCode,
+}
+
+impl std::fmt::Display for GrantType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GrantType::AuthorizationCode => write!(f, "authorization_code"),
+            GrantType::ClientCredentials => write!(f, "client_credentials"),
+            GrantType::RefreshToken => write!(f, "refresh_token"),
+            GrantType::DeviceCode => write!(f, "urn:ietf:params:oauth:grant-type:device_code"),
+        }
+    }
+}
+
+impl std::str::FromStr for GrantType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "authorization_code" => Ok(GrantType::AuthorizationCode),
+            "client_credentials" => Ok(GrantType::ClientCredentials),
+            "refresh_token" => Ok(GrantType::RefreshToken),
+            "urn:ietf:params:oauth:grant-type:device_code" => Ok(GrantType::DeviceCode),
+            _ => Err(format!("Unknown grant type: {}", s)),
+        }
+    }
+}
+
+// relying party that may request tokens from us
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct OAuthClient {
+    pub client_id: String,
+    // None for public clients (cli tools, spa) that cant keep a secret
+    #[serde(skip_serializing)]
+    pub client_secret_hash: Option<String>,
+    pub name: String,
+    #[sqlx(json)]
+    pub redirect_uris: Vec<String>,
+    #[sqlx(json)]
+    pub grant_types: Vec<GrantType>,
+    #[sqlx(json)]
+    pub scopes: Vec<String>,
+    pub access_token_ttl_secs: i64,
+    pub refresh_token_ttl_secs: i64,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub is_active: bool,
+}
+
+impl OAuthClient {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        name: String,
+        client_secret_hash: Option<String>,
+        redirect_uris: Vec<String>,
+        grant_types: Vec<GrantType>,
+        scopes: Vec<String>,
+        access_token_ttl_secs: i64,
+        refresh_token_ttl_secs: i64,
+    ) -> Self {
+        let now = Utc::now();
+        Self {
+            client_id: Uuid::new_v4().simple().to_string(),
+            client_secret_hash,
+            name,
+            redirect_uris,
+            grant_types,
+            scopes,
+            access_token_ttl_secs,
+            refresh_token_ttl_secs,
+            created_at: now,
+            updated_at: now,
+            is_active: true,
+        }
+    }
+
+    pub fn is_confidential(&self) -> bool {
+        self.client_secret_hash.is_some()
+    }
+
+    pub fn allows_grant(&self, grant_type: GrantType) -> bool {
+        self.grant_types.contains(&grant_type)
+    }
+
+    // exact match only, no prefix or wildcard matching
+    pub fn has_redirect_uri(&self, uri: &str) -> bool {
+        self.redirect_uris.iter().any(|u| u == uri)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ClientResponse {
+    pub client_id: String,
+    pub name: String,
+    pub confidential: bool,
+    pub redirect_uris: Vec<String>,
+    pub grant_types: Vec<GrantType>,
+    pub scopes: Vec<String>,
+    pub access_token_ttl_secs: i64,
+    pub refresh_token_ttl_secs: i64,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl From<OAuthClient> for ClientResponse {
+    fn from(client: OAuthClient) -> Self {
+        Self {
+            confidential: client.is_confidential(),
+            client_id: client.client_id,
+            name: client.name,
+            redirect_uris: client.redirect_uris,
+            grant_types: client.grant_types,
+            scopes: client.scopes,
+            access_token_ttl_secs: client.access_token_ttl_secs,
+            refresh_token_ttl_secs: client.refresh_token_ttl_secs,
+            created_at: client.created_at,
+            updated_at: client.updated_at,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_client() -> OAuthClient {
+        OAuthClient::new(
+            "Test App".to_string(),
+            Some("secret_hash".to_string()),
+            vec!["https://app.example.com/callback".to_string()],
+            vec![GrantType::AuthorizationCode, GrantType::RefreshToken],
+            vec!["openid".to_string(), "profile".to_string()],
+            3600,
+            86400,
+        )
+    }
+
+    #[test]
+    fn test_grant_type_display_and_parse() {
+        for grant in [
+            GrantType::AuthorizationCode,
+            GrantType::ClientCredentials,
+            GrantType::RefreshToken,
+            GrantType::DeviceCode,
+        ] {
+            assert_eq!(grant.to_string().parse::<GrantType>().unwrap(), grant);
+        }
+        assert!("password".parse::<GrantType>().is_err());
+    }
+
+    #[test]
+    fn test_grant_type_serializes_to_wire_name() {
+        let json = serde_json::to_string(&GrantType::DeviceCode).unwrap();
+        assert_eq!(json, "\"urn:ietf:params:oauth:grant-type:device_code\"");
+    }
+
+    #[test]
+    fn test_new_client() {
+        let client = test_client();
+
+        assert!(!client.client_id.is_empty());
+        assert!(client.is_confidential());
+        assert!(client.allows_grant(GrantType::AuthorizationCode));
+        assert!(!client.allows_grant(GrantType::ClientCredentials));
+        assert!(client.is_active);
+    }
+
+    #[test]
+    fn test_redirect_uri_exact_match() {
+        let client = test_client();
+
+        assert!(client.has_redirect_uri("https://app.example.com/callback"));
+        assert!(!client.has_redirect_uri("https://app.example.com/callback/evil"));
+        assert!(!client.has_redirect_uri("https://app.example.com"));
+    }
+
+    #[test]
+    fn test_client_response_excludes_secret_hash() {
+        let response: ClientResponse = test_client().into();
+        let json = serde_json::to_string(&response).unwrap();
+
+        assert!(!json.contains("secret_hash"));
+        assert!(response.confidential);
+    }
+}
@@ -0,0 +1,81 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+// scopes a user has granted to one oauth client, grows with every approved escalation
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct Consent {
+    pub user_id: String,
+    pub client_id: String,
+    #[sqlx(json)]
+    pub scopes: Vec<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl Consent {
+    pub fn new(user_id: String, client_id: String, scopes: Vec<String>) -> Self {
+        let now = Utc::now();
+        Self {
+            user_id,
+            client_id,
+            scopes,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    // requested scopes the user has not agreed to yet
+    pub fn missing_scopes(&self, requested: &[String]) -> Vec<String> {
+        requested
+            .iter()
+            .filter(|s| !self.scopes.contains(s))
+            .cloned()
+            .collect()
+    }
+
+    pub fn grant(&mut self, scopes: &[String]) {
+        for scope in self.missing_scopes(scopes) {
+            self.scopes.push(scope);
+        }
+        self.updated_at = Utc::now();
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct GrantResponse {
+    pub client_id: String,
+    pub client_name: String,
+    pub scopes: Vec<String>,
+    pub granted_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_missing_scopes() {
+        let consent = Consent::new(
+            "user".to_string(),
+            "client".to_string(),
+            vec!["openid".to_string()],
+        );
+
+        let requested = vec!["openid".to_string(), "email".to_string()];
+        assert_eq!(consent.missing_scopes(&requested), vec!["email"]);
+        assert!(consent.missing_scopes(&["openid".to_string()]).is_empty());
+    }
+
+    #[test]
+    fn test_grant_merges_scopes() {
+        let mut consent = Consent::new(
+            "user".to_string(),
+            "client".to_string(),
+            vec!["openid".to_string()],
+        );
+
+        consent.grant(&["openid".to_string(), "profile".to_string()]);
+        assert_eq!(consent.scopes, vec!["openid", "profile"]);
+    }
+}
@@ -0,0 +1,27 @@
+use chrono::{DateTime, Duration, Utc};
+
+// argon2 verifier of the last directory password that worked, only used while the directory is down
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct CachedCredential {
+    pub user_id: String,
+    // login name lowercased, offline there is no directory to resolve it
+    pub username: String,
+    pub verifier: String,
+    pub cached_at: DateTime<Utc>,
+}
+
+impl CachedCredential {
+    pub fn new(user_id: String, username: &str, verifier: String) -> Self {
+        Self {
+            user_id,
+            username: username.to_lowercase(),
+            verifier,
+            cached_at: Utc::now(),
+        }
+    }
+
+    // only accepted for the grace period after the last online login
+    pub fn is_within_grace(&self, grace: Duration) -> bool {
+        Utc::now() <= self.cached_at + grace
+    }
+}
@@ -0,0 +1,119 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "TEXT")]
+#[sqlx(rename_all = "lowercase")]
+pub enum DeviceAuthorizationStatus {
+    Pending,
+    Approved,
+    Denied,
+    // tokens already issued, device code cant be used again
+    Consumed,
+}
+
+impl std::fmt::Display for DeviceAuthorizationStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DeviceAuthorizationStatus::Pending => write!(f, "pending"),
+            DeviceAuthorizationStatus::Approved => write!(f, "approved"),
+            DeviceAuthorizationStatus::Denied => write!(f, "denied"),
+            DeviceAuthorizationStatus::Consumed => write!(f, "consumed"),
+        }
+    }
+}
+
+// RFC 8628 device authorization, device code is only stored as sha256
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct DeviceAuthorization {
+    pub device_code_hash: String,
+    pub user_code: String,
+    pub client_id: String,
+    #[sqlx(json)]
+    pub scopes: Vec<String>,
+    pub status: DeviceAuthorizationStatus,
+    pub user_id: Option<String>,
+    pub interval_secs: i64,
+    pub last_polled_at: Option<DateTime<Utc>>,
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl DeviceAuthorization {
+    pub fn new(
+        device_code_hash: String,
+        user_code: String,
+        client_id: String,
+        scopes: Vec<String>,
+        interval_secs: i64,
+        expires_in_secs: i64,
+    ) -> Self {
+        let now = Utc::now();
+        Self {
+            device_code_hash,
+            user_code,
+            client_id,
+            scopes,
+            status: DeviceAuthorizationStatus::Pending,
+            user_id: None,
+            interval_secs,
+            last_polled_at: None,
+            expires_at: now + Duration::seconds(expires_in_secs),
+            created_at: now,
+        }
+    }
+
+    pub fn is_expired(&self) -> bool {
+        Utc::now() > self.expires_at
+    }
+
+    // client polled before the interval was over
+    pub fn polled_too_fast(&self, now: DateTime<Utc>) -> bool {
+        self.last_polled_at
+            .is_some_and(|last| now < last + Duration::seconds(self.interval_secs))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_authorization() -> DeviceAuthorization {
+        DeviceAuthorization::new(
+            "hash".to_string(),
+            "BCDFGHJK".to_string(),
+            "client".to_string(),
+            vec![],
+            5,
+            600,
+        )
+    }
+
+    #[test]
+    fn test_new_authorization_is_pending() {
+        let auth = test_authorization();
+
+        assert_eq!(auth.status, DeviceAuthorizationStatus::Pending);
+        assert!(auth.user_id.is_none());
+        assert!(!auth.is_expired());
+    }
+
+    #[test]
+    fn test_polled_too_fast() {
+        let mut auth = test_authorization();
+        let now = Utc::now();
+
+        // first poll is always fine
+        assert!(!auth.polled_too_fast(now));
+
+        auth.last_polled_at = Some(now);
+        assert!(auth.polled_too_fast(now + Duration::seconds(2)));
+        assert!(!auth.polled_too_fast(now + Duration::seconds(5)));
+    }
+
+    #[test]
+    fn test_status_display() {
+        assert_eq!(DeviceAuthorizationStatus::Pending.to_string(), "pending");
+        assert_eq!(DeviceAuthorizationStatus::Consumed.to_string(), "consumed");
+    }
+}
@@ -0,0 +1,25 @@
+use chrono::{DateTime, Utc};
+
+// failed logins of one key (account from one ip, whole account or ip), reset after a quiet window
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct LoginAttempt {
+    pub key: String,
+    // "local:<email>" / "ldap:<username>", None for ip keys; the admin unlock goes by this
+    pub subject: Option<String>,
+    pub failures: i64,
+    pub last_failure_at: DateTime<Utc>,
+    pub locked_until: Option<DateTime<Utc>>,
+}
+
+impl LoginAttempt {
+    pub fn is_locked(&self) -> bool {
+        self.locked_until.is_some_and(|until| until > Utc::now())
+    }
+
+    // secs until the lock is gone, at least 1 for the Retry-After header
+    pub fn retry_after_secs(&self) -> i64 {
+        self.locked_until
+            .map(|until| (until - Utc::now()).num_seconds().max(1))
+            .unwrap_or(0)
+    }
+}
@@ -1,5 +1,19 @@
 //! Domain models for the authentication service.
 
 mod user;
+mod client;
+mod device;
+mod token;
+mod permission;
+mod consent;
+mod credential;
+mod login_attempt;
 
 pub use user::{User, UserRole, CreateUser, AuthProviderType, UserResponse};
+pub use client::{OAuthClient, GrantType, ClientResponse};
+pub use device::{DeviceAuthorization, DeviceAuthorizationStatus};
+pub use token::{RefreshToken, PasswordResetToken};
+pub use consent::{Consent, GrantResponse};
+pub use credential::CachedCredential;
+pub use login_attempt::LoginAttempt;
+pub use permission::{Permission, parse_scope, format_scope, grantable_scopes};
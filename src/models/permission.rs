@@ -0,0 +1,143 @@
+use serde::{Deserialize, Serialize};
+
+use super::UserRole;
+
+// permissions are handed out as oauth scopes, the wire name is the scope token
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Permission {
+    #[serde(rename = "openid")]
+    OpenId,
+    #[serde(rename = "profile")]
+    Profile,
+    #[serde(rename = "email")]
+    Email,
+    #[serde(rename = "users:read")]
+    UsersRead,
+    #[serde(rename = "users:write")]
+    UsersWrite,
+    #[serde(rename = "clients:read")]
+    ClientsRead,
+    #[serde(rename = "clients:write")]
+    ClientsWrite,
+}
+
+const USER_PERMISSIONS: &[Permission] = &[
+    Permission::OpenId,
+    Permission::Profile,
+    Permission::Email,
+];
+
+const ADMIN_PERMISSIONS: &[Permission] = &[
+    Permission::OpenId,
+    Permission::Profile,
+    Permission::Email,
+    Permission::UsersRead,
+    Permission::UsersWrite,
+    Permission::ClientsRead,
+    Permission::ClientsWrite,
+];
+
+impl Permission {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Permission::OpenId => "openid",
+            Permission::Profile => "profile",
+            Permission::Email => "email",
+            Permission::UsersRead => "users:read",
+            Permission::UsersWrite => "users:write",
+            Permission::ClientsRead => "clients:read",
+            Permission::ClientsWrite => "clients:write",
+        }
+    }
+}
+
+impl std::fmt::Display for Permission {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl std::str::FromStr for Permission {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ADMIN_PERMISSIONS
+            .iter()
+            .find(|p| p.as_str() == s)
+            .copied()
+            .ok_or_else(|| format!("Unknown scope: {}", s))
+    }
+}
+
+impl UserRole {
+    pub fn permissions(&self) -> &'static [Permission] {
+        match self {
+            UserRole::User => USER_PERMISSIONS,
+            UserRole::Admin => ADMIN_PERMISSIONS,
+        }
+    }
+
+    pub fn has_permission(&self, scope: &str) -> bool {
+        self.permissions().iter().any(|p| p.as_str() == scope)
+    }
+}
+
+// space separated scope parameter (RFC 6749 section 3.3)
+pub fn parse_scope(scope: &str) -> Vec<String> {
+    let mut scopes: Vec<String> = Vec::new();
+    for s in scope.split_whitespace() {
+        if !scopes.iter().any(|existing| existing == s) {
+            scopes.push(s.to_string());
+        }
+    }
+    scopes
+}
+
+pub fn format_scope(scopes: &[String]) -> String {
+    scopes.join(" ")
+}
+
+// what a token for this user may carry: requested by the client and covered by the role
+pub fn grantable_scopes(role: UserRole, requested: &[String]) -> Vec<String> {
+    requested
+        .iter()
+        .filter(|s| role.has_permission(s))
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_permission_roundtrip() {
+        for permission in ADMIN_PERMISSIONS {
+            let parsed: Permission = permission.as_str().parse().unwrap();
+            assert_eq!(&parsed, permission);
+        }
+        assert!("builds:read".parse::<Permission>().is_err());
+    }
+
+    #[test]
+    fn test_role_permissions() {
+        assert!(UserRole::User.has_permission("profile"));
+        assert!(!UserRole::User.has_permission("users:write"));
+        assert!(UserRole::Admin.has_permission("users:write"));
+        assert!(UserRole::Admin.has_permission("clients:read"));
+    }
+
+    #[test]
+    fn test_parse_scope() {
+        assert_eq!(parse_scope("openid  profile openid"), vec!["openid", "profile"]);
+        assert!(parse_scope("").is_empty());
+    }
+
+    #[test]
+    fn test_grantable_scopes_is_intersection() {
+        let requested = vec!["profile".to_string(), "users:read".to_string()];
+
+        assert_eq!(grantable_scopes(UserRole::User, &requested), vec!["profile"]);
+        assert_eq!(grantable_scopes(UserRole::Admin, &requested), requested);
+    }
+}
@@ -0,0 +1,119 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+// opaque refresh token issued to an oauth client, only the sha256 is stored
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct RefreshToken {
+    pub token_hash: String,
+    pub user_id: String,
+    pub client_id: String,
+    #[sqlx(json)]
+    pub scopes: Vec<String>,
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+impl RefreshToken {
+    pub fn new(
+        token_hash: String,
+        user_id: String,
+        client_id: String,
+        scopes: Vec<String>,
+        ttl_secs: i64,
+    ) -> Self {
+        let now = Utc::now();
+        Self {
+            token_hash,
+            user_id,
+            client_id,
+            scopes,
+            expires_at: now + Duration::seconds(ttl_secs),
+            created_at: now,
+            revoked_at: None,
+        }
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.revoked_at.is_none() && Utc::now() <= self.expires_at
+    }
+}
+
+// one time link from the forgot password mail, only the sha256 is stored
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct PasswordResetToken {
+    pub token_hash: String,
+    pub user_id: String,
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    pub used_at: Option<DateTime<Utc>>,
+}
+
+impl PasswordResetToken {
+    pub fn new(token_hash: String, user_id: String, ttl_secs: i64) -> Self {
+        let now = Utc::now();
+        Self {
+            token_hash,
+            user_id,
+            expires_at: now + Duration::seconds(ttl_secs),
+            created_at: now,
+            used_at: None,
+        }
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.used_at.is_none() && Utc::now() <= self.expires_at
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_new_refresh_token_is_valid() {
+        let token = RefreshToken::new(
+            "hash".to_string(),
+            "user".to_string(),
+            "client".to_string(),
+            vec![],
+            3600,
+        );
+
+        assert!(token.is_valid());
+    }
+
+    #[test]
+    fn test_revoked_or_expired_token_is_invalid() {
+        let mut token = RefreshToken::new(
+            "hash".to_string(),
+            "user".to_string(),
+            "client".to_string(),
+            vec![],
+            3600,
+        );
+        token.revoked_at = Some(Utc::now());
+        assert!(!token.is_valid());
+
+        let expired = RefreshToken::new(
+            "hash".to_string(),
+            "user".to_string(),
+            "client".to_string(),
+            vec![],
+            -1,
+        );
+        assert!(!expired.is_valid());
+    }
+
+    #[test]
+    fn test_password_reset_token_validity() {
+        let mut token = PasswordResetToken::new("hash".to_string(), "user".to_string(), 1800);
+        assert!(token.is_valid());
+
+        token.used_at = Some(Utc::now());
+        assert!(!token.is_valid());
+
+        let expired = PasswordResetToken::new("hash".to_string(), "user".to_string(), -1);
+        assert!(!expired.is_valid());
+    }
+}
@@ -39,6 +39,8 @@ pub enum AuthProviderType {
     Local,
     Google,
     ActiveDirectory,
+    // generic ldap directory (OpenLDAP, FreeIPA)
+    Ldap,
     Oidc,
 }
 
@@ -48,6 +50,7 @@ impl std::fmt::Display for AuthProviderType {
             AuthProviderType::Local => write!(f, "local"),
             AuthProviderType::Google => write!(f, "google"),
             AuthProviderType::ActiveDirectory => write!(f, "activedirectory"),
+            AuthProviderType::Ldap => write!(f, "ldap"),
             AuthProviderType::Oidc => write!(f, "oidc"),
         }
     }
@@ -66,6 +69,8 @@ pub struct User {
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
     pub is_active: bool,
+    // access tokens issued before this are refused, they are not stored anywhere to revoke them
+    pub password_changed_at: Option<DateTime<Utc>>,
 }
 
 impl User {
@@ -82,6 +87,7 @@ impl User {
             created_at: now,
             updated_at: now,
             is_active: true,
+            password_changed_at: None,
         }
     }
 
@@ -89,6 +95,11 @@ impl User {
         self.role == UserRole::Admin
     }
 
+    // iat only has seconds, a token issued in the same second as the change still counts as newer
+    pub fn token_predates_password_change(&self, issued_at: i64) -> bool {
+        self.password_changed_at.is_some_and(|changed| issued_at < changed.timestamp())
+    }
+
     pub fn new_external(
         name: String,
         email: String,
@@ -108,6 +119,7 @@ impl User {
             created_at: now,
             updated_at: now,
             is_active: true,
+            password_changed_at: None,
         }
     }
 }
@@ -181,9 +193,29 @@ mod tests {
         assert_eq!(AuthProviderType::Local.to_string(), "local");
         assert_eq!(AuthProviderType::Google.to_string(), "google");
         assert_eq!(AuthProviderType::ActiveDirectory.to_string(), "activedirectory");
+        assert_eq!(AuthProviderType::Ldap.to_string(), "ldap");
         assert_eq!(AuthProviderType::Oidc.to_string(), "oidc");
     }
 
+    // ==================== Password Change Tests ====================
+
+    #[test]
+    fn test_token_predates_password_change() {
+        let mut user = User::new_local(
+            "Test User".to_string(),
+            "test@example.com".to_string(),
+            "hashed_password".to_string(),
+            UserRole::User,
+        );
+        let now = Utc::now();
+        assert!(!user.token_predates_password_change(now.timestamp() - 60));
+
+        user.password_changed_at = Some(now);
+        assert!(user.token_predates_password_change(now.timestamp() - 1));
+        assert!(!user.token_predates_password_change(now.timestamp()));
+        assert!(!user.token_predates_password_change(now.timestamp() + 1));
+    }
+
     // ==================== User Creation Tests ====================
 
     #[test]
@@ -6,5 +6,12 @@
 mod traits;
 mod sqlite;
 
-pub use traits::UserRepository;
-pub use sqlite::SqliteUserRepository;
+pub use traits::{
+    UserRepository, ClientRepository, DeviceAuthorizationRepository, RefreshTokenRepository, ConsentRepository,
+    CredentialCacheRepository, PasswordResetRepository, PasswordHistoryRepository, LoginAttemptRepository,
+};
+pub use sqlite::{
+    SqliteUserRepository, SqliteClientRepository, SqliteDeviceAuthorizationRepository,
+    SqliteRefreshTokenRepository, SqliteConsentRepository, SqliteCredentialCacheRepository,
+    SqlitePasswordResetRepository, SqlitePasswordHistoryRepository, SqliteLoginAttemptRepository,
+};
@@ -1,9 +1,16 @@
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use sqlx::SqlitePool;
+use sqlx::types::Json;
 
 use crate::error::AppError;
-use crate::models::User;
-use super::traits::UserRepository;
+use crate::models::{
+    CachedCredential, Consent, DeviceAuthorization, LoginAttempt, OAuthClient, PasswordResetToken, RefreshToken, User,
+};
+use super::traits::{
+    ClientRepository, ConsentRepository, CredentialCacheRepository, DeviceAuthorizationRepository, LoginAttemptRepository,
+    PasswordHistoryRepository, PasswordResetRepository, RefreshTokenRepository, UserRepository,
+};
 
 pub struct SqliteUserRepository {
     pool: SqlitePool,
@@ -29,6 +36,7 @@ impl SqliteUserRepository {
                 created_at TEXT NOT NULL,
                 updated_at TEXT NOT NULL,
                 is_active INTEGER NOT NULL DEFAULT 1,
+                password_changed_at TEXT,
                 UNIQUE(auth_provider, external_id)
             )
             "#,
@@ -36,6 +44,16 @@ impl SqliteUserRepository {
         .execute(&self.pool)
         .await?;
 
+        // databases from before password_changed_at, sqlite has no ADD COLUMN IF NOT EXISTS
+        let columns: Vec<(String,)> = sqlx::query_as("SELECT name FROM pragma_table_info('users')")
+            .fetch_all(&self.pool)
+            .await?;
+        if !columns.iter().any(|(name,)| name == "password_changed_at") {
+            sqlx::query("ALTER TABLE users ADD COLUMN password_changed_at TEXT")
+                .execute(&self.pool)
+                .await?;
+        }
+
         sqlx::query("CREATE INDEX IF NOT EXISTS idx_users_email ON users(email)")
             .execute(&self.pool)
             .await?;
@@ -56,7 +74,7 @@ impl UserRepository for SqliteUserRepository {
         let user = sqlx::query_as::<_, User>(
             r#"
             SELECT id, name, email, password_hash, role, auth_provider,
-                   external_id, created_at, updated_at, is_active
+                   external_id, created_at, updated_at, is_active, password_changed_at
             FROM users
             WHERE id = ? AND is_active = 1
             "#,
@@ -74,7 +92,7 @@ impl UserRepository for SqliteUserRepository {
         let user = sqlx::query_as::<_, User>(
             r#"
             SELECT id, name, email, password_hash, role, auth_provider,
-                   external_id, created_at, updated_at, is_active
+                   external_id, created_at, updated_at, is_active, password_changed_at
             FROM users
             WHERE LOWER(email) = ? AND is_active = 1
             "#,
@@ -94,7 +112,7 @@ impl UserRepository for SqliteUserRepository {
         let user = sqlx::query_as::<_, User>(
             r#"
             SELECT id, name, email, password_hash, role, auth_provider,
-                   external_id, created_at, updated_at, is_active
+                   external_id, created_at, updated_at, is_active, password_changed_at
             FROM users
             WHERE auth_provider = ? AND external_id = ? AND is_active = 1
             "#,
@@ -107,31 +125,86 @@ impl UserRepository for SqliteUserRepository {
         Ok(user)
     }
 
+    async fn find_by_external_id_including_inactive(
+        &self,
+        provider: &str,
+        external_id: &str,
+    ) -> Result<Option<User>, AppError> {
+        let user = sqlx::query_as::<_, User>(
+            r#"
+            SELECT id, name, email, password_hash, role, auth_provider,
+                   external_id, created_at, updated_at, is_active, password_changed_at
+            FROM users
+            WHERE auth_provider = ? AND external_id = ?
+            "#,
+        )
+        .bind(provider)
+        .bind(external_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(user)
+    }
+
+    async fn find_by_email_including_inactive(&self, email: &str) -> Result<Option<User>, AppError> {
+        let user = sqlx::query_as::<_, User>(
+            r#"
+            SELECT id, name, email, password_hash, role, auth_provider,
+                   external_id, created_at, updated_at, is_active, password_changed_at
+            FROM users
+            WHERE LOWER(email) = ?
+            "#,
+        )
+        .bind(email.to_lowercase())
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(user)
+    }
+
+    async fn list_by_provider(&self, provider: &str) -> Result<Vec<User>, AppError> {
+        let users = sqlx::query_as::<_, User>(
+            r#"
+            SELECT id, name, email, password_hash, role, auth_provider,
+                   external_id, created_at, updated_at, is_active, password_changed_at
+            FROM users
+            WHERE auth_provider = ?
+            ORDER BY created_at
+            "#,
+        )
+        .bind(provider)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(users)
+    }
+
     async fn create(&self, user: &User) -> Result<(), AppError> {
         sqlx::query(
             r#"
             INSERT INTO users (id, name, email, password_hash, role, auth_provider,
-                              external_id, created_at, updated_at, is_active)
-            VALUES (?, ?, LOWER(?), ?, ?, ?, ?, ?, ?, ?)
+                              external_id, created_at, updated_at, is_active, password_changed_at)
+            VALUES (?, ?, LOWER(?), ?, ?, ?, ?, ?, ?, ?, ?)
             "#,
         )
         .bind(&user.id)
         .bind(&user.name)
         .bind(&user.email)
         .bind(&user.password_hash)
-        .bind(&user.role.to_string())
-        .bind(&user.auth_provider.to_string())
+        .bind(user.role.to_string())
+        .bind(user.auth_provider.to_string())
         .bind(&user.external_id)
         .bind(user.created_at.to_rfc3339())
         .bind(user.updated_at.to_rfc3339())
         .bind(user.is_active)
+        .bind(user.password_changed_at.map(|t| t.to_rfc3339()))
         .execute(&self.pool)
         .await
         .map_err(|e| {
-            if let sqlx::Error::Database(ref db_err) = e {
-                if db_err.message().contains("UNIQUE constraint failed") {
-                    return AppError::Conflict("Email already registered".to_string());
-                }
+            if let sqlx::Error::Database(ref db_err) = e
+                && db_err.message().contains("UNIQUE constraint failed")
+            {
+                return AppError::Conflict("Email already registered".to_string());
             }
             AppError::DatabaseError(e.to_string())
         })?;
@@ -144,18 +217,19 @@ impl UserRepository for SqliteUserRepository {
             r#"
             UPDATE users
             SET name = ?, email = LOWER(?), password_hash = ?, role = ?,
-                auth_provider = ?, external_id = ?, updated_at = ?, is_active = ?
+                auth_provider = ?, external_id = ?, updated_at = ?, is_active = ?, password_changed_at = ?
             WHERE id = ?
             "#,
         )
         .bind(&user.name)
         .bind(&user.email)
         .bind(&user.password_hash)
-        .bind(&user.role.to_string())
-        .bind(&user.auth_provider.to_string())
+        .bind(user.role.to_string())
+        .bind(user.auth_provider.to_string())
         .bind(&user.external_id)
         .bind(user.updated_at.to_rfc3339())
         .bind(user.is_active)
+        .bind(user.password_changed_at.map(|t| t.to_rfc3339()))
         .bind(&user.id)
         .execute(&self.pool)
         .await?;
@@ -196,3 +270,889 @@ impl UserRepository for SqliteUserRepository {
         Ok(count.0)
     }
 }
+
+pub struct SqliteClientRepository {
+    pool: SqlitePool,
+}
+
+impl SqliteClientRepository {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    // lists are stored as json arrays
+    pub async fn initialize(&self) -> Result<(), AppError> {
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS oauth_clients (
+                client_id TEXT PRIMARY KEY NOT NULL,
+                client_secret_hash TEXT,
+                name TEXT NOT NULL,
+                redirect_uris TEXT NOT NULL DEFAULT '[]',
+                grant_types TEXT NOT NULL DEFAULT '[]',
+                scopes TEXT NOT NULL DEFAULT '[]',
+                access_token_ttl_secs INTEGER NOT NULL,
+                refresh_token_ttl_secs INTEGER NOT NULL,
+                created_at TEXT NOT NULL,
+                updated_at TEXT NOT NULL,
+                is_active INTEGER NOT NULL DEFAULT 1
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ClientRepository for SqliteClientRepository {
+    async fn find_by_client_id(&self, client_id: &str) -> Result<Option<OAuthClient>, AppError> {
+        let client = sqlx::query_as::<_, OAuthClient>(
+            r#"
+            SELECT client_id, client_secret_hash, name, redirect_uris, grant_types, scopes,
+                   access_token_ttl_secs, refresh_token_ttl_secs, created_at, updated_at, is_active
+            FROM oauth_clients
+            WHERE client_id = ? AND is_active = 1
+            "#,
+        )
+        .bind(client_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(client)
+    }
+
+    async fn list(&self) -> Result<Vec<OAuthClient>, AppError> {
+        let clients = sqlx::query_as::<_, OAuthClient>(
+            r#"
+            SELECT client_id, client_secret_hash, name, redirect_uris, grant_types, scopes,
+                   access_token_ttl_secs, refresh_token_ttl_secs, created_at, updated_at, is_active
+            FROM oauth_clients
+            WHERE is_active = 1
+            ORDER BY created_at
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(clients)
+    }
+
+    async fn create(&self, client: &OAuthClient) -> Result<(), AppError> {
+        sqlx::query(
+            r#"
+            INSERT INTO oauth_clients (client_id, client_secret_hash, name, redirect_uris,
+                                       grant_types, scopes, access_token_ttl_secs,
+                                       refresh_token_ttl_secs, created_at, updated_at, is_active)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(&client.client_id)
+        .bind(&client.client_secret_hash)
+        .bind(&client.name)
+        .bind(Json(&client.redirect_uris))
+        .bind(Json(&client.grant_types))
+        .bind(Json(&client.scopes))
+        .bind(client.access_token_ttl_secs)
+        .bind(client.refresh_token_ttl_secs)
+        .bind(client.created_at.to_rfc3339())
+        .bind(client.updated_at.to_rfc3339())
+        .bind(client.is_active)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| {
+            if let sqlx::Error::Database(ref db_err) = e
+                && db_err.message().contains("UNIQUE constraint failed")
+            {
+                return AppError::Conflict("Client already registered".to_string());
+            }
+            AppError::DatabaseError(e.to_string())
+        })?;
+
+        Ok(())
+    }
+
+    async fn update(&self, client: &OAuthClient) -> Result<(), AppError> {
+        let result = sqlx::query(
+            r#"
+            UPDATE oauth_clients
+            SET client_secret_hash = ?, name = ?, redirect_uris = ?, grant_types = ?, scopes = ?,
+                access_token_ttl_secs = ?, refresh_token_ttl_secs = ?, updated_at = ?, is_active = ?
+            WHERE client_id = ?
+            "#,
+        )
+        .bind(&client.client_secret_hash)
+        .bind(&client.name)
+        .bind(Json(&client.redirect_uris))
+        .bind(Json(&client.grant_types))
+        .bind(Json(&client.scopes))
+        .bind(client.access_token_ttl_secs)
+        .bind(client.refresh_token_ttl_secs)
+        .bind(client.updated_at.to_rfc3339())
+        .bind(client.is_active)
+        .bind(&client.client_id)
+        .execute(&self.pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound("Client not found".to_string()));
+        }
+
+        Ok(())
+    }
+
+    async fn delete(&self, client_id: &str) -> Result<(), AppError> {
+        let result = sqlx::query("UPDATE oauth_clients SET is_active = 0 WHERE client_id = ? AND is_active = 1")
+            .bind(client_id)
+            .execute(&self.pool)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound("Client not found".to_string()));
+        }
+
+        Ok(())
+    }
+}
+
+pub struct SqliteDeviceAuthorizationRepository {
+    pool: SqlitePool,
+}
+
+impl SqliteDeviceAuthorizationRepository {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn initialize(&self) -> Result<(), AppError> {
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS device_authorizations (
+                device_code_hash TEXT PRIMARY KEY NOT NULL,
+                user_code TEXT NOT NULL UNIQUE,
+                client_id TEXT NOT NULL,
+                scopes TEXT NOT NULL DEFAULT '[]',
+                status TEXT NOT NULL DEFAULT 'pending',
+                user_id TEXT,
+                interval_secs INTEGER NOT NULL,
+                last_polled_at TEXT,
+                expires_at TEXT NOT NULL,
+                created_at TEXT NOT NULL
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl DeviceAuthorizationRepository for SqliteDeviceAuthorizationRepository {
+    async fn create(&self, authorization: &DeviceAuthorization) -> Result<(), AppError> {
+        sqlx::query(
+            r#"
+            INSERT INTO device_authorizations (device_code_hash, user_code, client_id, scopes, status,
+                                               user_id, interval_secs, last_polled_at, expires_at,
+                                               created_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(&authorization.device_code_hash)
+        .bind(&authorization.user_code)
+        .bind(&authorization.client_id)
+        .bind(Json(&authorization.scopes))
+        .bind(authorization.status.to_string())
+        .bind(&authorization.user_id)
+        .bind(authorization.interval_secs)
+        .bind(authorization.last_polled_at.map(|t| t.to_rfc3339()))
+        .bind(authorization.expires_at.to_rfc3339())
+        .bind(authorization.created_at.to_rfc3339())
+        .execute(&self.pool)
+        .await
+        .map_err(|e| {
+            if let sqlx::Error::Database(ref db_err) = e
+                && db_err.message().contains("UNIQUE constraint failed")
+            {
+                return AppError::Conflict("User code already in use".to_string());
+            }
+            AppError::DatabaseError(e.to_string())
+        })?;
+
+        Ok(())
+    }
+
+    async fn find_by_device_code_hash(
+        &self,
+        device_code_hash: &str,
+    ) -> Result<Option<DeviceAuthorization>, AppError> {
+        let authorization = sqlx::query_as::<_, DeviceAuthorization>(
+            r#"
+            SELECT device_code_hash, user_code, client_id, scopes, status, user_id,
+                   interval_secs, last_polled_at, expires_at, created_at
+            FROM device_authorizations
+            WHERE device_code_hash = ?
+            "#,
+        )
+        .bind(device_code_hash)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(authorization)
+    }
+
+    async fn find_by_user_code(&self, user_code: &str) -> Result<Option<DeviceAuthorization>, AppError> {
+        let authorization = sqlx::query_as::<_, DeviceAuthorization>(
+            r#"
+            SELECT device_code_hash, user_code, client_id, scopes, status, user_id,
+                   interval_secs, last_polled_at, expires_at, created_at
+            FROM device_authorizations
+            WHERE user_code = ?
+            "#,
+        )
+        .bind(user_code)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(authorization)
+    }
+
+    async fn update(&self, authorization: &DeviceAuthorization) -> Result<(), AppError> {
+        let result = sqlx::query(
+            r#"
+            UPDATE device_authorizations
+            SET scopes = ?, status = ?, user_id = ?, interval_secs = ?, last_polled_at = ?
+            WHERE device_code_hash = ?
+            "#,
+        )
+        .bind(Json(&authorization.scopes))
+        .bind(authorization.status.to_string())
+        .bind(&authorization.user_id)
+        .bind(authorization.interval_secs)
+        .bind(authorization.last_polled_at.map(|t| t.to_rfc3339()))
+        .bind(&authorization.device_code_hash)
+        .execute(&self.pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound("Device authorization not found".to_string()));
+        }
+
+        Ok(())
+    }
+
+    async fn consume(&self, device_code_hash: &str) -> Result<bool, AppError> {
+        let result = sqlx::query(
+            r#"
+            UPDATE device_authorizations
+            SET status = 'consumed', last_polled_at = ?
+            WHERE device_code_hash = ? AND status = 'approved'
+            "#,
+        )
+        .bind(Utc::now().to_rfc3339())
+        .bind(device_code_hash)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn delete_expired(&self) -> Result<u64, AppError> {
+        let result = sqlx::query("DELETE FROM device_authorizations WHERE expires_at < ?")
+            .bind(Utc::now().to_rfc3339())
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+}
+
+pub struct SqliteRefreshTokenRepository {
+    pool: SqlitePool,
+}
+
+impl SqliteRefreshTokenRepository {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn initialize(&self) -> Result<(), AppError> {
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS refresh_tokens (
+                token_hash TEXT PRIMARY KEY NOT NULL,
+                user_id TEXT NOT NULL,
+                client_id TEXT NOT NULL,
+                scopes TEXT NOT NULL DEFAULT '[]',
+                expires_at TEXT NOT NULL,
+                created_at TEXT NOT NULL,
+                revoked_at TEXT
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_refresh_tokens_user ON refresh_tokens(user_id)")
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl RefreshTokenRepository for SqliteRefreshTokenRepository {
+    async fn create(&self, token: &RefreshToken) -> Result<(), AppError> {
+        sqlx::query(
+            r#"
+            INSERT INTO refresh_tokens (token_hash, user_id, client_id, scopes, expires_at,
+                                        created_at, revoked_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(&token.token_hash)
+        .bind(&token.user_id)
+        .bind(&token.client_id)
+        .bind(Json(&token.scopes))
+        .bind(token.expires_at.to_rfc3339())
+        .bind(token.created_at.to_rfc3339())
+        .bind(token.revoked_at.map(|t| t.to_rfc3339()))
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn find_by_hash(&self, token_hash: &str) -> Result<Option<RefreshToken>, AppError> {
+        let token = sqlx::query_as::<_, RefreshToken>(
+            r#"
+            SELECT token_hash, user_id, client_id, scopes, expires_at, created_at, revoked_at
+            FROM refresh_tokens
+            WHERE token_hash = ?
+            "#,
+        )
+        .bind(token_hash)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(token)
+    }
+
+    async fn revoke(&self, token_hash: &str) -> Result<bool, AppError> {
+        let result = sqlx::query("UPDATE refresh_tokens SET revoked_at = ? WHERE token_hash = ? AND revoked_at IS NULL")
+            .bind(Utc::now().to_rfc3339())
+            .bind(token_hash)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn revoke_all_for_user(&self, user_id: &str) -> Result<u64, AppError> {
+        let result = sqlx::query("UPDATE refresh_tokens SET revoked_at = ? WHERE user_id = ? AND revoked_at IS NULL")
+            .bind(Utc::now().to_rfc3339())
+            .bind(user_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    async fn revoke_for_client(&self, user_id: &str, client_id: &str) -> Result<u64, AppError> {
+        let result = sqlx::query(
+            "UPDATE refresh_tokens SET revoked_at = ? WHERE user_id = ? AND client_id = ? AND revoked_at IS NULL",
+        )
+        .bind(Utc::now().to_rfc3339())
+        .bind(user_id)
+        .bind(client_id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+}
+
+pub struct SqlitePasswordResetRepository {
+    pool: SqlitePool,
+}
+
+impl SqlitePasswordResetRepository {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn initialize(&self) -> Result<(), AppError> {
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS password_reset_tokens (
+                token_hash TEXT PRIMARY KEY NOT NULL,
+                user_id TEXT NOT NULL,
+                expires_at TEXT NOT NULL,
+                created_at TEXT NOT NULL,
+                used_at TEXT
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_password_reset_user ON password_reset_tokens(user_id)")
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl PasswordResetRepository for SqlitePasswordResetRepository {
+    async fn create(&self, token: &PasswordResetToken) -> Result<(), AppError> {
+        sqlx::query(
+            r#"
+            INSERT INTO password_reset_tokens (token_hash, user_id, expires_at, created_at, used_at)
+            VALUES (?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(&token.token_hash)
+        .bind(&token.user_id)
+        .bind(token.expires_at.to_rfc3339())
+        .bind(token.created_at.to_rfc3339())
+        .bind(token.used_at.map(|t| t.to_rfc3339()))
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn find_by_hash(&self, token_hash: &str) -> Result<Option<PasswordResetToken>, AppError> {
+        let token = sqlx::query_as::<_, PasswordResetToken>(
+            r#"
+            SELECT token_hash, user_id, expires_at, created_at, used_at
+            FROM password_reset_tokens
+            WHERE token_hash = ?
+            "#,
+        )
+        .bind(token_hash)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(token)
+    }
+
+    async fn mark_used(&self, token_hash: &str) -> Result<bool, AppError> {
+        let result = sqlx::query("UPDATE password_reset_tokens SET used_at = ? WHERE token_hash = ? AND used_at IS NULL")
+            .bind(Utc::now().to_rfc3339())
+            .bind(token_hash)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn find_latest_for_user(&self, user_id: &str) -> Result<Option<PasswordResetToken>, AppError> {
+        let token = sqlx::query_as::<_, PasswordResetToken>(
+            r#"
+            SELECT token_hash, user_id, expires_at, created_at, used_at
+            FROM password_reset_tokens
+            WHERE user_id = ?
+            ORDER BY created_at DESC
+            LIMIT 1
+            "#,
+        )
+        .bind(user_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(token)
+    }
+
+    async fn invalidate_for_user(&self, user_id: &str) -> Result<u64, AppError> {
+        let result = sqlx::query("UPDATE password_reset_tokens SET used_at = ? WHERE user_id = ? AND used_at IS NULL")
+            .bind(Utc::now().to_rfc3339())
+            .bind(user_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    async fn delete_expired(&self) -> Result<u64, AppError> {
+        let result = sqlx::query("DELETE FROM password_reset_tokens WHERE expires_at < ?")
+            .bind(Utc::now().to_rfc3339())
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+}
+
+pub struct SqlitePasswordHistoryRepository {
+    pool: SqlitePool,
+}
+
+impl SqlitePasswordHistoryRepository {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    // id instead of created_at for the order, two changes in the same second are still ordered
+    pub async fn initialize(&self) -> Result<(), AppError> {
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS password_history (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                user_id TEXT NOT NULL,
+                password_hash TEXT NOT NULL,
+                created_at TEXT NOT NULL
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_password_history_user ON password_history(user_id)")
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl PasswordHistoryRepository for SqlitePasswordHistoryRepository {
+    async fn add(&self, user_id: &str, password_hash: &str) -> Result<(), AppError> {
+        sqlx::query("INSERT INTO password_history (user_id, password_hash, created_at) VALUES (?, ?, ?)")
+            .bind(user_id)
+            .bind(password_hash)
+            .bind(Utc::now().to_rfc3339())
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn recent(&self, user_id: &str, limit: usize) -> Result<Vec<String>, AppError> {
+        let hashes = sqlx::query_scalar::<_, String>(
+            "SELECT password_hash FROM password_history WHERE user_id = ? ORDER BY id DESC LIMIT ?",
+        )
+        .bind(user_id)
+        .bind(limit as i64)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(hashes)
+    }
+
+    async fn prune(&self, user_id: &str, keep: usize) -> Result<u64, AppError> {
+        let result = sqlx::query(
+            r#"
+            DELETE FROM password_history
+            WHERE user_id = ?
+              AND id NOT IN (SELECT id FROM password_history WHERE user_id = ? ORDER BY id DESC LIMIT ?)
+            "#,
+        )
+        .bind(user_id)
+        .bind(user_id)
+        .bind(keep as i64)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+}
+
+pub struct SqliteConsentRepository {
+    pool: SqlitePool,
+}
+
+impl SqliteConsentRepository {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn initialize(&self) -> Result<(), AppError> {
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS consents (
+                user_id TEXT NOT NULL,
+                client_id TEXT NOT NULL,
+                scopes TEXT NOT NULL DEFAULT '[]',
+                created_at TEXT NOT NULL,
+                updated_at TEXT NOT NULL,
+                PRIMARY KEY (user_id, client_id)
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ConsentRepository for SqliteConsentRepository {
+    async fn find(&self, user_id: &str, client_id: &str) -> Result<Option<Consent>, AppError> {
+        let consent = sqlx::query_as::<_, Consent>(
+            r#"
+            SELECT user_id, client_id, scopes, created_at, updated_at
+            FROM consents
+            WHERE user_id = ? AND client_id = ?
+            "#,
+        )
+        .bind(user_id)
+        .bind(client_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(consent)
+    }
+
+    async fn list_for_user(&self, user_id: &str) -> Result<Vec<Consent>, AppError> {
+        let consents = sqlx::query_as::<_, Consent>(
+            r#"
+            SELECT user_id, client_id, scopes, created_at, updated_at
+            FROM consents
+            WHERE user_id = ?
+            ORDER BY created_at
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(consents)
+    }
+
+    async fn save(&self, consent: &Consent) -> Result<(), AppError> {
+        sqlx::query(
+            r#"
+            INSERT INTO consents (user_id, client_id, scopes, created_at, updated_at)
+            VALUES (?, ?, ?, ?, ?)
+            ON CONFLICT (user_id, client_id)
+            DO UPDATE SET scopes = excluded.scopes, updated_at = excluded.updated_at
+            "#,
+        )
+        .bind(&consent.user_id)
+        .bind(&consent.client_id)
+        .bind(Json(&consent.scopes))
+        .bind(consent.created_at.to_rfc3339())
+        .bind(consent.updated_at.to_rfc3339())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn delete(&self, user_id: &str, client_id: &str) -> Result<bool, AppError> {
+        let result = sqlx::query("DELETE FROM consents WHERE user_id = ? AND client_id = ?")
+            .bind(user_id)
+            .bind(client_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+pub struct SqliteCredentialCacheRepository {
+    pool: SqlitePool,
+}
+
+impl SqliteCredentialCacheRepository {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn initialize(&self) -> Result<(), AppError> {
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS cached_credentials (
+                user_id TEXT PRIMARY KEY NOT NULL,
+                username TEXT NOT NULL,
+                verifier TEXT NOT NULL,
+                cached_at TEXT NOT NULL
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_cached_credentials_username ON cached_credentials(username)")
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl CredentialCacheRepository for SqliteCredentialCacheRepository {
+    async fn store(&self, credential: &CachedCredential) -> Result<(), AppError> {
+        sqlx::query(
+            r#"
+            INSERT INTO cached_credentials (user_id, username, verifier, cached_at)
+            VALUES (?, ?, ?, ?)
+            ON CONFLICT (user_id)
+            DO UPDATE SET username = excluded.username, verifier = excluded.verifier, cached_at = excluded.cached_at
+            "#,
+        )
+        .bind(&credential.user_id)
+        .bind(&credential.username)
+        .bind(&credential.verifier)
+        .bind(credential.cached_at.to_rfc3339())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    // newest one if two accounts were logged in with the same name
+    async fn find_by_username(&self, username: &str) -> Result<Option<CachedCredential>, AppError> {
+        let credential = sqlx::query_as::<_, CachedCredential>(
+            r#"
+            SELECT user_id, username, verifier, cached_at
+            FROM cached_credentials
+            WHERE username = ?
+            ORDER BY cached_at DESC
+            LIMIT 1
+            "#,
+        )
+        .bind(username.to_lowercase())
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(credential)
+    }
+
+    async fn delete(&self, user_id: &str) -> Result<bool, AppError> {
+        let result = sqlx::query("DELETE FROM cached_credentials WHERE user_id = ?")
+            .bind(user_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+pub struct SqliteLoginAttemptRepository {
+    pool: SqlitePool,
+}
+
+impl SqliteLoginAttemptRepository {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn initialize(&self) -> Result<(), AppError> {
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS login_attempts (
+                key TEXT PRIMARY KEY NOT NULL,
+                subject TEXT,
+                failures INTEGER NOT NULL,
+                last_failure_at TEXT NOT NULL,
+                locked_until TEXT
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_login_attempts_subject ON login_attempts(subject)")
+            .execute(&self.pool)
+            .await?;
+
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_login_attempts_last_failure ON login_attempts(last_failure_at)")
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl LoginAttemptRepository for SqliteLoginAttemptRepository {
+    async fn find(&self, key: &str) -> Result<Option<LoginAttempt>, AppError> {
+        let attempt = sqlx::query_as::<_, LoginAttempt>(
+            "SELECT key, subject, failures, last_failure_at, locked_until FROM login_attempts WHERE key = ?",
+        )
+        .bind(key)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(attempt)
+    }
+
+    async fn record_failure(
+        &self,
+        key: &str,
+        subject: Option<&str>,
+        window_start: DateTime<Utc>,
+    ) -> Result<LoginAttempt, AppError> {
+        let attempt = sqlx::query_as::<_, LoginAttempt>(
+            r#"
+            INSERT INTO login_attempts (key, subject, failures, last_failure_at, locked_until)
+            VALUES (?, ?, 1, ?, NULL)
+            ON CONFLICT (key) DO UPDATE SET
+                failures = CASE WHEN last_failure_at < ? THEN 1 ELSE failures + 1 END,
+                locked_until = CASE WHEN last_failure_at < ? THEN NULL ELSE locked_until END,
+                last_failure_at = excluded.last_failure_at
+            RETURNING key, subject, failures, last_failure_at, locked_until
+            "#,
+        )
+        .bind(key)
+        .bind(subject)
+        .bind(Utc::now().to_rfc3339())
+        .bind(window_start.to_rfc3339())
+        .bind(window_start.to_rfc3339())
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(attempt)
+    }
+
+    async fn lock(&self, key: &str, until: DateTime<Utc>) -> Result<(), AppError> {
+        sqlx::query("UPDATE login_attempts SET locked_until = ? WHERE key = ?")
+            .bind(until.to_rfc3339())
+            .bind(key)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn delete(&self, key: &str) -> Result<bool, AppError> {
+        let result = sqlx::query("DELETE FROM login_attempts WHERE key = ?")
+            .bind(key)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn delete_for_subject(&self, subject: &str) -> Result<u64, AppError> {
+        let result = sqlx::query("DELETE FROM login_attempts WHERE subject = ?")
+            .bind(subject)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    async fn delete_stale(&self, before: DateTime<Utc>) -> Result<u64, AppError> {
+        let result = sqlx::query(
+            "DELETE FROM login_attempts WHERE last_failure_at < ? AND (locked_until IS NULL OR locked_until < ?)",
+        )
+        .bind(before.to_rfc3339())
+        .bind(Utc::now().to_rfc3339())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+}
@@ -1,6 +1,9 @@
 use async_trait::async_trait;
 use crate::error::AppError;
-use crate::models::User;
+use crate::models::{
+    CachedCredential, Consent, DeviceAuthorization, LoginAttempt, OAuthClient, PasswordResetToken, RefreshToken, User,
+};
+use chrono::{DateTime, Utc};
 
 // Created for different auth backends (ad, google, etc)
 #[async_trait]
@@ -12,6 +15,15 @@ pub trait UserRepository: Send + Sync {
     async fn find_by_external_id(&self, provider: &str, external_id: &str)
         -> Result<Option<User>, AppError>;
 
+    // like the two above but deactivated accounts too, directory users can come back
+    async fn find_by_external_id_including_inactive(&self, provider: &str, external_id: &str)
+        -> Result<Option<User>, AppError>;
+
+    async fn find_by_email_including_inactive(&self, email: &str) -> Result<Option<User>, AppError>;
+
+    // all users of one provider, deactivated ones too, used by the directory sync
+    async fn list_by_provider(&self, provider: &str) -> Result<Vec<User>, AppError>;
+
     async fn create(&self, user: &User) -> Result<(), AppError>;
 
     async fn update(&self, user: &User) -> Result<(), AppError>;
@@ -22,3 +34,125 @@ pub trait UserRepository: Send + Sync {
 
     async fn count(&self) -> Result<i64, AppError>;
 }
+
+// registered oauth clients (relying parties)
+#[async_trait]
+pub trait ClientRepository: Send + Sync {
+    async fn find_by_client_id(&self, client_id: &str) -> Result<Option<OAuthClient>, AppError>;
+
+    async fn list(&self) -> Result<Vec<OAuthClient>, AppError>;
+
+    async fn create(&self, client: &OAuthClient) -> Result<(), AppError>;
+
+    async fn update(&self, client: &OAuthClient) -> Result<(), AppError>;
+
+    async fn delete(&self, client_id: &str) -> Result<(), AppError>;
+}
+
+// pending RFC 8628 device authorizations
+#[async_trait]
+pub trait DeviceAuthorizationRepository: Send + Sync {
+    async fn create(&self, authorization: &DeviceAuthorization) -> Result<(), AppError>;
+
+    async fn find_by_device_code_hash(&self, device_code_hash: &str)
+        -> Result<Option<DeviceAuthorization>, AppError>;
+
+    async fn find_by_user_code(&self, user_code: &str) -> Result<Option<DeviceAuthorization>, AppError>;
+
+    async fn update(&self, authorization: &DeviceAuthorization) -> Result<(), AppError>;
+
+    // approved -> consumed, false if another poll got there first
+    async fn consume(&self, device_code_hash: &str) -> Result<bool, AppError>;
+
+    async fn delete_expired(&self) -> Result<u64, AppError>;
+}
+
+#[async_trait]
+pub trait RefreshTokenRepository: Send + Sync {
+    async fn create(&self, token: &RefreshToken) -> Result<(), AppError>;
+
+    async fn find_by_hash(&self, token_hash: &str) -> Result<Option<RefreshToken>, AppError>;
+
+    // false if it was already revoked or is unknown, only one caller gets true
+    async fn revoke(&self, token_hash: &str) -> Result<bool, AppError>;
+
+    async fn revoke_all_for_user(&self, user_id: &str) -> Result<u64, AppError>;
+
+    async fn revoke_for_client(&self, user_id: &str, client_id: &str) -> Result<u64, AppError>;
+}
+
+#[async_trait]
+pub trait ConsentRepository: Send + Sync {
+    async fn find(&self, user_id: &str, client_id: &str) -> Result<Option<Consent>, AppError>;
+
+    async fn list_for_user(&self, user_id: &str) -> Result<Vec<Consent>, AppError>;
+
+    // insert or replace the grant of this user for this client
+    async fn save(&self, consent: &Consent) -> Result<(), AppError>;
+
+    async fn delete(&self, user_id: &str, client_id: &str) -> Result<bool, AppError>;
+}
+
+#[async_trait]
+pub trait PasswordResetRepository: Send + Sync {
+    async fn create(&self, token: &PasswordResetToken) -> Result<(), AppError>;
+
+    async fn find_by_hash(&self, token_hash: &str) -> Result<Option<PasswordResetToken>, AppError>;
+
+    // false if it was already used, two requests with the same link cant both win
+    async fn mark_used(&self, token_hash: &str) -> Result<bool, AppError>;
+
+    // newest token of the user, used or not
+    async fn find_latest_for_user(&self, user_id: &str) -> Result<Option<PasswordResetToken>, AppError>;
+
+    // marks every unused token of the user as used
+    async fn invalidate_for_user(&self, user_id: &str) -> Result<u64, AppError>;
+
+    async fn delete_expired(&self) -> Result<u64, AppError>;
+}
+
+// old password hashes of local accounts, newest first
+#[async_trait]
+pub trait PasswordHistoryRepository: Send + Sync {
+    async fn add(&self, user_id: &str, password_hash: &str) -> Result<(), AppError>;
+
+    async fn recent(&self, user_id: &str, limit: usize) -> Result<Vec<String>, AppError>;
+
+    // deletes everything but the newest `keep` entries of the user
+    async fn prune(&self, user_id: &str, keep: usize) -> Result<u64, AppError>;
+}
+
+// offline fallback for directory logins, one credential per user
+#[async_trait]
+pub trait CredentialCacheRepository: Send + Sync {
+    // insert or replace the credential of this user
+    async fn store(&self, credential: &CachedCredential) -> Result<(), AppError>;
+
+    async fn find_by_username(&self, username: &str) -> Result<Option<CachedCredential>, AppError>;
+
+    async fn delete(&self, user_id: &str) -> Result<bool, AppError>;
+}
+
+// failed login counters for the brute force protection
+#[async_trait]
+pub trait LoginAttemptRepository: Send + Sync {
+    async fn find(&self, key: &str) -> Result<Option<LoginAttempt>, AppError>;
+
+    // +1 in one statement (parallel guesses all count), starts at 1 again if the last failure is before window_start
+    async fn record_failure(
+        &self,
+        key: &str,
+        subject: Option<&str>,
+        window_start: DateTime<Utc>,
+    ) -> Result<LoginAttempt, AppError>;
+
+    async fn lock(&self, key: &str, until: DateTime<Utc>) -> Result<(), AppError>;
+
+    async fn delete(&self, key: &str) -> Result<bool, AppError>;
+
+    // every key of one account, for the admin unlock
+    async fn delete_for_subject(&self, subject: &str) -> Result<u64, AppError>;
+
+    // no failure since `before` and not locked anymore
+    async fn delete_stale(&self, before: DateTime<Utc>) -> Result<u64, AppError>;
+}
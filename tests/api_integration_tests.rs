@@ -5,28 +5,67 @@ mod common;
 use std::sync::Arc;
 
 use actix_web::{test, web, App, http::StatusCode};
+use base64::Engine;
 use serde_json::json;
 
-use syt_ek962_security_concepts::auth::{JwtService, LocalAuthProvider, PasswordHasher};
+use syt_ek962_security_concepts::auth::{
+    DeviceFlowService, InMemoryDirectory, JwtService, LdapAuthProvider, LocalAuthProvider, LoginThrottle,
+    PasswordHasher, PasswordResetService,
+};
+use syt_ek962_security_concepts::config::{LockoutConfig, PasswordResetConfig};
+use syt_ek962_security_concepts::mail::FileOutboxMailer;
 use syt_ek962_security_concepts::handlers::{AppState, configure_routes};
-use syt_ek962_security_concepts::models::{User, UserRole};
-use syt_ek962_security_concepts::repository::UserRepository;
+use syt_ek962_security_concepts::models::{GrantType, OAuthClient, RefreshToken, User, UserRole};
+use syt_ek962_security_concepts::repository::{ClientRepository, RefreshTokenRepository, UserRepository};
 
-use common::{MockUserRepository, test_jwt_config};
+use common::{
+    MockClientRepository, MockConsentRepository, MockCredentialCacheRepository, MockDeviceAuthorizationRepository,
+    MockLoginAttemptRepository, MockPasswordResetRepository, MockRefreshTokenRepository, MockUserRepository, reset_token_from_mail,
+    create_test_user, test_device_flow_config, test_jwt_config, test_ldap_config, test_password_reset_config,
+};
 
 fn create_test_app_state(repo: Arc<dyn UserRepository>) -> web::Data<AppState> {
+    create_test_app_state_with_clients(repo, Arc::new(MockClientRepository::new()))
+}
+
+fn create_test_app_state_with_clients(
+    repo: Arc<dyn UserRepository>,
+    clients: Arc<dyn ClientRepository>,
+) -> web::Data<AppState> {
+    web::Data::new(test_app_state(repo, clients))
+}
+
+fn create_test_app_state_with_ldap(repo: Arc<dyn UserRepository>, ldap: LdapAuthProvider) -> web::Data<AppState> {
     web::Data::new(AppState {
+        ldap_provider: Some(Arc::new(ldap)),
+        ..test_app_state(repo, Arc::new(MockClientRepository::new()))
+    })
+}
+
+fn test_app_state(repo: Arc<dyn UserRepository>, clients: Arc<dyn ClientRepository>) -> AppState {
+    AppState {
         jwt_service: JwtService::new(test_jwt_config()),
         auth_provider: LocalAuthProvider::new(repo.clone()),
         google_provider: None,
         ldap_provider: None,
         repository: repo,
-    })
+        client_repository: clients,
+        refresh_token_repository: Arc::new(MockRefreshTokenRepository::default()),
+        consent_repository: Arc::new(MockConsentRepository::default()),
+        device_flow: DeviceFlowService::new(
+            test_device_flow_config(),
+            Arc::new(MockDeviceAuthorizationRepository::default()),
+        ),
+        redirect_uri_schemes: vec!["at.tgm.app".to_string()],
+        directory_sync: None,
+        password_reset: None,
+        login_throttle: LoginThrottle::new(LockoutConfig::default(), Arc::new(MockLoginAttemptRepository::default())),
+    }
 }
 
 fn create_user_with_password(email: &str, password: &str, role: UserRole) -> User {
     let hasher = PasswordHasher::new();
-    let hash = hasher.hash(password).unwrap();
+    let hash = hasher.blocking_hash(password).unwrap();
     User::new_local("Test User".to_string(), email.to_string(), hash, role)
 }
 
@@ -154,6 +193,273 @@ async fn test_signin_empty_password() {
     assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
 }
 
+// ==================== Password Change Tests ====================
+
+#[actix_rt::test]
+async fn test_change_password_revokes_sessions() {
+    let user = create_user_with_password("test@example.com", "old_password_123", UserRole::User);
+    let user_id = user.id.clone();
+    let repo = Arc::new(MockUserRepository::with_user(user));
+    let refresh_tokens = Arc::new(MockRefreshTokenRepository::default());
+    for hash in ["a", "b"] {
+        let token = RefreshToken::new(hash.to_string(), user_id.clone(), "client-1".to_string(), vec![], 3600);
+        refresh_tokens.create(&token).await.unwrap();
+    }
+
+    let app_state = web::Data::new(AppState {
+        refresh_token_repository: refresh_tokens.clone(),
+        ..test_app_state(repo, Arc::new(MockClientRepository::new()))
+    });
+    let token = app_state
+        .jwt_service
+        .generate_token(&user_id, "test@example.com", UserRole::User)
+        .unwrap();
+
+    let app = test::init_service(
+        App::new()
+            .app_data(app_state)
+            .configure(configure_routes)
+    ).await;
+
+    let change = |current: &str, new: &str| test::TestRequest::post()
+        .uri("/auth/password")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(json!({"current_password": current, "new_password": new}))
+        .to_request();
+
+    let resp = test::call_service(&app, change("wrong_password", "new_password_456")).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+    let resp = test::call_service(&app, change("old_password_123", "short")).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    let resp = test::call_service(&app, change("old_password_123", "new_password_456")).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert!(body["token"].as_str().is_some());
+
+    assert!(!refresh_tokens.find_by_hash("a").await.unwrap().unwrap().is_valid());
+    assert!(!refresh_tokens.find_by_hash("b").await.unwrap().unwrap().is_valid());
+
+    for (password, status) in [("old_password_123", StatusCode::UNAUTHORIZED), ("new_password_456", StatusCode::OK)] {
+        let req = test::TestRequest::post()
+            .uri("/auth/signin")
+            .set_json(json!({"email": "test@example.com", "password": password}))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), status);
+    }
+}
+
+#[actix_rt::test]
+async fn test_change_password_invalidates_older_access_tokens() {
+    let user = create_user_with_password("test@example.com", "old_password_123", UserRole::User);
+    let user_id = user.id.clone();
+    let repo = Arc::new(MockUserRepository::with_user(user));
+    let app_state = create_test_app_state(repo);
+    let old_token = app_state
+        .jwt_service
+        .generate_token(&user_id, "test@example.com", UserRole::User)
+        .unwrap();
+
+    let app = test::init_service(
+        App::new()
+            .app_data(app_state)
+            .configure(configure_routes)
+    ).await;
+
+    // iat has whole seconds only
+    tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+
+    let req = test::TestRequest::post()
+        .uri("/auth/password")
+        .insert_header(("Authorization", format!("Bearer {}", old_token)))
+        .set_json(json!({"current_password": "old_password_123", "new_password": "new_password_456"}))
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    let new_token = body["token"].as_str().unwrap().to_string();
+
+    let grants = |token: &str| test::TestRequest::get()
+        .uri("/auth/grants")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    let resp = test::call_service(&app, grants(&old_token)).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    let resp = test::call_service(&app, grants(&new_token)).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let verify = |token: &str| test::TestRequest::post()
+        .uri("/auth/verify")
+        .set_json(json!({"token": token}))
+        .to_request();
+    let resp = test::call_service(&app, verify(&old_token)).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    let resp = test::call_service(&app, verify(&new_token)).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+}
+
+#[actix_rt::test]
+async fn test_change_password_requires_token() {
+    let app_state = create_test_app_state(Arc::new(MockUserRepository::new()));
+
+    let app = test::init_service(
+        App::new()
+            .app_data(app_state)
+            .configure(configure_routes)
+    ).await;
+
+    let req = test::TestRequest::post()
+        .uri("/auth/password")
+        .set_json(json!({"current_password": "old_password_123", "new_password": "new_password_456"}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[actix_rt::test]
+async fn test_forgot_password_flow() {
+    let dir = tempfile::tempdir().unwrap();
+    let user = create_user_with_password("test@example.com", "old_password_123", UserRole::User);
+    let repo = Arc::new(MockUserRepository::with_user(user));
+    let state = test_app_state(repo.clone(), Arc::new(MockClientRepository::new()));
+    let reset = PasswordResetService::new(
+        test_password_reset_config(dir.path()),
+        repo,
+        Arc::new(MockPasswordResetRepository::default()),
+        Arc::clone(&state.refresh_token_repository),
+        Arc::new(FileOutboxMailer::new(dir.path(), "auth@example.com").unwrap()),
+    );
+    let app_state = web::Data::new(AppState {
+        password_reset: Some(Arc::new(reset)),
+        ..state
+    });
+    let outbox = FileOutboxMailer::new(dir.path(), "auth@example.com").unwrap();
+
+    let app = test::init_service(
+        App::new()
+            .app_data(app_state)
+            .configure(configure_routes)
+    ).await;
+
+    let forgot = |email: &str| test::TestRequest::post()
+        .uri("/auth/password/forgot")
+        .set_json(json!({"email": email}))
+        .to_request();
+
+    // same answer whether the account exists or not
+    let resp = test::call_service(&app, forgot("nobody@example.com")).await;
+    assert_eq!(resp.status(), StatusCode::ACCEPTED);
+    let unknown: serde_json::Value = test::read_body_json(resp).await;
+    let resp = test::call_service(&app, forgot("test@example.com")).await;
+    assert_eq!(resp.status(), StatusCode::ACCEPTED);
+    let known: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(unknown, known);
+
+    // mail is sent in the background
+    let mut mails = outbox.messages().unwrap();
+    for _ in 0..100 {
+        if !mails.is_empty() {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        mails = outbox.messages().unwrap();
+    }
+    assert_eq!(mails.len(), 1);
+    let token = reset_token_from_mail(&mails[0]);
+
+    let reset = |password: &str| test::TestRequest::post()
+        .uri("/auth/password/reset")
+        .set_json(json!({"token": token, "new_password": password}))
+        .to_request();
+
+    let resp = test::call_service(&app, reset("short")).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    let resp = test::call_service(&app, reset("new_password_456")).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let resp = test::call_service(&app, reset("third_password_789")).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    let req = test::TestRequest::post()
+        .uri("/auth/signin")
+        .set_json(json!({"email": "test@example.com", "password": "new_password_456"}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+}
+
+#[actix_rt::test]
+async fn test_forgot_password_rate_limited() {
+    let dir = tempfile::tempdir().unwrap();
+    let repo = Arc::new(MockUserRepository::with_user(create_test_user("a@example.com", "hash", UserRole::User)));
+    repo.create(&create_test_user("b@example.com", "hash", UserRole::User)).await.unwrap();
+    let state = test_app_state(repo.clone(), Arc::new(MockClientRepository::new()));
+    let reset = PasswordResetService::new(
+        PasswordResetConfig {
+            max_requests_per_email: 2,
+            max_requests_per_ip: 3,
+            ..test_password_reset_config(dir.path())
+        },
+        repo,
+        Arc::new(MockPasswordResetRepository::default()),
+        Arc::clone(&state.refresh_token_repository),
+        Arc::new(FileOutboxMailer::new(dir.path(), "auth@example.com").unwrap()),
+    );
+    let app_state = web::Data::new(AppState {
+        password_reset: Some(Arc::new(reset)),
+        ..state
+    });
+    let outbox = FileOutboxMailer::new(dir.path(), "auth@example.com").unwrap();
+
+    let app = test::init_service(
+        App::new()
+            .app_data(app_state)
+            .configure(configure_routes)
+    ).await;
+
+    let forgot = |email: &str, ip: &str| test::TestRequest::post()
+        .uri("/auth/password/forgot")
+        .peer_addr(format!("{}:40000", ip).parse().unwrap())
+        .set_json(json!({"email": email}))
+        .to_request();
+
+    // third for the same address and fourth from the same ip are dropped, the answer stays the same
+    for (email, ip) in [("a@example.com", "10.0.0.1"), ("A@example.com", "10.0.0.2"), ("a@example.com", "10.0.0.3")] {
+        let resp = test::call_service(&app, forgot(email, ip)).await;
+        assert_eq!(resp.status(), StatusCode::ACCEPTED);
+    }
+    for _ in 0..3 {
+        test::call_service(&app, forgot("nobody@example.com", "10.0.0.9")).await;
+    }
+    let resp = test::call_service(&app, forgot("b@example.com", "10.0.0.9")).await;
+    assert_eq!(resp.status(), StatusCode::ACCEPTED);
+
+    // mails go out in the background, give the dropped ones time to show up if they were sent
+    tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+    let mails = outbox.messages().unwrap();
+    assert_eq!(mails.len(), 2);
+    assert!(mails.iter().all(|mail| mail.to == "a@example.com"));
+}
+
+#[actix_rt::test]
+async fn test_forgot_password_not_configured() {
+    let app_state = create_test_app_state(Arc::new(MockUserRepository::new()));
+
+    let app = test::init_service(
+        App::new()
+            .app_data(app_state)
+            .configure(configure_routes)
+    ).await;
+
+    let req = test::TestRequest::post()
+        .uri("/auth/password/forgot")
+        .set_json(json!({"email": "test@example.com"}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+}
+
 // ==================== Token Verification Tests ====================
 
 #[actix_rt::test]
@@ -189,6 +495,7 @@ async fn test_verify_valid_token() {
     let body: serde_json::Value = test::read_body_json(resp).await;
     assert_eq!(body["valid"], true);
     assert_eq!(body["email"], "test@example.com");
+    assert_eq!(body["scope"], "openid profile email");
 }
 
 #[actix_rt::test]
@@ -415,12 +722,18 @@ async fn test_register_with_short_password() {
     assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
 }
 
-// ==================== LDAP Sign In Tests ====================
-
 #[actix_rt::test]
-async fn test_ldap_signin_not_configured() {
-    let repo = Arc::new(MockUserRepository::new());
-    let app_state = create_test_app_state(repo); // LDAP not configured
+async fn test_register_with_common_password() {
+    let admin = create_user_with_password("admin@example.com", "admin_password", UserRole::Admin);
+    let admin_id = admin.id.clone();
+    let repo = Arc::new(MockUserRepository::with_user(admin));
+    let app_state = create_test_app_state(repo);
+
+    let admin_token = app_state.jwt_service.generate_token(
+        &admin_id,
+        "admin@example.com",
+        UserRole::Admin,
+    ).unwrap();
 
     let app = test::init_service(
         App::new()
@@ -429,15 +742,1453 @@ async fn test_ldap_signin_not_configured() {
     ).await;
 
     let req = test::TestRequest::post()
-        .uri("/auth/ldap/signin")
+        .uri("/auth/admin/register")
+        .insert_header(("Authorization", format!("Bearer {}", admin_token)))
         .set_json(json!({
-            "username": "testuser",
-            "password": "password123"
+            "name": "New User",
+            "email": "newuser@example.com",
+            "password": "passwordpassword"
         }))
         .to_request();
 
     let resp = test::call_service(&app, req).await;
     assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert!(body["error"].as_str().unwrap().contains("too common"));
+}
+
+#[actix_rt::test]
+async fn test_import_users_with_legacy_hashes() {
+    let admin = create_user_with_password("admin@example.com", "admin_password", UserRole::Admin);
+    let admin_id = admin.id.clone();
+    let repo = Arc::new(MockUserRepository::with_user(admin));
+    let app_state = create_test_app_state(repo);
+
+    let admin_token = app_state.jwt_service.generate_token(
+        &admin_id,
+        "admin@example.com",
+        UserRole::Admin,
+    ).unwrap();
+
+    let app = test::init_service(
+        App::new()
+            .app_data(app_state)
+            .configure(configure_routes)
+    ).await;
+
+    let req = test::TestRequest::post()
+        .uri("/auth/admin/import")
+        .insert_header(("Authorization", format!("Bearer {}", admin_token)))
+        .set_json(json!({
+            "users": [
+                {
+                    "name": "From Django",
+                    "email": "django@example.com",
+                    "password_hash": "pbkdf2_sha256$1000$seasalt$iQbGf4uimNHthLwDk+B/WUv+q9caE1SVKDrO8xeKykc="
+                },
+                {
+                    "name": "Broken",
+                    "email": "broken@example.com",
+                    "password_hash": "$2y$10$tooshort"
+                },
+                {
+                    "name": "Existing",
+                    "email": "admin@example.com",
+                    "password_hash": "pbkdf2_sha256$1000$seasalt$iQbGf4uimNHthLwDk+B/WUv+q9caE1SVKDrO8xeKykc="
+                }
+            ]
+        }))
+        .to_request();
+
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["imported"].as_array().unwrap().len(), 1);
+    assert_eq!(body["imported"][0]["email"], "django@example.com");
+    let failed = body["failed"].as_array().unwrap();
+    assert_eq!(failed.len(), 2);
+    assert_eq!(failed[0]["email"], "broken@example.com");
+    assert!(!failed[0]["error"].as_str().unwrap().contains("tooshort"));
+
+    let req = test::TestRequest::post()
+        .uri("/auth/signin")
+        .set_json(json!({"email": "django@example.com", "password": "legacy_password_1"}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+}
+
+#[actix_rt::test]
+async fn test_import_users_requires_admin() {
+    let user = create_user_with_password("user@example.com", "user_password", UserRole::User);
+    let user_id = user.id.clone();
+    let repo = Arc::new(MockUserRepository::with_user(user));
+    let app_state = create_test_app_state(repo);
+
+    let token = app_state.jwt_service.generate_token(&user_id, "user@example.com", UserRole::User).unwrap();
+
+    let app = test::init_service(
+        App::new()
+            .app_data(app_state)
+            .configure(configure_routes)
+    ).await;
+
+    let req = test::TestRequest::post()
+        .uri("/auth/admin/import")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(json!({"users": []}))
+        .to_request();
+
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+}
+
+// ==================== OAuth Client Admin Tests ====================
+
+fn admin_state_with_clients(clients: Arc<MockClientRepository>) -> (web::Data<AppState>, String) {
+    let admin = create_user_with_password("admin@example.com", "admin_password", UserRole::Admin);
+    let admin_id = admin.id.clone();
+    let repo = Arc::new(MockUserRepository::with_user(admin));
+    let app_state = create_test_app_state_with_clients(repo, clients);
+
+    let admin_token = app_state.jwt_service.generate_token(
+        &admin_id,
+        "admin@example.com",
+        UserRole::Admin,
+    ).unwrap();
+
+    (app_state, admin_token)
+}
+
+#[actix_rt::test]
+async fn test_create_client_as_admin() {
+    let clients = Arc::new(MockClientRepository::new());
+    let (app_state, admin_token) = admin_state_with_clients(clients.clone());
+
+    let app = test::init_service(
+        App::new()
+            .app_data(app_state.clone())
+            .configure(configure_routes)
+    ).await;
+
+    let req = test::TestRequest::post()
+        .uri("/auth/admin/clients")
+        .insert_header(("Authorization", format!("Bearer {}", admin_token)))
+        .set_json(json!({
+            "name": "Reporting App",
+            "redirect_uris": ["https://reports.example.com/callback"],
+            "grant_types": ["authorization_code", "refresh_token"],
+            "scopes": ["openid", "profile"]
+        }))
+        .to_request();
+
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::CREATED);
+
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["client"]["name"], "Reporting App");
+    assert_eq!(body["client"]["confidential"], true);
+    assert_eq!(body["client"]["access_token_ttl_secs"], 3600);
+    assert!(body["client"].get("client_secret_hash").is_none());
+
+    let client_id = body["client"]["client_id"].as_str().unwrap();
+    let secret = body["client_secret"].as_str().unwrap();
+
+    // Only the hash is stored
+    let stored = clients.find_by_client_id(client_id).await.unwrap().unwrap();
+    let hash = stored.client_secret_hash.unwrap();
+    assert_ne!(hash, secret);
+    assert!(app_state.auth_provider.password_hasher().blocking_verify(secret, &hash).unwrap());
+}
+
+#[actix_rt::test]
+async fn test_create_public_client_has_no_secret() {
+    let (app_state, admin_token) = admin_state_with_clients(Arc::new(MockClientRepository::new()));
+
+    let app = test::init_service(
+        App::new()
+            .app_data(app_state)
+            .configure(configure_routes)
+    ).await;
+
+    let req = test::TestRequest::post()
+        .uri("/auth/admin/clients")
+        .insert_header(("Authorization", format!("Bearer {}", admin_token)))
+        .set_json(json!({
+            "name": "Build CLI",
+            "grant_types": ["urn:ietf:params:oauth:grant-type:device_code"],
+            "confidential": false
+        }))
+        .to_request();
+
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::CREATED);
+
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["client"]["confidential"], false);
+    assert!(body["client_secret"].is_null());
+}
+
+#[actix_rt::test]
+async fn test_create_client_without_admin_token() {
+    let user = create_user_with_password("user@example.com", "user_password", UserRole::User);
+    let user_id = user.id.clone();
+    let repo = Arc::new(MockUserRepository::with_user(user));
+    let app_state = create_test_app_state(repo);
+
+    let user_token = app_state.jwt_service.generate_token(
+        &user_id,
+        "user@example.com",
+        UserRole::User,
+    ).unwrap();
+
+    let app = test::init_service(
+        App::new()
+            .app_data(app_state)
+            .configure(configure_routes)
+    ).await;
+
+    let req = test::TestRequest::post()
+        .uri("/auth/admin/clients")
+        .insert_header(("Authorization", format!("Bearer {}", user_token)))
+        .set_json(json!({
+            "name": "Sneaky App",
+            "redirect_uris": ["https://evil.example.com/callback"],
+            "grant_types": ["authorization_code"]
+        }))
+        .to_request();
+
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+}
+
+#[actix_rt::test]
+async fn test_create_client_authorization_code_requires_redirect_uri() {
+    let (app_state, admin_token) = admin_state_with_clients(Arc::new(MockClientRepository::new()));
+
+    let app = test::init_service(
+        App::new()
+            .app_data(app_state)
+            .configure(configure_routes)
+    ).await;
+
+    let req = test::TestRequest::post()
+        .uri("/auth/admin/clients")
+        .insert_header(("Authorization", format!("Bearer {}", admin_token)))
+        .set_json(json!({
+            "name": "Broken App",
+            "grant_types": ["authorization_code"]
+        }))
+        .to_request();
+
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+}
+
+#[actix_rt::test]
+async fn test_create_client_rejects_unsafe_redirect_uris() {
+    let (app_state, admin_token) = admin_state_with_clients(Arc::new(MockClientRepository::new()));
+
+    let app = test::init_service(
+        App::new()
+            .app_data(app_state)
+            .configure(configure_routes)
+    ).await;
+
+    let create = |redirect_uri: &str| test::TestRequest::post()
+        .uri("/auth/admin/clients")
+        .insert_header(("Authorization", format!("Bearer {}", admin_token)))
+        .set_json(json!({
+            "name": "Native App",
+            "redirect_uris": [redirect_uri],
+            "grant_types": ["authorization_code"],
+            "confidential": false
+        }))
+        .to_request();
+
+    for rejected in ["http://app.example.com/callback", "javascript:alert(1)", "com.other.app:/callback"] {
+        let resp = test::call_service(&app, create(rejected)).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST, "{} accepted", rejected);
+    }
+
+    for accepted in ["http://127.0.0.1:53682/callback", "at.tgm.app:/callback"] {
+        let resp = test::call_service(&app, create(accepted)).await;
+        assert_eq!(resp.status(), StatusCode::CREATED, "{} rejected", accepted);
+    }
+}
+
+#[actix_rt::test]
+async fn test_update_and_delete_client() {
+    let clients = Arc::new(MockClientRepository::new());
+    let (app_state, admin_token) = admin_state_with_clients(clients.clone());
+
+    let app = test::init_service(
+        App::new()
+            .app_data(app_state)
+            .configure(configure_routes)
+    ).await;
+
+    let req = test::TestRequest::post()
+        .uri("/auth/admin/clients")
+        .insert_header(("Authorization", format!("Bearer {}", admin_token)))
+        .set_json(json!({
+            "name": "Old Name",
+            "grant_types": ["client_credentials"]
+        }))
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    let client_id = body["client"]["client_id"].as_str().unwrap().to_string();
+
+    let req = test::TestRequest::patch()
+        .uri(&format!("/auth/admin/clients/{}", client_id))
+        .insert_header(("Authorization", format!("Bearer {}", admin_token)))
+        .set_json(json!({
+            "name": "New Name",
+            "scopes": ["users:read"]
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let stored = clients.find_by_client_id(&client_id).await.unwrap().unwrap();
+    assert_eq!(stored.name, "New Name");
+    assert_eq!(stored.scopes, vec!["users:read".to_string()]);
+
+    let req = test::TestRequest::delete()
+        .uri(&format!("/auth/admin/clients/{}", client_id))
+        .insert_header(("Authorization", format!("Bearer {}", admin_token)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/auth/admin/clients/{}", client_id))
+        .insert_header(("Authorization", format!("Bearer {}", admin_token)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_rt::test]
+async fn test_regenerate_client_secret() {
+    let clients = Arc::new(MockClientRepository::new());
+    let (app_state, admin_token) = admin_state_with_clients(clients.clone());
+
+    let app = test::init_service(
+        App::new()
+            .app_data(app_state.clone())
+            .configure(configure_routes)
+    ).await;
+
+    let req = test::TestRequest::post()
+        .uri("/auth/admin/clients")
+        .insert_header(("Authorization", format!("Bearer {}", admin_token)))
+        .set_json(json!({
+            "name": "Backend Service",
+            "grant_types": ["client_credentials"]
+        }))
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    let client_id = body["client"]["client_id"].as_str().unwrap().to_string();
+    let old_secret = body["client_secret"].as_str().unwrap().to_string();
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/auth/admin/clients/{}/secret", client_id))
+        .insert_header(("Authorization", format!("Bearer {}", admin_token)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    let new_secret = body["client_secret"].as_str().unwrap();
+    assert_ne!(new_secret, old_secret);
+
+    let hash = clients
+        .find_by_client_id(&client_id)
+        .await
+        .unwrap()
+        .unwrap()
+        .client_secret_hash
+        .unwrap();
+    let hasher = app_state.auth_provider.password_hasher();
+    assert!(hasher.blocking_verify(new_secret, &hash).unwrap());
+    assert!(!hasher.blocking_verify(&old_secret, &hash).unwrap());
+}
+
+// ==================== Device Authorization Grant Tests ====================
+
+const DEVICE_GRANT: &str = "urn:ietf:params:oauth:grant-type:device_code";
+
+fn cli_client() -> OAuthClient {
+    OAuthClient::new(
+        "Build CLI".to_string(),
+        None,
+        vec![],
+        vec![GrantType::DeviceCode, GrantType::RefreshToken],
+        vec!["profile".to_string()],
+        600,
+        86400,
+    )
+}
+
+fn device_test_state(client: OAuthClient) -> (web::Data<AppState>, String) {
+    let user = create_user_with_password("dev@example.com", "dev_password_123", UserRole::User);
+    let user_id = user.id.clone();
+    let repo = Arc::new(MockUserRepository::with_user(user));
+    let app_state = create_test_app_state_with_clients(
+        repo,
+        Arc::new(MockClientRepository::with_client(client)),
+    );
+
+    let user_token = app_state.jwt_service.generate_token(
+        &user_id,
+        "dev@example.com",
+        UserRole::User,
+    ).unwrap();
+
+    (app_state, user_token)
+}
+
+#[actix_rt::test]
+async fn test_device_flow_approve_and_poll() {
+    let client = cli_client();
+    let client_id = client.client_id.clone();
+    let (app_state, user_token) = device_test_state(client);
+
+    let app = test::init_service(
+        App::new()
+            .app_data(app_state)
+            .configure(configure_routes)
+    ).await;
+
+    let req = test::TestRequest::post()
+        .uri("/oauth/device_authorization")
+        .set_form([("client_id", client_id.as_str()), ("scope", "profile")])
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    let device_code = body["device_code"].as_str().unwrap().to_string();
+    let user_code = body["user_code"].as_str().unwrap().to_string();
+    assert_eq!(user_code.len(), 9);
+    assert_eq!(body["verification_uri"], "http://localhost:8080/device.html");
+    assert!(body["verification_uri_complete"].as_str().unwrap().ends_with(&user_code));
+
+    // Not approved yet
+    let poll = || test::TestRequest::post()
+        .uri("/oauth/token")
+        .set_form([
+            ("grant_type", DEVICE_GRANT),
+            ("device_code", device_code.as_str()),
+            ("client_id", client_id.as_str()),
+        ])
+        .to_request();
+    let resp = test::call_service(&app, poll()).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["error"], "authorization_pending");
+
+    // User sees the client and approves
+    let req = test::TestRequest::get()
+        .uri(&format!("/oauth/device?user_code={}", user_code.to_lowercase()))
+        .insert_header(("Authorization", format!("Bearer {}", user_token)))
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["client_name"], "Build CLI");
+    assert_eq!(body["scopes"], json!(["profile"]));
+
+    let req = test::TestRequest::post()
+        .uri("/oauth/device")
+        .insert_header(("Authorization", format!("Bearer {}", user_token)))
+        .set_json(json!({ "user_code": user_code, "approve": true }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    // Device gets its tokens exactly once
+    let resp = test::call_service(&app, poll()).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["token_type"], "Bearer");
+    assert_eq!(body["expires_in"], 600);
+    assert_eq!(body["scope"], "profile");
+    assert!(body["access_token"].is_string());
+    assert!(body["refresh_token"].is_string());
+
+    let resp = test::call_service(&app, poll()).await;
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["error"], "invalid_grant");
+}
+
+#[actix_rt::test]
+async fn test_device_flow_denied() {
+    let client = cli_client();
+    let client_id = client.client_id.clone();
+    let (app_state, user_token) = device_test_state(client);
+
+    let app = test::init_service(
+        App::new()
+            .app_data(app_state)
+            .configure(configure_routes)
+    ).await;
+
+    let req = test::TestRequest::post()
+        .uri("/oauth/device_authorization")
+        .set_form([("client_id", client_id.as_str())])
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    let device_code = body["device_code"].as_str().unwrap().to_string();
+
+    let req = test::TestRequest::post()
+        .uri("/oauth/device")
+        .insert_header(("Authorization", format!("Bearer {}", user_token)))
+        .set_json(json!({ "user_code": body["user_code"], "approve": false }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let req = test::TestRequest::post()
+        .uri("/oauth/token")
+        .set_form([
+            ("grant_type", DEVICE_GRANT),
+            ("device_code", device_code.as_str()),
+            ("client_id", client_id.as_str()),
+        ])
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["error"], "access_denied");
+}
+
+#[actix_rt::test]
+async fn test_device_wrong_user_codes_are_throttled() {
+    let client = cli_client();
+    let client_id = client.client_id.clone();
+    let (app_state, user_token) = device_test_state(client);
+
+    let app = test::init_service(
+        App::new()
+            .app_data(app_state)
+            .configure(configure_routes)
+    ).await;
+
+    let req = test::TestRequest::post()
+        .uri("/oauth/device_authorization")
+        .set_form([("client_id", client_id.as_str())])
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    let user_code = body["user_code"].as_str().unwrap().to_string();
+
+    let info = |code: &str| test::TestRequest::get()
+        .uri(&format!("/oauth/device?user_code={}", code))
+        .insert_header(("Authorization", format!("Bearer {}", user_token)))
+        .to_request();
+    let approve = |code: &str| test::TestRequest::post()
+        .uri("/oauth/device")
+        .insert_header(("Authorization", format!("Bearer {}", user_token)))
+        .set_json(json!({ "user_code": code, "approve": true }))
+        .to_request();
+
+    // default max_failures is 5, lookups and approvals count together
+    for _ in 0..3 {
+        let resp = test::call_service(&app, info("WXZB-WXZB")).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+    for _ in 0..2 {
+        let resp = test::call_service(&app, approve("WXZB-WXZB")).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    let resp = test::call_service(&app, approve("BCDF-GHJK")).await;
+    assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+    // the right code does not help while locked
+    let resp = test::call_service(&app, info(&user_code)).await;
+    assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+    let resp = test::call_service(&app, approve(&user_code)).await;
+    assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+}
+
+#[actix_rt::test]
+async fn test_device_verify_requires_login() {
+    let (app_state, _) = device_test_state(cli_client());
+
+    let app = test::init_service(
+        App::new()
+            .app_data(app_state)
+            .configure(configure_routes)
+    ).await;
+
+    let req = test::TestRequest::post()
+        .uri("/oauth/device")
+        .set_json(json!({ "user_code": "BCDF-GHJK", "approve": true }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[actix_rt::test]
+async fn test_device_authorization_rejects_unknown_scope() {
+    let client = cli_client();
+    let client_id = client.client_id.clone();
+    let (app_state, _) = device_test_state(client);
+
+    let app = test::init_service(
+        App::new()
+            .app_data(app_state)
+            .configure(configure_routes)
+    ).await;
+
+    let req = test::TestRequest::post()
+        .uri("/oauth/device_authorization")
+        .set_form([("client_id", client_id.as_str()), ("scope", "admin:everything")])
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["error"], "invalid_scope");
+}
+
+#[actix_rt::test]
+async fn test_device_authorization_unknown_client() {
+    let (app_state, _) = device_test_state(cli_client());
+
+    let app = test::init_service(
+        App::new()
+            .app_data(app_state)
+            .configure(configure_routes)
+    ).await;
+
+    let req = test::TestRequest::post()
+        .uri("/oauth/device_authorization")
+        .set_form([("client_id", "does-not-exist")])
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["error"], "invalid_client");
+}
+
+#[actix_rt::test]
+async fn test_token_unsupported_grant_type() {
+    let (app_state, _) = device_test_state(cli_client());
+
+    let app = test::init_service(
+        App::new()
+            .app_data(app_state)
+            .configure(configure_routes)
+    ).await;
+
+    let req = test::TestRequest::post()
+        .uri("/oauth/token")
+        .set_form([("grant_type", "password")])
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["error"], "unsupported_grant_type");
+}
+
+#[actix_rt::test]
+async fn test_refresh_token_rotation() {
+    let client = cli_client();
+    let client_id = client.client_id.clone();
+    let (app_state, user_token) = device_test_state(client);
+
+    let app = test::init_service(
+        App::new()
+            .app_data(app_state)
+            .configure(configure_routes)
+    ).await;
+
+    let req = test::TestRequest::post()
+        .uri("/oauth/device_authorization")
+        .set_form([("client_id", client_id.as_str())])
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    let device_code = body["device_code"].as_str().unwrap().to_string();
+
+    let req = test::TestRequest::post()
+        .uri("/oauth/device")
+        .insert_header(("Authorization", format!("Bearer {}", user_token)))
+        .set_json(json!({ "user_code": body["user_code"], "approve": true }))
+        .to_request();
+    test::call_service(&app, req).await;
+
+    let req = test::TestRequest::post()
+        .uri("/oauth/token")
+        .set_form([
+            ("grant_type", DEVICE_GRANT),
+            ("device_code", device_code.as_str()),
+            ("client_id", client_id.as_str()),
+        ])
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    let first_refresh = body["refresh_token"].as_str().unwrap().to_string();
+
+    let refresh = |token: &str| test::TestRequest::post()
+        .uri("/oauth/token")
+        .set_form([
+            ("grant_type", "refresh_token"),
+            ("refresh_token", token),
+            ("client_id", client_id.as_str()),
+        ])
+        .to_request();
+
+    let resp = test::call_service(&app, refresh(&first_refresh)).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    let second_refresh = body["refresh_token"].as_str().unwrap().to_string();
+    assert_ne!(first_refresh, second_refresh);
+
+    let resp = test::call_service(&app, refresh(&second_refresh)).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    let third_refresh = body["refresh_token"].as_str().unwrap().to_string();
+
+    // Old refresh token was revoked by the rotation
+    let body: serde_json::Value = test::call_and_read_body_json(&app, refresh(&second_refresh)).await;
+    assert_eq!(body["error"], "invalid_grant");
+
+    // Replaying it counts as theft, the current token of that client is gone too
+    let body: serde_json::Value = test::call_and_read_body_json(&app, refresh(&third_refresh)).await;
+    assert_eq!(body["error"], "invalid_grant");
+}
+
+#[actix_rt::test]
+async fn test_token_confidential_client_wrong_secret() {
+    let hasher = PasswordHasher::new();
+    let client = OAuthClient::new(
+        "Backend".to_string(),
+        Some(hasher.blocking_hash("correct_secret").unwrap()),
+        vec![],
+        vec![GrantType::DeviceCode],
+        vec![],
+        600,
+        86400,
+    );
+    let client_id = client.client_id.clone();
+    let (app_state, _) = device_test_state(client);
+
+    let app = test::init_service(
+        App::new()
+            .app_data(app_state)
+            .configure(configure_routes)
+    ).await;
+
+    let req = test::TestRequest::post()
+        .uri("/oauth/device_authorization")
+        .set_form([("client_id", client_id.as_str()), ("client_secret", "wrong_secret")])
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+    // Without any secret the confidential client is rejected too
+    let req = test::TestRequest::post()
+        .uri("/oauth/device_authorization")
+        .set_form([("client_id", client_id.as_str())])
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[actix_rt::test]
+async fn test_token_client_secret_basic_is_form_urlencoded() {
+    let hasher = PasswordHasher::new();
+    let client = OAuthClient::new(
+        "Backend".to_string(),
+        Some(hasher.blocking_hash("s3cret:with+special%chars").unwrap()),
+        vec![],
+        vec![GrantType::DeviceCode],
+        vec![],
+        600,
+        86400,
+    );
+    let client_id = client.client_id.clone();
+    let (app_state, _) = device_test_state(client);
+
+    let app = test::init_service(
+        App::new()
+            .app_data(app_state)
+            .configure(configure_routes)
+    ).await;
+
+    let basic = |secret: &str| {
+        let credentials = base64::engine::general_purpose::STANDARD.encode(format!("{}:{}", client_id, secret));
+        test::TestRequest::post()
+            .uri("/oauth/device_authorization")
+            .insert_header(("Authorization", format!("Basic {}", credentials)))
+            .set_form([("scope", "")])
+            .to_request()
+    };
+
+    let resp = test::call_service(&app, basic("s3cret%3Awith%2Bspecial%25chars")).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    // not decoded twice, and a raw + is a space
+    let resp = test::call_service(&app, basic("s3cret%253Awith%2Bspecial%25chars")).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    let resp = test::call_service(&app, basic("s3cret%3Awith+special%25chars")).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+}
+
+// ==================== Scope Tests ====================
+
+#[actix_rt::test]
+async fn test_admin_endpoint_requires_scope() {
+    let admin = create_user_with_password("admin@example.com", "admin_password_123", UserRole::Admin);
+    let admin_id = admin.id.clone();
+    let repo = Arc::new(MockUserRepository::with_user(admin));
+    let app_state = create_test_app_state(repo);
+
+    // admin role but the token was only granted openid
+    let narrow_token = app_state.jwt_service.generate_scoped_token(
+        &admin_id,
+        "admin@example.com",
+        UserRole::Admin,
+        &["openid".to_string()],
+        600,
+    ).unwrap();
+
+    let read_token = app_state.jwt_service.generate_scoped_token(
+        &admin_id,
+        "admin@example.com",
+        UserRole::Admin,
+        &["clients:read".to_string()],
+        600,
+    ).unwrap();
+
+    let app = test::init_service(
+        App::new()
+            .app_data(app_state)
+            .configure(configure_routes)
+    ).await;
+
+    let req = test::TestRequest::get()
+        .uri("/auth/admin/clients")
+        .insert_header(("Authorization", format!("Bearer {}", narrow_token)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    let req = test::TestRequest::get()
+        .uri("/auth/admin/clients")
+        .insert_header(("Authorization", format!("Bearer {}", read_token)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    // read scope is not enough to register clients
+    let req = test::TestRequest::post()
+        .uri("/auth/admin/clients")
+        .insert_header(("Authorization", format!("Bearer {}", read_token)))
+        .set_json(json!({ "name": "App", "grant_types": ["client_credentials"] }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+}
+
+#[actix_rt::test]
+async fn test_create_client_rejects_unknown_scope() {
+    let (app_state, admin_token) = admin_state_with_clients(Arc::new(MockClientRepository::new()));
+
+    let app = test::init_service(
+        App::new()
+            .app_data(app_state)
+            .configure(configure_routes)
+    ).await;
+
+    let req = test::TestRequest::post()
+        .uri("/auth/admin/clients")
+        .insert_header(("Authorization", format!("Bearer {}", admin_token)))
+        .set_json(json!({
+            "name": "App",
+            "grant_types": ["client_credentials"],
+            "scopes": ["reports:read"]
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+}
+
+#[actix_rt::test]
+async fn test_device_flow_scopes_intersect_with_role() {
+    let mut client = cli_client();
+    client.scopes = vec!["profile".to_string(), "users:read".to_string()];
+    let client_id = client.client_id.clone();
+    let (app_state, user_token) = device_test_state(client);
+    let jwt_config = test_jwt_config();
+
+    let app = test::init_service(
+        App::new()
+            .app_data(app_state)
+            .configure(configure_routes)
+    ).await;
+
+    let req = test::TestRequest::post()
+        .uri("/oauth/device_authorization")
+        .set_form([("client_id", client_id.as_str()), ("scope", "profile users:read")])
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    let device_code = body["device_code"].as_str().unwrap().to_string();
+
+    let req = test::TestRequest::post()
+        .uri("/oauth/device")
+        .insert_header(("Authorization", format!("Bearer {}", user_token)))
+        .set_json(json!({ "user_code": body["user_code"], "approve": true }))
+        .to_request();
+    test::call_service(&app, req).await;
+
+    let req = test::TestRequest::post()
+        .uri("/oauth/token")
+        .set_form([
+            ("grant_type", DEVICE_GRANT),
+            ("device_code", device_code.as_str()),
+            ("client_id", client_id.as_str()),
+        ])
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+
+    // plain user role has no users:read, so only profile ends up in the token
+    assert_eq!(body["scope"], "profile");
+
+    let claims = JwtService::new(jwt_config)
+        .validate_token(body["access_token"].as_str().unwrap())
+        .unwrap();
+    assert!(claims.has_scope("profile"));
+    assert!(!claims.has_scope("users:read"));
+}
+
+// ==================== Consent / Grant Tests ====================
+
+// runs device_authorization, approval by the user and the token request, returns the token response
+async fn authorize_device(
+    app_state: &web::Data<AppState>,
+    client_id: &str,
+    user_token: &str,
+    scope: &str,
+) -> serde_json::Value {
+    let app = &test::init_service(
+        App::new()
+            .app_data(app_state.clone())
+            .configure(configure_routes)
+    ).await;
+
+    let req = test::TestRequest::post()
+        .uri("/oauth/device_authorization")
+        .set_form([("client_id", client_id), ("scope", scope)])
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(app, req).await;
+    let device_code = body["device_code"].as_str().unwrap().to_string();
+
+    let req = test::TestRequest::post()
+        .uri("/oauth/device")
+        .insert_header(("Authorization", format!("Bearer {}", user_token)))
+        .set_json(json!({ "user_code": body["user_code"], "approve": true }))
+        .to_request();
+    let resp = test::call_service(app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let req = test::TestRequest::post()
+        .uri("/oauth/token")
+        .set_form([
+            ("grant_type", DEVICE_GRANT),
+            ("device_code", device_code.as_str()),
+            ("client_id", client_id),
+        ])
+        .to_request();
+    test::call_and_read_body_json(app, req).await
+}
+
+#[actix_rt::test]
+async fn test_consent_page_shows_only_new_scopes() {
+    let mut client = cli_client();
+    client.scopes = vec!["openid".to_string(), "profile".to_string()];
+    let client_id = client.client_id.clone();
+    let (app_state, user_token) = device_test_state(client);
+
+    let app = test::init_service(
+        App::new()
+            .app_data(app_state.clone())
+            .configure(configure_routes)
+    ).await;
+
+    let lookup = |user_code: &serde_json::Value| test::TestRequest::get()
+        .uri(&format!("/oauth/device?user_code={}", user_code.as_str().unwrap()))
+        .insert_header(("Authorization", format!("Bearer {}", user_token)))
+        .to_request();
+
+    // first authorization: everything is new
+    let req = test::TestRequest::post()
+        .uri("/oauth/device_authorization")
+        .set_form([("client_id", client_id.as_str()), ("scope", "openid")])
+        .to_request();
+    let grant: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    let body: serde_json::Value = test::call_and_read_body_json(&app, lookup(&grant["user_code"])).await;
+    assert_eq!(body["new_scopes"], json!(["openid"]));
+
+    authorize_device(&app_state, &client_id, &user_token, "openid").await;
+
+    // scope escalation: only profile has to be confirmed
+    let req = test::TestRequest::post()
+        .uri("/oauth/device_authorization")
+        .set_form([("client_id", client_id.as_str()), ("scope", "openid profile")])
+        .to_request();
+    let grant: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    let body: serde_json::Value = test::call_and_read_body_json(&app, lookup(&grant["user_code"])).await;
+    assert_eq!(body["scopes"], json!(["openid", "profile"]));
+    assert_eq!(body["new_scopes"], json!(["profile"]));
+}
+
+#[actix_rt::test]
+async fn test_list_and_revoke_grants() {
+    let client = cli_client();
+    let client_id = client.client_id.clone();
+    let (app_state, user_token) = device_test_state(client);
+
+    let app = test::init_service(
+        App::new()
+            .app_data(app_state.clone())
+            .configure(configure_routes)
+    ).await;
+
+    let tokens = authorize_device(&app_state, &client_id, &user_token, "profile").await;
+    let refresh_token = tokens["refresh_token"].as_str().unwrap().to_string();
+
+    let req = test::TestRequest::get()
+        .uri("/auth/grants")
+        .insert_header(("Authorization", format!("Bearer {}", user_token)))
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body.as_array().unwrap().len(), 1);
+    assert_eq!(body[0]["client_id"], client_id.as_str());
+    assert_eq!(body[0]["client_name"], "Build CLI");
+    assert_eq!(body[0]["scopes"], json!(["profile"]));
+
+    let req = test::TestRequest::delete()
+        .uri(&format!("/auth/grants/{}", client_id))
+        .insert_header(("Authorization", format!("Bearer {}", user_token)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+
+    // refresh tokens of the app are gone with the grant
+    let req = test::TestRequest::post()
+        .uri("/oauth/token")
+        .set_form([
+            ("grant_type", "refresh_token"),
+            ("refresh_token", refresh_token.as_str()),
+            ("client_id", client_id.as_str()),
+        ])
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["error"], "invalid_grant");
+
+    let req = test::TestRequest::get()
+        .uri("/auth/grants")
+        .insert_header(("Authorization", format!("Bearer {}", user_token)))
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert!(body.as_array().unwrap().is_empty());
+
+    let req = test::TestRequest::delete()
+        .uri(&format!("/auth/grants/{}", client_id))
+        .insert_header(("Authorization", format!("Bearer {}", user_token)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_rt::test]
+async fn test_grants_require_login() {
+    let (app_state, _) = device_test_state(cli_client());
+
+    let app = test::init_service(
+        App::new()
+            .app_data(app_state)
+            .configure(configure_routes)
+    ).await;
+
+    let req = test::TestRequest::get().uri("/auth/grants").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+}
+
+// ==================== LDAP Sign In Tests ====================
+
+#[actix_rt::test]
+async fn test_ldap_signin_not_configured() {
+    let repo = Arc::new(MockUserRepository::new());
+    let app_state = create_test_app_state(repo); // LDAP not configured
+
+    let app = test::init_service(
+        App::new()
+            .app_data(app_state)
+            .configure(configure_routes)
+    ).await;
+
+    let req = test::TestRequest::post()
+        .uri("/auth/ldap/signin")
+        .set_json(json!({
+            "username": "testuser",
+            "password": "password123"
+        }))
+        .to_request();
+
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+}
+
+#[actix_rt::test]
+async fn test_ldap_change_expired_password() {
+    let user_dn = "CN=Max,OU=Users,DC=example,DC=com";
+    let directory = InMemoryDirectory::new();
+    directory.add_user(user_dn, "Old-Password-1", &[("sAMAccountName", "max"), ("mail", "max@example.com")]);
+    directory.add_user("CN=svc,DC=example,DC=com", "service-pw", &[]);
+    directory.set_min_password_length(12);
+    directory.fail_bind(user_dn, 49, "80090308: LdapErr: DSID-0C09044E, comment: AcceptSecurityContext error, data 532, v4563");
+
+    let repo = Arc::new(MockUserRepository::new());
+    let config = syt_ek962_security_concepts::LdapConfig {
+        bind_dn: Some("CN=svc,DC=example,DC=com".to_string()),
+        bind_password: Some("service-pw".to_string()),
+        ..test_ldap_config()
+    };
+    let provider = LdapAuthProvider::with_client(config, repo.clone(), Arc::new(directory.clone()));
+
+    let app_state = create_test_app_state_with_ldap(repo, provider);
+
+    let app = test::init_service(
+        App::new()
+            .app_data(app_state)
+            .configure(configure_routes)
+    ).await;
+
+    // too short for the directory policy
+    let req = test::TestRequest::post()
+        .uri("/auth/ldap/password")
+        .set_json(json!({"username": "max", "old_password": "Old-Password-1", "new_password": "short"}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert!(body["error"].as_str().unwrap().contains("password policy"));
+
+    let req = test::TestRequest::post()
+        .uri("/auth/ldap/password")
+        .set_json(json!({"username": "max", "old_password": "wrong", "new_password": "New-Password-2"}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+    let req = test::TestRequest::post()
+        .uri("/auth/ldap/password")
+        .set_json(json!({"username": "max", "old_password": "Old-Password-1", "new_password": "New-Password-2"}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(directory.password_of(user_dn).as_deref(), Some("New-Password-2"));
+}
+
+#[actix_rt::test]
+async fn test_ldap_offline_signin_token_marked() {
+    let directory = InMemoryDirectory::new();
+    directory.add_user(
+        "CN=Max,OU=Users,DC=example,DC=com",
+        "secret",
+        &[("sAMAccountName", "max"), ("userPrincipalName", "max@example.com"), ("mail", "max@example.com")],
+    );
+
+    let repo = Arc::new(MockUserRepository::new());
+    let config = syt_ek962_security_concepts::LdapConfig {
+        offline_grace_secs: Some(3600),
+        ..test_ldap_config()
+    };
+    let provider = LdapAuthProvider::with_client(config, repo.clone(), Arc::new(directory.clone()))
+        .with_credential_cache(Arc::new(MockCredentialCacheRepository::default()));
+    let app_state = create_test_app_state_with_ldap(repo, provider);
+    let jwt_service = JwtService::new(test_jwt_config());
+
+    let app = test::init_service(
+        App::new()
+            .app_data(app_state)
+            .configure(configure_routes)
+    ).await;
+
+    let signin = || test::TestRequest::post()
+        .uri("/auth/ldap/signin")
+        .set_json(json!({"username": "max", "password": "secret"}))
+        .to_request();
+
+    let body: serde_json::Value = test::call_and_read_body_json(&app, signin()).await;
+    let claims = jwt_service.validate_token(body["token"].as_str().unwrap()).unwrap();
+    assert!(!claims.offline);
+
+    directory.set_unavailable(true);
+    let body: serde_json::Value = test::call_and_read_body_json(&app, signin()).await;
+    let claims = jwt_service.validate_token(body["token"].as_str().unwrap()).unwrap();
+    assert!(claims.offline);
+}
+
+#[actix_rt::test]
+async fn test_directory_sync_requires_admin_and_config() {
+    let (app_state, admin_token) = admin_state_with_clients(Arc::new(MockClientRepository::new()));
+    let user_token = app_state
+        .jwt_service
+        .generate_token("user-id", "user@example.com", UserRole::User)
+        .unwrap();
+
+    let app = test::init_service(
+        App::new()
+            .app_data(app_state)
+            .configure(configure_routes)
+    ).await;
+
+    let req = test::TestRequest::post()
+        .uri("/auth/admin/directory-sync")
+        .insert_header(("Authorization", format!("Bearer {}", user_token)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    // ldap not configured
+    let req = test::TestRequest::get()
+        .uri("/auth/admin/directory-sync")
+        .insert_header(("Authorization", format!("Bearer {}", admin_token)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_rt::test]
+async fn test_hashing_metrics_count_signin_verify() {
+    let user = create_user_with_password("test@example.com", "secure_password_123", UserRole::User);
+    let repo = Arc::new(MockUserRepository::with_user(user));
+    let app_state = create_test_app_state(repo);
+    let admin_token = app_state
+        .jwt_service
+        .generate_token("admin-id", "admin@example.com", UserRole::Admin)
+        .unwrap();
+
+    let app = test::init_service(
+        App::new()
+            .app_data(app_state)
+            .configure(configure_routes)
+    ).await;
+
+    let req = test::TestRequest::post()
+        .uri("/auth/signin")
+        .set_json(json!({
+            "email": "test@example.com",
+            "password": "secure_password_123"
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let req = test::TestRequest::get()
+        .uri("/auth/admin/metrics/hashing")
+        .insert_header(("Authorization", format!("Bearer {}", admin_token)))
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["verify"]["count"], 1);
+    assert_eq!(body["rejected"], 0);
+    assert_eq!(body["in_flight"], 0);
+
+    let req = test::TestRequest::get()
+        .uri("/auth/admin/metrics/hashing")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+}
+
+// ==================== Lockout Tests ====================
+
+#[actix_rt::test]
+async fn test_signin_lockout_per_client_and_admin_unlock() {
+    let user = create_user_with_password("test@example.com", "secure_password_123", UserRole::User);
+    let user_id = user.id.clone();
+    let repo = Arc::new(MockUserRepository::with_user(user));
+    let state = test_app_state(repo.clone(), Arc::new(MockClientRepository::new()));
+    let app_state = web::Data::new(AppState {
+        login_throttle: LoginThrottle::new(
+            LockoutConfig { max_failures: 2, ..LockoutConfig::default() },
+            Arc::new(MockLoginAttemptRepository::default()),
+        ),
+        ..state
+    });
+    let admin_token = app_state
+        .jwt_service
+        .generate_token("admin-id", "admin@example.com", UserRole::Admin)
+        .unwrap();
+
+    let app = test::init_service(
+        App::new()
+            .app_data(app_state)
+            .configure(configure_routes)
+    ).await;
+
+    let signin = |password: &str, ip: &str| {
+        test::TestRequest::post()
+            .uri("/auth/signin")
+            .peer_addr(format!("{}:40000", ip).parse().unwrap())
+            .set_json(json!({ "email": "test@example.com", "password": password }))
+            .to_request()
+    };
+
+    for _ in 0..2 {
+        let resp = test::call_service(&app, signin("wrong_password", "10.0.0.1")).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    // right password does not help anymore from this ip
+    let resp = test::call_service(&app, signin("secure_password_123", "10.0.0.1")).await;
+    assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+    let retry_after: i64 = resp.headers().get("Retry-After").unwrap().to_str().unwrap().parse().unwrap();
+    assert!(retry_after > 0 && retry_after <= 30);
+
+    // the real user somewhere else is not locked out
+    let resp = test::call_service(&app, signin("secure_password_123", "10.0.0.2")).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let req = test::TestRequest::delete()
+        .uri(&format!("/auth/admin/users/{}/lockout", user_id))
+        .insert_header(("Authorization", format!("Bearer {}", admin_token)))
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["cleared"], 2);
+
+    let resp = test::call_service(&app, signin("secure_password_123", "10.0.0.1")).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+}
+
+#[actix_rt::test]
+async fn test_password_change_guesses_count_towards_lockout() {
+    let user = create_user_with_password("test@example.com", "secure_password_123", UserRole::User);
+    let user_id = user.id.clone();
+    let repo = Arc::new(MockUserRepository::with_user(user));
+    let state = test_app_state(repo.clone(), Arc::new(MockClientRepository::new()));
+    let app_state = web::Data::new(AppState {
+        login_throttle: LoginThrottle::new(
+            LockoutConfig { max_failures: 2, ..LockoutConfig::default() },
+            Arc::new(MockLoginAttemptRepository::default()),
+        ),
+        ..state
+    });
+    let token = app_state
+        .jwt_service
+        .generate_token(&user_id, "test@example.com", UserRole::User)
+        .unwrap();
+
+    let app = test::init_service(
+        App::new()
+            .app_data(app_state)
+            .configure(configure_routes)
+    ).await;
+
+    let change = |current: &str| test::TestRequest::post()
+        .uri("/auth/password")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(json!({"current_password": current, "new_password": "new_password_456"}))
+        .to_request();
+
+    for _ in 0..2 {
+        let resp = test::call_service(&app, change("wrong_password")).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    // locked for the password change and for sign in alike
+    let resp = test::call_service(&app, change("secure_password_123")).await;
+    assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+
+    let req = test::TestRequest::post()
+        .uri("/auth/signin")
+        .set_json(json!({"email": "test@example.com", "password": "secure_password_123"}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+}
+
+#[actix_rt::test]
+async fn test_ldap_password_change_guesses_count_towards_lockout() {
+    let directory = InMemoryDirectory::new();
+    directory.add_user(
+        "CN=Max,OU=Users,DC=example,DC=com",
+        "Old-Password-1",
+        &[("sAMAccountName", "max"), ("mail", "max@example.com")],
+    );
+    directory.add_user("CN=svc,DC=example,DC=com", "service-pw", &[]);
+
+    let repo = Arc::new(MockUserRepository::new());
+    let config = syt_ek962_security_concepts::LdapConfig {
+        bind_dn: Some("CN=svc,DC=example,DC=com".to_string()),
+        bind_password: Some("service-pw".to_string()),
+        ..test_ldap_config()
+    };
+    let provider = LdapAuthProvider::with_client(config, repo.clone(), Arc::new(directory.clone()));
+    let app_state = web::Data::new(AppState {
+        ldap_provider: Some(Arc::new(provider)),
+        login_throttle: LoginThrottle::new(
+            LockoutConfig { max_failures: 2, ..LockoutConfig::default() },
+            Arc::new(MockLoginAttemptRepository::default()),
+        ),
+        ..test_app_state(repo, Arc::new(MockClientRepository::new()))
+    });
+
+    let app = test::init_service(
+        App::new()
+            .app_data(app_state)
+            .configure(configure_routes)
+    ).await;
+
+    let change = |old: &str| test::TestRequest::post()
+        .uri("/auth/ldap/password")
+        .set_json(json!({"username": "max", "old_password": old, "new_password": "New-Password-2"}))
+        .to_request();
+
+    for _ in 0..2 {
+        let resp = test::call_service(&app, change("wrong")).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    let resp = test::call_service(&app, change("Old-Password-1")).await;
+    assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(directory.password_of("CN=Max,OU=Users,DC=example,DC=com").as_deref(), Some("Old-Password-1"));
+
+    let req = test::TestRequest::post()
+        .uri("/auth/ldap/signin")
+        .set_json(json!({"username": "MAX", "password": "Old-Password-1"}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+}
+
+#[actix_rt::test]
+async fn test_unlock_requires_admin_and_existing_user() {
+    let (app_state, admin_token) = admin_state_with_clients(Arc::new(MockClientRepository::new()));
+    let user_token = app_state
+        .jwt_service
+        .generate_token("user-id", "user@example.com", UserRole::User)
+        .unwrap();
+
+    let app = test::init_service(
+        App::new()
+            .app_data(app_state)
+            .configure(configure_routes)
+    ).await;
+
+    let req = test::TestRequest::delete()
+        .uri("/auth/admin/users/user-id/lockout")
+        .insert_header(("Authorization", format!("Bearer {}", user_token)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    let req = test::TestRequest::delete()
+        .uri("/auth/admin/users/unknown/lockout")
+        .insert_header(("Authorization", format!("Bearer {}", admin_token)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
 }
 
 // ==================== Google OAuth Tests ====================
@@ -4,11 +4,20 @@ mod common;
 
 use std::sync::Arc;
 
-use syt_ek962_security_concepts::auth::{LocalAuthProvider, AuthProvider};
-use syt_ek962_security_concepts::models::{User, UserRole, AuthProviderType};
-use syt_ek962_security_concepts::error::AppError;
-
-use common::MockUserRepository;
+use syt_ek962_security_concepts::auth::{
+    LocalAuthProvider, AuthProvider, DeviceFlowService, DevicePollOutcome, LdapAuthProvider, InMemoryDirectory,
+    DirectorySyncService, LoginThrottle, PasswordHasher, PasswordHistory, PasswordPolicy, PasswordResetService,
+};
+use syt_ek962_security_concepts::mail::FileOutboxMailer;
+use syt_ek962_security_concepts::config::{DeviceFlowConfig, GroupRoleMapping, LdapConfig, NestedGroupMode, PasswordPolicyConfig, Argon2Config, LockoutConfig};
+use syt_ek962_security_concepts::models::{User, UserRole, AuthProviderType, OAuthClient, GrantType, RefreshToken};
+use syt_ek962_security_concepts::error::{AccountRestriction, AppError};
+use syt_ek962_security_concepts::repository::{RefreshTokenRepository, UserRepository};
+
+use common::{
+    MockCredentialCacheRepository, MockDeviceAuthorizationRepository, MockLoginAttemptRepository, MockPasswordHistoryRepository, MockPasswordResetRepository,
+    MockRefreshTokenRepository, MockUserRepository, reset_token_from_mail, test_ldap_config, test_password_reset_config,
+};
 
 // ==================== Registration Tests ====================
 
@@ -182,6 +191,236 @@ async fn test_authenticate_external_user_without_password_fails() {
     assert!(matches!(result, Err(AppError::Unauthorized(_))));
 }
 
+#[tokio::test]
+async fn test_failed_logins_do_equivalent_work() {
+    let external_user = User::new_external(
+        "Google User".to_string(),
+        "google@example.com".to_string(),
+        AuthProviderType::Google,
+        "google-sub-123".to_string(),
+        UserRole::User,
+    );
+    let repo = Arc::new(MockUserRepository::with_user(external_user));
+    let hasher = PasswordHasher::with_config(&Argon2Config {
+        memory_kib: 8 * 1024,
+        iterations: 1,
+        parallelism: 1,
+        ..Argon2Config::default()
+    })
+    .unwrap();
+    let provider = LocalAuthProvider::new(repo.clone()).with_password_hasher(hasher.clone());
+    provider
+        .register("Test User", "test@example.com", "correct_password_123", UserRole::User)
+        .await
+        .unwrap();
+    // like main at startup, the dummy hash is created once
+    hasher.verify_dummy("warm up").await.unwrap();
+
+    // wrong password, unknown email, user without local password
+    let mut work = Vec::new();
+    for email in ["test@example.com", "nobody@example.com", "google@example.com"] {
+        let before = hasher.metrics();
+        let result = provider.authenticate(email, "wrong_password").await;
+        let after = hasher.metrics();
+
+        assert!(matches!(result, Err(AppError::Unauthorized(_))));
+        work.push((after.verify.count - before.verify.count, after.hash.count - before.hash.count));
+    }
+
+    assert_eq!(work, vec![(1, 0); 3]);
+}
+
+// ==================== Password Change Tests ====================
+
+#[tokio::test]
+async fn test_change_password_replaces_hash() {
+    let repo = Arc::new(MockUserRepository::new());
+    let provider = LocalAuthProvider::new(repo.clone());
+    let user = provider
+        .register("Test User", "test@example.com", "old_password_123", UserRole::User)
+        .await
+        .unwrap();
+
+    provider
+        .change_password(&user.id, "old_password_123", "new_password_456")
+        .await
+        .unwrap();
+
+    assert!(provider.authenticate("test@example.com", "old_password_123").await.is_err());
+    assert!(provider.authenticate("test@example.com", "new_password_456").await.is_ok());
+}
+
+#[tokio::test]
+async fn test_change_password_rejections() {
+    let repo = Arc::new(MockUserRepository::new());
+    let provider = LocalAuthProvider::new(repo.clone());
+    let user = provider
+        .register("Test User", "test@example.com", "old_password_123", UserRole::User)
+        .await
+        .unwrap();
+    let google = User::new_external(
+        "Google".to_string(),
+        "google@example.com".to_string(),
+        AuthProviderType::Google,
+        "sub".to_string(),
+        UserRole::User,
+    );
+    repo.create(&google).await.unwrap();
+
+    let wrong = provider.change_password(&user.id, "wrong_password", "new_password_456").await;
+    let same = provider.change_password(&user.id, "old_password_123", "old_password_123").await;
+    let external = provider.change_password(&google.id, "whatever", "new_password_456").await;
+
+    assert!(matches!(wrong, Err(AppError::Unauthorized(_))));
+    assert!(matches!(same, Err(AppError::ValidationError(_))));
+    assert!(matches!(external, Err(AppError::ValidationError(_))));
+}
+
+// ==================== Rehash Tests ====================
+
+fn argon2_hasher(iterations: u32) -> PasswordHasher {
+    PasswordHasher::with_config(&Argon2Config {
+        memory_kib: 8 * 1024,
+        iterations,
+        parallelism: 1,
+        ..Argon2Config::default()
+    })
+    .unwrap()
+}
+
+#[tokio::test]
+async fn test_authenticate_rehashes_with_current_params() {
+    let repo = Arc::new(MockUserRepository::new());
+    let old = LocalAuthProvider::new(repo.clone()).with_password_hasher(argon2_hasher(1));
+    old.register("Test User", "test@example.com", "secure_password_123", UserRole::User)
+        .await
+        .unwrap();
+
+    let current = LocalAuthProvider::new(repo.clone()).with_password_hasher(argon2_hasher(2));
+
+    // wrong password does not touch the hash
+    assert!(current.authenticate("test@example.com", "wrong_password").await.is_err());
+    let stored = repo.find_by_email("test@example.com").await.unwrap().unwrap();
+    assert!(stored.password_hash.unwrap().contains("t=1"));
+
+    let result = current.authenticate("test@example.com", "secure_password_123").await.unwrap();
+    let stored = repo.find_by_email("test@example.com").await.unwrap().unwrap();
+    let hash = stored.password_hash.unwrap();
+    assert!(hash.contains("m=8192,t=2,p=1"));
+    assert_eq!(result.user.password_hash.as_deref(), Some(hash.as_str()));
+
+    // second login keeps the upgraded hash
+    current.authenticate("test@example.com", "secure_password_123").await.unwrap();
+    let again = repo.find_by_email("test@example.com").await.unwrap().unwrap();
+    assert_eq!(again.password_hash.unwrap(), hash);
+}
+
+#[tokio::test]
+async fn test_authenticate_moves_hash_to_current_pepper() {
+    let repo = Arc::new(MockUserRepository::new());
+    let pepper = |peppers: &str| {
+        PasswordHasher::with_config(&Argon2Config {
+            memory_kib: 8 * 1024,
+            iterations: 1,
+            parallelism: 1,
+            pepper: Some(syt_ek962_security_concepts::config::parse_peppers(peppers).unwrap()),
+            ..Argon2Config::default()
+        })
+        .unwrap()
+    };
+    let old = "a:first-pepper-secret-with-32-chars-or-more";
+    let new = "b:second-pepper-secret-with-32-chars-or-more";
+
+    LocalAuthProvider::new(repo.clone())
+        .with_password_hasher(pepper(old))
+        .register("Test User", "test@example.com", "secure_password_123", UserRole::User)
+        .await
+        .unwrap();
+
+    let rotated = LocalAuthProvider::new(repo.clone()).with_password_hasher(pepper(&format!("{}\n{}", new, old)));
+    rotated.authenticate("test@example.com", "secure_password_123").await.unwrap();
+
+    // keyid=Yg is "b", the old pepper can be removed now
+    let hash = repo.find_by_email("test@example.com").await.unwrap().unwrap().password_hash.unwrap();
+    assert!(hash.contains(",keyid=Yg$"));
+    let only_new = LocalAuthProvider::new(repo.clone()).with_password_hasher(pepper(new));
+    assert!(only_new.authenticate("test@example.com", "secure_password_123").await.is_ok());
+}
+
+// ==================== Legacy Import Tests ====================
+
+#[tokio::test]
+async fn test_imported_legacy_hash_upgraded_on_login() {
+    let repo = Arc::new(MockUserRepository::new());
+    let provider = LocalAuthProvider::new(repo.clone()).with_password_hasher(argon2_hasher(1));
+    // sha512-crypt spec test vector, password "Hello world!"
+    let legacy = "$6$saltstring$svn8UoSVapNtMuq1ukKS4tPQd8iKwSMHWjl/O817G3uBnIFNjnQJuesI68u4OTLiBFdcbYEdFCoEOfaS35inz1";
+
+    provider.import("Old App", "old@example.com", legacy, UserRole::User).await.unwrap();
+
+    assert!(provider.authenticate("old@example.com", "Hello world?").await.is_err());
+    let stored = repo.find_by_email("old@example.com").await.unwrap().unwrap();
+    assert_eq!(stored.password_hash.as_deref(), Some(legacy));
+
+    provider.authenticate("old@example.com", "Hello world!").await.unwrap();
+    let stored = repo.find_by_email("old@example.com").await.unwrap().unwrap();
+    assert!(stored.password_hash.unwrap().starts_with("$argon2id$v=19$m=8192,t=1,p=1$"));
+    assert!(provider.authenticate("old@example.com", "Hello world!").await.is_ok());
+}
+
+#[tokio::test]
+async fn test_import_rejects_unknown_hashes() {
+    let repo = Arc::new(MockUserRepository::new());
+    let provider = LocalAuthProvider::new(repo.clone());
+
+    for hash in ["plaintext_password", "$1$md5salt$notsupported", "pbkdf2_sha256$0$salt$aGFzaA=="] {
+        let result = provider.import("Bad", "bad@example.com", hash, UserRole::User).await;
+        assert!(matches!(result, Err(AppError::ValidationError(_))), "{} accepted", hash);
+    }
+    assert!(repo.find_by_email("bad@example.com").await.unwrap().is_none());
+}
+
+// ==================== Password Policy Tests ====================
+
+#[tokio::test]
+async fn test_register_rejected_by_password_policy() {
+    let repo = Arc::new(MockUserRepository::new());
+    let provider = LocalAuthProvider::new(repo.clone());
+
+    let short = provider.register("Test User", "test@example.com", "short", UserRole::User).await;
+    let common = provider.register("Test User", "test@example.com", "passwordpassword", UserRole::User).await;
+    let own_name = provider.register("Anna Berger", "anna@example.com", "berger_forever_1", UserRole::User).await;
+
+    assert!(matches!(short, Err(AppError::ValidationError(_))));
+    assert!(matches!(common, Err(AppError::ValidationError(_))));
+    assert!(matches!(own_name, Err(AppError::ValidationError(_))));
+    assert!(repo.find_by_email("test@example.com").await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_change_password_uses_configured_policy() {
+    let repo = Arc::new(MockUserRepository::new());
+    let policy = PasswordPolicy::new(PasswordPolicyConfig {
+        min_character_classes: 3,
+        ..Default::default()
+    })
+    .unwrap();
+    let provider = LocalAuthProvider::new(repo.clone()).with_password_policy(Arc::new(policy));
+    let user = provider
+        .register("Test User", "test@example.com", "Old_password_123", UserRole::User)
+        .await
+        .unwrap();
+
+    let weak = provider.change_password(&user.id, "Old_password_123", "only_lowercase_here").await;
+    assert!(matches!(weak, Err(AppError::ValidationError(_))));
+    assert!(provider.authenticate("test@example.com", "Old_password_123").await.is_ok());
+
+    provider
+        .change_password(&user.id, "Old_password_123", "New_password_456")
+        .await
+        .unwrap();
+}
+
 // ==================== Provider Name Test ====================
 
 #[tokio::test]
@@ -200,9 +439,9 @@ async fn test_password_hasher_access() {
     let provider = LocalAuthProvider::new(repo);
 
     let hasher = provider.password_hasher();
-    let hash = hasher.hash("test_password").unwrap();
+    let hash = hasher.hash("test_password").await.unwrap();
 
-    assert!(hasher.verify("test_password", &hash).unwrap());
+    assert!(hasher.verify("test_password", &hash).await.unwrap());
 }
 
 // ==================== Edge Cases ====================
@@ -247,26 +486,916 @@ async fn test_multiple_users_independent() {
 
     // Register multiple users
     provider
-        .register("User 1", "user1@example.com", "password_user_1", UserRole::User)
+        .register("User 1", "user1@example.com", "password_one_1", UserRole::User)
         .await
         .unwrap();
 
     provider
-        .register("User 2", "user2@example.com", "password_user_2", UserRole::Admin)
+        .register("User 2", "user2@example.com", "password_two_2", UserRole::Admin)
         .await
         .unwrap();
 
     // Authenticate each with their own password
-    let result1 = provider.authenticate("user1@example.com", "password_user_1").await;
-    let result2 = provider.authenticate("user2@example.com", "password_user_2").await;
+    let result1 = provider.authenticate("user1@example.com", "password_one_1").await;
+    let result2 = provider.authenticate("user2@example.com", "password_two_2").await;
 
     assert!(result1.is_ok());
     assert!(result2.is_ok());
 
     // Cross-authentication should fail
-    let cross1 = provider.authenticate("user1@example.com", "password_user_2").await;
-    let cross2 = provider.authenticate("user2@example.com", "password_user_1").await;
+    let cross1 = provider.authenticate("user1@example.com", "password_two_2").await;
+    let cross2 = provider.authenticate("user2@example.com", "password_one_1").await;
 
     assert!(cross1.is_err());
     assert!(cross2.is_err());
 }
+
+// ==================== Password Reset Tests ====================
+
+struct ResetFixture {
+    service: PasswordResetService,
+    provider: LocalAuthProvider,
+    repo: Arc<MockUserRepository>,
+    refresh_tokens: Arc<MockRefreshTokenRepository>,
+    outbox: FileOutboxMailer,
+    _dir: tempfile::TempDir,
+}
+
+async fn reset_fixture(expiration_secs: i64) -> ResetFixture {
+    let dir = tempfile::tempdir().unwrap();
+    let repo = Arc::new(MockUserRepository::new());
+    let refresh_tokens = Arc::new(MockRefreshTokenRepository::default());
+    let provider = LocalAuthProvider::new(repo.clone());
+    provider
+        .register("Max", "max@example.com", "old_password_123", UserRole::User)
+        .await
+        .unwrap();
+
+    let config = syt_ek962_security_concepts::PasswordResetConfig {
+        expiration_secs,
+        ..test_password_reset_config(dir.path())
+    };
+    let service = PasswordResetService::new(
+        config,
+        repo.clone(),
+        Arc::new(MockPasswordResetRepository::default()),
+        refresh_tokens.clone(),
+        Arc::new(FileOutboxMailer::new(dir.path(), "auth@example.com").unwrap()),
+    );
+
+    ResetFixture {
+        service,
+        provider,
+        repo,
+        refresh_tokens,
+        outbox: FileOutboxMailer::new(dir.path(), "auth@example.com").unwrap(),
+        _dir: dir,
+    }
+}
+
+#[tokio::test]
+async fn test_password_reset_flow() {
+    let fixture = reset_fixture(1800).await;
+    let user = fixture.repo.find_by_email("max@example.com").await.unwrap().unwrap();
+    let session = RefreshToken::new("hash".to_string(), user.id.clone(), "client-1".to_string(), vec![], 3600);
+    fixture.refresh_tokens.create(&session).await.unwrap();
+
+    fixture.service.request_reset("MAX@example.com").await.unwrap();
+
+    let mails = fixture.outbox.messages().unwrap();
+    assert_eq!(mails.len(), 1);
+    assert_eq!(mails[0].to, "max@example.com");
+    let token = reset_token_from_mail(&mails[0]);
+
+    fixture.service.reset_password(&token, "new_password_456").await.unwrap();
+
+    assert!(fixture.provider.authenticate("max@example.com", "old_password_123").await.is_err());
+    assert!(fixture.provider.authenticate("max@example.com", "new_password_456").await.is_ok());
+    assert!(!fixture.refresh_tokens.find_by_hash("hash").await.unwrap().unwrap().is_valid());
+    let reset = fixture.repo.find_by_id(&user.id).await.unwrap().unwrap();
+    assert!(reset.password_changed_at.is_some());
+
+    // single use
+    let again = fixture.service.reset_password(&token, "third_password_789").await;
+    assert!(matches!(again, Err(AppError::ValidationError(_))));
+}
+
+#[tokio::test]
+async fn test_password_reset_unknown_and_external_accounts_get_no_mail() {
+    let fixture = reset_fixture(1800).await;
+    let google = User::new_external(
+        "Google".to_string(),
+        "google@example.com".to_string(),
+        AuthProviderType::Google,
+        "sub".to_string(),
+        UserRole::User,
+    );
+    fixture.repo.create(&google).await.unwrap();
+
+    fixture.service.request_reset("nobody@example.com").await.unwrap();
+    fixture.service.request_reset("google@example.com").await.unwrap();
+
+    assert!(fixture.outbox.messages().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_password_reset_inactive_account() {
+    let fixture = reset_fixture(1800).await;
+    let mut user = fixture.repo.find_by_email("max@example.com").await.unwrap().unwrap();
+
+    fixture.service.request_reset("max@example.com").await.unwrap();
+    let token = reset_token_from_mail(&fixture.outbox.messages().unwrap()[0]);
+
+    // deactivated after the link was sent
+    user.is_active = false;
+    fixture.repo.update(&user).await.unwrap();
+
+    let result = fixture.service.reset_password(&token, "new_password_456").await;
+    assert!(matches!(result, Err(AppError::ValidationError(_))));
+
+    fixture.service.request_reset("max@example.com").await.unwrap();
+    assert_eq!(fixture.outbox.messages().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn test_password_reset_no_second_mail_within_resend_interval() {
+    let fixture = reset_fixture(1800).await;
+    let service = PasswordResetService::new(
+        syt_ek962_security_concepts::PasswordResetConfig {
+            resend_interval_secs: 300,
+            ..test_password_reset_config(fixture._dir.path())
+        },
+        fixture.repo.clone(),
+        Arc::new(MockPasswordResetRepository::default()),
+        fixture.refresh_tokens.clone(),
+        Arc::new(FileOutboxMailer::new(fixture._dir.path(), "auth@example.com").unwrap()),
+    );
+
+    service.request_reset("max@example.com").await.unwrap();
+    service.request_reset("max@example.com").await.unwrap();
+
+    let mails = fixture.outbox.messages().unwrap();
+    assert_eq!(mails.len(), 1);
+    // the first link still works
+    service.reset_password(&reset_token_from_mail(&mails[0]), "new_password_456").await.unwrap();
+}
+
+#[tokio::test]
+async fn test_password_reset_only_newest_link_works() {
+    let fixture = reset_fixture(1800).await;
+
+    fixture.service.request_reset("max@example.com").await.unwrap();
+    fixture.service.request_reset("max@example.com").await.unwrap();
+
+    let mails = fixture.outbox.messages().unwrap();
+    let first = reset_token_from_mail(&mails[0]);
+    let second = reset_token_from_mail(&mails[1]);
+
+    let result = fixture.service.reset_password(&first, "new_password_456").await;
+    assert!(matches!(result, Err(AppError::ValidationError(_))));
+    fixture.service.reset_password(&second, "new_password_456").await.unwrap();
+}
+
+#[tokio::test]
+async fn test_password_reset_expired_or_unknown_token() {
+    let fixture = reset_fixture(-1).await;
+
+    fixture.service.request_reset("max@example.com").await.unwrap();
+    let token = reset_token_from_mail(&fixture.outbox.messages().unwrap()[0]);
+
+    let expired = fixture.service.reset_password(&token, "new_password_456").await;
+    let unknown = fixture.service.reset_password("not-a-token", "new_password_456").await;
+
+    assert!(matches!(expired, Err(AppError::ValidationError(_))));
+    assert!(matches!(unknown, Err(AppError::ValidationError(_))));
+    assert!(fixture.provider.authenticate("max@example.com", "old_password_123").await.is_ok());
+}
+
+#[tokio::test]
+async fn test_password_reset_checks_policy_and_keeps_token() {
+    let fixture = reset_fixture(1800).await;
+
+    fixture.service.request_reset("max@example.com").await.unwrap();
+    let token = reset_token_from_mail(&fixture.outbox.messages().unwrap()[0]);
+
+    let weak = fixture.service.reset_password(&token, "qwertyuiopasdfgh").await;
+    assert!(matches!(weak, Err(AppError::ValidationError(_))));
+
+    // rejected password does not burn the link
+    fixture.service.reset_password(&token, "new_password_456").await.unwrap();
+    assert!(fixture.provider.authenticate("max@example.com", "new_password_456").await.is_ok());
+}
+
+// ==================== Password History Tests ====================
+
+#[tokio::test]
+async fn test_change_password_rejects_recent_passwords() {
+    let repo = Arc::new(MockUserRepository::new());
+    let history_repo = Arc::new(MockPasswordHistoryRepository::default());
+    let history = Arc::new(PasswordHistory::new(history_repo.clone(), 2));
+    let provider = LocalAuthProvider::new(repo.clone()).with_password_history(history);
+    let user = provider
+        .register("Test User", "test@example.com", "first_password_1", UserRole::User)
+        .await
+        .unwrap();
+    assert_eq!(history_repo.len(&user.id), 1);
+
+    provider.change_password(&user.id, "first_password_1", "second_password_2").await.unwrap();
+
+    let back = provider.change_password(&user.id, "second_password_2", "first_password_1").await;
+    assert!(matches!(back, Err(AppError::ValidationError(ref msg)) if msg.contains("last 2 passwords")));
+
+    provider.change_password(&user.id, "second_password_2", "third_password_3").await.unwrap();
+    assert_eq!(history_repo.len(&user.id), 2);
+
+    // pruned, the first one is allowed again
+    provider.change_password(&user.id, "third_password_3", "first_password_1").await.unwrap();
+    assert!(provider.authenticate("test@example.com", "first_password_1").await.is_ok());
+}
+
+#[tokio::test]
+async fn test_password_reset_rejects_current_password_without_history_entries() {
+    let dir = tempfile::tempdir().unwrap();
+    let repo = Arc::new(MockUserRepository::new());
+    let history_repo = Arc::new(MockPasswordHistoryRepository::default());
+    // registered before the history existed
+    LocalAuthProvider::new(repo.clone())
+        .register("Max", "max@example.com", "old_password_123", UserRole::User)
+        .await
+        .unwrap();
+
+    let service = PasswordResetService::new(
+        test_password_reset_config(dir.path()),
+        repo.clone(),
+        Arc::new(MockPasswordResetRepository::default()),
+        Arc::new(MockRefreshTokenRepository::default()),
+        Arc::new(FileOutboxMailer::new(dir.path(), "auth@example.com").unwrap()),
+    )
+    .with_password_history(Arc::new(PasswordHistory::new(history_repo.clone(), 3)));
+
+    service.request_reset("max@example.com").await.unwrap();
+    let outbox = FileOutboxMailer::new(dir.path(), "auth@example.com").unwrap();
+    let token = reset_token_from_mail(&outbox.messages().unwrap()[0]);
+
+    let same = service.reset_password(&token, "old_password_123").await;
+    assert!(matches!(same, Err(AppError::ValidationError(_))));
+
+    let user = service.reset_password(&token, "new_password_456").await.unwrap();
+    assert_eq!(history_repo.len(&user.id), 1);
+}
+
+// ==================== Device Flow Tests ====================
+
+fn device_flow(interval_secs: i64) -> DeviceFlowService {
+    DeviceFlowService::new(
+        DeviceFlowConfig {
+            verification_uri: "http://localhost:8080/device.html".to_string(),
+            expiration_secs: 600,
+            interval_secs,
+        },
+        Arc::new(MockDeviceAuthorizationRepository::default()),
+    )
+}
+
+fn device_client() -> OAuthClient {
+    OAuthClient::new(
+        "CLI".to_string(),
+        None,
+        vec![],
+        vec![GrantType::DeviceCode],
+        vec![],
+        600,
+        86400,
+    )
+}
+
+#[tokio::test]
+async fn test_device_poll_too_fast_slows_down() {
+    let service = device_flow(5);
+    let client = device_client();
+
+    let grant = service.start(&client, vec![]).await.unwrap();
+
+    let first = service.poll(&grant.device_code, &client.client_id).await.unwrap();
+    assert!(matches!(first, DevicePollOutcome::Pending));
+
+    let second = service.poll(&grant.device_code, &client.client_id).await.unwrap();
+    assert!(matches!(second, DevicePollOutcome::SlowDown));
+}
+
+#[tokio::test]
+async fn test_device_poll_wrong_client() {
+    let service = device_flow(0);
+    let client = device_client();
+
+    let grant = service.start(&client, vec![]).await.unwrap();
+
+    let outcome = service.poll(&grant.device_code, "other-client").await.unwrap();
+    assert!(matches!(outcome, DevicePollOutcome::Invalid));
+}
+
+#[tokio::test]
+async fn test_device_poll_unknown_code() {
+    let service = device_flow(0);
+
+    let outcome = service.poll("unknown", "client").await.unwrap();
+    assert!(matches!(outcome, DevicePollOutcome::Invalid));
+}
+
+#[tokio::test]
+async fn test_device_approve_once() {
+    let service = device_flow(0);
+    let client = device_client();
+
+    let grant = service.start(&client, vec![]).await.unwrap();
+
+    let approved = service.approve(&grant.user_code, "user-1").await.unwrap();
+    assert_eq!(approved.user_id.as_deref(), Some("user-1"));
+
+    // Code is no longer pending, so it cannot be approved again (e.g. by someone else)
+    let again = service.approve(&grant.user_code, "user-2").await;
+    assert!(matches!(again, Err(AppError::NotFound(_))));
+
+    let outcome = service.poll(&grant.device_code, &client.client_id).await.unwrap();
+    assert!(matches!(outcome, DevicePollOutcome::Approved(a) if a.user_id.as_deref() == Some("user-1")));
+}
+
+#[tokio::test]
+async fn test_device_approved_code_issues_tokens_once() {
+    let service = device_flow(0);
+    let client = device_client();
+
+    let grant = service.start(&client, vec![]).await.unwrap();
+    service.approve(&grant.user_code, "user-1").await.unwrap();
+
+    let (first, second) = tokio::join!(
+        service.poll(&grant.device_code, &client.client_id),
+        service.poll(&grant.device_code, &client.client_id),
+    );
+    let outcomes = [first.unwrap(), second.unwrap()];
+    assert_eq!(outcomes.iter().filter(|o| matches!(o, DevicePollOutcome::Approved(_))).count(), 1);
+    assert_eq!(outcomes.iter().filter(|o| matches!(o, DevicePollOutcome::Invalid)).count(), 1);
+
+    let again = service.poll(&grant.device_code, &client.client_id).await.unwrap();
+    assert!(matches!(again, DevicePollOutcome::Invalid));
+}
+
+// ==================== LDAP Tests (InMemoryDirectory) ====================
+
+const MAX_DN: &str = "CN=Max Mustermann,OU=Users,DC=example,DC=com";
+const SERVICE_DN: &str = "CN=svc-auth,OU=Service,DC=example,DC=com";
+
+fn ldap_directory() -> InMemoryDirectory {
+    let directory = InMemoryDirectory::new();
+    directory.add_user(
+        MAX_DN,
+        "secret",
+        &[
+            ("sAMAccountName", "max"),
+            ("userPrincipalName", "max@example.com"),
+            ("displayName", "Max Mustermann"),
+            ("mail", "max@example.com"),
+        ],
+    );
+    directory.add_user(SERVICE_DN, "service-pw", &[("sAMAccountName", "svc-auth")]);
+    directory.add_group("CN=Staff,OU=Groups,DC=example,DC=com", &[MAX_DN]);
+    directory.add_group("CN=Admins,OU=Groups,DC=example,DC=com", &["CN=Staff,OU=Groups,DC=example,DC=com"]);
+    directory
+}
+
+// service account lookup, nested groups over the AD in-chain rule
+fn search_then_bind_config() -> LdapConfig {
+    LdapConfig {
+        bind_dn: Some(SERVICE_DN.to_string()),
+        bind_password: Some("service-pw".to_string()),
+        nested_groups: NestedGroupMode::InChain,
+        group_roles: vec![GroupRoleMapping {
+            group: "Admins".to_string(),
+            role: UserRole::Admin,
+        }],
+        ..test_ldap_config()
+    }
+}
+
+fn ldap_provider(config: LdapConfig, directory: &InMemoryDirectory, repo: Arc<MockUserRepository>) -> LdapAuthProvider {
+    LdapAuthProvider::with_client(config, repo, Arc::new(directory.clone()))
+}
+
+#[tokio::test]
+async fn test_ldap_direct_bind_creates_user() {
+    let directory = ldap_directory();
+    let repo = Arc::new(MockUserRepository::new());
+    let provider = ldap_provider(test_ldap_config(), &directory, repo.clone());
+
+    let result = provider.authenticate("max", "secret").await.unwrap();
+
+    assert_eq!(result.user.name, "Max Mustermann");
+    assert_eq!(result.user.email, "max@example.com");
+    assert_eq!(result.user.auth_provider, AuthProviderType::ActiveDirectory);
+    // only direct groups, Staff has no role mapping
+    assert_eq!(result.user.role, UserRole::User);
+    assert!(repo.find_by_id(&result.user.id).await.unwrap().is_some());
+}
+
+#[tokio::test]
+async fn test_ldap_wrong_password_and_unknown_user_look_the_same() {
+    let directory = ldap_directory();
+    let provider = ldap_provider(search_then_bind_config(), &directory, Arc::new(MockUserRepository::new()));
+
+    let wrong = provider.authenticate("max", "wrong").await.unwrap_err();
+    let unknown = provider.authenticate("nobody", "wrong").await.unwrap_err();
+
+    assert!(matches!(wrong, AppError::Unauthorized(_)));
+    assert_eq!(wrong.to_string(), unknown.to_string());
+}
+
+#[tokio::test]
+async fn test_ldap_empty_password_rejected() {
+    let directory = ldap_directory();
+    let provider = ldap_provider(test_ldap_config(), &directory, Arc::new(MockUserRepository::new()));
+
+    let result = provider.authenticate("max", "").await;
+
+    assert!(matches!(result, Err(AppError::Unauthorized(_))));
+    assert_eq!(directory.connection_count(), 0);
+}
+
+#[tokio::test]
+async fn test_ldap_search_then_bind_nested_admin_group() {
+    let directory = ldap_directory();
+    let provider = ldap_provider(search_then_bind_config(), &directory, Arc::new(MockUserRepository::new()));
+
+    let result = provider.authenticate("MAX", "secret").await.unwrap();
+
+    assert_eq!(result.user.role, UserRole::Admin);
+}
+
+#[tokio::test]
+async fn test_ldap_expired_password_reported() {
+    let directory = ldap_directory();
+    directory.fail_bind(
+        MAX_DN,
+        49,
+        "80090308: LdapErr: DSID-0C09044E, comment: AcceptSecurityContext error, data 532, v4563",
+    );
+    let provider = ldap_provider(search_then_bind_config(), &directory, Arc::new(MockUserRepository::new()));
+
+    let result = provider.authenticate("max", "secret").await;
+
+    assert!(matches!(
+        result,
+        Err(AppError::AccountRestricted(AccountRestriction::PasswordExpired))
+    ));
+}
+
+#[tokio::test]
+async fn test_ldap_directory_unavailable() {
+    let directory = ldap_directory();
+    directory.set_unavailable(true);
+    let provider = ldap_provider(search_then_bind_config(), &directory, Arc::new(MockUserRepository::new()));
+
+    let result = provider.authenticate("max", "secret").await;
+
+    assert!(matches!(result, Err(AppError::LdapError(_))));
+}
+
+// one hour offline grace
+fn cached_provider(
+    directory: &InMemoryDirectory,
+    repo: Arc<MockUserRepository>,
+    cache: Arc<MockCredentialCacheRepository>,
+) -> LdapAuthProvider {
+    let config = LdapConfig {
+        offline_grace_secs: Some(3600),
+        ..search_then_bind_config()
+    };
+    ldap_provider(config, directory, repo).with_credential_cache(cache)
+}
+
+#[tokio::test]
+async fn test_ldap_offline_login_with_cached_credential() {
+    let directory = ldap_directory();
+    let cache = Arc::new(MockCredentialCacheRepository::default());
+    let provider = cached_provider(&directory, Arc::new(MockUserRepository::new()), cache.clone());
+
+    let online = provider.authenticate("max", "secret").await.unwrap();
+    assert!(!online.offline);
+    assert_eq!(cache.len(), 1);
+
+    directory.set_unavailable(true);
+    let offline = provider.authenticate("MAX", "secret").await.unwrap();
+
+    assert!(offline.offline);
+    assert_eq!(offline.user.id, online.user.id);
+}
+
+#[tokio::test]
+async fn test_ldap_offline_login_rejections_look_like_outage() {
+    let directory = ldap_directory();
+    let cache = Arc::new(MockCredentialCacheRepository::default());
+    let provider = cached_provider(&directory, Arc::new(MockUserRepository::new()), cache.clone());
+    provider.authenticate("max", "secret").await.unwrap();
+    directory.set_unavailable(true);
+
+    let wrong = provider.authenticate("max", "wrong").await;
+    let unknown = provider.authenticate("nobody", "secret").await;
+    cache.age_all(chrono::Duration::hours(2));
+    let expired = provider.authenticate("max", "secret").await;
+
+    // the wrong password is told apart internally for the lockout, the client sees the same 503
+    assert!(matches!(wrong, Err(AppError::OfflineLoginRejected(_))));
+    assert!(matches!(unknown, Err(AppError::LdapError(_))));
+    assert!(matches!(expired, Err(AppError::LdapError(_))));
+    assert_eq!(wrong.unwrap_err().to_string(), unknown.unwrap_err().to_string());
+}
+
+#[tokio::test]
+async fn test_ldap_offline_login_off_without_cache() {
+    let directory = ldap_directory();
+    let config = LdapConfig {
+        offline_grace_secs: Some(3600),
+        ..search_then_bind_config()
+    };
+    let provider = ldap_provider(config, &directory, Arc::new(MockUserRepository::new()));
+    provider.authenticate("max", "secret").await.unwrap();
+    directory.set_unavailable(true);
+
+    let result = provider.authenticate("max", "secret").await;
+
+    assert!(matches!(result, Err(AppError::LdapError(_))));
+}
+
+#[tokio::test]
+async fn test_ldap_restricted_account_loses_cached_credential() {
+    let directory = ldap_directory();
+    let cache = Arc::new(MockCredentialCacheRepository::default());
+    let provider = cached_provider(&directory, Arc::new(MockUserRepository::new()), cache.clone());
+    provider.authenticate("max", "secret").await.unwrap();
+
+    // account disabled in AD
+    directory.fail_bind(
+        MAX_DN,
+        49,
+        "80090308: LdapErr: DSID-0C09044E, comment: AcceptSecurityContext error, data 533, v4563",
+    );
+    let restricted = provider.authenticate("max", "secret").await;
+    assert!(matches!(restricted, Err(AppError::AccountRestricted(_))));
+    assert_eq!(cache.len(), 0);
+
+    directory.clear_bind_failures();
+    directory.set_unavailable(true);
+    let offline = provider.authenticate("max", "secret").await;
+    assert!(matches!(offline, Err(AppError::LdapError(_))));
+}
+
+#[tokio::test]
+async fn test_ldap_pooled_connection_reused() {
+    let directory = ldap_directory();
+    let provider = ldap_provider(search_then_bind_config(), &directory, Arc::new(MockUserRepository::new()));
+
+    provider.authenticate("max", "secret").await.unwrap();
+    provider.authenticate("max", "secret").await.unwrap();
+    let _ = provider.authenticate("max", "wrong").await;
+
+    assert_eq!(directory.connection_count(), 1);
+}
+
+#[tokio::test]
+async fn test_ldap_stale_pooled_connection_replaced() {
+    let directory = ldap_directory();
+    let provider = ldap_provider(search_then_bind_config(), &directory, Arc::new(MockUserRepository::new()));
+
+    provider.authenticate("max", "secret").await.unwrap();
+    // server restart, the idle connection is dead
+    directory.disconnect_all();
+
+    provider.authenticate("max", "secret").await.unwrap();
+    assert_eq!(directory.connection_count(), 2);
+}
+
+#[tokio::test]
+async fn test_ldap_sync_deactivates_removed_and_disabled_users() {
+    let directory = ldap_directory();
+    directory.add_user(
+        "CN=Anna,OU=Users,DC=example,DC=com",
+        "secret",
+        &[("sAMAccountName", "anna"), ("userPrincipalName", "anna@example.com"), ("mail", "anna@example.com")],
+    );
+    directory.add_user(
+        "CN=Gone,OU=Users,DC=example,DC=com",
+        "secret",
+        &[("sAMAccountName", "gone"), ("userPrincipalName", "gone@example.com"), ("mail", "gone@example.com")],
+    );
+
+    let repo = Arc::new(MockUserRepository::new());
+    let provider = Arc::new(ldap_provider(search_then_bind_config(), &directory, repo.clone()));
+    for username in ["max", "anna", "gone"] {
+        provider.authenticate(username, "secret").await.unwrap();
+    }
+
+    directory.remove("CN=Gone,OU=Users,DC=example,DC=com");
+    // ACCOUNTDISABLE flag
+    directory.set_attribute("CN=Anna,OU=Users,DC=example,DC=com", "userAccountControl", &["514"]);
+
+    let sync = DirectorySyncService::new(provider, repo.clone(), Arc::new(MockRefreshTokenRepository::default()));
+    let report = sync.run().await.unwrap();
+
+    assert_eq!(report.local_users, 3);
+    let mut deactivated: Vec<(String, String)> = report
+        .deactivated
+        .into_iter()
+        .map(|d| (d.username, d.reason))
+        .collect();
+    deactivated.sort();
+    assert_eq!(
+        deactivated,
+        vec![
+            ("anna".to_string(), "disabled in directory".to_string()),
+            ("gone".to_string(), "not found in directory".to_string()),
+        ]
+    );
+    let max = repo
+        .find_by_external_id(&AuthProviderType::ActiveDirectory.to_string(), "max")
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(max.is_active);
+}
+
+#[tokio::test]
+async fn test_ldap_sync_reactivates_users_back_in_directory() {
+    const ANNA_DN: &str = "CN=Anna,OU=Users,DC=example,DC=com";
+    const GONE_DN: &str = "CN=Gone,OU=Users,DC=example,DC=com";
+    let anna = [("sAMAccountName", "anna"), ("userPrincipalName", "anna@example.com"), ("mail", "anna@example.com")];
+    let gone = [("sAMAccountName", "gone"), ("userPrincipalName", "gone@example.com"), ("mail", "gone@example.com")];
+
+    let directory = ldap_directory();
+    directory.add_user(ANNA_DN, "secret", &anna);
+    directory.add_user(GONE_DN, "secret", &gone);
+
+    let repo = Arc::new(MockUserRepository::new());
+    let provider = Arc::new(ldap_provider(search_then_bind_config(), &directory, repo.clone()));
+    let gone_id = provider.authenticate("gone", "secret").await.unwrap().user.id;
+    let anna_id = provider.authenticate("anna", "secret").await.unwrap().user.id;
+
+    directory.remove(GONE_DN);
+    directory.set_attribute(ANNA_DN, "userAccountControl", &["514"]);
+    let sync = DirectorySyncService::new(provider.clone(), repo.clone(), Arc::new(MockRefreshTokenRepository::default()));
+    assert_eq!(sync.run().await.unwrap().deactivated.len(), 2);
+    // already deactivated, not reported again
+    assert!(sync.run().await.unwrap().deactivated.is_empty());
+
+    // back in the directory, the login finds the old account instead of running into the unique email
+    directory.add_user(GONE_DN, "secret", &gone);
+    let result = provider.authenticate("gone", "secret").await.unwrap();
+    assert_eq!(result.user.id, gone_id);
+    assert!(repo.find_by_id(&gone_id).await.unwrap().unwrap().is_active);
+
+    // enabled again, the sync brings it back
+    directory.set_attribute(ANNA_DN, "userAccountControl", &["512"]);
+    let report = sync.run().await.unwrap();
+    assert!(report.deactivated.is_empty());
+    let reactivated: Vec<&str> = report.reactivated.iter().map(|r| r.user_id.as_str()).collect();
+    assert_eq!(reactivated, vec![anna_id.as_str()]);
+    assert!(repo.find_by_id(&anna_id).await.unwrap().unwrap().is_active);
+}
+
+#[tokio::test]
+async fn test_ldap_change_password_ad() {
+    let directory = ldap_directory();
+    let provider = ldap_provider(search_then_bind_config(), &directory, Arc::new(MockUserRepository::new()));
+
+    provider.change_password("max", "secret", "New-Secret-1").await.unwrap();
+
+    assert!(provider.authenticate("max", "New-Secret-1").await.is_ok());
+    assert!(matches!(provider.authenticate("max", "secret").await, Err(AppError::Unauthorized(_))));
+
+    // AD keeps a password history
+    let reuse = provider.change_password("max", "New-Secret-1", "secret").await;
+    assert!(matches!(reuse, Err(AppError::ValidationError(_))));
+}
+
+#[tokio::test]
+async fn test_ldap_change_password_openldap() {
+    let directory = InMemoryDirectory::new();
+    directory.add_user("uid=max,ou=people,dc=example,dc=com", "secret", &[("uid", "max"), ("mail", "max@example.com")]);
+    directory.add_user("cn=admin,dc=example,dc=com", "admin-pw", &[]);
+
+    let config = LdapConfig {
+        provider: AuthProviderType::Ldap,
+        user_base_dn: "ou=people,dc=example,dc=com".to_string(),
+        username_attribute: "uid".to_string(),
+        user_filter: "(uid={username})".to_string(),
+        bind_dn: Some("cn=admin,dc=example,dc=com".to_string()),
+        bind_password: Some("admin-pw".to_string()),
+        ..test_ldap_config()
+    };
+    let provider = ldap_provider(config, &directory, Arc::new(MockUserRepository::new()));
+
+    let wrong = provider.change_password("max", "wrong", "New-Secret-1").await;
+    assert!(matches!(wrong, Err(AppError::Unauthorized(_))));
+
+    provider.change_password("max", "secret", "New-Secret-1").await.unwrap();
+    assert_eq!(
+        directory.password_of("uid=max,ou=people,dc=example,dc=com").as_deref(),
+        Some("New-Secret-1")
+    );
+}
+
+#[tokio::test]
+async fn test_ldap_change_password_needs_encryption() {
+    let directory = ldap_directory();
+    directory.set_plaintext(true);
+    let provider = ldap_provider(search_then_bind_config(), &directory, Arc::new(MockUserRepository::new()));
+
+    let result = provider.change_password("max", "secret", "New-Secret-1").await;
+
+    assert!(matches!(result, Err(AppError::LdapError(_))));
+    assert_eq!(directory.password_of(MAX_DN).as_deref(), Some("secret"));
+}
+
+// ==================== Login Lockout Tests ====================
+
+fn login_throttle(config: LockoutConfig) -> (LoginThrottle, Arc<MockLoginAttemptRepository>) {
+    let repo = Arc::new(MockLoginAttemptRepository::default());
+    (LoginThrottle::new(config, repo.clone()), repo)
+}
+
+fn retry_after(result: Result<(), AppError>) -> i64 {
+    match result {
+        Err(AppError::TooManyAttempts(secs)) => secs,
+        other => panic!("expected lockout, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_lockout_backoff_doubles_and_is_capped() {
+    let (throttle, _) = login_throttle(LockoutConfig {
+        max_failures: 3,
+        lockout_secs: 30,
+        max_lockout_secs: 100,
+        ..LockoutConfig::default()
+    });
+    let subject = LoginThrottle::local_subject("Max@Example.com");
+
+    for _ in 0..2 {
+        throttle.record_failure(&subject, "10.0.0.1").await;
+    }
+    assert!(throttle.check(&subject, "10.0.0.1").await.is_ok());
+
+    throttle.record_failure(&subject, "10.0.0.1").await;
+    assert!((29..=30).contains(&retry_after(throttle.check(&subject, "10.0.0.1").await)));
+    // email is case insensitive
+    assert!(throttle.check(&LoginThrottle::local_subject("max@example.com"), "10.0.0.1").await.is_err());
+
+    throttle.record_failure(&subject, "10.0.0.1").await;
+    assert!((59..=60).contains(&retry_after(throttle.check(&subject, "10.0.0.1").await)));
+
+    throttle.record_failure(&subject, "10.0.0.1").await;
+    assert!((99..=100).contains(&retry_after(throttle.check(&subject, "10.0.0.1").await)));
+}
+
+#[tokio::test]
+async fn test_lockout_runs_out_and_window_resets_counter() {
+    let (throttle, repo) = login_throttle(LockoutConfig {
+        max_failures: 2,
+        ..LockoutConfig::default()
+    });
+    let subject = LoginThrottle::ldap_subject("max");
+
+    throttle.record_failure(&subject, "10.0.0.1").await;
+    throttle.record_failure(&subject, "10.0.0.1").await;
+    assert!(throttle.check(&subject, "10.0.0.1").await.is_err());
+
+    // lock over and a quiet window later the next failure counts as the first one again
+    repo.age_all(chrono::Duration::hours(1));
+    assert!(throttle.check(&subject, "10.0.0.1").await.is_ok());
+    throttle.record_failure(&subject, "10.0.0.1").await;
+    assert!(throttle.check(&subject, "10.0.0.1").await.is_ok());
+}
+
+#[tokio::test]
+async fn test_account_lock_only_blocks_clients_that_failed() {
+    let (throttle, _) = login_throttle(LockoutConfig {
+        max_failures: 100,
+        account_max_failures: 3,
+        ..LockoutConfig::default()
+    });
+    let subject = LoginThrottle::local_subject("victim@example.com");
+
+    // attacker spread over several ips
+    for ip in ["10.0.0.1", "10.0.0.2", "10.0.0.3"] {
+        throttle.record_failure(&subject, ip).await;
+    }
+
+    assert!(throttle.check(&subject, "10.0.0.1").await.is_err());
+    assert!(throttle.check(&subject, "10.0.0.3").await.is_err());
+    // the real user never failed and can still log in
+    assert!(throttle.check(&subject, "192.168.1.20").await.is_ok());
+}
+
+#[tokio::test]
+async fn test_ip_lock_covers_all_accounts() {
+    let (throttle, _) = login_throttle(LockoutConfig {
+        ip_max_failures: 3,
+        ..LockoutConfig::default()
+    });
+
+    for email in ["a@example.com", "b@example.com", "c@example.com"] {
+        throttle.record_failure(&LoginThrottle::local_subject(email), "10.0.0.9").await;
+    }
+
+    let other = LoginThrottle::local_subject("d@example.com");
+    assert!(throttle.check(&other, "10.0.0.9").await.is_err());
+    assert!(throttle.check(&other, "10.0.0.10").await.is_ok());
+}
+
+#[tokio::test]
+async fn test_guard_counts_only_wrong_credentials() {
+    let (throttle, _) = login_throttle(LockoutConfig {
+        max_failures: 1,
+        ..LockoutConfig::default()
+    });
+    let subject = LoginThrottle::local_subject("max@example.com");
+
+    let unavailable = throttle
+        .guard(&subject, "10.0.0.1", async { Err::<(), _>(AppError::LdapError("down".to_string())) })
+        .await;
+    assert!(matches!(unavailable, Err(AppError::LdapError(_))));
+    assert!(throttle.check(&subject, "10.0.0.1").await.is_ok());
+
+    let wrong = throttle
+        .guard(&subject, "10.0.0.1", async { Err::<(), _>(AppError::Unauthorized("bad".to_string())) })
+        .await;
+    assert!(matches!(wrong, Err(AppError::Unauthorized(_))));
+
+    // locked, the login itself does not run anymore
+    let locked: Result<(), _> = throttle
+        .guard(&subject, "10.0.0.1", async { panic!("login must not run") })
+        .await;
+    assert!(matches!(locked, Err(AppError::TooManyAttempts(_))));
+
+    assert_eq!(throttle.unlock(std::slice::from_ref(&subject)).await.unwrap(), 2);
+    assert!(throttle.guard(&subject, "10.0.0.1", async { Ok(()) }).await.is_ok());
+}
+
+#[tokio::test]
+async fn test_guard_concurrent_guesses_stop_at_max_failures() {
+    let (throttle, _) = login_throttle(LockoutConfig {
+        max_failures: 3,
+        ..LockoutConfig::default()
+    });
+    let throttle = Arc::new(throttle);
+    let hasher = argon2_hasher(1);
+    let provider = Arc::new(LocalAuthProvider::new(Arc::new(MockUserRepository::new())).with_password_hasher(hasher.clone()));
+    provider
+        .register("Test User", "test@example.com", "secure_password_123", UserRole::User)
+        .await
+        .unwrap();
+
+    let subject = LoginThrottle::local_subject("test@example.com");
+    let guesses: Vec<_> = (0..10)
+        .map(|i| {
+            let (throttle, provider, subject) = (Arc::clone(&throttle), Arc::clone(&provider), subject.clone());
+            tokio::spawn(async move {
+                let password = format!("wrong_password_{}", i);
+                throttle
+                    .guard(&subject, "10.0.0.1", provider.authenticate("test@example.com", &password))
+                    .await
+            })
+        })
+        .collect();
+
+    let mut locked = 0;
+    for guess in guesses {
+        match guess.await.unwrap() {
+            Err(AppError::TooManyAttempts(_)) => locked += 1,
+            Err(AppError::Unauthorized(_)) => {}
+            other => panic!("unexpected result {:?}", other.map(|_| ())),
+        }
+    }
+
+    assert_eq!(hasher.metrics().verify.count, 3);
+    assert_eq!(locked, 7);
+}
+
+#[tokio::test]
+async fn test_guard_counts_wrong_offline_passwords() {
+    let (throttle, _) = login_throttle(LockoutConfig {
+        max_failures: 2,
+        ..LockoutConfig::default()
+    });
+    let directory = ldap_directory();
+    let provider = cached_provider(&directory, Arc::new(MockUserRepository::new()), Arc::new(MockCredentialCacheRepository::default()));
+    provider.authenticate("max", "secret").await.unwrap();
+    directory.set_unavailable(true);
+
+    let subject = LoginThrottle::ldap_subject("max");
+    for _ in 0..2 {
+        let wrong = throttle.guard(&subject, "10.0.0.1", provider.authenticate("max", "wrong")).await;
+        assert!(matches!(wrong, Err(AppError::OfflineLoginRejected(_))));
+    }
+
+    let locked = throttle.guard(&subject, "10.0.0.1", provider.authenticate("max", "secret")).await;
+    assert!(matches!(locked, Err(AppError::TooManyAttempts(_))));
+}
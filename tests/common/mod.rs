@@ -5,8 +5,17 @@ use std::collections::HashMap;
 use std::sync::RwLock;
 
 use syt_ek962_security_concepts::error::AppError;
-use syt_ek962_security_concepts::models::{User, UserRole, AuthProviderType};
-use syt_ek962_security_concepts::repository::UserRepository;
+use chrono::Utc;
+
+use syt_ek962_security_concepts::models::{
+    User, UserRole, AuthProviderType, OAuthClient, DeviceAuthorization, DeviceAuthorizationStatus, RefreshToken, Consent,
+    CachedCredential, PasswordResetToken, LoginAttempt,
+};
+use syt_ek962_security_concepts::repository::{
+    UserRepository, ClientRepository, DeviceAuthorizationRepository, RefreshTokenRepository,
+    ConsentRepository, CredentialCacheRepository, PasswordHistoryRepository, PasswordResetRepository,
+    LoginAttemptRepository,
+};
 
 /// In-memory mock repository for testing
 pub struct MockUserRepository {
@@ -26,6 +35,7 @@ impl MockUserRepository {
         repo
     }
 
+    #[allow(dead_code)]
     pub fn with_users(users: Vec<User>) -> Self {
         let repo = Self::new();
         {
@@ -76,6 +86,33 @@ impl UserRepository for MockUserRepository {
             .cloned())
     }
 
+    async fn find_by_external_id_including_inactive(
+        &self,
+        provider: &str,
+        external_id: &str,
+    ) -> Result<Option<User>, AppError> {
+        let users = self.users.read().unwrap();
+        Ok(users
+            .values()
+            .find(|u| u.auth_provider.to_string() == provider && u.external_id.as_deref() == Some(external_id))
+            .cloned())
+    }
+
+    async fn find_by_email_including_inactive(&self, email: &str) -> Result<Option<User>, AppError> {
+        let users = self.users.read().unwrap();
+        let email_lower = email.to_lowercase();
+        Ok(users.values().find(|u| u.email.to_lowercase() == email_lower).cloned())
+    }
+
+    async fn list_by_provider(&self, provider: &str) -> Result<Vec<User>, AppError> {
+        let users = self.users.read().unwrap();
+        Ok(users
+            .values()
+            .filter(|u| u.auth_provider.to_string() == provider)
+            .cloned()
+            .collect())
+    }
+
     async fn create(&self, user: &User) -> Result<(), AppError> {
         let mut users = self.users.write().unwrap();
 
@@ -119,6 +156,443 @@ impl UserRepository for MockUserRepository {
     }
 }
 
+/// In-memory mock client repository for testing
+#[allow(dead_code)]
+pub struct MockClientRepository {
+    clients: RwLock<HashMap<String, OAuthClient>>,
+}
+
+#[allow(dead_code)]
+impl MockClientRepository {
+    pub fn new() -> Self {
+        Self {
+            clients: RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub fn with_client(client: OAuthClient) -> Self {
+        let repo = Self::new();
+        repo.clients.write().unwrap().insert(client.client_id.clone(), client);
+        repo
+    }
+}
+
+impl Default for MockClientRepository {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl ClientRepository for MockClientRepository {
+    async fn find_by_client_id(&self, client_id: &str) -> Result<Option<OAuthClient>, AppError> {
+        let clients = self.clients.read().unwrap();
+        Ok(clients.get(client_id).filter(|c| c.is_active).cloned())
+    }
+
+    async fn list(&self) -> Result<Vec<OAuthClient>, AppError> {
+        let clients = self.clients.read().unwrap();
+        Ok(clients.values().filter(|c| c.is_active).cloned().collect())
+    }
+
+    async fn create(&self, client: &OAuthClient) -> Result<(), AppError> {
+        let mut clients = self.clients.write().unwrap();
+        if clients.contains_key(&client.client_id) {
+            return Err(AppError::Conflict("Client already registered".to_string()));
+        }
+        clients.insert(client.client_id.clone(), client.clone());
+        Ok(())
+    }
+
+    async fn update(&self, client: &OAuthClient) -> Result<(), AppError> {
+        let mut clients = self.clients.write().unwrap();
+        if clients.contains_key(&client.client_id) {
+            clients.insert(client.client_id.clone(), client.clone());
+            Ok(())
+        } else {
+            Err(AppError::NotFound("Client not found".to_string()))
+        }
+    }
+
+    async fn delete(&self, client_id: &str) -> Result<(), AppError> {
+        let mut clients = self.clients.write().unwrap();
+        match clients.get_mut(client_id) {
+            Some(client) if client.is_active => {
+                client.is_active = false;
+                Ok(())
+            }
+            _ => Err(AppError::NotFound("Client not found".to_string())),
+        }
+    }
+}
+
+/// In-memory mock device authorization repository for testing
+#[allow(dead_code)]
+#[derive(Default)]
+pub struct MockDeviceAuthorizationRepository {
+    authorizations: RwLock<HashMap<String, DeviceAuthorization>>,
+}
+
+#[async_trait]
+impl DeviceAuthorizationRepository for MockDeviceAuthorizationRepository {
+    async fn create(&self, authorization: &DeviceAuthorization) -> Result<(), AppError> {
+        let mut authorizations = self.authorizations.write().unwrap();
+        if authorizations.values().any(|a| a.user_code == authorization.user_code) {
+            return Err(AppError::Conflict("User code already in use".to_string()));
+        }
+        authorizations.insert(authorization.device_code_hash.clone(), authorization.clone());
+        Ok(())
+    }
+
+    async fn find_by_device_code_hash(
+        &self,
+        device_code_hash: &str,
+    ) -> Result<Option<DeviceAuthorization>, AppError> {
+        let authorizations = self.authorizations.read().unwrap();
+        Ok(authorizations.get(device_code_hash).cloned())
+    }
+
+    async fn find_by_user_code(&self, user_code: &str) -> Result<Option<DeviceAuthorization>, AppError> {
+        let authorizations = self.authorizations.read().unwrap();
+        Ok(authorizations.values().find(|a| a.user_code == user_code).cloned())
+    }
+
+    async fn update(&self, authorization: &DeviceAuthorization) -> Result<(), AppError> {
+        let mut authorizations = self.authorizations.write().unwrap();
+        if authorizations.contains_key(&authorization.device_code_hash) {
+            authorizations.insert(authorization.device_code_hash.clone(), authorization.clone());
+            Ok(())
+        } else {
+            Err(AppError::NotFound("Device authorization not found".to_string()))
+        }
+    }
+
+    async fn consume(&self, device_code_hash: &str) -> Result<bool, AppError> {
+        let mut authorizations = self.authorizations.write().unwrap();
+        match authorizations.get_mut(device_code_hash) {
+            Some(a) if a.status == DeviceAuthorizationStatus::Approved => {
+                a.status = DeviceAuthorizationStatus::Consumed;
+                a.last_polled_at = Some(Utc::now());
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn delete_expired(&self) -> Result<u64, AppError> {
+        let mut authorizations = self.authorizations.write().unwrap();
+        let before = authorizations.len();
+        authorizations.retain(|_, a| !a.is_expired());
+        Ok((before - authorizations.len()) as u64)
+    }
+}
+
+/// In-memory mock refresh token repository for testing
+#[allow(dead_code)]
+#[derive(Default)]
+pub struct MockRefreshTokenRepository {
+    tokens: RwLock<HashMap<String, RefreshToken>>,
+}
+
+#[allow(dead_code)]
+impl MockRefreshTokenRepository {
+    pub fn all(&self) -> Vec<RefreshToken> {
+        self.tokens.read().unwrap().values().cloned().collect()
+    }
+}
+
+#[async_trait]
+impl RefreshTokenRepository for MockRefreshTokenRepository {
+    async fn create(&self, token: &RefreshToken) -> Result<(), AppError> {
+        let mut tokens = self.tokens.write().unwrap();
+        tokens.insert(token.token_hash.clone(), token.clone());
+        Ok(())
+    }
+
+    async fn find_by_hash(&self, token_hash: &str) -> Result<Option<RefreshToken>, AppError> {
+        let tokens = self.tokens.read().unwrap();
+        Ok(tokens.get(token_hash).cloned())
+    }
+
+    async fn revoke(&self, token_hash: &str) -> Result<bool, AppError> {
+        let mut tokens = self.tokens.write().unwrap();
+        match tokens.get_mut(token_hash) {
+            Some(token) if token.revoked_at.is_none() => {
+                token.revoked_at = Some(Utc::now());
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn revoke_all_for_user(&self, user_id: &str) -> Result<u64, AppError> {
+        let mut tokens = self.tokens.write().unwrap();
+        let mut count = 0;
+        for token in tokens.values_mut().filter(|t| t.user_id == user_id && t.revoked_at.is_none()) {
+            token.revoked_at = Some(Utc::now());
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    async fn revoke_for_client(&self, user_id: &str, client_id: &str) -> Result<u64, AppError> {
+        let mut tokens = self.tokens.write().unwrap();
+        let mut count = 0;
+        for token in tokens
+            .values_mut()
+            .filter(|t| t.user_id == user_id && t.client_id == client_id && t.revoked_at.is_none())
+        {
+            token.revoked_at = Some(Utc::now());
+            count += 1;
+        }
+        Ok(count)
+    }
+}
+
+/// In-memory consent store, keyed by (user_id, client_id)
+#[allow(dead_code)]
+#[derive(Default)]
+pub struct MockConsentRepository {
+    consents: RwLock<HashMap<(String, String), Consent>>,
+}
+
+#[async_trait]
+impl ConsentRepository for MockConsentRepository {
+    async fn find(&self, user_id: &str, client_id: &str) -> Result<Option<Consent>, AppError> {
+        let consents = self.consents.read().unwrap();
+        Ok(consents.get(&(user_id.to_string(), client_id.to_string())).cloned())
+    }
+
+    async fn list_for_user(&self, user_id: &str) -> Result<Vec<Consent>, AppError> {
+        let consents = self.consents.read().unwrap();
+        Ok(consents.values().filter(|c| c.user_id == user_id).cloned().collect())
+    }
+
+    async fn save(&self, consent: &Consent) -> Result<(), AppError> {
+        let mut consents = self.consents.write().unwrap();
+        consents.insert(
+            (consent.user_id.clone(), consent.client_id.clone()),
+            consent.clone(),
+        );
+        Ok(())
+    }
+
+    async fn delete(&self, user_id: &str, client_id: &str) -> Result<bool, AppError> {
+        let mut consents = self.consents.write().unwrap();
+        Ok(consents.remove(&(user_id.to_string(), client_id.to_string())).is_some())
+    }
+}
+
+/// In-memory password reset tokens, keyed by token hash
+#[allow(dead_code)]
+#[derive(Default)]
+pub struct MockPasswordResetRepository {
+    tokens: RwLock<HashMap<String, PasswordResetToken>>,
+}
+
+#[async_trait]
+impl PasswordResetRepository for MockPasswordResetRepository {
+    async fn create(&self, token: &PasswordResetToken) -> Result<(), AppError> {
+        let mut tokens = self.tokens.write().unwrap();
+        tokens.insert(token.token_hash.clone(), token.clone());
+        Ok(())
+    }
+
+    async fn find_by_hash(&self, token_hash: &str) -> Result<Option<PasswordResetToken>, AppError> {
+        let tokens = self.tokens.read().unwrap();
+        Ok(tokens.get(token_hash).cloned())
+    }
+
+    async fn mark_used(&self, token_hash: &str) -> Result<bool, AppError> {
+        let mut tokens = self.tokens.write().unwrap();
+        match tokens.get_mut(token_hash) {
+            Some(token) if token.used_at.is_none() => {
+                token.used_at = Some(Utc::now());
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn find_latest_for_user(&self, user_id: &str) -> Result<Option<PasswordResetToken>, AppError> {
+        let tokens = self.tokens.read().unwrap();
+        Ok(tokens.values().filter(|t| t.user_id == user_id).max_by_key(|t| t.created_at).cloned())
+    }
+
+    async fn invalidate_for_user(&self, user_id: &str) -> Result<u64, AppError> {
+        let mut tokens = self.tokens.write().unwrap();
+        let mut count = 0;
+        for token in tokens.values_mut().filter(|t| t.user_id == user_id && t.used_at.is_none()) {
+            token.used_at = Some(Utc::now());
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    async fn delete_expired(&self) -> Result<u64, AppError> {
+        let mut tokens = self.tokens.write().unwrap();
+        let before = tokens.len();
+        tokens.retain(|_, t| t.expires_at >= Utc::now());
+        Ok((before - tokens.len()) as u64)
+    }
+}
+
+/// In-memory password history, oldest first per user
+#[allow(dead_code)]
+#[derive(Default)]
+pub struct MockPasswordHistoryRepository {
+    hashes: RwLock<HashMap<String, Vec<String>>>,
+}
+
+#[allow(dead_code)]
+impl MockPasswordHistoryRepository {
+    pub fn len(&self, user_id: &str) -> usize {
+        self.hashes.read().unwrap().get(user_id).map_or(0, Vec::len)
+    }
+}
+
+#[async_trait]
+impl PasswordHistoryRepository for MockPasswordHistoryRepository {
+    async fn add(&self, user_id: &str, password_hash: &str) -> Result<(), AppError> {
+        let mut hashes = self.hashes.write().unwrap();
+        hashes.entry(user_id.to_string()).or_default().push(password_hash.to_string());
+        Ok(())
+    }
+
+    async fn recent(&self, user_id: &str, limit: usize) -> Result<Vec<String>, AppError> {
+        let hashes = self.hashes.read().unwrap();
+        Ok(hashes
+            .get(user_id)
+            .map(|h| h.iter().rev().take(limit).cloned().collect())
+            .unwrap_or_default())
+    }
+
+    async fn prune(&self, user_id: &str, keep: usize) -> Result<u64, AppError> {
+        let mut hashes = self.hashes.write().unwrap();
+        let Some(user_hashes) = hashes.get_mut(user_id) else {
+            return Ok(0);
+        };
+        let pruned = user_hashes.len().saturating_sub(keep);
+        user_hashes.drain(..pruned);
+        Ok(pruned as u64)
+    }
+}
+
+/// In-memory credential cache for offline LDAP logins, keyed by user_id
+#[allow(dead_code)]
+#[derive(Default)]
+pub struct MockCredentialCacheRepository {
+    credentials: RwLock<HashMap<String, CachedCredential>>,
+}
+
+#[allow(dead_code)]
+impl MockCredentialCacheRepository {
+    pub fn len(&self) -> usize {
+        self.credentials.read().unwrap().len()
+    }
+
+    /// Moves the cache timestamp of every credential into the past
+    pub fn age_all(&self, age: chrono::Duration) {
+        for credential in self.credentials.write().unwrap().values_mut() {
+            credential.cached_at -= age;
+        }
+    }
+}
+
+#[async_trait]
+impl CredentialCacheRepository for MockCredentialCacheRepository {
+    async fn store(&self, credential: &CachedCredential) -> Result<(), AppError> {
+        let mut credentials = self.credentials.write().unwrap();
+        credentials.insert(credential.user_id.clone(), credential.clone());
+        Ok(())
+    }
+
+    async fn find_by_username(&self, username: &str) -> Result<Option<CachedCredential>, AppError> {
+        let credentials = self.credentials.read().unwrap();
+        let username = username.to_lowercase();
+        Ok(credentials.values().find(|c| c.username == username).cloned())
+    }
+
+    async fn delete(&self, user_id: &str) -> Result<bool, AppError> {
+        let mut credentials = self.credentials.write().unwrap();
+        Ok(credentials.remove(user_id).is_some())
+    }
+}
+
+/// In-memory failed login counters, keyed like the real table
+#[allow(dead_code)]
+#[derive(Default)]
+pub struct MockLoginAttemptRepository {
+    attempts: RwLock<HashMap<String, LoginAttempt>>,
+}
+
+#[allow(dead_code)]
+impl MockLoginAttemptRepository {
+    /// Moves every failure and lock into the past
+    pub fn age_all(&self, age: chrono::Duration) {
+        for attempt in self.attempts.write().unwrap().values_mut() {
+            attempt.last_failure_at -= age;
+            attempt.locked_until = attempt.locked_until.map(|until| until - age);
+        }
+    }
+}
+
+#[async_trait]
+impl LoginAttemptRepository for MockLoginAttemptRepository {
+    async fn find(&self, key: &str) -> Result<Option<LoginAttempt>, AppError> {
+        Ok(self.attempts.read().unwrap().get(key).cloned())
+    }
+
+    async fn record_failure(
+        &self,
+        key: &str,
+        subject: Option<&str>,
+        window_start: chrono::DateTime<Utc>,
+    ) -> Result<LoginAttempt, AppError> {
+        let mut attempts = self.attempts.write().unwrap();
+        let attempt = attempts.entry(key.to_string()).or_insert_with(|| LoginAttempt {
+            key: key.to_string(),
+            subject: subject.map(str::to_string),
+            failures: 0,
+            last_failure_at: Utc::now(),
+            locked_until: None,
+        });
+        if attempt.last_failure_at < window_start {
+            attempt.failures = 0;
+            attempt.locked_until = None;
+        }
+        attempt.failures += 1;
+        attempt.last_failure_at = Utc::now();
+        Ok(attempt.clone())
+    }
+
+    async fn lock(&self, key: &str, until: chrono::DateTime<Utc>) -> Result<(), AppError> {
+        if let Some(attempt) = self.attempts.write().unwrap().get_mut(key) {
+            attempt.locked_until = Some(until);
+        }
+        Ok(())
+    }
+
+    async fn delete(&self, key: &str) -> Result<bool, AppError> {
+        Ok(self.attempts.write().unwrap().remove(key).is_some())
+    }
+
+    async fn delete_for_subject(&self, subject: &str) -> Result<u64, AppError> {
+        let mut attempts = self.attempts.write().unwrap();
+        let before = attempts.len();
+        attempts.retain(|_, a| a.subject.as_deref() != Some(subject));
+        Ok((before - attempts.len()) as u64)
+    }
+
+    async fn delete_stale(&self, before: chrono::DateTime<Utc>) -> Result<u64, AppError> {
+        let mut attempts = self.attempts.write().unwrap();
+        let count = attempts.len();
+        let now = Utc::now();
+        attempts.retain(|_, a| a.last_failure_at >= before || a.locked_until.is_some_and(|until| until >= now));
+        Ok((count - attempts.len()) as u64)
+    }
+}
+
 /// Helper to create a test user with password hash
 #[allow(dead_code)]
 pub fn create_test_user(email: &str, password_hash: &str, role: UserRole) -> User {
@@ -148,6 +622,7 @@ pub fn create_external_user(
 }
 
 /// Test JWT configuration
+#[allow(dead_code)]
 pub fn test_jwt_config() -> syt_ek962_security_concepts::config::JwtConfig {
     syt_ek962_security_concepts::config::JwtConfig {
         secret: "test_secret_key_for_testing_at_least_32_chars".to_string(),
@@ -155,3 +630,56 @@ pub fn test_jwt_config() -> syt_ek962_security_concepts::config::JwtConfig {
         issuer: "test-auth-service".to_string(),
     }
 }
+
+/// Test device flow configuration (no poll interval so tests dont have to sleep)
+#[allow(dead_code)]
+pub fn test_device_flow_config() -> syt_ek962_security_concepts::config::DeviceFlowConfig {
+    syt_ek962_security_concepts::config::DeviceFlowConfig {
+        verification_uri: "http://localhost:8080/device.html".to_string(),
+        expiration_secs: 600,
+        interval_secs: 0,
+    }
+}
+
+/// Test password reset configuration, mails go to the given outbox directory
+#[allow(dead_code)]
+pub fn test_password_reset_config(outbox: &std::path::Path) -> syt_ek962_security_concepts::config::PasswordResetConfig {
+    use syt_ek962_security_concepts::config::{MailConfig, PasswordResetConfig};
+
+    PasswordResetConfig {
+        reset_uri: "http://localhost:8080/reset-password.html".to_string(),
+        expiration_secs: 1800,
+        resend_interval_secs: 0,
+        max_requests_per_email: 0,
+        max_requests_per_ip: 0,
+        mail: MailConfig::Outbox {
+            dir: outbox.to_str().unwrap().to_string(),
+            from: "auth@example.com".to_string(),
+        },
+    }
+}
+
+/// Token from the link in a password reset mail
+#[allow(dead_code)]
+pub fn reset_token_from_mail(mail: &syt_ek962_security_concepts::mail::Mail) -> String {
+    mail.body
+        .split("?token=")
+        .nth(1)
+        .and_then(|rest| rest.split_whitespace().next())
+        .expect("no reset link in mail")
+        .to_string()
+}
+
+/// Test LDAP configuration (AD, direct UPN bind, run against InMemoryDirectory)
+#[allow(dead_code)]
+pub fn test_ldap_config() -> syt_ek962_security_concepts::config::LdapConfig {
+    use syt_ek962_security_concepts::config::{LdapConfig, NestedGroupMode};
+
+    LdapConfig {
+        nested_groups: NestedGroupMode::None,
+        group_base_dn: "OU=Groups,DC=example,DC=com".to_string(),
+        user_filter: "(&(objectClass=user)(sAMAccountName={username}))".to_string(),
+        pool_size: 2,
+        ..LdapConfig::new(vec!["memory://directory".to_string()], "OU=Users,DC=example,DC=com", "example.com")
+    }
+}
@@ -2,8 +2,20 @@
 
 mod common;
 
-use syt_ek962_security_concepts::models::{User, UserRole, AuthProviderType};
-use syt_ek962_security_concepts::repository::UserRepository;
+use sqlx::sqlite::SqlitePoolOptions;
+
+use syt_ek962_security_concepts::models::{
+    User, UserRole, AuthProviderType, OAuthClient, GrantType, Consent, RefreshToken, CachedCredential,
+    PasswordResetToken, DeviceAuthorization, DeviceAuthorizationStatus,
+};
+use chrono::{Duration, Utc};
+use syt_ek962_security_concepts::repository::{
+    UserRepository, ClientRepository, SqliteClientRepository, ConsentRepository, SqliteConsentRepository,
+    RefreshTokenRepository, SqliteRefreshTokenRepository, SqliteUserRepository, CredentialCacheRepository,
+    SqliteCredentialCacheRepository, PasswordResetRepository, SqlitePasswordResetRepository,
+    PasswordHistoryRepository, SqlitePasswordHistoryRepository, LoginAttemptRepository, SqliteLoginAttemptRepository,
+    DeviceAuthorizationRepository, SqliteDeviceAuthorizationRepository,
+};
 use syt_ek962_security_concepts::error::AppError;
 
 use common::MockUserRepository;
@@ -358,3 +370,400 @@ async fn test_multiple_providers_same_email_different_external_id() {
     let not_found = repo.find_by_external_id("activedirectory", "google-123").await.unwrap();
     assert!(not_found.is_none());
 }
+
+// ==================== SQLite Client Repository Tests ====================
+
+async fn sqlite_client_repo() -> SqliteClientRepository {
+    let pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect("sqlite::memory:")
+        .await
+        .unwrap();
+    let repo = SqliteClientRepository::new(pool);
+    repo.initialize().await.unwrap();
+    repo
+}
+
+fn test_client(name: &str) -> OAuthClient {
+    OAuthClient::new(
+        name.to_string(),
+        Some("hash".to_string()),
+        vec!["https://app.example.com/callback".to_string()],
+        vec![GrantType::AuthorizationCode, GrantType::DeviceCode],
+        vec!["openid".to_string()],
+        3600,
+        86400,
+    )
+}
+
+#[tokio::test]
+async fn test_sqlite_client_roundtrip() {
+    let repo = sqlite_client_repo().await;
+    let client = test_client("App");
+
+    repo.create(&client).await.unwrap();
+
+    let found = repo.find_by_client_id(&client.client_id).await.unwrap().unwrap();
+    assert_eq!(found.name, "App");
+    assert_eq!(found.client_secret_hash, Some("hash".to_string()));
+    assert_eq!(found.redirect_uris, client.redirect_uris);
+    assert_eq!(found.grant_types, vec![GrantType::AuthorizationCode, GrantType::DeviceCode]);
+    assert_eq!(found.scopes, vec!["openid".to_string()]);
+    assert_eq!(found.access_token_ttl_secs, 3600);
+}
+
+#[tokio::test]
+async fn test_sqlite_client_update_and_list() {
+    let repo = sqlite_client_repo().await;
+    let mut client = test_client("App");
+    repo.create(&client).await.unwrap();
+    repo.create(&test_client("Other")).await.unwrap();
+
+    client.client_secret_hash = None;
+    client.scopes.push("profile".to_string());
+    repo.update(&client).await.unwrap();
+
+    let found = repo.find_by_client_id(&client.client_id).await.unwrap().unwrap();
+    assert!(!found.is_confidential());
+    assert_eq!(found.scopes.len(), 2);
+
+    assert_eq!(repo.list().await.unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn test_sqlite_client_delete_is_soft() {
+    let repo = sqlite_client_repo().await;
+    let client = test_client("App");
+    repo.create(&client).await.unwrap();
+
+    repo.delete(&client.client_id).await.unwrap();
+
+    assert!(repo.find_by_client_id(&client.client_id).await.unwrap().is_none());
+    assert!(repo.list().await.unwrap().is_empty());
+    assert!(matches!(repo.delete(&client.client_id).await, Err(AppError::NotFound(_))));
+}
+
+// ==================== SQLite Consent Repository Tests ====================
+
+async fn memory_pool() -> sqlx::SqlitePool {
+    SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect("sqlite::memory:")
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_sqlite_consent_save_is_upsert() {
+    let repo = SqliteConsentRepository::new(memory_pool().await);
+    repo.initialize().await.unwrap();
+
+    let mut consent = Consent::new("user-1".to_string(), "client-1".to_string(), vec!["openid".to_string()]);
+    repo.save(&consent).await.unwrap();
+
+    consent.grant(&["profile".to_string()]);
+    repo.save(&consent).await.unwrap();
+
+    let found = repo.find("user-1", "client-1").await.unwrap().unwrap();
+    assert_eq!(found.scopes, vec!["openid", "profile"]);
+
+    repo.save(&Consent::new("user-1".to_string(), "client-2".to_string(), vec![])).await.unwrap();
+    repo.save(&Consent::new("user-2".to_string(), "client-1".to_string(), vec![])).await.unwrap();
+    assert_eq!(repo.list_for_user("user-1").await.unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn test_sqlite_consent_delete() {
+    let repo = SqliteConsentRepository::new(memory_pool().await);
+    repo.initialize().await.unwrap();
+
+    repo.save(&Consent::new("user-1".to_string(), "client-1".to_string(), vec![])).await.unwrap();
+
+    assert!(repo.delete("user-1", "client-1").await.unwrap());
+    assert!(!repo.delete("user-1", "client-1").await.unwrap());
+    assert!(repo.find("user-1", "client-1").await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_sqlite_refresh_tokens_revoke_for_client() {
+    let repo = SqliteRefreshTokenRepository::new(memory_pool().await);
+    repo.initialize().await.unwrap();
+
+    for (hash, client) in [("a", "client-1"), ("b", "client-1"), ("c", "client-2")] {
+        let token = RefreshToken::new(hash.to_string(), "user-1".to_string(), client.to_string(), vec![], 3600);
+        repo.create(&token).await.unwrap();
+    }
+
+    assert_eq!(repo.revoke_for_client("user-1", "client-1").await.unwrap(), 2);
+
+    assert!(!repo.find_by_hash("a").await.unwrap().unwrap().is_valid());
+    assert!(repo.find_by_hash("c").await.unwrap().unwrap().is_valid());
+}
+
+#[tokio::test]
+async fn test_sqlite_refresh_token_revoked_once() {
+    let repo = SqliteRefreshTokenRepository::new(memory_pool().await);
+    repo.initialize().await.unwrap();
+
+    let token = RefreshToken::new("a".to_string(), "user-1".to_string(), "client-1".to_string(), vec![], 3600);
+    repo.create(&token).await.unwrap();
+
+    assert!(repo.revoke("a").await.unwrap());
+    assert!(!repo.revoke("a").await.unwrap());
+    assert!(!repo.revoke("unknown").await.unwrap());
+}
+
+// ==================== SQLite Device Authorization Tests ====================
+
+#[tokio::test]
+async fn test_sqlite_device_authorization_consumed_once() {
+    let repo = SqliteDeviceAuthorizationRepository::new(memory_pool().await);
+    repo.initialize().await.unwrap();
+
+    let mut authorization =
+        DeviceAuthorization::new("a".to_string(), "ABCDEFGH".to_string(), "client-1".to_string(), vec![], 5, 600);
+    repo.create(&authorization).await.unwrap();
+
+    // still pending, nothing to consume
+    assert!(!repo.consume("a").await.unwrap());
+
+    authorization.status = DeviceAuthorizationStatus::Approved;
+    authorization.user_id = Some("user-1".to_string());
+    repo.update(&authorization).await.unwrap();
+
+    assert!(repo.consume("a").await.unwrap());
+    assert!(!repo.consume("a").await.unwrap());
+    let found = repo.find_by_device_code_hash("a").await.unwrap().unwrap();
+    assert_eq!(found.status, DeviceAuthorizationStatus::Consumed);
+    assert!(found.last_polled_at.is_some());
+}
+
+// ==================== SQLite Password Reset Tests ====================
+
+#[tokio::test]
+async fn test_sqlite_password_reset_token_used_once() {
+    let repo = SqlitePasswordResetRepository::new(memory_pool().await);
+    repo.initialize().await.unwrap();
+
+    repo.create(&PasswordResetToken::new("a".to_string(), "user-1".to_string(), 1800)).await.unwrap();
+
+    assert!(repo.find_by_hash("a").await.unwrap().unwrap().is_valid());
+    assert!(repo.mark_used("a").await.unwrap());
+    assert!(!repo.mark_used("a").await.unwrap());
+    assert!(!repo.find_by_hash("a").await.unwrap().unwrap().is_valid());
+    assert!(!repo.mark_used("unknown").await.unwrap());
+}
+
+#[tokio::test]
+async fn test_sqlite_password_reset_latest_for_user() {
+    let repo = SqlitePasswordResetRepository::new(memory_pool().await);
+    repo.initialize().await.unwrap();
+
+    let mut older = PasswordResetToken::new("a".to_string(), "user-1".to_string(), 1800);
+    older.created_at -= Duration::minutes(10);
+    repo.create(&older).await.unwrap();
+    repo.create(&PasswordResetToken::new("b".to_string(), "user-1".to_string(), 1800)).await.unwrap();
+    repo.invalidate_for_user("user-1").await.unwrap();
+
+    let latest = repo.find_latest_for_user("user-1").await.unwrap().unwrap();
+    assert_eq!(latest.token_hash, "b");
+    assert!(repo.find_latest_for_user("user-2").await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_sqlite_password_reset_invalidate_and_cleanup() {
+    let repo = SqlitePasswordResetRepository::new(memory_pool().await);
+    repo.initialize().await.unwrap();
+
+    repo.create(&PasswordResetToken::new("a".to_string(), "user-1".to_string(), 1800)).await.unwrap();
+    repo.create(&PasswordResetToken::new("b".to_string(), "user-1".to_string(), 1800)).await.unwrap();
+    repo.create(&PasswordResetToken::new("c".to_string(), "user-2".to_string(), 1800)).await.unwrap();
+    repo.create(&PasswordResetToken::new("old".to_string(), "user-2".to_string(), -60)).await.unwrap();
+
+    assert_eq!(repo.invalidate_for_user("user-1").await.unwrap(), 2);
+    assert!(repo.find_by_hash("c").await.unwrap().unwrap().is_valid());
+
+    assert_eq!(repo.delete_expired().await.unwrap(), 1);
+    assert!(repo.find_by_hash("old").await.unwrap().is_none());
+}
+
+// ==================== SQLite Password History Tests ====================
+
+#[tokio::test]
+async fn test_sqlite_password_history_newest_first_and_pruned() {
+    let repo = SqlitePasswordHistoryRepository::new(memory_pool().await);
+    repo.initialize().await.unwrap();
+
+    for hash in ["h1", "h2", "h3", "h4"] {
+        repo.add("user-1", hash).await.unwrap();
+    }
+    repo.add("user-2", "other").await.unwrap();
+
+    assert_eq!(repo.recent("user-1", 2).await.unwrap(), vec!["h4", "h3"]);
+
+    assert_eq!(repo.prune("user-1", 3).await.unwrap(), 1);
+    assert_eq!(repo.recent("user-1", 10).await.unwrap(), vec!["h4", "h3", "h2"]);
+    assert_eq!(repo.recent("user-2", 10).await.unwrap(), vec!["other"]);
+    assert!(repo.recent("unknown", 10).await.unwrap().is_empty());
+}
+
+// ==================== SQLite Credential Cache Tests ====================
+
+#[tokio::test]
+async fn test_sqlite_credential_cache_store_is_upsert() {
+    let repo = SqliteCredentialCacheRepository::new(memory_pool().await);
+    repo.initialize().await.unwrap();
+
+    repo.store(&CachedCredential::new("user-1".to_string(), "Max", "old".to_string())).await.unwrap();
+    repo.store(&CachedCredential::new("user-1".to_string(), "max", "new".to_string())).await.unwrap();
+
+    let found = repo.find_by_username("MAX").await.unwrap().unwrap();
+    assert_eq!(found.user_id, "user-1");
+    assert_eq!(found.verifier, "new");
+    assert!(repo.find_by_username("anna").await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_sqlite_credential_cache_delete() {
+    let repo = SqliteCredentialCacheRepository::new(memory_pool().await);
+    repo.initialize().await.unwrap();
+
+    repo.store(&CachedCredential::new("user-1".to_string(), "max", "hash".to_string())).await.unwrap();
+
+    assert!(repo.delete("user-1").await.unwrap());
+    assert!(!repo.delete("user-1").await.unwrap());
+    assert!(repo.find_by_username("max").await.unwrap().is_none());
+}
+
+// ==================== SQLite Login Attempt Repository Tests ====================
+
+#[tokio::test]
+async fn test_sqlite_login_attempts_count_and_reset_after_window() {
+    let repo = SqliteLoginAttemptRepository::new(memory_pool().await);
+    repo.initialize().await.unwrap();
+    let window_start = Utc::now() - Duration::minutes(15);
+
+    repo.record_failure("account:local:max", Some("local:max"), window_start).await.unwrap();
+    let second = repo.record_failure("account:local:max", Some("local:max"), window_start).await.unwrap();
+    assert_eq!(second.failures, 2);
+    assert_eq!(second.subject.as_deref(), Some("local:max"));
+
+    repo.lock("account:local:max", Utc::now() + Duration::seconds(30)).await.unwrap();
+    assert!(repo.find("account:local:max").await.unwrap().unwrap().is_locked());
+
+    // last failure before the window -> starts again, lock gone
+    let restarted = repo
+        .record_failure("account:local:max", Some("local:max"), Utc::now() + Duration::seconds(1))
+        .await
+        .unwrap();
+    assert_eq!(restarted.failures, 1);
+    assert!(restarted.locked_until.is_none());
+}
+
+#[tokio::test]
+async fn test_sqlite_login_attempts_delete() {
+    let repo = SqliteLoginAttemptRepository::new(memory_pool().await);
+    repo.initialize().await.unwrap();
+    let window_start = Utc::now() - Duration::minutes(15);
+
+    repo.record_failure("account_ip:local:max|10.0.0.1", Some("local:max"), window_start).await.unwrap();
+    repo.record_failure("account:local:max", Some("local:max"), window_start).await.unwrap();
+    repo.record_failure("ip:10.0.0.1", None, window_start).await.unwrap();
+    repo.record_failure("ip:10.0.0.2", None, window_start).await.unwrap();
+    repo.lock("ip:10.0.0.2", Utc::now() + Duration::minutes(5)).await.unwrap();
+
+    assert_eq!(repo.delete_for_subject("local:max").await.unwrap(), 2);
+    assert!(repo.delete("ip:10.0.0.1").await.unwrap());
+    assert!(!repo.delete("ip:10.0.0.1").await.unwrap());
+
+    // still locked, stays even without recent failures
+    assert_eq!(repo.delete_stale(Utc::now() + Duration::seconds(1)).await.unwrap(), 0);
+    assert!(repo.find("ip:10.0.0.2").await.unwrap().is_some());
+}
+
+// ==================== SQLite User Repository Tests ====================
+
+#[tokio::test]
+async fn test_sqlite_directory_lookups_include_inactive() {
+    let repo = SqliteUserRepository::new(memory_pool().await);
+    repo.initialize().await.unwrap();
+
+    let ad_user = |name: &str| User::new_external(
+        name.to_string(),
+        format!("{}@company.com", name),
+        AuthProviderType::ActiveDirectory,
+        name.to_string(),
+        UserRole::User,
+    );
+
+    let active = ad_user("max");
+    let mut inactive = ad_user("anna");
+    inactive.is_active = false;
+    let google = User::new_external(
+        "Google".to_string(),
+        "google@example.com".to_string(),
+        AuthProviderType::Google,
+        "sub".to_string(),
+        UserRole::User,
+    );
+
+    for user in [&active, &inactive, &google] {
+        repo.create(user).await.unwrap();
+    }
+
+    let users = repo.list_by_provider("activedirectory").await.unwrap();
+    let ids: Vec<&str> = users.iter().map(|u| u.id.as_str()).collect();
+    assert_eq!(ids, vec![active.id.as_str(), inactive.id.as_str()]);
+
+    // the normal lookups dont see the deactivated account, the sync ones do
+    assert!(repo.find_by_external_id("activedirectory", "anna").await.unwrap().is_none());
+    assert!(repo.find_by_email("anna@company.com").await.unwrap().is_none());
+    let found = repo.find_by_external_id_including_inactive("activedirectory", "anna").await.unwrap().unwrap();
+    assert_eq!(found.id, inactive.id);
+    assert!(!found.is_active);
+    let found = repo.find_by_email_including_inactive("ANNA@company.com").await.unwrap().unwrap();
+    assert_eq!(found.id, inactive.id);
+    assert!(repo.find_by_external_id_including_inactive("google", "anna").await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_sqlite_password_changed_at_on_old_table() {
+    let pool = memory_pool().await;
+    // users table as created before password_changed_at existed
+    sqlx::query(
+        r#"
+        CREATE TABLE users (
+            id TEXT PRIMARY KEY NOT NULL,
+            name TEXT NOT NULL,
+            email TEXT NOT NULL UNIQUE,
+            password_hash TEXT,
+            role TEXT NOT NULL DEFAULT 'user',
+            auth_provider TEXT NOT NULL DEFAULT 'local',
+            external_id TEXT,
+            created_at TEXT NOT NULL,
+            updated_at TEXT NOT NULL,
+            is_active INTEGER NOT NULL DEFAULT 1,
+            UNIQUE(auth_provider, external_id)
+        )
+        "#,
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    let repo = SqliteUserRepository::new(pool);
+    repo.initialize().await.unwrap();
+    // second start must not try to add it again
+    repo.initialize().await.unwrap();
+
+    let mut user = User::new_local("Max".to_string(), "max@example.com".to_string(), "hash".to_string(), UserRole::User);
+    repo.create(&user).await.unwrap();
+    assert!(repo.find_by_id(&user.id).await.unwrap().unwrap().password_changed_at.is_none());
+
+    let changed = Utc::now();
+    user.password_changed_at = Some(changed);
+    repo.update(&user).await.unwrap();
+
+    let found = repo.find_by_id(&user.id).await.unwrap().unwrap();
+    assert_eq!(found.password_changed_at.map(|t| t.timestamp()), Some(changed.timestamp()));
+}